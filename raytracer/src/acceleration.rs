@@ -7,17 +7,63 @@
 
 use std::{
     cell::UnsafeCell,
-    mem::MaybeUninit,
+    convert::TryFrom,
+    io::{self, Read, Write},
+    mem::{self, MaybeUninit},
     ptr,
     sync::atomic::{AtomicUsize, Ordering},
 };
 
+#[cfg(feature = "simd")]
+use std::simd::{prelude::*, SimdElement};
+
 use crate::{
-    math::{Axis, Ray, Vector3, VECTOR_MAX, VECTOR_MIN},
+    math::{Axis, Float, Ray, Vector3, VECTOR_MAX, VECTOR_MIN},
     object::AabbIntersector,
 };
 
+#[cfg(feature = "embree")]
+mod embree;
+#[cfg(feature = "embree")]
+pub use embree::EmbreeBvh;
+
 const OBJECT_BUCKETS: usize = 32;
+const SPATIAL_BINS: usize = 32;
+
+/// How many rays a `RayPacket` holds, which fill a 256-bit SIMD register.
+#[cfg(all(feature = "simd", not(feature = "f32")))]
+pub const PACKET_SIZE: usize = 4;
+
+/// How many rays a `RayPacket` holds, which fill a 256-bit SIMD register.
+#[cfg(all(feature = "simd", feature = "f32"))]
+pub const PACKET_SIZE: usize = 8;
+
+/// One value for each ray of a `RayPacket`.
+#[cfg(feature = "simd")]
+pub type Lanes = Simd<Float, PACKET_SIZE>;
+
+/// Which rays of a `RayPacket` something applies to.
+#[cfg(feature = "simd")]
+pub type LaneMask = Mask<<Float as SimdElement>::Mask, PACKET_SIZE>;
+
+/// The deepest an SBVH can get. Nodes this deep become leaves, so a `FlatBvh` can always be
+/// traversed with a fixed-size stack.
+const MAX_DEPTH: usize = 64;
+
+/// How many more references than primitives spatial splits may create, as a fraction of the
+/// number of primitives.
+const SPATIAL_SPLIT_BUDGET: Float = 0.5;
+
+/// Spatial splits are only considered where the children of the best object split overlap by
+/// more than this fraction of the surface area of the root, as in the SBVH paper.
+const SPATIAL_SPLIT_ALPHA: Float = 1e-5;
+
+/// The first bytes of a serialized `FlatBvh`.
+const FLAT_BVH_MAGIC: &[u8; 4] = b"SBVH";
+
+/// The version of the serialized `FlatBvh` format. Bump this whenever the format or how SBVHs are
+/// built changes, so stale caches are rebuilt.
+const FLAT_BVH_VERSION: u32 = 1;
 
 /// An atomic arena object for quick read/writes between threads.
 pub struct AtomicArena<T> {
@@ -119,7 +165,28 @@ impl Aabb {
         }
     }
 
-    pub fn surface_area(&self) -> f64 {
+    /// The box where this box and `other` overlap, which is empty if they don't.
+    pub fn intersection(&self, other: &Self) -> Self {
+        let min = Vector3::new(
+            self.min.x.max(other.min.x),
+            self.min.y.max(other.min.y),
+            self.min.z.max(other.min.z),
+        );
+        let max = Vector3::new(
+            self.max.x.min(other.max.x),
+            self.max.y.min(other.max.y),
+            self.max.z.min(other.max.z),
+        );
+
+        Self::new(min, max)
+    }
+
+    /// Whether this box contains no points at all.
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn surface_area(&self) -> Float {
         let xs = self.max.x - self.min.x;
         let ys = self.max.y - self.min.y;
         let zs = self.max.z - self.min.z;
@@ -127,11 +194,14 @@ impl Aabb {
         2. * xs * ys + 2. * xs * zs + 2. * ys * zs
     }
 
-    pub fn extent(&self, axis: Axis) -> f64 {
+    pub fn extent(&self, axis: Axis) -> Float {
         self.max.axis(axis) - self.min.axis(axis)
     }
 
-    pub fn intersect(&self, ray: &Ray) -> bool {
+    /// How far along `ray` it enters this box, and how far along it leaves the box. A ray that
+    /// starts inside the box enters it at zero. `None` if the ray misses the box, or the box is
+    /// entirely behind it.
+    pub fn intersect(&self, ray: &Ray) -> Option<(Float, Float)> {
         let size = self.max - self.centroid;
 
         let nro = self.centroid - ray.origin;
@@ -144,10 +214,46 @@ impl Aabb {
         let ssize = s * size;
         let t1 = ray.inverse() * (nro + ssize);
         let t2 = ray.inverse() * (nro - ssize);
-        let tn = f64::max(f64::max(t1.x, t1.y), t1.z);
-        let tf = f64::min(f64::min(t2.x, t2.y), t2.z);
+        let tn = Float::max(Float::max(t1.x, t1.y), t1.z);
+        let tf = Float::min(Float::min(t2.x, t2.y), t2.z);
+
+        if tn > tf || tf < 0. {
+            None
+        } else {
+            Some((tn.max(0.), tf))
+        }
+    }
+
+    /// How far along `ray` it enters this box, or zero if it starts inside it. `None` if the
+    /// ray misses the box.
+    pub fn intersect_near(&self, ray: &Ray) -> Option<Float> {
+        self.intersect(ray).map(|(near, _)| near)
+    }
+}
+
+#[cfg(feature = "simd")]
+impl Aabb {
+    /// Intersect every ray of a packet with this box at once, like `intersect_near`. Returns
+    /// which rays enter the box no further along than `max_t`, and how far along they enter it.
+    pub fn intersect_packet(&self, packet: &RayPacket, max_t: Lanes) -> (LaneMask, Lanes) {
+        let min = [self.min.x, self.min.y, self.min.z];
+        let max = [self.max.x, self.max.y, self.max.z];
+
+        let infinity = Lanes::splat(Float::INFINITY);
+        let mut tn = -infinity;
+        let mut tf = infinity;
+        for axis in 0..3 {
+            let t1 = (Lanes::splat(min[axis]) - packet.origin[axis]) * packet.inverse[axis];
+            let t2 = (Lanes::splat(max[axis]) - packet.origin[axis]) * packet.inverse[axis];
+
+            // a ray parallel to a face and starting right on it gets NaN, and lies in the slab
+            let in_plane = t1.is_nan() | t2.is_nan();
+            tn = tn.simd_max(in_plane.select(-infinity, t1.simd_min(t2)));
+            tf = tf.simd_min(in_plane.select(infinity, t1.simd_max(t2)));
+        }
 
-        !(tn > tf || tf < 0.)
+        let tn = tn.simd_max(Lanes::splat(0.));
+        (tn.simd_le(tf) & tn.simd_le(max_t), tn)
     }
 }
 
@@ -175,12 +281,16 @@ impl From<AabbIntersector> for Aabb {
 #[derive(Debug, Clone)]
 pub struct Split {
     pub axis: Axis,
-    pub position: f64,
+    pub position: Float,
 }
 
 pub trait Primitive: Sized {
     fn points(&self) -> &[Vector3];
-    fn split(&self, split: Split) -> (Self, Option<Self>);
+
+    /// Split the part of this primitive inside `bounds` by a plane, returning the bounding boxes
+    /// of the parts on either side of it. A side the primitive doesn't reach is empty.
+    fn split(&self, bounds: &Aabb, split: &Split) -> (Aabb, Aabb);
+
     fn bounding_box(&self) -> &Aabb;
 }
 
@@ -196,26 +306,33 @@ pub struct ObjectBucket {
     count: usize,
 }
 
+/// The bucket of an object split that a reference falls in.
+fn object_bucket(reference: &Reference, aabb_total: &Aabb, split_axis: Axis) -> usize {
+    let bucket = ((reference.bounding_box.centroid.axis(split_axis)
+        - aabb_total.min.axis(split_axis))
+        / aabb_total.extent(split_axis)
+        * (OBJECT_BUCKETS as Float)) as usize;
+
+    bucket.min(OBJECT_BUCKETS - 1)
+}
+
+/// The best object split of some references, with its cost, the first bucket on its right-hand
+/// side, and the bounding boxes of either side.
 fn object_split_candidate(
     refs: &[Reference],
     aabb_total: &Aabb,
-    input: &[usize],
     split_axis: Axis,
-) -> (f64, usize) {
+) -> (Float, usize, Aabb, Aabb) {
     let mut buckets: [ObjectBucket; OBJECT_BUCKETS] = Default::default();
-    let extent = aabb_total.extent(split_axis);
 
     // place refs into buckets
-    for idx in input {
-        let bucket_idx = ((refs[*idx].bounding_box.centroid.axis(split_axis)
-            - aabb_total.min.axis(split_axis))
-            / extent
-            * (OBJECT_BUCKETS as f64)) as usize;
+    for reference in refs {
+        let bucket_idx = object_bucket(reference, aabb_total, split_axis);
 
         buckets[bucket_idx].count += 1;
         buckets[bucket_idx].bounding_box = buckets[bucket_idx]
             .bounding_box
-            .union(&refs[*idx].bounding_box);
+            .union(&reference.bounding_box);
     }
 
     // compute all split left-hand sides
@@ -229,16 +346,17 @@ fn object_split_candidate(
 
     // compute split rhs and find split with minimum SAH
     let mut rhs_split = (0, Aabb::default());
-    let mut min_cost = f64::MAX;
+    let mut min_cost = Float::MAX;
     let mut min_idx = 0;
+    let mut min_boxes = (Aabb::default(), Aabb::default());
     for i in (1..OBJECT_BUCKETS).rev() {
         rhs_split.0 += buckets[i].count;
         rhs_split.1 = rhs_split.1.union(&buckets[i].bounding_box);
 
         let lhs_split = &lhs_splits[i - 1];
         let traverse_cost = 1.;
-        let n_lhs = lhs_split.0 as f64;
-        let n_rhs = rhs_split.0 as f64;
+        let n_lhs = lhs_split.0 as Float;
+        let n_rhs = rhs_split.0 as Float;
         let cost = traverse_cost
             + (n_lhs * lhs_split.1.surface_area() + n_rhs * rhs_split.1.surface_area())
                 / aabb_total.surface_area();
@@ -246,40 +364,132 @@ fn object_split_candidate(
         if cost < min_cost {
             min_cost = cost;
             min_idx = i;
+            min_boxes = (lhs_split.1.clone(), rhs_split.1.clone());
         }
     }
 
-    (min_cost, min_idx)
+    (min_cost, min_idx, min_boxes.0, min_boxes.1)
 }
 
 fn object_split(
-    refs: &[Reference],
+    refs: Vec<Reference>,
     aabb_total: &Aabb,
-    input: &[usize],
-    output: &mut [usize],
     split_axis: Axis,
     bucket_idx: usize,
-) -> usize {
-    let extent = aabb_total.extent(split_axis);
-
-    let mut lhs_ptr = 0;
-    let mut rhs_ptr = output.len() - 1;
-    for idx in input {
-        let this_bucket = ((refs[*idx].bounding_box.centroid.axis(split_axis)
-            - aabb_total.min.axis(split_axis))
-            / extent
-            * (OBJECT_BUCKETS as f64)) as usize;
-
-        if this_bucket < bucket_idx {
-            output[lhs_ptr] = *idx;
-            lhs_ptr += 1;
+) -> (Vec<Reference>, Vec<Reference>) {
+    refs.into_iter()
+        .partition(|reference| object_bucket(reference, aabb_total, split_axis) < bucket_idx)
+}
+
+#[derive(Debug, Clone, Default)]
+struct SpatialBin {
+    bounding_box: Aabb,
+    entries: usize,
+    exits: usize,
+}
+
+/// The best spatial split of some references, which splits the primitives themselves at a plane
+/// instead of sorting them to either side, with its cost and the position of the plane.
+fn spatial_split_candidate<P: Primitive>(
+    prims: &[P],
+    refs: &[Reference],
+    aabb_total: &Aabb,
+    split_axis: Axis,
+) -> (Float, Float) {
+    let min = aabb_total.min.axis(split_axis);
+    let bin_size = aabb_total.extent(split_axis) / SPATIAL_BINS as Float;
+    let bin_of = |x: Float| (((x - min) / bin_size) as usize).min(SPATIAL_BINS - 1);
+
+    // chop each ref into the bins it passes through
+    let mut bins = vec![SpatialBin::default(); SPATIAL_BINS];
+    for reference in refs {
+        let first = bin_of(reference.bounding_box.min.axis(split_axis));
+        let last = bin_of(reference.bounding_box.max.axis(split_axis));
+
+        let mut rest = reference.bounding_box.clone();
+        for (i, bin) in bins.iter_mut().enumerate().take(last).skip(first) {
+            let split = Split {
+                axis: split_axis,
+                position: min + bin_size * (i + 1) as Float,
+            };
+            let (lhs, rhs) = prims[reference.prim_idx].split(&rest, &split);
+            bin.bounding_box = bin.bounding_box.union(&lhs);
+            rest = rhs;
+        }
+        bins[last].bounding_box = bins[last].bounding_box.union(&rest);
+
+        bins[first].entries += 1;
+        bins[last].exits += 1;
+    }
+
+    // compute all split left-hand sides
+    let mut lhs_splits = Vec::with_capacity(SPATIAL_BINS - 1);
+    let mut lhs_split = (0, Aabb::default());
+    for bin in bins.iter().take(SPATIAL_BINS - 1) {
+        lhs_split.0 += bin.entries;
+        lhs_split.1 = lhs_split.1.union(&bin.bounding_box);
+        lhs_splits.push(lhs_split.clone());
+    }
+
+    // compute split rhs and find split with minimum SAH
+    let mut rhs_split = (0, Aabb::default());
+    let mut min_cost = Float::MAX;
+    let mut min_position = min;
+    for i in (1..SPATIAL_BINS).rev() {
+        rhs_split.0 += bins[i].exits;
+        rhs_split.1 = rhs_split.1.union(&bins[i].bounding_box);
+
+        let lhs_split = &lhs_splits[i - 1];
+        let traverse_cost = 1.;
+        let n_lhs = lhs_split.0 as Float;
+        let n_rhs = rhs_split.0 as Float;
+        let cost = traverse_cost
+            + (n_lhs * lhs_split.1.surface_area() + n_rhs * rhs_split.1.surface_area())
+                / aabb_total.surface_area();
+
+        if cost < min_cost {
+            min_cost = cost;
+            min_position = min + bin_size * i as Float;
+        }
+    }
+
+    (min_cost, min_position)
+}
+
+/// Whether a reference lies across a split plane, and would be split in two by it.
+fn straddles(reference: &Reference, split: &Split) -> bool {
+    reference.bounding_box.min.axis(split.axis) < split.position
+        && reference.bounding_box.max.axis(split.axis) > split.position
+}
+
+fn spatial_split<P: Primitive>(
+    prims: &[P],
+    refs: Vec<Reference>,
+    split: &Split,
+) -> (Vec<Reference>, Vec<Reference>) {
+    let mut lhs = Vec::new();
+    let mut rhs = Vec::new();
+    for reference in refs {
+        if straddles(&reference, split) {
+            // duplicate the reference, clipped to either side
+            let (lhs_box, rhs_box) =
+                prims[reference.prim_idx].split(&reference.bounding_box, split);
+            lhs.push(Reference {
+                prim_idx: reference.prim_idx,
+                bounding_box: lhs_box,
+            });
+            rhs.push(Reference {
+                prim_idx: reference.prim_idx,
+                bounding_box: rhs_box,
+            });
+        } else if reference.bounding_box.centroid.axis(split.axis) < split.position {
+            lhs.push(reference);
         } else {
-            output[rhs_ptr] = *idx;
-            rhs_ptr -= 1;
+            rhs.push(reference);
         }
     }
 
-    lhs_ptr
+    (lhs, rhs)
 }
 
 #[derive(Debug)]
@@ -293,46 +503,28 @@ pub struct Sbvh {
     pub root_node: usize,
 }
 
-impl Sbvh {
-    pub fn new<P: Primitive>(prims: &[P]) -> Self {
-        let refs = prims
-            .iter()
-            .enumerate()
-            .map(|(i, prim)| Reference {
-                prim_idx: i,
-                bounding_box: prim.bounding_box().clone(),
-            })
-            .collect::<Vec<_>>();
-
-        let mut buffer_a = Vec::with_capacity(refs.len());
-        for i in 0..refs.len() {
-            buffer_a.push(i);
-        }
-        let mut buffer_b = vec![0; refs.len()];
+/// The state shared while building an SBVH.
+struct Builder<'a, P: Primitive> {
+    prims: &'a [P],
 
-        let output = AtomicArena::new(2 * refs.len() - 1);
+    /// The surface area of the root node's bounding box.
+    root_area: Float,
 
-        let root_node = Self::build(&refs, &mut buffer_a, &mut buffer_b, &output);
+    /// How many more references spatial splits can still create.
+    budget: AtomicUsize,
 
-        Self {
-            nodes: output,
-            root_node,
-        }
-    }
+    output: &'a AtomicArena<SbvhNode>,
+}
 
-    fn build(
-        refs: &[Reference],
-        input_buffer: &mut [usize],
-        output_buffer: &mut [usize],
-        output: &AtomicArena<SbvhNode>,
-    ) -> usize {
-        if input_buffer.len() < 2 {
-            return Self::create_leaf(refs, input_buffer, output);
+impl<P: Primitive + Sync> Builder<'_, P> {
+    fn build(&self, refs: Vec<Reference>, depth: usize) -> usize {
+        if refs.len() < 2 || depth >= MAX_DEPTH {
+            return self.create_leaf(refs);
         }
 
         let mut aabb_total = Aabb::default();
-        for idx in input_buffer.iter() {
-            aabb_total = aabb_total.union(&refs[*idx].bounding_box);
+        for reference in refs.iter() {
+            aabb_total = aabb_total.union(&reference.bounding_box);
         }
 
         let (ex, ey, ez) = (
@@ -348,39 +540,112 @@ impl Sbvh {
             Axis::Z
         };
 
-        let leaf_cost = input_buffer.len() as f64;
+        let leaf_cost = refs.len() as Float;
+
+        let (object_split_cost, object_split_bucket, lhs_box, rhs_box) =
+            object_split_candidate(&refs, &aabb_total, split_axis);
+
+        // try to split the primitives themselves where the object split's children overlap,
+        // like around long, thin triangles
+        let overlap = lhs_box.intersection(&rhs_box);
+        if !overlap.is_empty()
+            && overlap.surface_area() / self.root_area > SPATIAL_SPLIT_ALPHA
+            && self.budget.load(Ordering::SeqCst) > 0
+        {
+            let (spatial_split_cost, position) =
+                spatial_split_candidate(self.prims, &refs, &aabb_total, split_axis);
+
+            if spatial_split_cost < object_split_cost && spatial_split_cost < leaf_cost {
+                let split = Split {
+                    axis: split_axis,
+                    position,
+                };
+                let (mut lhs_count, mut rhs_count, mut duplicates) = (0, 0, 0);
+                for reference in refs.iter() {
+                    if straddles(reference, &split) {
+                        duplicates += 1;
+                    } else if reference.bounding_box.centroid.axis(split.axis) < split.position {
+                        lhs_count += 1;
+                    } else {
+                        rhs_count += 1;
+                    }
+                }
 
-        let (object_split_cost, object_split_bucket) =
-            object_split_candidate(refs, &aabb_total, input_buffer, split_axis);
+                // only split if both sides get something, and reserve the duplicates up front
+                // so other threads can't spend them too
+                if lhs_count + duplicates > 0
+                    && rhs_count + duplicates > 0
+                    && self
+                        .budget
+                        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |b| {
+                            b.checked_sub(duplicates)
+                        })
+                        .is_ok()
+                {
+                    let (lhs, rhs) = spatial_split(self.prims, refs, &split);
+                    return self.build_children(lhs, rhs, depth);
+                }
+            }
+        }
 
         if object_split_cost < leaf_cost {
-            let split_point = object_split(
-                refs,
-                &aabb_total,
-                input_buffer,
-                output_buffer,
-                split_axis,
-                object_split_bucket,
-            );
+            let (lhs, rhs) = object_split(refs, &aabb_total, split_axis, object_split_bucket);
+            self.build_children(lhs, rhs, depth)
+        } else {
+            self.create_leaf(refs)
+        }
+    }
 
-            let (a_in, b_in) = output_buffer.split_at_mut(split_point);
-            let (a_out, b_out) = input_buffer.split_at_mut(split_point);
+    fn build_children(&self, lhs: Vec<Reference>, rhs: Vec<Reference>, depth: usize) -> usize {
+        // a split that puts everything on one side would never end
+        if lhs.is_empty() || rhs.is_empty() {
+            let mut refs = lhs;
+            refs.extend(rhs);
+            return self.create_leaf(refs);
+        }
 
-            let (lhs, rhs) = rayon::join(
-                || Self::build(refs, a_in, a_out, output),
-                || Self::build(refs, b_in, b_out, output),
-            );
+        let (lhs, rhs) = rayon::join(|| self.build(lhs, depth + 1), || self.build(rhs, depth + 1));
 
-            output.push(SbvhNode::Node { lhs, rhs })
-        } else {
-            Self::create_leaf(refs, input_buffer, output)
-        }
+        self.output.push(SbvhNode::Node { lhs, rhs })
+    }
+
+    fn create_leaf(&self, refs: Vec<Reference>) -> usize {
+        self.output.push(SbvhNode::Leaf { refs })
     }
+}
+
+impl Sbvh {
+    pub fn new<P: Primitive + Sync>(prims: &[P]) -> Self {
+        let refs = prims
+            .iter()
+            .enumerate()
+            .map(|(i, prim)| Reference {
+                prim_idx: i,
+                bounding_box: prim.bounding_box().clone(),
+            })
+            .collect::<Vec<_>>();
 
-    fn create_leaf(refs: &[Reference], idxs: &[usize], output: &AtomicArena<SbvhNode>) -> usize {
-        output.push(SbvhNode::Leaf {
-            refs: idxs.iter().map(|idx| refs[*idx].clone()).collect(),
-        })
+        // spatial splits duplicate references, so leave room for the extra nodes they make
+        let budget = (refs.len() as Float * SPATIAL_SPLIT_BUDGET) as usize;
+        let output = AtomicArena::new(2 * (refs.len() + budget));
+
+        let mut root_box = Aabb::default();
+        for reference in refs.iter() {
+            root_box = root_box.union(&reference.bounding_box);
+        }
+
+        let builder = Builder {
+            prims,
+            root_area: root_box.surface_area(),
+            budget: AtomicUsize::new(budget),
+            output: &output,
+        };
+        let root_node = builder.build(refs, 0);
+
+        Self {
+            nodes: output,
+            root_node,
+        }
     }
 
     pub fn node_bounding_box(&self, idx: usize) -> Aabb {
@@ -445,6 +710,908 @@ impl TreeNode {
             TreeNode::Leaf { bounding, .. } => bounding,
         }
     }
+
+    /// Recompute the bounding boxes of this node and every node below it from the bounding
+    /// boxes of their primitives, given by `prim_bounds`, keeping the structure of the tree.
+    pub fn refit<F>(&mut self, prim_bounds: &F)
+    where
+        F: Fn(usize) -> Aabb,
+    {
+        match self {
+            TreeNode::Branch { a, b, bounding } => {
+                a.refit(prim_bounds);
+                b.refit(prim_bounds);
+                *bounding = a.bounding().union(b.bounding());
+            }
+            TreeNode::Leaf { indices, bounding } => {
+                *bounding = indices
+                    .iter()
+                    .fold(Aabb::default(), |acc, &i| acc.union(&prim_bounds(i)));
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum FlatNodeKind {
+    /// A branch, whose left child is the node right after it and whose right child is at `rhs`.
+    Branch { rhs: usize },
+
+    /// A leaf, whose primitives are `indices[start..start + len]` of its `FlatBvh`.
+    Leaf { start: usize, len: usize },
+}
+
+#[derive(Debug, Clone)]
+pub struct FlatNode {
+    pub bounding: Aabb,
+    pub kind: FlatNodeKind,
+}
+
+/// A BVH flattened into one array of nodes in depth-first order, so it can be traversed without
+/// recursion or allocating.
+#[derive(Debug, Clone)]
+pub struct FlatBvh {
+    pub nodes: Vec<FlatNode>,
+
+    /// The primitive indices of every leaf, back to back.
+    pub indices: Vec<usize>,
+}
+
+/// An acceleration structure over a list of primitives, which finds the primitives a ray might
+/// hit without testing every one of them.
+pub trait Accelerator {
+    /// The bounding box of every primitive.
+    fn bounding(&self) -> &Aabb;
+
+    /// Call `f` with the index of every primitive in every leaf `ray` passes through, visiting
+    /// nearer leaves first. `f` returns how far along the ray primitives still matter, like the
+    /// distance to the closest hit so far, and leaves the ray only reaches beyond that are
+    /// skipped. A primitive can be visited more than once if it was put in several leaves.
+    /// Returns how many nodes were visited.
+    fn traverse<F>(&self, ray: &Ray, f: F) -> usize
+    where
+        F: FnMut(usize) -> Float;
+
+    /// Gather statistics about the shape of this structure, to diagnose meshes that are slow to
+    /// trace.
+    fn stats(&self) -> BvhStats;
+}
+
+/// Statistics about the shape of an acceleration structure, see [`Accelerator::stats`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct BvhStats {
+    /// The number of nodes, branches and leaves.
+    pub nodes: usize,
+
+    /// The number of leaves.
+    pub leaves: usize,
+
+    /// The depth of the deepest leaf, where the root is at depth 0.
+    pub depth: usize,
+
+    /// The number of primitive references in every leaf. This is more than the number of
+    /// primitives when spatial splits put primitives in several leaves.
+    pub references: usize,
+
+    /// The memory taken by the nodes and the primitive references of the leaves, in bytes.
+    pub bytes: usize,
+
+    /// How many leaves hold each number of primitives, so `leaf_sizes[n]` is the number of
+    /// leaves with `n` primitives.
+    pub leaf_sizes: Vec<usize>,
+
+    /// The expected cost of tracing a ray through the BVH by the surface area heuristic, in
+    /// units of one node visit, or one primitive intersection. Lower is better.
+    pub sah_cost: Float,
+}
+
+impl BvhStats {
+    fn new<N>(nodes: &[N], references: &[usize]) -> Self {
+        Self {
+            nodes: nodes.len(),
+            leaves: 0,
+            depth: 0,
+            references: references.len(),
+            bytes: mem::size_of_val(nodes) + mem::size_of_val(references),
+            leaf_sizes: Vec::new(),
+            sah_cost: 0.,
+        }
+    }
+
+    /// Count a branch, which a ray through the root goes through with probability `weight`.
+    fn add_branch(&mut self, weight: Float) {
+        self.sah_cost += weight;
+    }
+
+    /// Count a leaf of `len` primitives, which a ray through the root goes through with
+    /// probability `weight`.
+    fn add_leaf(&mut self, len: usize, weight: Float) {
+        self.leaves += 1;
+        if self.leaf_sizes.len() <= len {
+            self.leaf_sizes.resize(len + 1, 0);
+        }
+        self.leaf_sizes[len] += 1;
+        self.sah_cost += weight * len as Float;
+    }
+}
+
+fn flatten_sbvh(sbvh: &Sbvh, idx: usize, output: &mut FlatBvh) {
+    let bounding = sbvh.node_bounding_box(idx);
+
+    match sbvh.nodes.get(idx).unwrap() {
+        SbvhNode::Leaf { refs } => {
+            output.nodes.push(FlatNode {
+                bounding,
+                kind: FlatNodeKind::Leaf {
+                    start: output.indices.len(),
+                    len: refs.len(),
+                },
+            });
+            output.indices.extend(refs.iter().map(|r| r.prim_idx));
+        }
+        SbvhNode::Node { lhs, rhs } => {
+            let this = output.nodes.len();
+            output.nodes.push(FlatNode {
+                bounding,
+                kind: FlatNodeKind::Branch { rhs: 0 },
+            });
+
+            flatten_sbvh(sbvh, *lhs, output);
+            output.nodes[this].kind = FlatNodeKind::Branch {
+                rhs: output.nodes.len(),
+            };
+            flatten_sbvh(sbvh, *rhs, output);
+        }
+    }
+}
+
+impl From<Sbvh> for FlatBvh {
+    fn from(sbvh: Sbvh) -> Self {
+        let mut output = Self {
+            nodes: Vec::new(),
+            indices: Vec::new(),
+        };
+        flatten_sbvh(&sbvh, sbvh.root_node, &mut output);
+        output
+    }
+}
+
+impl Accelerator for FlatBvh {
+    fn bounding(&self) -> &Aabb {
+        &self.nodes[0].bounding
+    }
+
+    fn traverse<F>(&self, ray: &Ray, mut f: F) -> usize
+    where
+        F: FnMut(usize) -> Float,
+    {
+        if self.nodes[0].bounding.intersect(ray).is_none() {
+            return 0;
+        }
+
+        // a branch pushes at most one node, and there are at most `MAX_DEPTH` above any node
+        let mut stack = [(0, 0.); MAX_DEPTH];
+        let mut stack_len = 0;
+        let mut max_t = Float::INFINITY;
+        let mut idx = 0;
+        let mut visited = 0;
+
+        loop {
+            visited += 1;
+            match self.nodes[idx].kind {
+                FlatNodeKind::Branch { rhs } => {
+                    let near = |i: usize| {
+                        self.nodes[i]
+                            .bounding
+                            .intersect_near(ray)
+                            .filter(|&t| t <= max_t)
+                    };
+
+                    // go down the nearer child, and come back for the other one later
+                    match (near(idx + 1), near(rhs)) {
+                        (Some(ta), Some(tb)) => {
+                            let (first, second) = if ta <= tb {
+                                (idx + 1, (rhs, tb))
+                            } else {
+                                (rhs, (idx + 1, ta))
+                            };
+                            stack[stack_len] = second;
+                            stack_len += 1;
+                            idx = first;
+                            continue;
+                        }
+                        (Some(_), None) => {
+                            idx += 1;
+                            continue;
+                        }
+                        (None, Some(_)) => {
+                            idx = rhs;
+                            continue;
+                        }
+                        (None, None) => (),
+                    }
+                }
+                FlatNodeKind::Leaf { start, len } => {
+                    for &i in self.indices[start..start + len].iter() {
+                        max_t = max_t.min(f(i));
+                    }
+                }
+            }
+
+            // pick up the next node that's still close enough to matter
+            loop {
+                if stack_len == 0 {
+                    return visited;
+                }
+                stack_len -= 1;
+
+                let (next, t) = stack[stack_len];
+                if t <= max_t {
+                    idx = next;
+                    break;
+                }
+            }
+        }
+    }
+
+    fn stats(&self) -> BvhStats {
+        let root_area = self.bounding().surface_area();
+        let mut stats = BvhStats::new(&self.nodes, &self.indices);
+
+        // the depth of each node on the stack, which is walked in the same order as it's stored
+        let mut stack = vec![0];
+        for node in self.nodes.iter() {
+            let depth = stack.pop().unwrap_or_default();
+            stats.depth = stats.depth.max(depth);
+
+            // the probability that a ray through the root also goes through this node
+            let weight = if root_area > 0. {
+                node.bounding.surface_area() / root_area
+            } else {
+                1.
+            };
+
+            match node.kind {
+                FlatNodeKind::Branch { .. } => {
+                    stack.push(depth + 1);
+                    stack.push(depth + 1);
+                    stats.add_branch(weight);
+                }
+                FlatNodeKind::Leaf { len, .. } => stats.add_leaf(len, weight),
+            }
+        }
+
+        stats
+    }
+}
+
+impl FlatBvh {
+    /// Recompute the bounding boxes of every node from the bounding boxes of their primitives,
+    /// given by `prim_bounds`, keeping the structure of the tree.
+    pub fn refit<F>(&mut self, prim_bounds: &F)
+    where
+        F: Fn(usize) -> Aabb,
+    {
+        // children always come after their parents, so walk backwards
+        for idx in (0..self.nodes.len()).rev() {
+            let bounding = match self.nodes[idx].kind {
+                FlatNodeKind::Branch { rhs } => self.nodes[idx + 1]
+                    .bounding
+                    .union(&self.nodes[rhs].bounding),
+                FlatNodeKind::Leaf { start, len } => self.indices[start..start + len]
+                    .iter()
+                    .fold(Aabb::default(), |acc, &i| acc.union(&prim_bounds(i))),
+            };
+            self.nodes[idx].bounding = bounding;
+        }
+    }
+
+    /// Serialize this BVH to `writer` in a compact little-endian binary format, which can be read
+    /// back with `read_from`. Bounds are always written as `f64`, whatever `Float` is.
+    #[allow(clippy::unnecessary_cast)]
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(FLAT_BVH_MAGIC)?;
+        writer.write_all(&FLAT_BVH_VERSION.to_le_bytes())?;
+        writer.write_all(&(self.nodes.len() as u64).to_le_bytes())?;
+        writer.write_all(&(self.indices.len() as u64).to_le_bytes())?;
+
+        for node in self.nodes.iter() {
+            let Aabb { min, max, .. } = &node.bounding;
+            for f in [min.x, min.y, min.z, max.x, max.y, max.z] {
+                writer.write_all(&(f as f64).to_le_bytes())?;
+            }
+
+            let (tag, a, b) = match node.kind {
+                FlatNodeKind::Branch { rhs } => (0u8, rhs, 0),
+                FlatNodeKind::Leaf { start, len } => (1u8, start, len),
+            };
+            writer.write_all(&[tag])?;
+            writer.write_all(&(a as u64).to_le_bytes())?;
+            writer.write_all(&(b as u64).to_le_bytes())?;
+        }
+
+        for &i in self.indices.iter() {
+            writer.write_all(&(i as u64).to_le_bytes())?;
+        }
+
+        Ok(())
+    }
+
+    /// Deserialize a BVH written by `write_to` over `primitives` primitives. The BVH is checked
+    /// to be well-formed, so a corrupt or stale file is an `InvalidData` error rather than a
+    /// panic while traversing.
+    #[allow(clippy::unnecessary_cast)]
+    pub fn read_from<R: Read>(reader: &mut R, primitives: usize) -> io::Result<Self> {
+        fn invalid(message: &str) -> io::Error {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid SBVH: {}", message),
+            )
+        }
+
+        fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+            let mut bytes = [0; 8];
+            reader.read_exact(&mut bytes)?;
+            Ok(u64::from_le_bytes(bytes))
+        }
+
+        fn read_usize<R: Read>(reader: &mut R) -> io::Result<usize> {
+            usize::try_from(read_u64(reader)?).map_err(|_| invalid("index out of range"))
+        }
+
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != FLAT_BVH_MAGIC {
+            return Err(invalid("bad magic"));
+        }
+
+        let mut version = [0; 4];
+        reader.read_exact(&mut version)?;
+        if u32::from_le_bytes(version) != FLAT_BVH_VERSION {
+            return Err(invalid("unsupported version"));
+        }
+
+        let node_count = read_usize(reader)?;
+        let index_count = read_usize(reader)?;
+        if node_count == 0 {
+            return Err(invalid("no nodes"));
+        }
+
+        // don't trust the counts with a huge allocation up front
+        let mut nodes = Vec::with_capacity(node_count.min(1 << 16));
+        for _ in 0..node_count {
+            let mut bounds = [0.; 6];
+            for f in bounds.iter_mut() {
+                *f = f64::from_bits(read_u64(reader)?) as Float;
+            }
+
+            let mut tag = [0];
+            reader.read_exact(&mut tag)?;
+            let a = read_usize(reader)?;
+            let b = read_usize(reader)?;
+            let kind = match tag[0] {
+                0 => FlatNodeKind::Branch { rhs: a },
+                1 => FlatNodeKind::Leaf { start: a, len: b },
+                _ => return Err(invalid("bad node kind")),
+            };
+
+            nodes.push(FlatNode {
+                bounding: Aabb::new(
+                    Vector3::new(bounds[0], bounds[1], bounds[2]),
+                    Vector3::new(bounds[3], bounds[4], bounds[5]),
+                ),
+                kind,
+            });
+        }
+
+        let mut indices = Vec::with_capacity(index_count.min(1 << 16));
+        for _ in 0..index_count {
+            let i = read_usize(reader)?;
+            if i >= primitives {
+                return Err(invalid("primitive index out of range"));
+            }
+            indices.push(i);
+        }
+
+        // walk the tree depth-first; it is only laid out like `flatten_sbvh` lays it out if every
+        // node is reached in order, and only traversable if it is no deeper than `MAX_DEPTH`
+        let mut stack = vec![(0, 0)];
+        let mut visited = 0;
+        while let Some((idx, depth)) = stack.pop() {
+            if idx != visited || depth > MAX_DEPTH {
+                return Err(invalid("malformed tree"));
+            }
+            visited += 1;
+
+            match nodes[idx].kind {
+                FlatNodeKind::Branch { rhs } => {
+                    if rhs <= idx + 1 || rhs >= node_count {
+                        return Err(invalid("malformed tree"));
+                    }
+                    stack.push((rhs, depth + 1));
+                    stack.push((idx + 1, depth + 1));
+                }
+                FlatNodeKind::Leaf { start, len } => {
+                    if start.checked_add(len).is_none_or(|end| end > index_count) {
+                        return Err(invalid("leaf out of range"));
+                    }
+                }
+            }
+        }
+        if visited != node_count {
+            return Err(invalid("malformed tree"));
+        }
+
+        Ok(Self { nodes, indices })
+    }
+}
+
+/// A few rays traced together, stored axis by axis so they can all be intersected with the
+/// same SIMD instructions. Only available with the `simd` feature.
+#[cfg(feature = "simd")]
+#[derive(Debug, Clone)]
+pub struct RayPacket {
+    pub origin: [Lanes; 3],
+    pub direction: [Lanes; 3],
+
+    /// The inverse of each ray's direction, see [`Ray::inverse`].
+    pub inverse: [Lanes; 3],
+}
+
+#[cfg(feature = "simd")]
+impl RayPacket {
+    pub fn new(rays: &[Ray; PACKET_SIZE]) -> Self {
+        let lanes = |f: &dyn Fn(&Ray) -> Vector3| {
+            let v = rays.iter().map(f).collect::<Vec<_>>();
+            [
+                Lanes::from_array(std::array::from_fn(|i| v[i].x)),
+                Lanes::from_array(std::array::from_fn(|i| v[i].y)),
+                Lanes::from_array(std::array::from_fn(|i| v[i].z)),
+            ]
+        };
+
+        Self {
+            origin: lanes(&|r| r.origin),
+            direction: lanes(&|r| r.direction),
+            inverse: lanes(&|r| r.inverse()),
+        }
+    }
+}
+
+#[cfg(feature = "simd")]
+impl FlatBvh {
+    /// Like `traverse`, but for a whole packet of rays at once. A node is visited if any ray of
+    /// the packet reaches it, and `f` is given the rays that reach each primitive's leaf, and
+    /// returns how far along each ray primitives still matter.
+    pub fn traverse_packet<F>(&self, packet: &RayPacket, mut f: F)
+    where
+        F: FnMut(usize, LaneMask) -> Lanes,
+    {
+        let mut max_t = Lanes::splat(Float::INFINITY);
+        let (mut active, _) = self.nodes[0].bounding.intersect_packet(packet, max_t);
+        if !active.any() {
+            return;
+        }
+
+        let mut stack = [0; MAX_DEPTH];
+        let mut stack_len = 0;
+        let mut idx = 0;
+
+        loop {
+            match self.nodes[idx].kind {
+                FlatNodeKind::Branch { rhs } => {
+                    let (ma, ta) = self.nodes[idx + 1].bounding.intersect_packet(packet, max_t);
+                    let (mb, tb) = self.nodes[rhs].bounding.intersect_packet(packet, max_t);
+
+                    // go down the child the packet reaches first, and come back for the other
+                    match (ma.any(), mb.any()) {
+                        (true, true) => {
+                            let infinity = Lanes::splat(Float::INFINITY);
+                            let (first, second) = if ma.select(ta, infinity).reduce_min()
+                                <= mb.select(tb, infinity).reduce_min()
+                            {
+                                ((idx + 1, ma), rhs)
+                            } else {
+                                ((rhs, mb), idx + 1)
+                            };
+                            stack[stack_len] = second;
+                            stack_len += 1;
+                            (idx, active) = first;
+                            continue;
+                        }
+                        (true, false) => {
+                            (idx, active) = (idx + 1, ma);
+                            continue;
+                        }
+                        (false, true) => {
+                            (idx, active) = (rhs, mb);
+                            continue;
+                        }
+                        (false, false) => (),
+                    }
+                }
+                FlatNodeKind::Leaf { start, len } => {
+                    for &i in self.indices[start..start + len].iter() {
+                        max_t = max_t.simd_min(f(i, active));
+                    }
+                }
+            }
+
+            // pick up the next node that any ray still reaches close enough to matter
+            loop {
+                if stack_len == 0 {
+                    return;
+                }
+                stack_len -= 1;
+
+                let next = stack[stack_len];
+                let (mask, _) = self.nodes[next].bounding.intersect_packet(packet, max_t);
+                if mask.any() {
+                    (idx, active) = (next, mask);
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// The cost of visiting a node of a kd-tree, relative to `KD_INTERSECTION_COST`, as in Wald
+/// and Havran's "On building fast kd-trees for ray tracing".
+const KD_TRAVERSAL_COST: Float = 15.;
+
+/// The cost of intersecting a primitive in a leaf of a kd-tree.
+const KD_INTERSECTION_COST: Float = 20.;
+
+/// How much cheaper a kd-tree split that cuts off empty space is made, so that kd-trees wrap
+/// their primitives tightly.
+const KD_EMPTY_BONUS: Float = 0.2;
+
+/// The deepest a kd-tree can get, so it can always be traversed with a fixed-size stack.
+const KD_MAX_DEPTH: usize = 64;
+
+#[derive(Debug, Clone)]
+pub enum KdNode {
+    /// A branch split by the plane at `position` along `axis`, whose child below the plane is
+    /// the node right after it and whose child above it is at `rhs`.
+    Branch {
+        axis: Axis,
+        position: Float,
+        rhs: usize,
+    },
+
+    /// A leaf, whose primitives are `indices[start..start + len]` of its `KdTree`.
+    Leaf { start: usize, len: usize },
+}
+
+/// A kd-tree, which splits space itself into two at every branch, rather than splitting the
+/// primitives into two groups like a BVH. Primitives that straddle a split are put on both
+/// sides. Nodes are stored in one array in depth-first order, like a `FlatBvh`.
+///
+/// Traversing a kd-tree never visits overlapping nodes, which makes it faster than a BVH for
+/// some meshes (CAD models with many long, axis-aligned triangles, for example), but it can't
+/// be refit, and it can take a lot more memory.
+#[derive(Debug, Clone)]
+pub struct KdTree {
+    /// The bounding box of every primitive, which the root splits.
+    pub bounding: Aabb,
+
+    pub nodes: Vec<KdNode>,
+
+    /// The primitive indices of every leaf, back to back.
+    pub indices: Vec<usize>,
+}
+
+/// The two halves of `bounds` on either side of `split`.
+fn split_bounds(bounds: &Aabb, split: &Split) -> (Aabb, Aabb) {
+    let (mut lhs_max, mut rhs_min) = (bounds.max, bounds.min);
+    match split.axis {
+        Axis::X => (lhs_max.x, rhs_min.x) = (split.position, split.position),
+        Axis::Y => (lhs_max.y, rhs_min.y) = (split.position, split.position),
+        Axis::Z => (lhs_max.z, rhs_min.z) = (split.position, split.position),
+    }
+
+    (
+        Aabb::new(bounds.min, lhs_max),
+        Aabb::new(rhs_min, bounds.max),
+    )
+}
+
+/// Find the split of a kd-tree node with the lowest SAH cost, with its cost. Every side of every
+/// reference is a candidate plane. A reference flat in the plane of the split goes below it.
+fn kd_split_candidate(refs: &[Reference], bounds: &Aabb) -> Option<(Float, Split)> {
+    let area = bounds.surface_area();
+    if area <= 0. {
+        return None;
+    }
+
+    let mut best: Option<(Float, Split)> = None;
+    for axis in [Axis::X, Axis::Y, Axis::Z] {
+        let (low, high) = (bounds.min.axis(axis), bounds.max.axis(axis));
+        if high <= low {
+            continue;
+        }
+
+        // the sides of the references, sorted so the references on each side of a plane can be
+        // counted with a binary search
+        let mut mins = Vec::with_capacity(refs.len());
+        let mut maxs = Vec::with_capacity(refs.len());
+        let mut flats = Vec::new();
+        for reference in refs.iter() {
+            let (min, max) = (
+                reference.bounding_box.min.axis(axis),
+                reference.bounding_box.max.axis(axis),
+            );
+            if min == max {
+                flats.push(min);
+            } else {
+                mins.push(min);
+                maxs.push(max);
+            }
+        }
+        mins.sort_by(Float::total_cmp);
+        maxs.sort_by(Float::total_cmp);
+        flats.sort_by(Float::total_cmp);
+
+        for &position in mins.iter().chain(maxs.iter()).chain(flats.iter()) {
+            // planes on the sides of the node would cut off nothing
+            if position <= low || position >= high {
+                continue;
+            }
+
+            let flats_below = flats.partition_point(|&f| f <= position);
+            let n_lhs = mins.partition_point(|&m| m < position) + flats_below;
+            let n_rhs =
+                maxs.len() - maxs.partition_point(|&m| m <= position) + flats.len() - flats_below;
+
+            let split = Split { axis, position };
+            let (lhs_bounds, rhs_bounds) = split_bounds(bounds, &split);
+            let mut cost = KD_TRAVERSAL_COST
+                + KD_INTERSECTION_COST
+                    * (n_lhs as Float * lhs_bounds.surface_area()
+                        + n_rhs as Float * rhs_bounds.surface_area())
+                    / area;
+            if n_lhs == 0 || n_rhs == 0 {
+                cost *= 1. - KD_EMPTY_BONUS;
+            }
+
+            if best.as_ref().is_none_or(|(best_cost, _)| cost < *best_cost) {
+                best = Some((cost, split));
+            }
+        }
+    }
+
+    best
+}
+
+struct KdBuilder<'a, P> {
+    prims: &'a [P],
+    max_depth: usize,
+    output: KdTree,
+}
+
+impl<P: Primitive> KdBuilder<'_, P> {
+    fn build(&mut self, refs: Vec<Reference>, bounds: Aabb, depth: usize) {
+        let leaf_cost = KD_INTERSECTION_COST * refs.len() as Float;
+        let split = if refs.len() > 1 && depth < self.max_depth {
+            kd_split_candidate(&refs, &bounds).filter(|(cost, _)| *cost < leaf_cost)
+        } else {
+            None
+        };
+
+        let split = match split {
+            Some((_, split)) => split,
+            None => return self.create_leaf(&refs),
+        };
+
+        let (lhs_bounds, rhs_bounds) = split_bounds(&bounds, &split);
+        let mut lhs = Vec::new();
+        let mut rhs = Vec::new();
+        for reference in refs.iter() {
+            let min = reference.bounding_box.min.axis(split.axis);
+            let max = reference.bounding_box.max.axis(split.axis);
+            if min == max {
+                if min <= split.position {
+                    lhs.push(reference.clone());
+                } else {
+                    rhs.push(reference.clone());
+                }
+                continue;
+            }
+
+            // clip straddling primitives to each side, so their boxes stay tight further down
+            let (lhs_box, rhs_box) = if min < split.position && max > split.position {
+                self.prims[reference.prim_idx].split(&reference.bounding_box, &split)
+            } else {
+                (Aabb::default(), Aabb::default())
+            };
+            let clipped = |side: Aabb, side_bounds: &Aabb| {
+                if side.is_empty() {
+                    reference.bounding_box.intersection(side_bounds)
+                } else {
+                    side
+                }
+            };
+
+            if min < split.position {
+                lhs.push(Reference {
+                    prim_idx: reference.prim_idx,
+                    bounding_box: clipped(lhs_box, &lhs_bounds),
+                });
+            }
+            if max > split.position {
+                rhs.push(Reference {
+                    prim_idx: reference.prim_idx,
+                    bounding_box: clipped(rhs_box, &rhs_bounds),
+                });
+            }
+        }
+        drop(refs);
+
+        let this = self.output.nodes.len();
+        self.output.nodes.push(KdNode::Branch {
+            axis: split.axis,
+            position: split.position,
+            rhs: 0,
+        });
+
+        self.build(lhs, lhs_bounds, depth + 1);
+        self.output.nodes[this] = KdNode::Branch {
+            axis: split.axis,
+            position: split.position,
+            rhs: self.output.nodes.len(),
+        };
+        self.build(rhs, rhs_bounds, depth + 1);
+    }
+
+    fn create_leaf(&mut self, refs: &[Reference]) {
+        self.output.nodes.push(KdNode::Leaf {
+            start: self.output.indices.len(),
+            len: refs.len(),
+        });
+        self.output.indices.extend(refs.iter().map(|r| r.prim_idx));
+    }
+}
+
+impl KdTree {
+    pub fn new<P: Primitive>(prims: &[P]) -> Self {
+        let refs = prims
+            .iter()
+            .enumerate()
+            .map(|(i, prim)| Reference {
+                prim_idx: i,
+                bounding_box: prim.bounding_box().clone(),
+            })
+            .collect::<Vec<_>>();
+
+        let bounding = refs
+            .iter()
+            .fold(Aabb::default(), |acc, r| acc.union(&r.bounding_box));
+
+        // the usual depth limit for kd-trees, since deeper ones mostly just duplicate primitives
+        let max_depth = (8. + 1.3 * (refs.len().max(1) as Float).log2()) as usize;
+
+        let mut builder = KdBuilder {
+            prims,
+            max_depth: max_depth.min(KD_MAX_DEPTH),
+            output: Self {
+                bounding: bounding.clone(),
+                nodes: Vec::new(),
+                indices: Vec::new(),
+            },
+        };
+        builder.build(refs, bounding, 0);
+
+        builder.output
+    }
+}
+
+impl Accelerator for KdTree {
+    fn bounding(&self) -> &Aabb {
+        &self.bounding
+    }
+
+    fn traverse<F>(&self, ray: &Ray, mut f: F) -> usize
+    where
+        F: FnMut(usize) -> Float,
+    {
+        let (mut t_min, mut t_max) = match self.bounding.intersect(ray) {
+            Some(interval) => interval,
+            None => return 0,
+        };
+
+        // a branch pushes at most one node, and there are at most `KD_MAX_DEPTH` above any node
+        let mut stack = [(0, 0., 0.); KD_MAX_DEPTH];
+        let mut stack_len = 0;
+        let mut max_t = Float::INFINITY;
+        let mut idx = 0;
+        let mut visited = 0;
+
+        loop {
+            visited += 1;
+            match self.nodes[idx] {
+                KdNode::Branch {
+                    axis,
+                    position,
+                    rhs,
+                } => {
+                    // the child the ray starts in comes first; a ray in the plane stays below it
+                    let origin = ray.origin.axis(axis);
+                    let direction = ray.direction.axis(axis);
+                    let (near, far) =
+                        if origin < position || (origin == position && direction <= 0.) {
+                            (idx + 1, rhs)
+                        } else {
+                            (rhs, idx + 1)
+                        };
+
+                    // NaN when the ray runs along the plane, and never crosses it
+                    let t = (position - origin) / direction;
+                    if t.is_nan() || t > t_max || t <= 0. {
+                        idx = near;
+                    } else if t < t_min {
+                        idx = far;
+                    } else {
+                        stack[stack_len] = (far, t, t_max);
+                        stack_len += 1;
+                        idx = near;
+                        t_max = t;
+                    }
+                    continue;
+                }
+                KdNode::Leaf { start, len } => {
+                    for &i in self.indices[start..start + len].iter() {
+                        max_t = max_t.min(f(i));
+                    }
+                }
+            }
+
+            // pick up the next node that's still close enough to matter
+            loop {
+                if stack_len == 0 {
+                    return visited;
+                }
+                stack_len -= 1;
+
+                let (next, near, far) = stack[stack_len];
+                if near <= max_t {
+                    (idx, t_min, t_max) = (next, near, far);
+                    break;
+                }
+            }
+        }
+    }
+
+    fn stats(&self) -> BvhStats {
+        let root_area = self.bounding.surface_area();
+        let mut stats = BvhStats::new(&self.nodes, &self.indices);
+
+        // the depth and bounds of each node on the stack, which is walked in the same order as
+        // it's stored
+        let mut stack = vec![(0, self.bounding.clone())];
+        for node in self.nodes.iter() {
+            let (depth, bounds) = stack.pop().unwrap_or_default();
+            stats.depth = stats.depth.max(depth);
+
+            // the probability that a ray through the root also goes through this node
+            let weight = if root_area > 0. {
+                bounds.surface_area() / root_area
+            } else {
+                1.
+            };
+
+            match *node {
+                KdNode::Branch { axis, position, .. } => {
+                    let (lhs, rhs) = split_bounds(&bounds, &Split { axis, position });
+                    stack.push((depth + 1, rhs));
+                    stack.push((depth + 1, lhs));
+                    stats.add_branch(weight);
+                }
+                KdNode::Leaf { len, .. } => stats.add_leaf(len, weight),
+            }
+        }
+
+        stats
+    }
 }
 
 #[derive(Clone)]
@@ -468,8 +1635,38 @@ impl Primitive for Triangle {
         &self.v
     }
 
-    fn split(&self, _split: Split) -> (Self, Option<Self>) {
-        (self.clone(), None) // TODO: this eventually
+    fn split(&self, bounds: &Aabb, split: &Split) -> (Aabb, Aabb) {
+        // clip the triangle's edges against the plane, keeping the points on either side
+        let mut lhs = Vec::with_capacity(4);
+        let mut rhs = Vec::with_capacity(4);
+        for i in 0..3 {
+            let (a, b) = (self.v[i], self.v[(i + 1) % 3]);
+            let da = a.axis(split.axis) - split.position;
+            let db = b.axis(split.axis) - split.position;
+
+            if da <= 0. {
+                lhs.push(a);
+            }
+            if da >= 0. {
+                rhs.push(a);
+            }
+            if (da < 0. && db > 0.) || (da > 0. && db < 0.) {
+                let p = a + (b - a) * (da / (da - db));
+                lhs.push(p);
+                rhs.push(p);
+            }
+        }
+
+        let clip = |points: &[Vector3]| {
+            let aabb = Aabb::from_vecs(points).intersection(bounds);
+            if points.is_empty() || aabb.is_empty() {
+                Aabb::default()
+            } else {
+                aabb
+            }
+        };
+
+        (clip(&lhs), clip(&rhs))
     }
 
     fn bounding_box(&self) -> &Aabb {
@@ -0,0 +1,152 @@
+//! A builder for scenes, so that a scene can be put together in one expression, without filling
+//! in every field of its objects and lights by hand.
+//!
+//! ```no_run
+//! use raytracer::{builder::SceneBuilder, material::Material, math::Vector3};
+//!
+//! let scene = SceneBuilder::new()
+//!     .resolution(640, 480)
+//!     .camera_at(Vector3::new(0., 1., -5.), 0., 0.)
+//!     .add_sphere(Vector3::new(0., 1., 0.), 1., Material::default())
+//!     .add_plane(Vector3::new(0., 0., 0.), Vector3::up(), Material::default())
+//!     .sun(Vector3::new(-1., -2., 1.), 1.)
+//!     .build();
+//! scene.render_to("render.png", image::ImageFormat::Png);
+//! ```
+
+use crate::{
+    camera::Camera,
+    lighting::{Light, Point, Sun},
+    material::{Color, Material},
+    math::{Float, Vector3},
+    object::{Aabb, Plane, SceneObject, Sphere},
+    scene::{Scene, SceneOptions},
+    skybox::Skybox,
+};
+
+/// A builder for a [`Scene`]. Anything not set is left as in [`Scene::default`]: an 800x600
+/// camera at the origin, a skybox colored by ray direction, default options, and no objects or
+/// lights.
+#[derive(Default)]
+pub struct SceneBuilder {
+    scene: Scene,
+}
+
+impl SceneBuilder {
+    /// Instantiate a new `SceneBuilder` of an empty scene.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the camera.
+    pub fn camera(mut self, camera: Camera) -> Self {
+        self.scene.camera = camera;
+        self
+    }
+
+    /// Move the camera to `origin`, turned by `yaw` and `pitch` (in radians).
+    pub fn camera_at(mut self, origin: Vector3, yaw: Float, pitch: Float) -> Self {
+        self.scene.camera.origin = origin;
+        self.scene.camera.yaw = yaw;
+        self.scene.camera.pitch = pitch;
+        self.scene.camera.orientation = None;
+        self
+    }
+
+    /// Set the camera's viewport size, in pixels.
+    pub fn resolution(mut self, vw: i32, vh: i32) -> Self {
+        self.scene.camera.vw = vw;
+        self.scene.camera.vh = vh;
+        self
+    }
+
+    /// Set the camera's vertical FOV, in degrees.
+    pub fn fov(mut self, fov: Float) -> Self {
+        self.scene.camera.set_fov(fov);
+        self
+    }
+
+    /// Add any object.
+    pub fn add_object<O: SceneObject + 'static>(mut self, object: O) -> Self {
+        self.scene.add_object(Box::new(object));
+        self
+    }
+
+    /// Add any object under `name`, see [`Scene::add_named_object`].
+    pub fn add_named_object<O: SceneObject + 'static>(
+        mut self,
+        name: impl Into<String>,
+        object: O,
+    ) -> Self {
+        self.scene.add_named_object(name, Box::new(object));
+        self
+    }
+
+    /// Add a sphere.
+    pub fn add_sphere(self, origin: Vector3, radius: Float, material: Material) -> Self {
+        self.add_object(Sphere::new(origin, radius, material))
+    }
+
+    /// Add a plane through `origin`, facing along `normal`.
+    pub fn add_plane(self, origin: Vector3, normal: Vector3, material: Material) -> Self {
+        self.add_object(Plane::new(origin, normal.normalize(), material))
+    }
+
+    /// Add an axis-aligned box centered on `pos`, reaching out `size` from it along each axis.
+    pub fn add_aabb(self, pos: Vector3, size: Vector3, material: Material) -> Self {
+        self.add_object(Aabb::new(pos, size, material))
+    }
+
+    /// Add any light.
+    pub fn add_light<L: Light + 'static>(mut self, light: L) -> Self {
+        self.scene.lights.push(Box::new(light));
+        self
+    }
+
+    /// Add a white sun light shining along `vector`, with default shadows.
+    pub fn sun(self, vector: Vector3, intensity: Float) -> Self {
+        self.add_light(Sun {
+            vector: vector.normalize(),
+            intensity,
+            ..Sun::default()
+        })
+    }
+
+    /// Add a white point light at `position`.
+    pub fn point_light(self, position: Vector3, intensity: Float) -> Self {
+        self.add_light(Point {
+            position,
+            intensity,
+            ..Point::default()
+        })
+    }
+
+    /// Set the skybox.
+    pub fn skybox<S: Skybox + 'static>(mut self, skybox: S) -> Self {
+        self.scene.skybox = Box::new(skybox);
+        self
+    }
+
+    /// Set the scene options.
+    pub fn options(mut self, options: SceneOptions) -> Self {
+        self.scene.options = options;
+        self
+    }
+
+    /// Set the ambient color of the scene.
+    pub fn ambient(mut self, ambient: Color) -> Self {
+        self.scene.options.ambient = ambient;
+        self
+    }
+
+    /// Set how many times a ray can bounce.
+    pub fn max_ray_depth(mut self, max_ray_depth: u32) -> Self {
+        self.scene.options.max_ray_depth = max_ray_depth;
+        self
+    }
+
+    /// Finish building the scene.
+    pub fn build(self) -> Scene {
+        self.scene
+    }
+}
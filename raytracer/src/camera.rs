@@ -1,7 +1,104 @@
-use crate::math::{Matrix, Vector3};
+use crate::math::{Float, Matrix, Quaternion, Vector3};
+
+/// The way the two eyes of a stereo render are packed into the output frame.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StereoLayout {
+    /// The left eye is placed on the left half of the frame, the right eye on the right half.
+    SideBySide,
+
+    /// The left eye is placed on the top half of the frame, the right eye on the bottom half.
+    TopBottom,
+}
+
+/// One of the two eyes of a stereo render.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Eye {
+    Left,
+    Right,
+}
+
+/// Stereo rendering options. When set on a camera, each eye is rendered at the
+/// camera's full viewport size, and both are packed into one frame.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Stereo {
+    /// The interpupillary distance, i.e. the distance between the two eyes, in scene units.
+    pub ipd: Float,
+
+    /// How the two eyes are laid out in the output frame.
+    pub layout: StereoLayout,
+}
+
+impl Default for Stereo {
+    fn default() -> Self {
+        Self {
+            // roughly 64mm, at 2 units to the meter
+            ipd: 0.128,
+            layout: StereoLayout::SideBySide,
+        }
+    }
+}
+
+impl Stereo {
+    /// Map a pixel in the packed output frame to the eye it belongs to, and the
+    /// pixel within that eye's viewport.
+    pub fn eye_at(&self, x: i32, y: i32, vw: i32, vh: i32) -> (Eye, i32, i32) {
+        match self.layout {
+            StereoLayout::SideBySide if x >= vw => (Eye::Right, x - vw, y),
+            StereoLayout::TopBottom if y >= vh => (Eye::Right, x, y - vh),
+            _ => (Eye::Left, x, y),
+        }
+    }
+}
+
+/// Radial lens distortion coefficients, following the Brown-Conrady model used by
+/// OpenCV and most camera calibration tools.
+///
+/// The model maps an ideal point in normalized image coordinates (pixel offsets from the
+/// image center divided by the focal length in pixels) to the point the lens actually
+/// images it at, so `k1` and `k2` from a calibrated camera can be used directly. Negative
+/// values produce barrel distortion, positive values produce pincushion distortion.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Distortion {
+    /// The second-order radial coefficient.
+    pub k1: Float,
+
+    /// The fourth-order radial coefficient.
+    pub k2: Float,
+}
+
+impl Distortion {
+    /// The number of fixed-point iterations used to undistort a point.
+    const UNDISTORT_ITERATIONS: usize = 10;
+
+    /// Compute the factor an ideal normalized image point at radius squared `r2` is scaled by.
+    pub fn factor(&self, r2: Float) -> Float {
+        1. + self.k1 * r2 + self.k2 * r2 * r2
+    }
+
+    /// Find the ideal normalized image point that the lens images at `(x, y)`.
+    ///
+    /// The model has no closed-form inverse, so this iterates the same way OpenCV's
+    /// `undistortPoints` does, which converges quickly for realistic coefficients.
+    pub fn undistort(&self, x: Float, y: Float) -> (Float, Float) {
+        let (mut ux, mut uy) = (x, y);
+        for _ in 0..Self::UNDISTORT_ITERATIONS {
+            let factor = self.factor(ux * ux + uy * uy);
+            if factor <= 0. {
+                break;
+            }
+            ux = x / factor;
+            uy = y / factor;
+        }
+        (ux, uy)
+    }
+}
 
 /// A Camera object. Represents a viewable area that a scene can be rendered to.
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Camera {
     /// The viewport width.
     pub vw: i32,
@@ -12,18 +109,28 @@ pub struct Camera {
     /// The origin of the camera.
     pub origin: Vector3,
 
-    /// The yaw of the camera's rotation.
-    pub yaw: f64,
+    /// The yaw of the camera's rotation. Ignored if `orientation` is set.
+    pub yaw: Float,
 
-    /// The pitch of the camera's rotation.
-    pub pitch: f64,
+    /// The pitch of the camera's rotation. Ignored if `orientation` is set.
+    pub pitch: Float,
+
+    /// The orientation of the camera as a quaternion. If set, this takes precedence
+    /// over `yaw` and `pitch`. Set using [`set_orientation`](Self::set_orientation).
+    pub orientation: Option<Quaternion>,
 
     /// The camera's vertical FOV in degrees. Set using
     /// [`set_fov`](Self::set_fov)
-    pub fov: f64,
+    pub fov: Float,
 
     /// A precomputed value used when determining ray direction from pixel. Do not set.
-    pub chf: f64,
+    pub chf: Float,
+
+    /// Stereo rendering options. If `None`, a single (mono) image is rendered.
+    pub stereo: Option<Stereo>,
+
+    /// Lens distortion. If `None`, the camera is a perfect pinhole camera.
+    pub distortion: Option<Distortion>,
 }
 
 impl Default for Camera {
@@ -34,35 +141,103 @@ impl Default for Camera {
             origin: Vector3::default(),
             yaw: 0.,
             pitch: 0.,
+            orientation: None,
             fov: 60.,
             chf: Self::chf(60.),
+            stereo: None,
+            distortion: None,
         }
     }
 }
 
 impl Camera {
     /// Calculate the chf for an FOV.
-    fn chf(fov: f64) -> f64 {
+    fn chf(fov: Float) -> Float {
         ((90. - fov * 0.5) * 0.017453).tan()
     }
 
-    pub fn set_fov(&mut self, fov: f64) {
+    pub fn set_fov(&mut self, fov: Float) {
         self.fov = fov;
         self.chf = Self::chf(fov);
     }
 
+    /// Set the orientation of the camera from a quaternion, overriding yaw and pitch.
+    pub fn set_orientation(&mut self, orientation: Quaternion) {
+        self.orientation = Some(orientation.normalize());
+    }
+
+    /// Get the orientation of the camera as a quaternion. If no orientation is set,
+    /// the equivalent orientation of the camera's yaw and pitch is returned, so that it
+    /// can be composed with or interpolated toward another orientation.
+    pub fn orientation(&self) -> Quaternion {
+        self.orientation
+            .unwrap_or_else(|| Quaternion::from_euler_zyx(self.pitch, -self.yaw, 0.))
+    }
+
+    /// The approximate angle, in radians, between the rays of two neighboring pixels.
+    pub fn pixel_angle(&self) -> Float {
+        1. / (self.vh as Float * 0.5 * self.chf)
+    }
+
+    /// The size of the final rendered frame. This is the viewport size, unless the camera
+    /// is rendering in stereo, in which case both eyes are packed into the frame.
+    pub fn frame_size(&self) -> (i32, i32) {
+        match self.stereo {
+            None => (self.vw, self.vh),
+            Some(Stereo {
+                layout: StereoLayout::SideBySide,
+                ..
+            }) => (self.vw * 2, self.vh),
+            Some(Stereo {
+                layout: StereoLayout::TopBottom,
+                ..
+            }) => (self.vw, self.vh * 2),
+        }
+    }
+
+    /// Calculate the right vector of the camera. Unlike crossing the forward vector with
+    /// the world up vector, this stays well defined when the camera looks straight up or down.
+    pub fn right(&self) -> Vector3 {
+        match self.orientation {
+            Some(orientation) => orientation.rotate(Vector3::new(1., 0., 0.)),
+            None => Vector3::new(self.yaw.cos(), 0., self.yaw.sin()),
+        }
+    }
+
+    /// Calculate the origin of one eye. The eyes are offset from the camera's origin
+    /// along its right vector, by half of the interpupillary distance each.
+    pub fn eye_origin(&self, eye: Eye, ipd: Float) -> Vector3 {
+        let right = self.right();
+        match eye {
+            Eye::Left => self.origin - right * (ipd * 0.5),
+            Eye::Right => self.origin + right * (ipd * 0.5),
+        }
+    }
+
     /// Calculate the Vector3 direction for a given screen point.
-    pub fn direction_at(&self, x: f64, y: f64) -> Vector3 {
+    pub fn direction_at(&self, x: Float, y: Float) -> Vector3 {
+        if let Some(orientation) = self.orientation {
+            return orientation.rotate(self.direction_fov(x, y));
+        }
+
         (Matrix::from_forward(self.direction_fov(x, y))
             * Matrix::from_euler_xyz(-self.pitch, self.yaw, 0.))
         .forward()
     }
 
     /// Calculate the direction of a pixel on the camera based on the FOV, in camera space.
-    pub fn direction_fov(&self, x: f64, y: f64) -> Vector3 {
-        let nx = x - self.vw as f64 * 0.5;
-        let ny = y - self.vh as f64 * 0.5;
-        let z = self.vh as f64 * 0.5 * self.chf;
-        Vector3::new(nx, -ny, -z).normalize()
+    pub fn direction_fov(&self, x: Float, y: Float) -> Vector3 {
+        let nx = x - self.vw as Float * 0.5;
+        let ny = y - self.vh as Float * 0.5;
+        let z = self.vh as Float * 0.5 * self.chf;
+        match self.distortion {
+            Some(distortion) => {
+                // output pixels are where the lens images a point, so undistort them to find
+                // the ideal point the ray passes through
+                let (ux, uy) = distortion.undistort(nx / z, ny / z);
+                Vector3::new(ux * z, -uy * z, -z).normalize()
+            }
+            None => Vector3::new(nx, -ny, -z).normalize(),
+        }
     }
 }
@@ -0,0 +1,217 @@
+//! Checkpoints of renders in progress, so that an interrupted render can be resumed rather than
+//! started over. See [`Scene::render_to_resumable`](crate::scene::Scene::render_to_resumable).
+
+use std::{
+    fmt,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use crate::math::{Float, Vector3};
+
+/// The first bytes of a serialized `Checkpoint`.
+const CHECKPOINT_MAGIC: &[u8; 4] = b"RTCK";
+
+/// The version of the serialized `Checkpoint` format. Bump this whenever the format changes.
+const CHECKPOINT_VERSION: u32 = 1;
+
+/// A hash of everything in a scene that changes how it renders, to tell whether a checkpoint
+/// belongs to it (see [`SceneObject::fingerprint`](crate::object::SceneObject::fingerprint)).
+/// It is FNV-1a, like [`Mesh::geometry_hash`](crate::object::Mesh::geometry_hash), so it is
+/// stable across runs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fingerprint {
+    hash: u64,
+
+    /// The first thing written that couldn't be hashed, see `write_unhashable`.
+    unhashable: Option<&'static str>,
+}
+
+impl Default for Fingerprint {
+    fn default() -> Self {
+        Self {
+            hash: 0xcbf29ce484222325,
+            unhashable: None,
+        }
+    }
+}
+
+impl Fingerprint {
+    /// Start a new, empty fingerprint.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Hash some bytes.
+    pub fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.hash = (self.hash ^ byte as u64).wrapping_mul(0x100000001b3);
+        }
+    }
+
+    /// Hash the `Debug` representation of a value, followed by a separator so that consecutive
+    /// values can't run into each other.
+    pub fn write_debug<T: fmt::Debug + ?Sized>(&mut self, value: &T) {
+        // writing to a fingerprint never fails
+        let _ = fmt::write(self, format_args!("{:?}", value));
+        self.write(&[0xff]);
+    }
+
+    /// Note that `what` changes how the scene renders, but can't be hashed (like a closure), so
+    /// the fingerprint can't tell renders apart. See [`unhashable`](Self::unhashable).
+    pub fn write_unhashable(&mut self, what: &'static str) {
+        self.unhashable.get_or_insert(what);
+    }
+
+    /// The first thing written with `write_unhashable`, if any. A fingerprint with something
+    /// unhashable in it mustn't be trusted to identify a render.
+    pub fn unhashable(&self) -> Option<&'static str> {
+        self.unhashable
+    }
+
+    /// Get the hash of everything written so far.
+    pub fn finish(&self) -> u64 {
+        self.hash
+    }
+}
+
+impl fmt::Write for Fingerprint {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write(s.as_bytes());
+        Ok(())
+    }
+}
+
+/// The rows of a frame rendered so far, as colors in the working color space, before they are
+/// encoded for the output.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Checkpoint {
+    /// Identifies the render this is a checkpoint of, so that a checkpoint of another scene, or
+    /// of the same scene with other settings, isn't resumed by mistake.
+    pub fingerprint: u64,
+
+    pub width: u32,
+    pub height: u32,
+
+    /// The colors of the rows rendered so far, row by row from the top left.
+    pub colors: Vec<Vector3>,
+}
+
+impl Checkpoint {
+    /// Start a checkpoint of a render with no rows rendered yet.
+    pub fn new(fingerprint: u64, width: u32, height: u32) -> Self {
+        Self {
+            fingerprint,
+            width,
+            height,
+            colors: Vec::new(),
+        }
+    }
+
+    /// How many whole rows have been rendered.
+    pub fn rows(&self) -> u32 {
+        (self.colors.len() / (self.width as usize).max(1)) as u32
+    }
+
+    /// Load a checkpoint from a file written by [`save`](Self::save).
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Self::read_from(&mut BufReader::new(File::open(path)?))
+    }
+
+    /// Save this checkpoint to a file. It is written next to `path` first and then moved over
+    /// it, so that being interrupted while saving doesn't lose the last checkpoint.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let mut partial = path.as_os_str().to_owned();
+        partial.push(".partial");
+
+        let mut writer = BufWriter::new(File::create(&partial)?);
+        self.write_to(&mut writer)?;
+        writer.flush()?;
+        drop(writer);
+
+        fs::rename(&partial, path)
+    }
+
+    /// Serialize this checkpoint to `writer` in a little-endian binary format, which can be read
+    /// back with `read_from`. Only whole rows are written, and colors are always written as
+    /// `f64`, whatever `Float` is.
+    #[allow(clippy::unnecessary_cast)]
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let rows = self.rows();
+
+        writer.write_all(CHECKPOINT_MAGIC)?;
+        writer.write_all(&CHECKPOINT_VERSION.to_le_bytes())?;
+        writer.write_all(&self.fingerprint.to_le_bytes())?;
+        writer.write_all(&self.width.to_le_bytes())?;
+        writer.write_all(&self.height.to_le_bytes())?;
+        writer.write_all(&rows.to_le_bytes())?;
+
+        for color in self.colors[..rows as usize * self.width as usize].iter() {
+            for f in [color.x, color.y, color.z] {
+                writer.write_all(&(f as f64).to_le_bytes())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Deserialize a checkpoint written by `write_to`.
+    #[allow(clippy::unnecessary_cast)]
+    pub fn read_from<R: Read>(reader: &mut R) -> io::Result<Self> {
+        fn invalid(message: &str) -> io::Error {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("invalid checkpoint: {}", message),
+            )
+        }
+
+        fn read_u32<R: Read>(reader: &mut R) -> io::Result<u32> {
+            let mut bytes = [0; 4];
+            reader.read_exact(&mut bytes)?;
+            Ok(u32::from_le_bytes(bytes))
+        }
+
+        fn read_u64<R: Read>(reader: &mut R) -> io::Result<u64> {
+            let mut bytes = [0; 8];
+            reader.read_exact(&mut bytes)?;
+            Ok(u64::from_le_bytes(bytes))
+        }
+
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != CHECKPOINT_MAGIC {
+            return Err(invalid("bad magic"));
+        }
+        if read_u32(reader)? != CHECKPOINT_VERSION {
+            return Err(invalid("unsupported version"));
+        }
+
+        let fingerprint = read_u64(reader)?;
+        let width = read_u32(reader)?;
+        let height = read_u32(reader)?;
+        let rows = read_u32(reader)?;
+        if rows > height {
+            return Err(invalid("more rows than the frame has"));
+        }
+
+        // don't trust the size with a huge allocation up front
+        let count = width as usize * rows as usize;
+        let mut colors = Vec::with_capacity(count.min(1 << 16));
+        for _ in 0..count {
+            let mut channels = [0.; 3];
+            for f in channels.iter_mut() {
+                *f = f64::from_bits(read_u64(reader)?) as Float;
+            }
+            colors.push(Vector3::new(channels[0], channels[1], channels[2]));
+        }
+
+        Ok(Self {
+            fingerprint,
+            width,
+            height,
+            colors,
+        })
+    }
+}
@@ -0,0 +1,227 @@
+//! A minimal OpenEXR writer, for uncompressed single-part scanline images of 32-bit float
+//! channels. That covers writing renders out with their full dynamic range, without pulling
+//! in a whole EXR library.
+
+use std::io::{self, Write};
+
+/// The magic number every OpenEXR file starts with.
+const MAGIC: [u8; 4] = [0x76, 0x2f, 0x31, 0x01];
+
+/// The format version, 2, with no flags set: a single-part scanline image with short names.
+const VERSION: [u8; 4] = [2, 0, 0, 0];
+
+/// The pixel type of 32-bit float channels.
+const PIXEL_TYPE_FLOAT: i32 = 2;
+
+/// Which extra channels to write to an EXR render, besides its color and alpha. See
+/// [`Scene::render_to_exr`](crate::scene::Scene::render_to_exr).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Channels {
+    /// Write the distance to what each pixel sees as `Z`, infinite where it sees the skybox.
+    pub depth: bool,
+
+    /// Write the world-space normal of what each pixel sees as `N.X`, `N.Y` and `N.Z`, zero
+    /// where it sees the skybox.
+    pub normal: bool,
+}
+
+impl Channels {
+    /// The names of the channels written to an EXR render with these extra channels, starting
+    /// with its color and alpha.
+    pub fn names(&self) -> Vec<&'static str> {
+        let mut names = vec!["R", "G", "B", "A"];
+        if self.depth {
+            names.push("Z");
+        }
+        if self.normal {
+            names.extend(["N.X", "N.Y", "N.Z"]);
+        }
+        names
+    }
+}
+
+/// Write an image of `width` by `height` pixels to `writer` as an OpenEXR file. Each channel
+/// is a name, like `R` or `Z`, and a value for every pixel, row by row from the top left.
+/// `attributes` are extra string attributes written to the header, like render metadata.
+///
+/// **Note:** Channel and attribute names are expected to be unique, and shorter than 32 bytes.
+/// Attribute names shouldn't be any of the standard ones, like `channels`.
+pub fn write<W: Write>(
+    writer: &mut W,
+    width: u32,
+    height: u32,
+    channels: &[(&str, &[f32])],
+    attributes: &[(&str, &str)],
+) -> io::Result<()> {
+    let names = channels.iter().map(|&(name, _)| name).collect::<Vec<_>>();
+    let values = channels
+        .iter()
+        .map(|&(_, values)| values)
+        .collect::<Vec<_>>();
+
+    let mut writer = Writer::new(writer, width, height, &names, attributes)?;
+    writer.write_lines(&values)?;
+    writer.finish()?;
+    Ok(())
+}
+
+/// Writes an OpenEXR file a few scanlines at a time, so that the whole image never has to be in
+/// memory at once, e.g. for very large renders. See [`write`] to write a whole image at once.
+pub struct Writer<W: Write> {
+    writer: W,
+    width: u32,
+    height: u32,
+
+    /// For each channel, sorted by name, its index in the order they were given in.
+    order: Vec<usize>,
+
+    /// The next scanline to write.
+    next: u32,
+}
+
+impl<W: Write> Writer<W> {
+    /// Start writing an image of `width` by `height` pixels with the named channels, writing
+    /// its header. `attributes` are written to the header too, like in [`write`].
+    pub fn new(
+        mut writer: W,
+        width: u32,
+        height: u32,
+        channels: &[&str],
+        attributes: &[(&str, &str)],
+    ) -> io::Result<Self> {
+        // channels are listed, and stored in each scanline, sorted by name
+        let mut order = (0..channels.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&i| channels[i]);
+
+        let mut header = Vec::new();
+        header.extend_from_slice(&MAGIC);
+        header.extend_from_slice(&VERSION);
+
+        let mut chlist = Vec::new();
+        for &i in order.iter() {
+            chlist.extend_from_slice(channels[i].as_bytes());
+            chlist.push(0);
+            chlist.extend_from_slice(&PIXEL_TYPE_FLOAT.to_le_bytes());
+            // pLinear and 3 reserved bytes, then the x and y sampling
+            chlist.extend_from_slice(&[0; 4]);
+            chlist.extend_from_slice(&1i32.to_le_bytes());
+            chlist.extend_from_slice(&1i32.to_le_bytes());
+        }
+        chlist.push(0);
+        attribute(&mut header, "channels", "chlist", &chlist);
+
+        // no compression, and scanlines from the top down
+        attribute(&mut header, "compression", "compression", &[0]);
+        let window = [0, 0, width as i32 - 1, height as i32 - 1]
+            .iter()
+            .flat_map(|c: &i32| c.to_le_bytes())
+            .collect::<Vec<_>>();
+        attribute(&mut header, "dataWindow", "box2i", &window);
+        attribute(&mut header, "displayWindow", "box2i", &window);
+        attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+        attribute(
+            &mut header,
+            "pixelAspectRatio",
+            "float",
+            &1f32.to_le_bytes(),
+        );
+        attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+        attribute(
+            &mut header,
+            "screenWindowWidth",
+            "float",
+            &1f32.to_le_bytes(),
+        );
+        for (name, value) in attributes.iter() {
+            attribute(&mut header, name, "string", value.as_bytes());
+        }
+        header.push(0);
+
+        // each scanline is its own chunk, found through a table of their offsets in the file,
+        // which is known up front since the chunks aren't compressed
+        let chunk_size = 8 + line_size(width, channels.len()) as u64;
+        let first_chunk = header.len() as u64 + 8 * height as u64;
+        writer.write_all(&header)?;
+        for y in 0..height as u64 {
+            writer.write_all(&(first_chunk + y * chunk_size).to_le_bytes())?;
+        }
+
+        Ok(Self {
+            writer,
+            width,
+            height,
+            order,
+            next: 0,
+        })
+    }
+
+    /// Write the next scanlines. `channels` has the values of each channel, in the order they
+    /// were named in [`new`](Self::new), row by row from the left of the first scanline. Every
+    /// channel must have the same number of whole rows.
+    pub fn write_lines(&mut self, channels: &[&[f32]]) -> io::Result<()> {
+        let width = self.width as usize;
+        let rows = channels
+            .first()
+            .map_or(0, |values| values.len() / width.max(1));
+        if channels.len() != self.order.len()
+            || channels.iter().any(|values| values.len() != rows * width)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "channels don't have the same number of whole rows",
+            ));
+        }
+        if self.next as usize + rows > self.height as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "more scanlines written than the image has",
+            ));
+        }
+
+        let line_size = line_size(self.width, channels.len());
+        let mut line = Vec::with_capacity(line_size);
+        for row in 0..rows {
+            line.clear();
+            for &i in self.order.iter() {
+                let values = &channels[i][row * width..(row + 1) * width];
+                line.extend(values.iter().flat_map(|v| v.to_le_bytes()));
+            }
+
+            self.writer.write_all(&(self.next as i32).to_le_bytes())?;
+            self.writer.write_all(&(line_size as i32).to_le_bytes())?;
+            self.writer.write_all(&line)?;
+            self.next += 1;
+        }
+
+        Ok(())
+    }
+
+    /// Finish writing the image, making sure every scanline was written, and get back the
+    /// inner writer.
+    pub fn finish(self) -> io::Result<W> {
+        if self.next != self.height {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "not every scanline was written",
+            ));
+        }
+
+        Ok(self.writer)
+    }
+}
+
+/// The size of a scanline's pixel data in bytes.
+fn line_size(width: u32, channels: usize) -> usize {
+    width as usize * 4 * channels
+}
+
+/// Append a header attribute, which is its name, its type name, and the size of its value
+/// followed by the value.
+fn attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    header.extend_from_slice(name.as_bytes());
+    header.push(0);
+    header.extend_from_slice(kind.as_bytes());
+    header.push(0);
+    header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    header.extend_from_slice(value);
+}
@@ -1,12 +1,22 @@
 #![allow(dead_code)]
 #![allow(clippy::many_single_char_names)]
 #![feature(new_uninit)]
+#![cfg_attr(feature = "simd", feature(portable_simd))]
 
 pub mod acceleration;
+pub mod builder;
 pub mod camera;
+pub mod checkpoint;
+pub mod exr;
 pub mod lighting;
 pub mod material;
 pub mod math;
+pub mod metadata;
 pub mod object;
+pub mod pnm;
 pub mod scene;
+pub mod sdl;
+#[cfg(feature = "serde")]
+pub mod serialize;
 pub mod skybox;
+pub mod validate;
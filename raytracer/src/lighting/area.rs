@@ -1,22 +1,93 @@
 use rand::Rng;
 
 use crate::{
+    checkpoint::Fingerprint,
     material::Color,
-    math::{blerp, Ray, Vector3},
-    object::Hit,
-    scene::{Scene, EPSILON},
+    math::{blerp, uniform_sphere, Float, Ray, Vector3},
+    object::{Hit, Mesh},
+    scene::Scene,
 };
 
-use super::{Light, LightShading, METER};
+use super::{Attenuation, Light, LightShading};
 
 /// A volume that an area light can take on.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum AreaSurface {
     /// A sphere with a center and radius.
-    Sphere(Vector3, f64),
+    Sphere(Vector3, Float),
 
     /// A rectangle in space, given four vectors as corners.
     Rectangle([Vector3; 4]),
+
+    /// The triangles of a mesh, for lights with more complicated shapes like rings or signs.
+    Mesh(MeshSurface),
+
+    /// A capsule around the line between two points, with a radius, for tube lights like
+    /// fluorescent tubes or neon strips. With a radius of 0, this is a line.
+    Capsule(Vector3, Vector3, Float),
+}
+
+/// The triangles of a mesh, sampled evenly by their area for an area light.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MeshSurface {
+    tris: Vec<[Vector3; 3]>,
+
+    /// The total area of each triangle and every triangle before it.
+    areas: Vec<Float>,
+}
+
+impl MeshSurface {
+    pub fn new(tris: Vec<[Vector3; 3]>) -> Self {
+        let mut total = 0.;
+        let areas = tris
+            .iter()
+            .map(|[a, b, c]| {
+                total += (*b - *a).cross(*c - *a).magnitude() * 0.5;
+                total
+            })
+            .collect();
+
+        Self { tris, areas }
+    }
+
+    /// Take the triangles of a mesh, where it is right now.
+    pub fn from_mesh(mesh: &Mesh) -> Self {
+        Self::new(
+            mesh.tris
+                .iter()
+                .map(|t| [mesh.verts[t[0]], mesh.verts[t[1]], mesh.verts[t[2]]])
+                .collect(),
+        )
+    }
+
+    /// The total area of the triangles.
+    pub fn area(&self) -> Float {
+        self.areas.last().copied().unwrap_or(0.)
+    }
+
+    /// Sample a point on the triangles, given random numbers from 0 to 1.
+    fn sample<F>(&self, random: F) -> Vector3
+    where
+        F: Fn() -> Float,
+    {
+        if self.tris.is_empty() {
+            return Vector3::default();
+        }
+
+        // pick a triangle with a chance proportional to its area
+        let target = random() * self.area();
+        let i = self
+            .areas
+            .partition_point(|&area| area < target)
+            .min(self.tris.len() - 1);
+
+        // then a point evenly distributed on it
+        let [a, b, c] = self.tris[i];
+        let (r1, r2) = (random().sqrt(), random());
+        a * (1. - r1) + b * (r1 * (1. - r2)) + c * (r1 * r2)
+    }
 }
 
 impl AreaSurface {
@@ -24,19 +95,13 @@ impl AreaSurface {
     /// random number from -1 to 1.
     pub fn sample<F>(&self, random: F) -> Vector3
     where
-        F: Fn() -> f64,
+        F: Fn() -> Float,
     {
         match self {
             Self::Sphere(position, radius) => {
-                // I know this isn't how you're supposed to implement this
-                // but I'm doing it anyway
-                let (mut x, mut y, mut z) = (random(), random(), random());
-                let mag = (x.powi(2) + y.powi(2) + z.powi(2)).sqrt();
-                let d = (random() * 0.5 + 0.5) / mag;
-                x *= d;
-                y *= d;
-                z *= d;
-                Vector3::new(x, y, z) * *radius + *position
+                // a point evenly distributed on the surface of the sphere
+                let dir = uniform_sphere(random() * 0.5 + 0.5, random() * 0.5 + 0.5);
+                dir * *radius + *position
             }
             Self::Rectangle(corners) => blerp(
                 random() * 0.5 + 0.5,
@@ -46,6 +111,32 @@ impl AreaSurface {
                 corners[2],
                 corners[3],
             ),
+            Self::Mesh(mesh) => mesh.sample(|| random() * 0.5 + 0.5),
+            Self::Capsule(a, b, radius) => {
+                let axis = *b - *a;
+                let length = axis.magnitude();
+                let t = random() * 0.5 + 0.5;
+                if *radius <= 0. {
+                    return *a + axis * t;
+                }
+
+                // pick the tube or the caps with a chance proportional to their area
+                let tube = length / (length + 2. * radius);
+                if length > 0. && random() * 0.5 + 0.5 < tube {
+                    let (u, v) = (axis / length).basis();
+                    let angle = random() * crate::math::consts::PI;
+                    return *a + axis * t + (u * angle.cos() + v * angle.sin()) * *radius;
+                }
+
+                // a point evenly distributed on a sphere, flipped onto the outer half of a cap
+                let dir = uniform_sphere(random() * 0.5 + 0.5, random() * 0.5 + 0.5);
+                let (center, outward) = if t < 0.5 { (*a, -axis) } else { (*b, axis) };
+                if dir.dot(outward) < 0. {
+                    center - dir * *radius
+                } else {
+                    center + dir * *radius
+                }
+            }
         }
     }
 }
@@ -53,20 +144,18 @@ impl AreaSurface {
 /// An area light, which is a light that emits in all directions from a specified position,
 /// over a surface area in space, creating softer shadows.
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Area {
     /// The color of this light.
     pub color: Color,
 
     /// The intensity of this light. Not totally sure what real-world unit
     /// to relate this value to...
-    pub intensity: f64,
-
-    /// The power at which specular lighting will be raised to. Generally speaking,
-    /// 16, 32, and 64 are good values.
-    pub specular_power: i32,
+    pub intensity: Float,
 
-    /// The strength at which specular lighting will be applied.
-    pub specular_strength: f64,
+    /// The strength at which specular lighting will be applied. The shape of the highlight
+    /// is determined by the roughness of the lit material.
+    pub specular_strength: Float,
 
     /// The surface representing this area light.
     pub surface: AreaSurface,
@@ -76,8 +165,11 @@ pub struct Area {
 
     /// The maximum distance at which this light can influence a hit point. It
     /// will not be considered if the distance from the hit point to the light is
-    /// greater than this value.
-    pub max_distance: f64,
+    /// greater than this value, and fades out smoothly before it.
+    pub max_distance: Float,
+
+    /// How the intensity of this light falls off with distance.
+    pub attenuation: Attenuation,
 }
 
 impl Default for Area {
@@ -85,11 +177,11 @@ impl Default for Area {
         Self {
             color: Color::new(255, 255, 255),
             intensity: 6.,
-            specular_power: 32,
             specular_strength: 0.7,
             surface: AreaSurface::Sphere(Vector3::new(0., 0., 0.), 0.),
             iterations: 4,
             max_distance: 50.,
+            attenuation: Attenuation::default(),
         }
     }
 }
@@ -99,19 +191,36 @@ impl Light for Area {
         &self.color
     }
 
-    fn intensity(&self) -> f64 {
+    fn intensity(&self) -> Float {
         self.intensity
     }
 
-    fn specular_power(&self) -> i32 {
-        self.specular_power
+    fn specular_strength(&self) -> Float {
+        self.specular_strength
     }
 
-    fn specular_strength(&self) -> f64 {
-        self.specular_strength
+    fn kind(&self) -> &'static str {
+        "area"
     }
 
-    fn shading(&self, ray: &Ray, hit: &Hit, scene: &Scene) -> LightShading {
+    fn clone_box(&self) -> Box<dyn Light> {
+        Box::new(self.clone())
+    }
+
+    fn fingerprint(&self, hasher: &mut Fingerprint) {
+        hasher.write_debug(self);
+    }
+
+    #[cfg(feature = "serde")]
+    fn tagged(&self) -> crate::serialize::TaggedLight<'_> {
+        crate::serialize::TaggedLight::Area(self)
+    }
+
+    fn samples(&self) -> u32 {
+        self.iterations
+    }
+
+    fn shading(&self, _ray: &Ray, hit: &Hit, scene: &Scene) -> LightShading {
         let mut samples = vec![];
 
         for _ in 0..self.iterations {
@@ -129,39 +238,25 @@ impl Light for Area {
 
             let lvec = lvec / dist;
 
-            // calculate diffuse
-            let mut diffuse = hit.normal.dot(lvec).clamp(0., f64::MAX);
-
-            // calculate specular
-            let halfway_dir = (lvec - ray.direction).normalize();
-            let mut specular = hit
-                .normal
-                .dot(halfway_dir)
-                .clamp(0., f64::MAX)
-                .powi(self.specular_power);
-
-            // apply shadowing
-            let shadow_ray = Ray::new(hit.vnear + hit.normal * EPSILON, lvec);
-            if let Some(shadow_hit) = scene.cast_ray_once(&shadow_ray) {
-                if shadow_hit.1.near <= dist {
-                    // TODO: deal with transparency
-
-                    // do we want a shadow_coefficient for point lights? probably not
-                    diffuse *= 0.;
-                    specular *= 0.;
-                }
-            }
+            // apply shadowing, letting light through transparent objects
+            let shadow_ray = Ray::new(scene.shadow_origin(hit), lvec);
+            let transmittance = scene.shadow_transmittance(&shadow_ray, dist);
 
             // calculate intensity
-            let lint = self.intensity / (dist / METER).powi(2);
+            let lint = self.intensity * self.attenuation.attenuate(dist, self.max_distance);
 
-            samples.push(LightShading::new(diffuse, specular, lint));
+            samples.push((lvec, transmittance, lint));
         }
 
-        LightShading::new(
-            samples.iter().map(|s| s.diffuse).sum::<f64>() / samples.len() as f64,
-            samples.iter().map(|s| s.specular).sum::<f64>() / samples.len() as f64,
-            samples.iter().map(|s| s.intensity).sum::<f64>() / samples.len() as f64,
+        // the samples are averaged into a single light from the average direction
+        let n = samples.len() as Float;
+        LightShading::with_transmittance(
+            samples
+                .iter()
+                .fold(Vector3::default(), |acc, s| acc + s.0)
+                .normalize(),
+            samples.iter().fold(Vector3::default(), |acc, s| acc + s.1) / n,
+            samples.iter().map(|s| s.2).sum::<Float>() / n,
         )
     }
 }
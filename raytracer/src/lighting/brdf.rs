@@ -0,0 +1,109 @@
+use crate::math::consts::PI;
+
+use crate::math::{Float, Lerp, Vector3};
+
+/// The reflectance at normal incidence used for all dielectric (non-metallic) materials.
+pub static DIELECTRIC_F0: Float = 0.04;
+
+/// The result of evaluating a BRDF for a single light direction.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BrdfSample {
+    /// The diffuse (Lambertian) reflectance, already tinted by the albedo.
+    pub diffuse: Vector3,
+
+    /// The specular reflectance.
+    pub specular: Vector3,
+}
+
+/// Schlick's approximation of the Fresnel reflectance of a dielectric with reflectance `f0` at
+/// normal incidence, where `cos_theta` is the cosine of the angle between the view and the normal.
+pub fn schlick_fresnel(f0: Float, cos_theta: Float) -> Float {
+    f0 + (1. - f0) * (1. - cos_theta.clamp(0., 1.)).powi(5)
+}
+
+/// The wavelengths (in nanometers) treated as the red, green and blue channels by `thin_film`.
+pub static RGB_WAVELENGTHS: [Float; 3] = [650., 532., 450.];
+
+/// Compute the tint of light reflected off a thin film of `film_ior` and `thickness` (in nanometers)
+/// layered on top of a surface of `substrate_ior`, like a soap bubble or an oil slick.
+///
+/// Light reflected off the top and bottom of the film interferes, which reflects some wavelengths
+/// more than others depending on the viewing angle. The result is normalized so that its average
+/// channel is 1, so it only shifts the hue of reflections and leaves their strength alone.
+/// `cos_theta` is the cosine of the angle between the view and the normal.
+pub fn thin_film(
+    cos_theta: Float,
+    thickness: Float,
+    film_ior: Float,
+    substrate_ior: Float,
+) -> Vector3 {
+    // refract into the film to find the distance light travels through it
+    let sin_theta_i = (1. - cos_theta * cos_theta).max(0.).sqrt();
+    let sin_theta_t = sin_theta_i / film_ior;
+    let cos_theta_t = (1. - sin_theta_t * sin_theta_t).max(0.).sqrt();
+
+    // amplitude reflection coefficients at the top (air to film) and bottom (film to substrate)
+    let r12 = (1. - film_ior) / (1. + film_ior);
+    let r23 = (film_ior - substrate_ior) / (film_ior + substrate_ior);
+
+    let [r, g, b] = RGB_WAVELENGTHS.map(|wavelength| {
+        let phase = 4. * PI * film_ior * thickness * cos_theta_t / wavelength;
+        let interference = 2. * r12 * r23 * phase.cos();
+        (r12 * r12 + r23 * r23 + interference) / (1. + r12 * r12 * r23 * r23 + interference)
+    });
+
+    let mean = (r + g + b) / 3.;
+    if mean <= 0. {
+        return Vector3::new(1., 1., 1.);
+    }
+
+    Vector3::new(r, g, b) / mean
+}
+
+/// Evaluate the Cook-Torrance microfacet BRDF, using the GGX normal distribution,
+/// the Smith-Schlick geometry term and Schlick's Fresnel approximation.
+///
+/// `normal`, `view` and `light` are unit vectors, where `view` and `light` point away from the surface.
+/// `albedo` is the base color of the surface in the range 0-1. Both terms are scaled so that a
+/// white, rough, dielectric surface lit head-on reflects about as much light as a Lambertian
+/// surface without the usual `1 / PI` factor, matching the intensity of lights in this raytracer.
+pub fn cook_torrance(
+    normal: Vector3,
+    view: Vector3,
+    light: Vector3,
+    albedo: Vector3,
+    roughness: Float,
+    metallic: Float,
+) -> BrdfSample {
+    let n_dot_l = normal.dot(light);
+    let n_dot_v = normal.dot(view);
+    if n_dot_l <= 0. || n_dot_v <= 0. {
+        return BrdfSample::default();
+    }
+
+    let halfway = (light + view).normalize();
+    let n_dot_h = normal.dot(halfway).max(0.);
+    let v_dot_h = view.dot(halfway).max(0.);
+
+    // clamp roughness so perfectly smooth surfaces still produce a (tiny) highlight
+    let roughness = roughness.clamp(0.02, 1.);
+    let alpha = roughness * roughness;
+
+    // GGX/Trowbridge-Reitz normal distribution
+    let a2 = alpha * alpha;
+    let d = a2 / (PI * (n_dot_h * n_dot_h * (a2 - 1.) + 1.).powi(2));
+
+    // Smith geometry term with Schlick-GGX
+    let k = (roughness + 1.).powi(2) / 8.;
+    let g = (n_dot_l / (n_dot_l * (1. - k) + k)) * (n_dot_v / (n_dot_v * (1. - k) + k));
+
+    // Schlick's Fresnel approximation, with metals tinting their reflections
+    let f0 = Vector3::new(DIELECTRIC_F0, DIELECTRIC_F0, DIELECTRIC_F0).lerp(albedo, metallic);
+    let fresnel = f0 + (Vector3::new(1., 1., 1.) - f0) * (1. - v_dot_h).powi(5);
+
+    let specular = fresnel * (d * g / (4. * n_dot_l * n_dot_v) * PI * n_dot_l);
+    let kd = (Vector3::new(1., 1., 1.) - fresnel) * (1. - metallic);
+    let diffuse = kd * albedo * n_dot_l;
+
+    BrdfSample { diffuse, specular }
+}
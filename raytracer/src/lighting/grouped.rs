@@ -0,0 +1,86 @@
+use crate::{
+    checkpoint::Fingerprint,
+    material::Color,
+    math::{Float, Ray},
+    object::Hit,
+    scene::Scene,
+    validate::Problem,
+};
+
+use super::{Light, LightLinks, LightShading};
+
+/// A light tagged with a group, so that the lighting of each group can be rendered into its own
+/// buffer with [`Scene::render_light_group`] and rebalanced afterwards without re-rendering.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Grouped<L: Light> {
+    /// The light being grouped.
+    pub light: L,
+
+    /// The name of the group.
+    pub group: String,
+}
+
+impl<L: Light> Grouped<L> {
+    pub fn new(light: L, group: impl Into<String>) -> Self {
+        Self {
+            light,
+            group: group.into(),
+        }
+    }
+}
+
+impl<L: Light> Light for Grouped<L> {
+    fn color(&self) -> &Color {
+        self.light.color()
+    }
+
+    fn intensity(&self) -> Float {
+        self.light.intensity()
+    }
+
+    fn specular_strength(&self) -> Float {
+        self.light.specular_strength()
+    }
+
+    fn kind(&self) -> &'static str {
+        self.light.kind()
+    }
+
+    fn clone_box(&self) -> Box<dyn Light> {
+        Box::new(Grouped::new(self.light.clone_box(), self.group.clone()))
+    }
+
+    fn fingerprint(&self, hasher: &mut Fingerprint) {
+        hasher.write_debug(&self.group);
+        self.light.fingerprint(hasher);
+    }
+
+    fn validate(&self) -> Vec<Problem> {
+        self.light.validate()
+    }
+
+    #[cfg(feature = "serde")]
+    fn tagged(&self) -> crate::serialize::TaggedLight<'_> {
+        crate::serialize::TaggedLight::Grouped {
+            light: &self.light,
+            group: &self.group,
+        }
+    }
+
+    fn links(&self) -> Option<&LightLinks> {
+        self.light.links()
+    }
+
+    fn group(&self) -> Option<&str> {
+        Some(&self.group)
+    }
+
+    fn samples(&self) -> u32 {
+        self.light.samples()
+    }
+
+    fn shading(&self, ray: &Ray, hit: &Hit, scene: &Scene) -> LightShading {
+        self.light.shading(ray, hit, scene)
+    }
+}
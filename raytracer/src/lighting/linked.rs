@@ -0,0 +1,134 @@
+use crate::{
+    checkpoint::Fingerprint,
+    material::Color,
+    math::{Float, Ray},
+    object::Hit,
+    scene::{ObjectHandle, Scene},
+    validate::Problem,
+};
+
+use super::{Light, LightShading};
+
+/// A reference to an object of a scene, either by its name or its handle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ObjectRef {
+    Name(String),
+    Handle(ObjectHandle),
+}
+
+impl ObjectRef {
+    /// Find the handle of the object this refers to in `scene`, if it is still there.
+    pub fn resolve(&self, scene: &Scene) -> Option<ObjectHandle> {
+        let handle = match self {
+            Self::Name(name) => scene.handle(name)?,
+            Self::Handle(handle) => *handle,
+        };
+        scene.objects.contains_key(handle).then_some(handle)
+    }
+
+    /// Whether this refers to the object with `handle` in `scene`.
+    pub fn is(&self, scene: &Scene, handle: ObjectHandle) -> bool {
+        self.resolve(scene) == Some(handle)
+    }
+}
+
+/// Which objects of a scene a light affects.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LightLinks {
+    /// The objects the light affects. If this is empty, it affects every object.
+    pub include: Vec<ObjectRef>,
+
+    /// The objects the light doesn't affect, even if they are included.
+    pub exclude: Vec<ObjectRef>,
+}
+
+impl LightLinks {
+    /// Find the objects these links refer to that aren't in `scene`.
+    pub fn missing<'a>(&'a self, scene: &'a Scene) -> impl Iterator<Item = &'a ObjectRef> {
+        self.include
+            .iter()
+            .chain(self.exclude.iter())
+            .filter(move |r| r.resolve(scene).is_none())
+    }
+
+    /// Whether a light with these links affects the object with `handle` in `scene`.
+    pub fn affects(&self, scene: &Scene, handle: ObjectHandle) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|r| r.is(scene, handle)))
+            && !self.exclude.iter().any(|r| r.is(scene, handle))
+    }
+}
+
+/// A light that only lights some objects of the scene, like a rim light meant for just the
+/// subject of a shot. Objects it doesn't light still cast shadows from it.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Linked<L: Light> {
+    /// The light being linked.
+    pub light: L,
+
+    /// Which objects the light affects.
+    pub links: LightLinks,
+}
+
+impl<L: Light> Linked<L> {
+    pub fn new(light: L, links: LightLinks) -> Self {
+        Self { light, links }
+    }
+}
+
+impl<L: Light> Light for Linked<L> {
+    fn color(&self) -> &Color {
+        self.light.color()
+    }
+
+    fn intensity(&self) -> Float {
+        self.light.intensity()
+    }
+
+    fn specular_strength(&self) -> Float {
+        self.light.specular_strength()
+    }
+
+    fn kind(&self) -> &'static str {
+        self.light.kind()
+    }
+
+    fn clone_box(&self) -> Box<dyn Light> {
+        Box::new(Linked::new(self.light.clone_box(), self.links.clone()))
+    }
+
+    fn fingerprint(&self, hasher: &mut Fingerprint) {
+        hasher.write_debug(&self.links);
+        self.light.fingerprint(hasher);
+    }
+
+    fn validate(&self) -> Vec<Problem> {
+        self.light.validate()
+    }
+
+    #[cfg(feature = "serde")]
+    fn tagged(&self) -> crate::serialize::TaggedLight<'_> {
+        crate::serialize::TaggedLight::Linked {
+            light: &self.light,
+            links: &self.links,
+        }
+    }
+
+    fn links(&self) -> Option<&LightLinks> {
+        Some(&self.links)
+    }
+
+    fn group(&self) -> Option<&str> {
+        self.light.group()
+    }
+
+    fn samples(&self) -> u32 {
+        self.light.samples()
+    }
+
+    fn shading(&self, ray: &Ray, hit: &Hit, scene: &Scene) -> LightShading {
+        self.light.shading(ray, hit, scene)
+    }
+}
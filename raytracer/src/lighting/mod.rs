@@ -1,31 +1,110 @@
 mod area;
+mod brdf;
+mod grouped;
+mod linked;
 mod point;
+mod spot;
 mod sun;
 
-use crate::{material::Color, math::Ray, object::Hit, scene::Scene};
+use crate::{
+    checkpoint::Fingerprint,
+    material::Color,
+    math::{Float, Ray, Vector3},
+    object::Hit,
+    scene::Scene,
+    validate::Problem,
+};
+
+#[cfg(feature = "serde")]
+use crate::serialize::TaggedLight;
 
 pub use area::*;
+pub use brdf::*;
+pub use grouped::*;
+pub use linked::*;
 pub use point::*;
+pub use spot::*;
 pub use sun::*;
 
 /// Approximately how many raytracer units equates to a meter.
 /// Used for lighting.
-pub static METER: f64 = 2.;
+pub static METER: Float = 2.;
+
+/// How the intensity of a light falls off with distance.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Attenuation {
+    /// The intensity falls off with the square of the distance, like real lights.
+    #[default]
+    InverseSquare,
+
+    /// The intensity falls off with the distance, reaching further than real lights.
+    Linear,
+
+    /// The intensity falls off with the distance raised to this power.
+    Exponent(Float),
+}
+
+impl Attenuation {
+    /// The factor the intensity of a light is multiplied by at `dist` away from it. It fades
+    /// smoothly to zero at `max_distance`, so there's no visible edge where lights stop being
+    /// considered.
+    pub fn attenuate(&self, dist: Float, max_distance: Float) -> Float {
+        let d = dist / METER;
+        let falloff = match self {
+            Self::InverseSquare => 1. / (d * d),
+            Self::Linear => 1. / d,
+            Self::Exponent(e) => 1. / d.powf(*e),
+        };
 
-/// The result of a light's influence on a ray intersection.
+        let window = (1. - (dist / max_distance).powi(4)).clamp(0., 1.);
+        falloff * window * window
+    }
+}
+
+/// The result of a light's influence on a ray intersection. The actual reflectance is
+/// calculated by the scene from the hit object's material, see [`cook_torrance`].
 #[derive(Clone, Debug, Default, PartialEq)]
 pub struct LightShading {
-    pub diffuse: f64,
-    pub specular: f64,
-    pub intensity: f64,
+    /// The unit vector pointing from the hit point toward the light.
+    pub direction: Vector3,
+
+    /// How visible the light is from the hit point, from 0 (fully shadowed) to 1.
+    pub visibility: Float,
+
+    /// The intensity of the light at the hit point.
+    pub intensity: Float,
+
+    /// The color the light is filtered by on its way to the hit point, for example by passing
+    /// through colored glass. White if nothing is in the way.
+    pub tint: Vector3,
 }
 
 impl LightShading {
-    pub fn new(diffuse: f64, specular: f64, intensity: f64) -> Self {
+    pub fn new(direction: Vector3, visibility: Float, intensity: Float) -> Self {
         Self {
-            diffuse,
-            specular,
+            direction,
+            visibility,
             intensity,
+            tint: Vector3::new(1., 1., 1.),
+        }
+    }
+
+    /// Create a light shading from how much of each channel of the light reaches the hit point
+    /// (see [`Scene::shadow_transmittance`]), splitting it into a visibility and a tint.
+    pub fn with_transmittance(
+        direction: Vector3,
+        transmittance: Vector3,
+        intensity: Float,
+    ) -> Self {
+        let visibility = transmittance.x.max(transmittance.y).max(transmittance.z);
+        if visibility <= 0. {
+            return Self::new(direction, 0., intensity);
+        }
+
+        Self {
+            tint: transmittance / visibility,
+            ..Self::new(direction, visibility, intensity)
         }
     }
 }
@@ -35,9 +114,112 @@ impl LightShading {
 /// of shading due to the light at a certain point.
 pub trait Light: Send + Sync {
     fn color(&self) -> &Color;
-    fn intensity(&self) -> f64;
-    fn specular_power(&self) -> i32;
-    fn specular_strength(&self) -> f64;
+    fn intensity(&self) -> Float;
+    fn specular_strength(&self) -> Float;
+
+    /// A short, human-readable name for the kind of this light, e.g. `"point"`.
+    fn kind(&self) -> &'static str;
+
+    /// A boxed copy of this light, so that scenes holding it can be cloned.
+    fn clone_box(&self) -> Box<dyn Light>;
+
+    /// Which objects this light affects, if not every object. See [`Linked`].
+    fn links(&self) -> Option<&LightLinks> {
+        None
+    }
+
+    /// The group this light belongs to, if any. See [`Grouped`].
+    fn group(&self) -> Option<&str> {
+        None
+    }
+
+    /// How many shadow rays this light casts toward each point it shades.
+    fn samples(&self) -> u32 {
+        1
+    }
+
+    /// Check this light for mistakes that would make it light the scene wrong. See
+    /// [`Scene::validate`].
+    fn validate(&self) -> Vec<Problem> {
+        Vec::new()
+    }
+
+    /// Hash everything about this light that changes how it lights the scene, so that a
+    /// checkpoint of one scene isn't resumed by another. By default, only its kind, color,
+    /// intensity, specular strength and samples are hashed.
+    fn fingerprint(&self, hasher: &mut Fingerprint) {
+        hasher.write_debug(&(
+            self.kind(),
+            self.color(),
+            self.intensity(),
+            self.specular_strength(),
+            self.samples(),
+        ));
+    }
+
+    /// This light, tagged with its kind so that it can be serialized (see
+    /// [`serialize`](crate::serialize)). Lights from outside this crate can't be.
+    #[cfg(feature = "serde")]
+    fn tagged(&self) -> TaggedLight<'_> {
+        TaggedLight::Unsupported(self.kind())
+    }
 
     fn shading(&self, ray: &Ray, hit: &Hit, scene: &Scene) -> LightShading;
 }
+
+impl<T: Light + ?Sized> Light for Box<T> {
+    fn color(&self) -> &Color {
+        (**self).color()
+    }
+
+    fn intensity(&self) -> Float {
+        (**self).intensity()
+    }
+
+    fn specular_strength(&self) -> Float {
+        (**self).specular_strength()
+    }
+
+    fn kind(&self) -> &'static str {
+        (**self).kind()
+    }
+
+    fn clone_box(&self) -> Box<dyn Light> {
+        (**self).clone_box()
+    }
+
+    fn links(&self) -> Option<&LightLinks> {
+        (**self).links()
+    }
+
+    fn group(&self) -> Option<&str> {
+        (**self).group()
+    }
+
+    fn samples(&self) -> u32 {
+        (**self).samples()
+    }
+
+    fn validate(&self) -> Vec<Problem> {
+        (**self).validate()
+    }
+
+    fn fingerprint(&self, hasher: &mut Fingerprint) {
+        (**self).fingerprint(hasher)
+    }
+
+    #[cfg(feature = "serde")]
+    fn tagged(&self) -> TaggedLight<'_> {
+        (**self).tagged()
+    }
+
+    fn shading(&self, ray: &Ray, hit: &Hit, scene: &Scene) -> LightShading {
+        (**self).shading(ray, hit, scene)
+    }
+}
+
+impl Clone for Box<dyn Light> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
@@ -1,36 +1,49 @@
+use rand::Rng;
+
 use crate::{
+    checkpoint::Fingerprint,
     material::Color,
-    math::{Ray, Vector3},
+    math::{concentric_disk, Float, Onb, Ray, Vector3},
     object::Hit,
-    scene::{Scene, EPSILON},
+    scene::Scene,
 };
 
-use super::{Light, LightShading, METER};
+use super::{Attenuation, Light, LightShading};
 
 /// A point light, which is a light that emits in all directions from a specified position.
 #[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Point {
     /// The color of this light.
     pub color: Color,
 
     /// The intensity of this light. Not totally sure what real-world unit
     /// to relate this value to...
-    pub intensity: f64,
-
-    /// The power at which specular lighting will be raised to. Generally speaking,
-    /// 16, 32, and 64 are good values.
-    pub specular_power: i32,
+    pub intensity: Float,
 
-    /// The strength at which specular lighting will be applied.
-    pub specular_strength: f64,
+    /// The strength at which specular lighting will be applied. The shape of the highlight
+    /// is determined by the roughness of the lit material.
+    pub specular_strength: Float,
 
     /// The position in space of this light.
     pub position: Vector3,
 
     /// The maximum distance at which this light can influence a hit point. It
     /// will not be considered if the distance from the hit point to the light is
-    /// greater than this value.
-    pub max_distance: f64,
+    /// greater than this value, and fades out smoothly before it.
+    pub max_distance: Float,
+
+    /// How the intensity of this light falls off with distance.
+    pub attenuation: Attenuation,
+
+    /// The radius of the light's sphere, which softens the edges of its shadows. With zero,
+    /// shadows are sharp. Unlike an [`super::Area`] light, the light itself still comes from
+    /// the center, only shadow rays are spread over the sphere.
+    pub radius: Float,
+
+    /// How many shadow rays to cast toward the light's sphere, if it has a radius. More
+    /// samples give smoother shadow edges, but take longer.
+    pub samples: u32,
 }
 
 impl Default for Point {
@@ -38,32 +51,68 @@ impl Default for Point {
         Self {
             color: Color::new(255, 255, 255),
             intensity: 6.,
-            specular_power: 32,
             specular_strength: 0.7,
             position: Vector3::new(0., 0., 0.),
             max_distance: 50.,
+            attenuation: Attenuation::default(),
+            radius: 0.,
+            samples: 16,
         }
     }
 }
 
+impl Point {
+    /// Pick a random point on the disk of the light's sphere as seen from along `lvec`.
+    fn sample_disk(&self, lvec: Vector3) -> Vector3 {
+        let onb = Onb::from_w(lvec);
+
+        // uniformly distributed over the disk
+        let mut rng = rand::thread_rng();
+        let (x, y) = concentric_disk(rng.gen(), rng.gen());
+
+        self.position + onb.to_world(Vector3::new(x, y, 0.) * self.radius)
+    }
+}
+
 impl Light for Point {
     fn color(&self) -> &Color {
         &self.color
     }
 
-    fn intensity(&self) -> f64 {
+    fn intensity(&self) -> Float {
         self.intensity
     }
 
-    fn specular_power(&self) -> i32 {
-        self.specular_power
+    fn specular_strength(&self) -> Float {
+        self.specular_strength
+    }
+
+    fn kind(&self) -> &'static str {
+        "point"
     }
 
-    fn specular_strength(&self) -> f64 {
-        self.specular_strength
+    fn clone_box(&self) -> Box<dyn Light> {
+        Box::new(self.clone())
     }
 
-    fn shading(&self, ray: &Ray, hit: &Hit, scene: &Scene) -> LightShading {
+    fn fingerprint(&self, hasher: &mut Fingerprint) {
+        hasher.write_debug(self);
+    }
+
+    #[cfg(feature = "serde")]
+    fn tagged(&self) -> crate::serialize::TaggedLight<'_> {
+        crate::serialize::TaggedLight::Point(self)
+    }
+
+    fn samples(&self) -> u32 {
+        if self.radius > 0. && self.samples > 0 {
+            self.samples
+        } else {
+            1
+        }
+    }
+
+    fn shading(&self, _ray: &Ray, hit: &Hit, scene: &Scene) -> LightShading {
         // vector pointing from hit to light pos
         let lvec = self.position - hit.vnear;
 
@@ -75,32 +124,22 @@ impl Light for Point {
 
         let lvec = lvec / dist;
 
-        // calculate diffuse
-        let mut diffuse = hit.normal.dot(lvec).clamp(0., f64::MAX);
-
-        // calculate specular
-        let halfway_dir = (lvec - ray.direction).normalize();
-        let mut specular = hit
-            .normal
-            .dot(halfway_dir)
-            .clamp(0., f64::MAX)
-            .powi(self.specular_power);
-
-        // apply shadowing
-        let shadow_ray = Ray::new(hit.vnear + hit.normal * EPSILON, lvec);
-        if let Some(shadow_hit) = scene.cast_ray_once(&shadow_ray) {
-            if shadow_hit.1.near <= dist {
-                // TODO: deal with transparency
-
-                // do we want a shadow_coefficient for point lights? probably not
-                diffuse *= 0.;
-                specular *= 0.;
-            }
-        }
+        // apply shadowing, letting light through transparent objects
+        let origin = scene.shadow_origin(hit);
+        let transmittance = if self.radius > 0. && self.samples > 0 {
+            // the average over the light's sphere
+            (0..self.samples).fold(Vector3::default(), |acc, _| {
+                let to = self.sample_disk(lvec) - origin;
+                let dist = to.magnitude();
+                acc + scene.shadow_transmittance(&Ray::new(origin, to / dist), dist)
+            }) / self.samples as Float
+        } else {
+            scene.shadow_transmittance(&Ray::new(origin, lvec), dist)
+        };
 
         // calculate intensity
-        let lint = self.intensity / (dist / METER).powi(2);
+        let lint = self.intensity * self.attenuation.attenuate(dist, self.max_distance);
 
-        LightShading::new(diffuse, specular, lint)
+        LightShading::with_transmittance(lvec, transmittance, lint)
     }
 }
@@ -0,0 +1,152 @@
+use crate::{
+    checkpoint::Fingerprint,
+    material::Color,
+    math::{Float, Ray, Vector3},
+    object::Hit,
+    scene::Scene,
+    validate::{self, Problem},
+};
+
+use super::{Attenuation, Light, LightShading};
+
+/// A spot light, which is a point light that only emits within a cone, like a flashlight or a
+/// stage light.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Spot {
+    /// The color of this light.
+    pub color: Color,
+
+    /// The intensity of this light, like that of a point light.
+    pub intensity: Float,
+
+    /// The strength at which specular lighting will be applied. The shape of the highlight
+    /// is determined by the roughness of the lit material.
+    pub specular_strength: Float,
+
+    /// The position in space of this light.
+    pub position: Vector3,
+
+    /// The direction this light is pointing in. Must be normalized.
+    pub direction: Vector3,
+
+    /// The angle (in radians) from the direction of the light within which it is at full
+    /// intensity.
+    pub inner_angle: Float,
+
+    /// The angle (in radians) from the direction of the light past which it emits nothing.
+    /// Between the inner and outer angles, the light fades out.
+    pub outer_angle: Float,
+
+    /// How quickly the light fades out between the inner and outer angles. Higher values
+    /// fade faster, tightening the edge of the cone.
+    pub falloff: Float,
+
+    /// The maximum distance at which this light can influence a hit point. It
+    /// will not be considered if the distance from the hit point to the light is
+    /// greater than this value, and fades out smoothly before it.
+    pub max_distance: Float,
+
+    /// How the intensity of this light falls off with distance.
+    pub attenuation: Attenuation,
+}
+
+impl Spot {
+    /// How strongly this light emits in `direction` (pointing away from the light), from 0
+    /// outside of its cone to 1 inside of its inner angle.
+    pub fn cone(&self, direction: Vector3) -> Float {
+        let (cos_inner, cos_outer) = (self.inner_angle.cos(), self.outer_angle.cos());
+        let cos = direction.dot(self.direction);
+        if cos <= cos_outer {
+            return 0.;
+        }
+        if cos >= cos_inner {
+            return 1.;
+        }
+
+        let t = (cos - cos_outer) / (cos_inner - cos_outer);
+        (t * t * (3. - 2. * t)).powf(self.falloff)
+    }
+}
+
+impl Default for Spot {
+    fn default() -> Self {
+        Self {
+            color: Color::new(255, 255, 255),
+            intensity: 6.,
+            specular_strength: 0.7,
+            position: Vector3::new(0., 0., 0.),
+            direction: Vector3::new(0., -1., 0.),
+            inner_angle: 0.3,
+            outer_angle: 0.5,
+            falloff: 1.,
+            max_distance: 50.,
+            attenuation: Attenuation::default(),
+        }
+    }
+}
+
+impl Light for Spot {
+    fn color(&self) -> &Color {
+        &self.color
+    }
+
+    fn intensity(&self) -> Float {
+        self.intensity
+    }
+
+    fn specular_strength(&self) -> Float {
+        self.specular_strength
+    }
+
+    fn kind(&self) -> &'static str {
+        "spot"
+    }
+
+    fn clone_box(&self) -> Box<dyn Light> {
+        Box::new(self.clone())
+    }
+
+    fn fingerprint(&self, hasher: &mut Fingerprint) {
+        hasher.write_debug(self);
+    }
+
+    fn validate(&self) -> Vec<Problem> {
+        if validate::is_unit(self.direction) {
+            Vec::new()
+        } else {
+            vec![Problem::UnnormalizedVector("direction")]
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    fn tagged(&self) -> crate::serialize::TaggedLight<'_> {
+        crate::serialize::TaggedLight::Spot(self)
+    }
+
+    fn shading(&self, _ray: &Ray, hit: &Hit, scene: &Scene) -> LightShading {
+        // vector pointing from hit to light pos
+        let lvec = self.position - hit.vnear;
+
+        let dist = lvec.magnitude();
+        if dist > self.max_distance {
+            return LightShading::default();
+        }
+
+        let lvec = lvec / dist;
+
+        // points outside of the cone aren't lit at all, so there's no need to cast a shadow ray
+        let cone = self.cone(-lvec);
+        if cone <= 0. {
+            return LightShading::default();
+        }
+
+        // apply shadowing, letting light through transparent objects
+        let shadow_ray = Ray::new(scene.shadow_origin(hit), lvec);
+        let transmittance = scene.shadow_transmittance(&shadow_ray, dist);
+
+        let lint = self.intensity * cone * self.attenuation.attenuate(dist, self.max_distance);
+
+        LightShading::with_transmittance(lvec, transmittance, lint)
+    }
+}
@@ -1,8 +1,12 @@
+use rand::Rng;
+
 use crate::{
+    checkpoint::Fingerprint,
     material::Color,
-    math::{Ray, Vector3},
+    math::{Float, Onb, Ray, Vector3},
     object::Hit,
-    scene::{Scene, EPSILON},
+    scene::Scene,
+    validate::{self, Problem},
 };
 
 use super::{Light, LightShading};
@@ -10,20 +14,18 @@ use super::{Light, LightShading};
 /// A sun light, which has no position, just a vector direction that is
 /// true for all points in the world.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Sun {
     /// The color of this light.
     pub color: Color,
 
     /// The intensity of this light. Not totally sure what real-world unit
     /// to relate this value to...
-    pub intensity: f64,
-
-    /// The power at which specular lighting will be raised to. Generally speaking,
-    /// 16, 32, and 64 are good values.
-    pub specular_power: i32,
+    pub intensity: Float,
 
-    /// The strength at which specular lighting will be applied.
-    pub specular_strength: f64,
+    /// The strength at which specular lighting will be applied. The shape of the highlight
+    /// is determined by the roughness of the lit material.
+    pub specular_strength: Float,
 
     /// The vector direction of this sun light.
     pub vector: Vector3,
@@ -35,7 +37,15 @@ pub struct Sun {
     ///
     /// For example, if a pixel is in shadow and this value is 0.4, it will render
     /// at 0.4x its base color.
-    pub shadow_coefficient: f64,
+    pub shadow_coefficient: Float,
+
+    /// The angular radius (in radians) of the sun's disk as seen from the ground, which softens
+    /// the edges of its shadows. The real sun's is about 0.0047. With zero, shadows are sharp.
+    pub angular_radius: Float,
+
+    /// How many shadow rays to cast toward the sun's disk, if it has an angular radius. More
+    /// samples give smoother shadow edges, but take longer.
+    pub samples: u32,
 }
 
 impl Default for Sun {
@@ -43,56 +53,104 @@ impl Default for Sun {
         Self {
             color: Color::new(255, 255, 255),
             intensity: 1.,
-            specular_power: 32,
             specular_strength: 0.5,
             vector: Vector3::new(0., -1., 0.),
             shadows: true,
             shadow_coefficient: 0.5,
+            angular_radius: 0.,
+            samples: 16,
         }
     }
 }
 
+impl Sun {
+    /// Pick a random direction toward the sun's disk, which is centered on `lvec`.
+    fn sample_disk(&self, lvec: Vector3) -> Vector3 {
+        let onb = Onb::from_w(lvec);
+
+        // uniformly distributed over the cone of the disk
+        let mut rng = rand::thread_rng();
+        let cos_max = self.angular_radius.cos();
+        let cos = 1. - rng.gen::<Float>() * (1. - cos_max);
+        let sin = (1. - cos * cos).sqrt();
+        let phi = rng.gen::<Float>() * crate::math::consts::TAU;
+
+        onb.to_world(Vector3::new(sin * phi.cos(), sin * phi.sin(), cos))
+            .normalize()
+    }
+}
+
 impl Light for Sun {
     fn color(&self) -> &Color {
         &self.color
     }
 
-    fn intensity(&self) -> f64 {
+    fn intensity(&self) -> Float {
         self.intensity
     }
 
-    fn specular_power(&self) -> i32 {
-        self.specular_power
+    fn specular_strength(&self) -> Float {
+        self.specular_strength
     }
 
-    fn specular_strength(&self) -> f64 {
-        self.specular_strength
+    fn kind(&self) -> &'static str {
+        "sun"
     }
 
-    fn shading(&self, ray: &Ray, hit: &Hit, scene: &Scene) -> LightShading {
-        let lvec = -self.vector;
+    fn clone_box(&self) -> Box<dyn Light> {
+        Box::new(self.clone())
+    }
+
+    fn fingerprint(&self, hasher: &mut Fingerprint) {
+        hasher.write_debug(self);
+    }
 
-        // calculate diffuse
-        let mut diffuse = hit.normal.dot(lvec).clamp(0., f64::MAX);
+    fn validate(&self) -> Vec<Problem> {
+        if validate::is_unit(self.vector) {
+            Vec::new()
+        } else {
+            vec![Problem::UnnormalizedVector("vector")]
+        }
+    }
 
-        // calculate specular
-        let halfway_dir = (lvec - ray.direction).normalize();
-        let mut specular = hit
-            .normal
-            .dot(halfway_dir)
-            .clamp(0., f64::MAX)
-            .powi(self.specular_power);
+    #[cfg(feature = "serde")]
+    fn tagged(&self) -> crate::serialize::TaggedLight<'_> {
+        crate::serialize::TaggedLight::Sun(self)
+    }
+
+    fn samples(&self) -> u32 {
+        if self.angular_radius > 0. && self.samples > 0 {
+            self.samples
+        } else {
+            1
+        }
+    }
+
+    fn shading(&self, _ray: &Ray, hit: &Hit, scene: &Scene) -> LightShading {
+        let lvec = -self.vector;
 
-        // apply shadowing
+        // apply shadowing, letting light through transparent objects. The shadow coefficient
+        // is how much light makes it past opaque objects
+        let mut transmittance = Vector3::new(1., 1., 1.);
         if self.shadows {
-            let shadow_ray = Ray::new(hit.vnear + hit.normal * EPSILON, lvec);
-            if let Some(_shadow_hit) = scene.cast_ray_once(&shadow_ray) {
-                // TODO: deal with transparency
-                diffuse *= self.shadow_coefficient;
-                specular *= self.shadow_coefficient;
-            }
+            let origin = scene.shadow_origin(hit);
+            let transmit = |direction: Vector3| {
+                scene.shadow_transmittance(&Ray::new(origin, direction), Float::INFINITY)
+            };
+
+            let through = if self.angular_radius > 0. && self.samples > 0 {
+                // the average over the sun's disk
+                (0..self.samples).fold(Vector3::default(), |acc, _| {
+                    acc + transmit(self.sample_disk(lvec))
+                }) / self.samples as Float
+            } else {
+                transmit(lvec)
+            };
+
+            let coefficient = self.shadow_coefficient;
+            transmittance = through * (1. - coefficient) + Vector3::new(1., 1., 1.) * coefficient;
         }
 
-        LightShading::new(diffuse, specular, self.intensity)
+        LightShading::with_transmittance(lvec, transmittance, self.intensity)
     }
 }
@@ -0,0 +1,995 @@
+pub mod presets;
+
+use std::{fmt, sync::Arc};
+
+use crate::{
+    checkpoint::Fingerprint,
+    lighting,
+    math::{lerp, Float, Lerp, Vector2, Vector3},
+    scene::EPSILON,
+};
+
+/// A 24-bit color, RGB.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Color {
+    pub fn white() -> Self {
+        Self::new(255, 255, 255)
+    }
+
+    pub fn black() -> Self {
+        Self::new(0, 0, 0)
+    }
+
+    pub fn red() -> Self {
+        Self::new(255, 0, 0)
+    }
+
+    pub fn green() -> Self {
+        Self::new(0, 255, 0)
+    }
+
+    pub fn blue() -> Self {
+        Self::new(0, 0, 255)
+    }
+
+    /// Instantiate a new Color.
+    pub fn new(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+
+    /// Instantiate a new Color from 3 f64s, expected to be in the range 0-1.
+    pub fn newf(r: Float, g: Float, b: Float) -> Self {
+        Self {
+            r: (r.clamp(0., 1.) * 255.0) as u8,
+            g: (g.clamp(0., 1.) * 255.0) as u8,
+            b: (b.clamp(0., 1.) * 255.0) as u8,
+        }
+    }
+
+    /// Instantiate a Color by encoding a linear color (0-1 per channel) to sRGB.
+    pub fn from_linear(v: Vector3) -> Self {
+        Self::newf(
+            linear_to_srgb(v.x),
+            linear_to_srgb(v.y),
+            linear_to_srgb(v.z),
+        )
+    }
+
+    /// Instantiate a Color from a Vector3. Useful for checking normals.
+    pub fn from_normal(n: Vector3) -> Self {
+        Self::newf(n.x / 2. + 0.5, n.y / 2. + 0.5, n.z / 2. + 0.5)
+    }
+
+    /// Instantiate a Color from HSV values.
+    ///
+    /// H is expected to be 0 <= H <= 360.
+    pub fn hsv(h: f32, sat: u8, val: u8) -> Self {
+        let s = sat as f32 / 255.;
+        let v = val as f32 / 255.;
+        let c = s * v;
+        let x = c * (1. - ((h / 60.) % 2. - 1.).abs());
+        let _m = v - c;
+        let (r, g, b) = if h >= 0. && h < 60. {
+            (c, x, 0.)
+        } else if h >= 60. && h < 120. {
+            (x, c, 0.)
+        } else if h >= 120. && h < 180. {
+            (0., c, x)
+        } else if h >= 180. && h < 240. {
+            (0., x, c)
+        } else if h >= 240. && h < 300. {
+            (x, 0., c)
+        } else {
+            (c, 0., x)
+        };
+
+        Self::newf(r as Float, g as Float, b as Float)
+    }
+}
+
+/// Convert an sRGB-encoded color channel (0 to 1) to linear.
+pub fn srgb_to_linear(c: Float) -> Float {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Convert a linear color channel to sRGB (0 to 1), clamping it to the displayable range.
+pub fn linear_to_srgb(c: Float) -> Float {
+    let c = c.clamp(0., 1.);
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1. / 2.4) - 0.055
+    }
+}
+
+/// How the colors of a texture are encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ColorSpace {
+    /// Colors are encoded with the sRGB transfer function, as in most images.
+    Srgb,
+
+    /// Colors are stored linearly, as in data textures like roughness maps.
+    Linear,
+}
+
+impl ColorSpace {
+    /// Convert a color (0-1 per channel) in this color space to linear.
+    pub fn to_linear(&self, color: Vector3) -> Vector3 {
+        match self {
+            Self::Srgb => Vector3::new(
+                srgb_to_linear(color.x),
+                srgb_to_linear(color.y),
+                srgb_to_linear(color.z),
+            ),
+            Self::Linear => color,
+        }
+    }
+
+    /// Convert a linear color (0-1 per channel) to this color space.
+    pub fn from_linear(&self, color: Vector3) -> Vector3 {
+        match self {
+            Self::Srgb => Vector3::new(
+                linear_to_srgb(color.x),
+                linear_to_srgb(color.y),
+                linear_to_srgb(color.z),
+            ),
+            Self::Linear => color,
+        }
+    }
+}
+
+impl From<Vector3> for Color {
+    fn from(v: Vector3) -> Self {
+        Self::newf(v.x, v.y, v.z)
+    }
+}
+
+impl From<image::Rgb<u8>> for Color {
+    fn from(rgb: image::Rgb<u8>) -> Self {
+        rgb.0.into()
+    }
+}
+
+impl From<[u8; 3]> for Color {
+    fn from(slice: [u8; 3]) -> Self {
+        Self::new(slice[0], slice[1], slice[2])
+    }
+}
+
+impl Lerp for Color {
+    fn lerp(self, other: Self, t: Float) -> Self {
+        Color {
+            r: lerp(self.r as Float, other.r as Float, t).clamp(0., 255.) as u8,
+            g: lerp(self.g as Float, other.g as Float, t).clamp(0., 255.) as u8,
+            b: lerp(self.b as Float, other.b as Float, t).clamp(0., 255.) as u8,
+        }
+    }
+}
+
+/// How an image texture is filtered when it is sampled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TextureFilter {
+    /// Use the nearest texel.
+    Nearest,
+
+    /// Blend between the four nearest texels.
+    Bilinear,
+}
+
+/// How an image texture is addressed outside of the 0-1 UV range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum WrapMode {
+    /// Tile the texture.
+    Repeat,
+
+    /// Stretch the edge texels of the texture.
+    Clamp,
+
+    /// Tile the texture, mirroring every other tile.
+    Mirror,
+}
+
+impl WrapMode {
+    /// Wrap a texel index into the range `0..size`.
+    pub fn wrap(&self, i: i64, size: u32) -> u32 {
+        let size = size as i64;
+        (match self {
+            Self::Repeat => i.rem_euclid(size),
+            Self::Clamp => i.clamp(0, size - 1),
+            Self::Mirror => {
+                let m = i.rem_euclid(size * 2);
+                if m < size {
+                    m
+                } else {
+                    size * 2 - 1 - m
+                }
+            }
+        }) as u32
+    }
+}
+
+/// An image with 16 bits per channel, which linear colors need so dark colors don't band.
+type Rgba16Image = image::ImageBuffer<image::Rgba<u16>, Vec<u16>>;
+
+/// The texels of an image texture: its image, followed by each of its mipmaps (each half the
+/// size of the last), if generated.
+#[derive(Debug, Clone)]
+enum Levels {
+    /// Texels as they were loaded, 8 bits per channel in the texture's color space.
+    Encoded(Vec<image::RgbaImage>),
+
+    /// Texels decoded from sRGB to linear, see [`ImageTexture::decoded`].
+    Decoded(Vec<Rgba16Image>),
+}
+
+impl Levels {
+    fn len(&self) -> usize {
+        match self {
+            Self::Encoded(levels) => levels.len(),
+            Self::Decoded(levels) => levels.len(),
+        }
+    }
+
+    fn dimensions(&self, level: usize) -> (u32, u32) {
+        match self {
+            Self::Encoded(levels) => levels[level].dimensions(),
+            Self::Decoded(levels) => levels[level].dimensions(),
+        }
+    }
+
+    /// Get the color (0 to 1 per channel) and alpha of a texel.
+    fn texel(&self, level: usize, x: u32, y: u32) -> (Vector3, Float) {
+        let texel = |[r, g, b, a]: [Float; 4], max: Float| (Vector3::new(r, g, b) / max, a / max);
+        match self {
+            Self::Encoded(levels) => {
+                let [r, g, b, a] = levels[level].get_pixel(x, y).0;
+                texel([r as Float, g as Float, b as Float, a as Float], 255.)
+            }
+            Self::Decoded(levels) => {
+                let [r, g, b, a] = levels[level].get_pixel(x, y).0;
+                texel([r as Float, g as Float, b as Float, a as Float], 65535.)
+            }
+        }
+    }
+
+    /// Generate the mipmaps of the first level, down to a single texel.
+    fn generate_mipmaps(&mut self) {
+        fn generate<P>(levels: &mut Vec<image::ImageBuffer<P, Vec<P::Subpixel>>>)
+        where
+            P: image::Pixel + 'static,
+            P::Subpixel: 'static,
+        {
+            levels.truncate(1);
+            loop {
+                let last = levels.last().unwrap();
+                let (w, h) = (last.width(), last.height());
+                if w == 1 && h == 1 {
+                    break;
+                }
+
+                let level = image::imageops::resize(
+                    last,
+                    (w / 2).max(1),
+                    (h / 2).max(1),
+                    image::imageops::FilterType::Triangle,
+                );
+                levels.push(level);
+            }
+        }
+
+        match self {
+            Self::Encoded(levels) => generate(levels),
+            Self::Decoded(levels) => generate(levels),
+        }
+    }
+}
+
+/// An image texture, optionally with a chain of mipmaps so that it can be sampled
+/// without aliasing from far away. Its alpha channel makes the surface partially transparent.
+#[derive(Debug, Clone)]
+pub struct ImageTexture {
+    /// The image and its mipmaps.
+    levels: Levels,
+
+    /// The filter used when sampling this texture.
+    pub filter: TextureFilter,
+
+    /// How this texture is addressed outside of the 0-1 UV range.
+    pub wrap: WrapMode,
+
+    /// How the colors of this texture's image are encoded.
+    pub color_space: ColorSpace,
+
+    /// Whether any texel of the image is not fully opaque, found once when it is loaded.
+    has_alpha: bool,
+}
+
+impl ImageTexture {
+    /// Instantiate a new, fully opaque ImageTexture from an image, without mipmaps.
+    pub fn new(image: image::RgbImage) -> Self {
+        Self::from_rgba(image::DynamicImage::ImageRgb8(image).into_rgba8())
+    }
+
+    /// Instantiate a new ImageTexture from an image with an alpha channel, without mipmaps.
+    pub fn from_rgba(image: image::RgbaImage) -> Self {
+        Self {
+            has_alpha: image.pixels().any(|p| p.0[3] < 255),
+            levels: Levels::Encoded(vec![image]),
+            filter: TextureFilter::Bilinear,
+            wrap: WrapMode::Repeat,
+            color_space: ColorSpace::Srgb,
+        }
+    }
+
+    /// Generate the mipmaps of this texture, down to a single texel.
+    pub fn with_mipmaps(mut self) -> Self {
+        self.levels.generate_mipmaps();
+        self
+    }
+
+    /// Decode the texels of this texture from sRGB to linear once, up front, so that it is
+    /// filtered and mipmapped in linear space and sampling it gives linear colors. Textures
+    /// whose color space is already linear, or that are already decoded, are unchanged.
+    ///
+    /// Only color textures should be decoded; data like normal maps is meant to be read as is.
+    pub fn decoded(mut self) -> Self {
+        let image = match &self.levels {
+            Levels::Encoded(levels) if self.color_space == ColorSpace::Srgb => &levels[0],
+            _ => return self,
+        };
+
+        let decode = |c: u8| (srgb_to_linear(c as Float / 255.) * 65535.).round() as u16;
+        let decoded = Rgba16Image::from_fn(image.width(), image.height(), |x, y| {
+            let [r, g, b, a] = image.get_pixel(x, y).0;
+            image::Rgba([decode(r), decode(g), decode(b), a as u16 * 257])
+        });
+
+        let mipmaps = self.has_mipmaps();
+        self.levels = Levels::Decoded(vec![decoded]);
+        if mipmaps {
+            self.levels.generate_mipmaps();
+        }
+
+        self
+    }
+
+    /// Set how this texture is addressed outside of the 0-1 UV range.
+    pub fn with_wrap(mut self, wrap: WrapMode) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
+    /// Set how the colors of this texture are encoded.
+    pub fn with_color_space(mut self, color_space: ColorSpace) -> Self {
+        self.color_space = color_space;
+        self
+    }
+
+    /// Set the filter used when sampling this texture.
+    pub fn with_filter(mut self, filter: TextureFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// The full-size image of this texture, 8 bits per channel in its color space. A decoded
+    /// texture is encoded back to sRGB, which gives back the image it was loaded from.
+    pub fn image(&self) -> image::RgbaImage {
+        match &self.levels {
+            Levels::Encoded(levels) => levels[0].clone(),
+            Levels::Decoded(levels) => {
+                let encode = |c: u16| (linear_to_srgb(c as Float / 65535.) * 255.).round() as u8;
+                image::RgbaImage::from_fn(levels[0].width(), levels[0].height(), |x, y| {
+                    let [r, g, b, a] = levels[0].get_pixel(x, y).0;
+                    image::Rgba([encode(r), encode(g), encode(b), (a / 257) as u8])
+                })
+            }
+        }
+    }
+
+    /// The width and height of the full-size image of this texture.
+    pub fn dimensions(&self) -> (u32, u32) {
+        self.levels.dimensions(0)
+    }
+
+    /// Whether or not mipmaps have been generated for this texture.
+    pub fn has_mipmaps(&self) -> bool {
+        self.levels.len() > 1
+    }
+
+    /// Whether this texture has been decoded to linear, see [`ImageTexture::decoded`].
+    pub fn is_decoded(&self) -> bool {
+        matches!(self.levels, Levels::Decoded(_))
+    }
+
+    /// The memory taken by the texels of this texture and its mipmaps, in bytes.
+    pub fn bytes(&self) -> usize {
+        match &self.levels {
+            Levels::Encoded(levels) => levels.iter().map(|level| level.as_raw().len()).sum(),
+            Levels::Decoded(levels) => levels.iter().map(|level| level.as_raw().len() * 2).sum(),
+        }
+    }
+
+    /// Whether or not any texel of this texture is not fully opaque.
+    pub fn has_alpha(&self) -> bool {
+        self.has_alpha
+    }
+
+    /// Get the nearest texel of the full-size image. The color is linear if the texture is
+    /// decoded.
+    pub fn at(&self, uv: Vector2) -> Color {
+        // nudge by half a step so that converting back to a color doesn't truncate
+        (self.nearest(0, uv).0 + Vector3::new(0.5, 0.5, 0.5) / 255.).into()
+    }
+
+    /// Get the alpha (0 to 1) of the full-size image at the given UV coordinates, filtered
+    /// with this texture's filter.
+    pub fn alpha_at(&self, uv: Vector2) -> Float {
+        match self.filter {
+            TextureFilter::Nearest => self.nearest(0, uv).1,
+            TextureFilter::Bilinear => self.bilinear(0, uv).1,
+        }
+    }
+
+    /// Sample this texture, where `footprint` is the approximate size of the sampled area in UV
+    /// coordinates. The footprint is used to select which mipmap(s) to sample from. The color
+    /// (0 to 1 per channel) is linear if the texture is decoded.
+    pub fn sample(&self, uv: Vector2, footprint: f32) -> Vector3 {
+        let (w, h) = self.dimensions();
+        let size = w.max(h) as f32;
+        let max_level = (self.levels.len() - 1) as f32;
+        let lod = (footprint * size).max(1.).log2().clamp(0., max_level);
+
+        match self.filter {
+            TextureFilter::Nearest => self.nearest(lod.round() as usize, uv).0,
+            TextureFilter::Bilinear => {
+                let (l0, t) = (lod.floor() as usize, lod.fract());
+                let (c0, _) = self.bilinear(l0, uv);
+                if t == 0. {
+                    return c0;
+                }
+
+                let (c1, _) = self.bilinear(l0 + 1, uv);
+                c0.lerp(c1, t as Float)
+            }
+        }
+    }
+
+    /// Get the color and alpha of the texel of a level nearest to `uv`.
+    fn nearest(&self, level: usize, uv: Vector2) -> (Vector3, Float) {
+        let (w, h) = self.levels.dimensions(level);
+        let x = self.wrap.wrap((uv.x * w as f32).floor() as i64, w);
+        let y = self.wrap.wrap((uv.y * h as f32).floor() as i64, h);
+        self.levels.texel(level, x, y)
+    }
+
+    /// Bilinearly sample a level, returning its color and alpha.
+    fn bilinear(&self, level: usize, uv: Vector2) -> (Vector3, Float) {
+        let (w, h) = self.levels.dimensions(level);
+
+        // texel centers lie at half-texel offsets
+        let x = uv.x * w as f32 - 0.5;
+        let y = uv.y * h as f32 - 0.5;
+        let (x0, y0) = (x.floor() as i64, y.floor() as i64);
+        let (tx, ty) = ((x - x0 as f32) as Float, (y - y0 as f32) as Float);
+        let (x1, y1) = (self.wrap.wrap(x0 + 1, w), self.wrap.wrap(y0 + 1, h));
+        let (x0, y0) = (self.wrap.wrap(x0, w), self.wrap.wrap(y0, h));
+
+        let texel = |x, y| self.levels.texel(level, x, y);
+        let (c00, a00) = texel(x0, y0);
+        let (c10, a10) = texel(x1, y0);
+        let (c01, a01) = texel(x0, y1);
+        let (c11, a11) = texel(x1, y1);
+        let top = c00.lerp(c10, tx);
+        let bottom = c01.lerp(c11, tx);
+        let alpha = lerp(lerp(a00, a10, tx), lerp(a01, a11, tx), ty);
+
+        (top.lerp(bottom, ty), alpha)
+    }
+}
+
+/// A procedural texture whose color at each UV coordinate is computed by a function, so
+/// users of the library can supply arbitrary shading logic.
+#[derive(Clone)]
+pub struct FunctionTexture(pub Arc<dyn Fn(f32, f32) -> Color + Send + Sync>);
+
+impl FunctionTexture {
+    /// Create a function texture, where `f` maps UV coordinates to a color.
+    pub fn new<F>(f: F) -> Self
+    where
+        F: Fn(f32, f32) -> Color + Send + Sync + 'static,
+    {
+        Self(Arc::new(f))
+    }
+
+    /// Get the color of this texture at the given UV coordinates.
+    pub fn at(&self, uv: Vector2) -> Color {
+        (self.0)(uv.x, uv.y)
+    }
+}
+
+impl fmt::Debug for FunctionTexture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("FunctionTexture")
+    }
+}
+
+/// A texture for a material.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Texture {
+    /// A texture that is just a solid color.
+    Solid(Color),
+
+    /// A texture that is an image. UVs will be used to pull the proper pixel.
+    Image(ImageTexture),
+
+    /// A texture that is a 2x2 checkerboard of two colors.
+    Checkerboard(Color, Color),
+
+    /// A procedural texture computed by a function of the UV coordinates. It is not filtered.
+    Function(FunctionTexture),
+
+    /// A texture taken from the vertex colors of a mesh, interpolated across each face. Objects
+    /// without vertex colors are white.
+    VertexColor,
+}
+
+impl Texture {
+    /// The memory taken by the texels of this texture, in bytes. Only image textures have any.
+    pub fn bytes(&self) -> usize {
+        match self {
+            Self::Image(texture) => texture.bytes(),
+            _ => 0,
+        }
+    }
+
+    /// Hash this texture, see [`Material::fingerprint`]. What function textures compute can't be
+    /// hashed, so they are written as unhashable (see [`Fingerprint::write_unhashable`]).
+    pub fn fingerprint(&self, hasher: &mut Fingerprint) {
+        match self {
+            Self::Image(texture) => {
+                // mipmaps are generated from the image, so only it needs hashing
+                let image = texture.image();
+                hasher.write_debug(&(
+                    image.dimensions(),
+                    texture.filter,
+                    texture.wrap,
+                    texture.color_space,
+                    texture.is_decoded(),
+                ));
+                hasher.write(image.as_raw());
+            }
+            Self::Function(_) => hasher.write_unhashable("a function texture"),
+            texture => hasher.write_debug(texture),
+        }
+    }
+
+    /// Create a procedural texture from a function mapping UV coordinates to a color.
+    pub fn function<F>(f: F) -> Self
+    where
+        F: Fn(f32, f32) -> Color + Send + Sync + 'static,
+    {
+        Self::Function(FunctionTexture::new(f))
+    }
+
+    /// Sample this texture as a tangent-space normal map, where red, green and blue map to
+    /// the X (tangent), Y (bitangent) and Z (normal) axes from -1 to 1.
+    pub fn normal_at(&self, uv: Vector2) -> Vector3 {
+        let color = self.at(uv);
+        Vector3::new(
+            color.r as Float / 255. * 2. - 1.,
+            color.g as Float / 255. * 2. - 1.,
+            color.b as Float / 255. * 2. - 1.,
+        )
+    }
+
+    /// Sample this texture with filtering, where `footprint` is the approximate size of the sampled
+    /// area in UV coordinates (for example, the area covered by a single pixel). Larger footprints
+    /// blur the texture to prevent aliasing. The color is 0 to 1 per channel, and linear for
+    /// decoded image textures.
+    pub fn sample(&self, uv: Vector2, footprint: f32) -> Vector3 {
+        match self {
+            Self::Solid(color) => (*color).into(),
+            Self::Image(image) => image.sample(uv, footprint),
+            Self::Function(function) => function.at(uv).into(),
+            Self::VertexColor => Color::white().into(),
+            Self::Checkerboard(col_a, col_b) => {
+                if footprint <= 0. {
+                    return self.at(uv).into();
+                }
+
+                // box filter the checkerboard analytically: it is the product of two square waves,
+                // and the integral of a square wave is a triangle wave
+                let footprint = footprint.min(1.);
+                let tri = |x: f32| {
+                    let f = x.rem_euclid(1.);
+                    if f < 0.5 {
+                        f
+                    } else {
+                        1. - f
+                    }
+                };
+                let wave =
+                    |x: f32| (tri(x + footprint * 0.5) - tri(x - footprint * 0.5)) / footprint;
+
+                Vector3::from(*col_a).lerp(
+                    (*col_b).into(),
+                    ((1. - wave(uv.x) * wave(uv.y)) * 0.5) as Float,
+                )
+            }
+        }
+    }
+
+    /// Decode an image texture to linear, see [`ImageTexture::decoded`]. Other textures are
+    /// unchanged.
+    pub fn decoded(self) -> Self {
+        match self {
+            Self::Image(image) => Self::Image(image.decoded()),
+            texture => texture,
+        }
+    }
+
+    /// Whether this is an image texture that has been decoded to linear.
+    pub fn is_decoded(&self) -> bool {
+        matches!(self, Self::Image(image) if image.is_decoded())
+    }
+
+    /// How the colors of this texture are encoded. Only image textures can be linear.
+    pub fn color_space(&self) -> ColorSpace {
+        match self {
+            Self::Image(image) => image.color_space,
+            _ => ColorSpace::Srgb,
+        }
+    }
+
+    /// Whether this texture is partially transparent anywhere. Only image textures can be.
+    pub fn has_alpha(&self) -> bool {
+        match self {
+            Self::Image(image) => image.has_alpha(),
+            _ => false,
+        }
+    }
+
+    /// Get the alpha (0 to 1) of this texture at the given UV coordinates. Only image textures
+    /// can be transparent.
+    pub fn alpha_at(&self, uv: Vector2) -> Float {
+        match self {
+            Self::Image(image) => image.alpha_at(uv),
+            _ => 1.,
+        }
+    }
+
+    /// Get the color of this texture at the given UV coordinates, without filtering.
+    pub fn at(&self, uv: Vector2) -> Color {
+        match self {
+            Self::Solid(color) => *color,
+            Self::Image(image) => image.at(uv),
+            Self::Function(function) => function.at(uv),
+            Self::VertexColor => Color::white(),
+            Self::Checkerboard(col_a, col_b) => {
+                match (uv.x.rem_euclid(1.) > 0.5, uv.y.rem_euclid(1.) > 0.5) {
+                    (false, false) => *col_a,
+                    (true, false) => *col_b,
+                    (false, true) => *col_b,
+                    (true, true) => *col_a,
+                }
+            }
+        }
+    }
+}
+
+/// A transformation applied to UV coordinates before a material's textures are sampled.
+/// Coordinates are scaled, then rotated, then offset. Coordinates outside of the 0-1 range
+/// are wrapped by the texture, see [`WrapMode`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UvTransform {
+    /// The offset added to UV coordinates.
+    pub offset: Vector2,
+
+    /// The scale of UV coordinates. For example, a scale of 2 tiles a texture twice along an axis.
+    pub scale: Vector2,
+
+    /// The counter-clockwise rotation of UV coordinates, in radians.
+    pub rotation: f32,
+}
+
+impl UvTransform {
+    /// Transform UV coordinates.
+    pub fn apply(&self, uv: Vector2) -> Vector2 {
+        if *self == Self::default() {
+            return uv;
+        }
+
+        let Vector2 { x: u, y: v } = uv * self.scale;
+        let (sin, cos) = self.rotation.sin_cos();
+        Vector2::new(u * cos - v * sin, u * sin + v * cos) + self.offset
+    }
+}
+
+impl Default for UvTransform {
+    fn default() -> Self {
+        Self {
+            offset: Vector2::new(0., 0.),
+            scale: Vector2::new(1., 1.),
+            rotation: 0.,
+        }
+    }
+}
+
+/// Triplanar projection options. A triplanar-projected texture is sampled by world position
+/// along the three axes, and the three samples are blended by the surface normal, so that
+/// surfaces without UV coordinates can be textured.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Triplanar {
+    /// The size, in world units, of one tile of the texture.
+    pub scale: Float,
+
+    /// How sharply the three projections blend into one another. Higher values produce
+    /// tighter transitions.
+    pub sharpness: Float,
+}
+
+impl Default for Triplanar {
+    fn default() -> Self {
+        Self {
+            scale: 1.,
+            sharpness: 4.,
+        }
+    }
+}
+
+/// A material for a scene object. Over time, this struct
+/// will be populated with more physical rendering
+/// properties.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct Material {
+    /// The texture of this material.
+    pub texture: Texture,
+
+    /// The transformation applied to UV coordinates before any of this material's textures are sampled.
+    pub uv_transform: UvTransform,
+
+    /// If set, this material's texture is projected by world position instead of sampled by UV coordinates.
+    pub triplanar: Option<Triplanar>,
+
+    /// The reflectiveness (0 to 1) of this material.
+    pub reflectiveness: Float,
+
+    /// The transparency of this object. At N=1, the object is completely transparent. At N=0, the object is completely opaque.
+    pub transparency: Float,
+
+    /// The index of refraction of this material. Higher numbers are more affected by refraction.
+    /// At IOR=1, light passes through perfectly.
+    pub ior: Float,
+
+    /// The emissivity of the material, that is, how strongly it emits light. At 0, it is not emissive at all.
+    /// The emitted light is added on top of the lit color of the material.
+    pub emissivity: Float,
+
+    /// The color of the light emitted by this material, scaled by `emissivity`. If this is `None`, the
+    /// material's `texture` is used.
+    pub emission: Option<Texture>,
+
+    /// The roughness (0 to 1) of the surface's microfacets. Smooth surfaces have small, sharp specular
+    /// highlights, and rough surfaces have wide, dim ones. Rough surfaces also reflect a blurrier
+    /// skybox.
+    pub roughness: Float,
+
+    /// How metallic (0 to 1) this material is. Metals have no diffuse lighting and tint their specular
+    /// highlights with their texture color.
+    pub metallic: Float,
+
+    /// The strength (0 to 1) of a clear, glossy dielectric layer coated on top of the material,
+    /// like the lacquer on car paint or varnished wood.
+    pub clearcoat: Float,
+
+    /// The roughness (0 to 1) of the clearcoat layer's highlights.
+    pub clearcoat_roughness: Float,
+
+    /// The thickness (in nanometers) of a thin film on the surface, which makes reflections
+    /// iridescent like a soap bubble or an oil slick. At 0, there is no film.
+    pub thin_film_thickness: Float,
+
+    /// The index of refraction of the thin film, see `thin_film_thickness`.
+    pub thin_film_ior: Float,
+
+    /// An optional opacity mask, where the brightness of the texture is the alpha of the surface.
+    /// Wherever the alpha is below `alpha_cutoff`, the surface is cut out and rays pass through it.
+    pub opacity: Option<Texture>,
+
+    /// The alpha (0 to 1) below which the surface is cut out, see `opacity`.
+    pub alpha_cutoff: Float,
+
+    /// An optional tangent-space normal map (in the common OpenGL, Y-up convention) that
+    /// perturbs the surface normal before lighting, adding fine detail to flat surfaces.
+    pub normal_map: Option<Texture>,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self {
+            texture: Texture::Solid(Color::new(255, 255, 255)),
+            uv_transform: UvTransform::default(),
+            triplanar: None,
+            reflectiveness: 0.,
+            transparency: 0.,
+            ior: 1.3,
+            emissivity: 0.,
+            emission: None,
+            roughness: 0.5,
+            metallic: 0.,
+            clearcoat: 0.,
+            clearcoat_roughness: 0.03,
+            thin_film_thickness: 0.,
+            thin_film_ior: 1.33,
+            opacity: None,
+            alpha_cutoff: 0.5,
+            normal_map: None,
+        }
+    }
+}
+
+impl Material {
+    /// The memory taken by the texels of all of this material's textures, in bytes.
+    pub fn texture_bytes(&self) -> usize {
+        let optional = [&self.emission, &self.opacity, &self.normal_map];
+        self.texture.bytes()
+            + optional
+                .iter()
+                .flat_map(|t| t.iter())
+                .map(Texture::bytes)
+                .sum::<usize>()
+    }
+
+    /// Hash everything about this material that changes how it renders, so that a checkpoint
+    /// of one scene isn't resumed by another (see
+    /// [`SceneObject::fingerprint`](crate::object::SceneObject::fingerprint)).
+    pub fn fingerprint(&self, hasher: &mut Fingerprint) {
+        self.texture.fingerprint(hasher);
+        for texture in [&self.emission, &self.opacity, &self.normal_map] {
+            match texture {
+                Some(texture) => texture.fingerprint(hasher),
+                None => hasher.write_debug(&None::<()>),
+            }
+        }
+        hasher.write_debug(&(self.uv_transform, self.triplanar));
+        hasher.write_debug(&[
+            self.reflectiveness,
+            self.transparency,
+            self.ior,
+            self.emissivity,
+            self.roughness,
+            self.metallic,
+            self.clearcoat,
+            self.clearcoat_roughness,
+            self.thin_film_thickness,
+            self.thin_film_ior,
+            self.alpha_cutoff,
+        ]);
+    }
+
+    /// Get the color of this material's texture at the given UV coordinates.
+    pub fn color_at(&self, uv: Vector2) -> Color {
+        self.texture.at(self.uv_transform.apply(uv))
+    }
+
+    /// Get the filtered color of this material's texture at the given UV coordinates,
+    /// see [`Texture::sample`].
+    pub fn sample_color(&self, uv: Vector2, footprint: f32) -> Vector3 {
+        let scale = self.uv_transform.scale.abs();
+        let scale = scale.x.max(scale.y);
+        self.texture
+            .sample(self.uv_transform.apply(uv), footprint * scale)
+    }
+
+    /// Get the filtered color of this material's texture projected at a world position, blended by
+    /// the surface normal. `footprint` is the size of the sampled area in world units.
+    pub fn triplanar_color(
+        &self,
+        triplanar: &Triplanar,
+        position: Vector3,
+        normal: Vector3,
+        footprint: Float,
+    ) -> Vector3 {
+        let p = position / triplanar.scale;
+        let footprint = (footprint / triplanar.scale) as f32;
+
+        // weigh each projection by how closely the normal faces its axis
+        let weights = Vector3::new(
+            normal.x.abs().powf(triplanar.sharpness),
+            normal.y.abs().powf(triplanar.sharpness),
+            normal.z.abs().powf(triplanar.sharpness),
+        );
+        let weights = weights / (weights.x + weights.y + weights.z);
+
+        let sample =
+            |u: Float, v: Float| self.sample_color(Vector2::new(u as f32, v as f32), footprint);
+        sample(p.z, -p.y) * weights.x + sample(p.x, p.z) * weights.y + sample(p.x, -p.y) * weights.z
+    }
+
+    /// Sample this material's normal map, if any, at the given UV coordinates.
+    pub fn normal_at(&self, uv: Vector2) -> Option<Vector3> {
+        self.normal_map
+            .as_ref()
+            .map(|normal_map| normal_map.normal_at(self.uv_transform.apply(uv)))
+    }
+
+    /// Get the light emitted by this material at the given UV coordinates, in the range 0-1
+    /// (before being scaled by `emissivity`), and linear if its texture is decoded.
+    pub fn emitted_at(&self, uv: Vector2) -> Vector3 {
+        self.emission
+            .as_ref()
+            .unwrap_or(&self.texture)
+            .sample(self.uv_transform.apply(uv), 0.)
+    }
+
+    /// Get the alpha (0 to 1) of this material's opacity mask at the given UV coordinates,
+    /// multiplied by the alpha of its texture.
+    pub fn alpha_at(&self, uv: Vector2) -> Float {
+        let alpha = self.texture_alpha_at(uv);
+        match &self.opacity {
+            Some(opacity) => {
+                let c = opacity.at(self.uv_transform.apply(uv));
+                alpha * (0.2126 * c.r as Float + 0.7152 * c.g as Float + 0.0722 * c.b as Float)
+                    / 255.
+            }
+            None => alpha,
+        }
+    }
+
+    /// Get the alpha (0 to 1) of this material's texture at the given UV coordinates. Surfaces
+    /// whose texture is partially transparent are blended with whatever is behind them.
+    pub fn texture_alpha_at(&self, uv: Vector2) -> Float {
+        match self.triplanar {
+            Some(_) => 1.,
+            None => self.texture.alpha_at(self.uv_transform.apply(uv)),
+        }
+    }
+
+    /// Whether no light ever gets through this material, since it is neither transparent, cut
+    /// out, nor textured with a partially transparent image anywhere, so it always blocks
+    /// shadow rays.
+    pub fn is_opaque(&self) -> bool {
+        self.transparency <= EPSILON
+            && self.opacity.is_none()
+            && (self.triplanar.is_some() || !self.texture.has_alpha())
+    }
+
+    /// Whether or not the surface is cut out (rays pass through it) at the given UV coordinates.
+    pub fn is_cut_out(&self, uv: Vector2) -> bool {
+        self.opacity.is_some() && self.alpha_at(uv) < self.alpha_cutoff
+    }
+
+    /// Get the tint applied to this material's reflections by its thin film, where `cos_theta` is
+    /// the cosine of the angle between the view and the normal. Without a film, this is white.
+    pub fn iridescence(&self, cos_theta: Float) -> Vector3 {
+        if self.thin_film_thickness <= 0. {
+            return Vector3::new(1., 1., 1.);
+        }
+
+        lighting::thin_film(
+            cos_theta,
+            self.thin_film_thickness,
+            self.thin_film_ior,
+            self.ior,
+        )
+    }
+}
@@ -0,0 +1,179 @@
+//! A library of ready-made materials for common real-world surfaces.
+
+use super::{Color, Material, Texture};
+
+/// The names of every preset, as accepted by `by_name`.
+pub static NAMES: &[&str] = &[
+    "glass",
+    "water",
+    "diamond",
+    "soap_bubble",
+    "gold",
+    "silver",
+    "copper",
+    "chrome",
+    "mirror",
+    "rubber",
+    "plastic",
+    "ceramic",
+    "car_paint",
+];
+
+/// Get a preset material by its name, see `NAMES`.
+pub fn by_name(name: &str) -> Option<Material> {
+    Some(match name {
+        "glass" => glass(),
+        "water" => water(),
+        "diamond" => diamond(),
+        "soap_bubble" => soap_bubble(),
+        "gold" => gold(),
+        "silver" => silver(),
+        "copper" => copper(),
+        "chrome" => chrome(),
+        "mirror" => mirror(),
+        "rubber" => rubber(),
+        "plastic" => plastic(),
+        "ceramic" => ceramic(),
+        "car_paint" => car_paint(),
+        _ => return None,
+    })
+}
+
+/// Clear, smooth window glass.
+pub fn glass() -> Material {
+    Material {
+        reflectiveness: 0.5,
+        transparency: 0.9,
+        ior: 1.5,
+        roughness: 0.05,
+        ..Default::default()
+    }
+}
+
+/// Still water with a faint blue tint.
+pub fn water() -> Material {
+    Material {
+        texture: Texture::Solid(Color::new(220, 235, 255)),
+        reflectiveness: 0.4,
+        transparency: 0.9,
+        ior: 1.33,
+        roughness: 0.05,
+        ..Default::default()
+    }
+}
+
+/// A cut diamond, strongly refracting and reflective.
+pub fn diamond() -> Material {
+    Material {
+        reflectiveness: 0.6,
+        transparency: 0.95,
+        ior: 2.42,
+        roughness: 0.02,
+        ..Default::default()
+    }
+}
+
+/// A soap bubble with iridescent reflections.
+pub fn soap_bubble() -> Material {
+    Material {
+        reflectiveness: 0.8,
+        transparency: 0.95,
+        ior: 1.,
+        roughness: 0.05,
+        thin_film_thickness: 400.,
+        thin_film_ior: 1.33,
+        ..Default::default()
+    }
+}
+
+/// Polished gold.
+pub fn gold() -> Material {
+    Material {
+        texture: Texture::Solid(Color::new(255, 195, 86)),
+        reflectiveness: 0.5,
+        roughness: 0.25,
+        metallic: 1.,
+        ..Default::default()
+    }
+}
+
+/// Polished silver.
+pub fn silver() -> Material {
+    Material {
+        texture: Texture::Solid(Color::new(252, 250, 245)),
+        reflectiveness: 0.6,
+        roughness: 0.15,
+        metallic: 1.,
+        ..Default::default()
+    }
+}
+
+/// Slightly worn copper.
+pub fn copper() -> Material {
+    Material {
+        texture: Texture::Solid(Color::new(244, 163, 137)),
+        reflectiveness: 0.4,
+        roughness: 0.3,
+        metallic: 1.,
+        ..Default::default()
+    }
+}
+
+/// Chrome plating.
+pub fn chrome() -> Material {
+    Material {
+        texture: Texture::Solid(Color::new(140, 142, 141)),
+        reflectiveness: 0.9,
+        roughness: 0.05,
+        metallic: 1.,
+        ..Default::default()
+    }
+}
+
+/// A perfect mirror.
+pub fn mirror() -> Material {
+    Material {
+        reflectiveness: 1.,
+        roughness: 0.,
+        metallic: 1.,
+        ..Default::default()
+    }
+}
+
+/// Dark, matte rubber.
+pub fn rubber() -> Material {
+    Material {
+        texture: Texture::Solid(Color::new(30, 30, 30)),
+        roughness: 0.9,
+        ..Default::default()
+    }
+}
+
+/// Smooth white plastic.
+pub fn plastic() -> Material {
+    Material {
+        roughness: 0.3,
+        ..Default::default()
+    }
+}
+
+/// Glazed white ceramic.
+pub fn ceramic() -> Material {
+    Material {
+        texture: Texture::Solid(Color::new(245, 243, 238)),
+        roughness: 0.4,
+        clearcoat: 0.8,
+        ..Default::default()
+    }
+}
+
+/// Glossy red car paint.
+pub fn car_paint() -> Material {
+    Material {
+        texture: Texture::Solid(Color::new(160, 10, 10)),
+        roughness: 0.5,
+        metallic: 0.3,
+        clearcoat: 1.,
+        ..Default::default()
+    }
+}
@@ -1,29 +1,30 @@
 use std::ops::Mul;
 
-use super::Vector3;
+use super::{Float, Quaternion, Vector3};
 
-/// A transformation matrix, which stores a position
-/// and rotation. Can be multiplied by other matrices
-/// to affect rotation and position.
+/// An affine transformation matrix, which stores a position and a rotation, scale or
+/// shear. It acts as a 4x4 matrix whose bottom row is always `0 0 0 1`, and can be
+/// multiplied by other matrices to combine their transformations.
 #[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Matrix {
-    pub x: f64,
-    pub y: f64,
-    pub z: f64,
-    pub m00: f64,
-    pub m01: f64,
-    pub m02: f64,
-    pub m10: f64,
-    pub m11: f64,
-    pub m12: f64,
-    pub m20: f64,
-    pub m21: f64,
-    pub m22: f64,
+    pub x: Float,
+    pub y: Float,
+    pub z: Float,
+    pub m00: Float,
+    pub m01: Float,
+    pub m02: Float,
+    pub m10: Float,
+    pub m11: Float,
+    pub m12: Float,
+    pub m20: Float,
+    pub m21: Float,
+    pub m22: Float,
 }
 
 impl Matrix {
     /// Create a new matrix from 3 coordinates in world space.
-    pub fn new(x: f64, y: f64, z: f64) -> Self {
+    pub fn new(x: Float, y: Float, z: Float) -> Self {
         Matrix {
             x,
             y,
@@ -32,6 +33,23 @@ impl Matrix {
         }
     }
 
+    /// The identity matrix, which doesn't transform anything.
+    pub fn identity() -> Self {
+        Self::default()
+    }
+
+    /// Create a new matrix from the rows of a 4x4 matrix, as returned by
+    /// [`rowed_components`](Self::rowed_components).
+    ///
+    /// **Note:** The bottom row is expected to be `0 0 0 1`. It is ignored otherwise, since
+    /// projective transformations can't be represented.
+    #[rustfmt::skip]
+    pub fn from_rowed_components(rows: [[Float; 4]; 4]) -> Self {
+        debug_assert!(rows[3] == [0., 0., 0., 1.], "matrix is not affine");
+        let [[m00, m01, m02, x], [m10, m11, m12, y], [m20, m21, m22, z], _] = rows;
+        Matrix { x, y, z, m00, m01, m02, m10, m11, m12, m20, m21, m22 }
+    }
+
     /// Create a new matrix from a forward vector.
     ///
     /// **Note:** This vector is expected to be normalized.
@@ -56,8 +74,18 @@ impl Matrix {
         }
     }
 
+    /// Create a new matrix that scales each axis by the components of `scale`.
+    pub fn from_scale(scale: Vector3) -> Self {
+        Matrix {
+            m00: scale.x,
+            m11: scale.y,
+            m22: scale.z,
+            ..Default::default()
+        }
+    }
+
     #[rustfmt::skip]
-    fn euler_matrices(x: f64, y: f64, z: f64) -> (Self, Self, Self) {
+    fn euler_matrices(x: Float, y: Float, z: Float) -> (Self, Self, Self) {
         (
             Matrix { x: 0., y: 0., z: 0., m00: 1., m01: 0., m02: 0., m10: 0., m11: x.cos(), m12: -x.sin(), m20: 0., m21: x.sin(), m22: x.cos() },
             Matrix { x: 0., y: 0., z: 0., m00: y.cos(), m01: 0., m02: y.sin(), m10: 0., m11: 1., m12: 0., m20: -y.sin(), m21: 0., m22: y.cos() },
@@ -66,20 +94,20 @@ impl Matrix {
     }
 
     /// Create a new matrix from Euler angles applied in XYZ order.
-    pub fn from_euler_xyz(x: f64, y: f64, z: f64) -> Self {
+    pub fn from_euler_xyz(x: Float, y: Float, z: Float) -> Self {
         let (a, b, c) = Self::euler_matrices(x, y, z);
         a * b * c
     }
 
     /// Create a new matrix from Euler angles applied in ZYX order.
-    pub fn from_euler_zyx(x: f64, y: f64, z: f64) -> Self {
+    pub fn from_euler_zyx(x: Float, y: Float, z: Float) -> Self {
         let (a, b, c) = Self::euler_matrices(x, y, z);
         c * b * a
     }
 
     /// Get the components of this matrix.
     #[rustfmt::skip]
-    pub fn components(self) -> [f64; 16] {
+    pub fn components(self) -> [Float; 16] {
         [
             self.m00, self.m01, self.m02, self.x,
             self.m10, self.m11, self.m12, self.y,
@@ -90,7 +118,7 @@ impl Matrix {
 
     /// Get the rowed components of this matrix.
     #[rustfmt::skip]
-    pub fn rowed_components(self) -> [[f64; 4]; 4] {
+    pub fn rowed_components(self) -> [[Float; 4]; 4] {
         [
             [self.m00, self.m01, self.m02, self.x],
             [self.m10, self.m11, self.m12, self.y],
@@ -99,6 +127,84 @@ impl Matrix {
         ]
     }
 
+    /// Get the determinant of this matrix, the factor it scales volumes by. It is negative
+    /// if the matrix mirrors space, and zero if the matrix can't be inverted.
+    pub fn determinant(self) -> Float {
+        self.m00 * (self.m11 * self.m22 - self.m12 * self.m21)
+            + self.m10 * (self.m02 * self.m21 - self.m01 * self.m22)
+            + self.m20 * (self.m01 * self.m12 - self.m02 * self.m11)
+    }
+
+    /// Get the transpose of the rotation/scale part of this matrix. The translation is
+    /// dropped, since transposing it would leave a projective matrix.
+    ///
+    /// The transpose of a rotation is its inverse, and the transpose of an inverse
+    /// transforms normals (see [`transform_normal`](Self::transform_normal)).
+    #[rustfmt::skip]
+    pub fn transpose(self) -> Self {
+        Matrix {
+            x: 0., y: 0., z: 0.,
+            m00: self.m00, m01: self.m10, m02: self.m20,
+            m10: self.m01, m11: self.m11, m12: self.m21,
+            m20: self.m02, m21: self.m12, m22: self.m22,
+        }
+    }
+
+    /// Get the inverse of this matrix, which undoes its transformation. If the matrix
+    /// flattens space (for example, scaling an axis by zero), the result is not finite.
+    pub fn inverse(self) -> Self {
+        // the inverse of the 3x3 part, from its cofactors
+        let c00 = self.m11 * self.m22 - self.m12 * self.m21;
+        let c01 = self.m02 * self.m21 - self.m01 * self.m22;
+        let c02 = self.m01 * self.m12 - self.m02 * self.m11;
+        let det = self.m00 * c00 + self.m10 * c01 + self.m20 * c02;
+        let r = 1. / det;
+
+        let mut inv = Matrix {
+            m00: c00 * r,
+            m01: c01 * r,
+            m02: c02 * r,
+            m10: (self.m12 * self.m20 - self.m10 * self.m22) * r,
+            m11: (self.m00 * self.m22 - self.m02 * self.m20) * r,
+            m12: (self.m02 * self.m10 - self.m00 * self.m12) * r,
+            m20: (self.m10 * self.m21 - self.m11 * self.m20) * r,
+            m21: (self.m01 * self.m20 - self.m00 * self.m21) * r,
+            m22: (self.m00 * self.m11 - self.m01 * self.m10) * r,
+            ..Default::default()
+        };
+
+        // then undo the translation
+        let t = -inv.transform_vector(self.pos());
+        inv.x = t.x;
+        inv.y = t.y;
+        inv.z = t.z;
+        inv
+    }
+
+    /// Transform a point by this matrix, including its translation.
+    pub fn transform_point(self, point: Vector3) -> Vector3 {
+        self.transform_vector(point) + self.pos()
+    }
+
+    /// Transform a direction by this matrix, ignoring its translation.
+    pub fn transform_vector(self, vec: Vector3) -> Vector3 {
+        Vector3::new(
+            self.m00 * vec.x + self.m01 * vec.y + self.m02 * vec.z,
+            self.m10 * vec.x + self.m11 * vec.y + self.m12 * vec.z,
+            self.m20 * vec.x + self.m21 * vec.y + self.m22 * vec.z,
+        )
+    }
+
+    /// Transform a surface normal by the matrix whose inverse is this one, so the normal stays
+    /// perpendicular to the surface even when it is scaled unevenly. The result is not
+    /// normalized.
+    ///
+    /// Taking the inverse rather than the matrix itself lets callers that already keep
+    /// the inverse around (to transform rays) avoid inverting again for every normal.
+    pub fn transform_normal(self, normal: Vector3) -> Vector3 {
+        self.transpose().transform_vector(normal)
+    }
+
     /// Get the right vector of this matrix.
     pub fn right(self) -> Vector3 {
         Vector3::new(self.m00, self.m01, self.m02)
@@ -145,6 +251,12 @@ impl From<Vector3> for Matrix {
     }
 }
 
+impl From<Quaternion> for Matrix {
+    fn from(q: Quaternion) -> Self {
+        q.to_matrix()
+    }
+}
+
 impl Mul for Matrix {
     type Output = Self;
 
@@ -1,25 +1,61 @@
 mod matrix;
+mod quaternion;
 mod ray;
+mod sampling;
+mod transform;
 mod vector;
+mod vector2;
 
 use std::ops::Range;
 
 pub use matrix::*;
+pub use quaternion::*;
 pub use ray::*;
+pub use sampling::*;
+pub use transform::*;
 pub use vector::*;
+pub use vector2::*;
+
+/// The floating point type of the core math: `f64`, or `f32` with the `f32` feature, which
+/// halves the memory of vectors and doubles the number of lanes that fit in a SIMD register,
+/// at the cost of precision.
+#[cfg(not(feature = "f32"))]
+pub type Float = f64;
+
+/// The floating point type of the core math: `f64`, or `f32` with the `f32` feature, which
+/// halves the memory of vectors and doubles the number of lanes that fit in a SIMD register,
+/// at the cost of precision.
+#[cfg(feature = "f32")]
+pub type Float = f32;
+
+/// Mathematical constants of the `Float` type, like `std::f64::consts`.
+pub mod consts {
+    #[cfg(not(feature = "f32"))]
+    pub use std::f64::consts::*;
+
+    #[cfg(feature = "f32")]
+    pub use std::f32::consts::*;
+}
 
 /// Remap a number from one range to another.
-pub fn remap(t: f64, a: Range<f64>, b: Range<f64>) -> f64 {
+pub fn remap(t: Float, a: Range<Float>, b: Range<Float>) -> Float {
     (t - a.start) * ((b.end - b.start) / (a.end - a.start)) + b.start
 }
 
 /// Linearly interpolate between two values.
-pub fn lerp(a: f64, b: f64, c: f64) -> f64 {
+pub fn lerp(a: Float, b: Float, c: Float) -> Float {
     a + (b - a) * c
 }
 
 /// Bilinearly interpolate between four vectors.
-pub fn blerp(tx: f64, ty: f64, c00: Vector3, c10: Vector3, c01: Vector3, c11: Vector3) -> Vector3 {
+pub fn blerp(
+    tx: Float,
+    ty: Float,
+    c00: Vector3,
+    c10: Vector3,
+    c01: Vector3,
+    c11: Vector3,
+) -> Vector3 {
     let a = c00 * (1. - tx) + c10 * tx;
     let b = c01 * (1. - tx) + c11 * tx;
     a * (1. - ty) + b * ty
@@ -27,11 +63,11 @@ pub fn blerp(tx: f64, ty: f64, c00: Vector3, c10: Vector3, c01: Vector3, c11: Ve
 
 /// A type that can be linearly interpolated between two values of itself.
 pub trait Lerp {
-    fn lerp(self, other: Self, t: f64) -> Self;
+    fn lerp(self, other: Self, t: Float) -> Self;
 }
 
-impl Lerp for f64 {
-    fn lerp(self, other: Self, t: f64) -> Self {
+impl Lerp for Float {
+    fn lerp(self, other: Self, t: Float) -> Self {
         lerp(self, other, t)
     }
 }
@@ -48,8 +84,8 @@ pub enum Axis {
 pub fn refraction_vec(
     in_ray: &Ray,
     normal: Vector3,
-    from_ior: f64,
-    to_ior: f64,
+    from_ior: Float,
+    to_ior: Float,
 ) -> Option<Vector3> {
     let n = from_ior / to_ior;
     let cos_i = -normal.dot(in_ray.direction);
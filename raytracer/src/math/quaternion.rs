@@ -0,0 +1,169 @@
+use std::ops::{Mul, Neg};
+
+use super::{Float, Matrix, Vector3};
+
+/// A quaternion, used to represent rotations/orientations in 3D space
+/// without suffering from gimbal lock.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Quaternion {
+    pub w: Float,
+    pub x: Float,
+    pub y: Float,
+    pub z: Float,
+}
+
+impl Quaternion {
+    /// Instantiate a new Quaternion from its components.
+    pub fn new(w: Float, x: Float, y: Float, z: Float) -> Self {
+        Self { w, x, y, z }
+    }
+
+    /// The identity quaternion, which represents no rotation.
+    pub fn identity() -> Self {
+        Self::new(1., 0., 0., 0.)
+    }
+
+    /// Create a quaternion that rotates `angle` radians around `axis`.
+    ///
+    /// **Note:** The axis is expected to be normalized.
+    pub fn from_axis_angle(axis: Vector3, angle: Float) -> Self {
+        let (s, c) = (angle * 0.5).sin_cos();
+        Self::new(c, axis.x * s, axis.y * s, axis.z * s)
+    }
+
+    /// Create a quaternion from Euler angles applied in XYZ order, the same rotation as
+    /// [`Matrix::from_euler_xyz`].
+    pub fn from_euler_xyz(x: Float, y: Float, z: Float) -> Self {
+        let (x, y, z) = Self::euler_axes(x, y, z);
+        x * y * z
+    }
+
+    /// Create a quaternion from Euler angles applied in ZYX order, the same rotation as
+    /// [`Matrix::from_euler_zyx`].
+    pub fn from_euler_zyx(x: Float, y: Float, z: Float) -> Self {
+        let (x, y, z) = Self::euler_axes(x, y, z);
+        z * y * x
+    }
+
+    fn euler_axes(x: Float, y: Float, z: Float) -> (Self, Self, Self) {
+        (
+            Self::from_axis_angle(Vector3::right(), x),
+            Self::from_axis_angle(Vector3::up(), y),
+            Self::from_axis_angle(Vector3::new(0., 0., 1.), z),
+        )
+    }
+
+    /// Find the dot product between two quaternions.
+    pub fn dot(self, other: Self) -> Float {
+        self.w * other.w + self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    /// Find the magnitude of this quaternion.
+    pub fn magnitude(self) -> Float {
+        self.dot(self).sqrt()
+    }
+
+    /// Normalize this quaternion so that it represents a pure rotation.
+    pub fn normalize(self) -> Self {
+        let m = self.magnitude();
+        Self::new(self.w / m, self.x / m, self.y / m, self.z / m)
+    }
+
+    /// Get the conjugate of this quaternion. For a normalized quaternion, this is its inverse rotation.
+    pub fn conjugate(self) -> Self {
+        Self::new(self.w, -self.x, -self.y, -self.z)
+    }
+
+    /// Rotate a vector by this quaternion.
+    ///
+    /// **Note:** This quaternion is expected to be normalized.
+    pub fn rotate(self, v: Vector3) -> Vector3 {
+        let u = Vector3::new(self.x, self.y, self.z);
+        let t = u.cross(v) * 2.;
+        v + t * self.w + u.cross(t)
+    }
+
+    /// Convert this quaternion to a rotation matrix, for example to combine it with a
+    /// translation and scale in a `Transformed`.
+    ///
+    /// **Note:** This quaternion is expected to be normalized.
+    pub fn to_matrix(self) -> Matrix {
+        let Self { w, x, y, z } = self;
+        Matrix {
+            m00: 1. - 2. * (y * y + z * z),
+            m01: 2. * (x * y - w * z),
+            m02: 2. * (x * z + w * y),
+            m10: 2. * (x * y + w * z),
+            m11: 1. - 2. * (x * x + z * z),
+            m12: 2. * (y * z - w * x),
+            m20: 2. * (x * z - w * y),
+            m21: 2. * (y * z + w * x),
+            m22: 1. - 2. * (x * x + y * y),
+            ..Default::default()
+        }
+    }
+
+    /// Spherically interpolate between two orientations, taking the shortest path.
+    pub fn slerp(self, other: Self, t: Float) -> Self {
+        let mut other = other;
+        let mut cos = self.dot(other);
+
+        // q and -q represent the same rotation, so go the short way around
+        if cos < 0. {
+            other = -other;
+            cos = -cos;
+        }
+
+        // when the orientations are nearly identical, fall back to a linear interpolation
+        if cos > 0.9995 {
+            return Self::new(
+                self.w + (other.w - self.w) * t,
+                self.x + (other.x - self.x) * t,
+                self.y + (other.y - self.y) * t,
+                self.z + (other.z - self.z) * t,
+            )
+            .normalize();
+        }
+
+        let theta = cos.acos();
+        let sin = theta.sin();
+        let a = ((1. - t) * theta).sin() / sin;
+        let b = (t * theta).sin() / sin;
+
+        Self::new(
+            self.w * a + other.w * b,
+            self.x * a + other.x * b,
+            self.y * a + other.y * b,
+            self.z * a + other.z * b,
+        )
+    }
+}
+
+impl Default for Quaternion {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl Mul for Quaternion {
+    type Output = Self;
+
+    /// Compose two rotations. The result applies `rhs` first, then `self`.
+    fn mul(self, rhs: Self) -> Self::Output {
+        Self {
+            w: self.w * rhs.w - self.x * rhs.x - self.y * rhs.y - self.z * rhs.z,
+            x: self.w * rhs.x + self.x * rhs.w + self.y * rhs.z - self.z * rhs.y,
+            y: self.w * rhs.y - self.x * rhs.z + self.y * rhs.w + self.z * rhs.x,
+            z: self.w * rhs.z + self.x * rhs.y - self.y * rhs.x + self.z * rhs.w,
+        }
+    }
+}
+
+impl Neg for Quaternion {
+    type Output = Self;
+
+    fn neg(self) -> Self::Output {
+        Self::new(-self.w, -self.x, -self.y, -self.z)
+    }
+}
@@ -1,4 +1,4 @@
-use super::Vector3;
+use super::{Float, Vector3};
 
 /// A ray, which has an `origin` and a `direction`.
 #[derive(Clone, Debug, PartialEq)]
@@ -23,7 +23,7 @@ impl Ray {
     }
 
     /// Returns the point in space along this ray, down `t` units.
-    pub fn along(&self, t: f64) -> Vector3 {
+    pub fn along(&self, t: Float) -> Vector3 {
         self.origin + self.direction * t
     }
 
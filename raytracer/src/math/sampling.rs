@@ -0,0 +1,65 @@
+use crate::math::consts::{FRAC_PI_4, TAU};
+
+use super::{Float, Vector3};
+
+/// An orthonormal basis, three perpendicular unit vectors, used to move directions sampled
+/// around the Z axis onto some other direction, like a surface normal.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Onb {
+    pub u: Vector3,
+    pub v: Vector3,
+    pub w: Vector3,
+}
+
+impl Onb {
+    /// Build a basis around `w`, picking `u` and `v` perpendicular to it.
+    ///
+    /// **Note:** `w` is expected to be normalized.
+    pub fn from_w(w: Vector3) -> Self {
+        let (u, v) = w.basis();
+        Self { u, v, w }
+    }
+
+    /// Move a vector in this basis, with `w` as its Z axis, into world space.
+    pub fn to_world(&self, local: Vector3) -> Vector3 {
+        self.u * local.x + self.v * local.y + self.w * local.z
+    }
+
+    /// Move a vector in world space into this basis.
+    pub fn to_local(&self, world: Vector3) -> Vector3 {
+        Vector3::new(world.dot(self.u), world.dot(self.v), world.dot(self.w))
+    }
+}
+
+/// Map two random numbers from 0 to 1 onto a point evenly distributed on the unit disk,
+/// keeping neighboring numbers close together on the disk (which stratified samples rely on)
+/// better than taking the square root of a radius does.
+pub fn concentric_disk(r1: Float, r2: Float) -> (Float, Float) {
+    let (a, b) = (r1 * 2. - 1., r2 * 2. - 1.);
+    if a == 0. && b == 0. {
+        return (0., 0.);
+    }
+
+    let (r, theta) = if a.abs() > b.abs() {
+        (a, FRAC_PI_4 * (b / a))
+    } else {
+        (b, FRAC_PI_4 * (2. - a / b))
+    };
+    (r * theta.cos(), r * theta.sin())
+}
+
+/// Map two random numbers from 0 to 1 onto a direction evenly distributed over the unit sphere.
+pub fn uniform_sphere(r1: Float, r2: Float) -> Vector3 {
+    let z = 1. - r1 * 2.;
+    let r = (1. - z * z).max(0.).sqrt();
+    let phi = r2 * TAU;
+    Vector3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+/// Map two random numbers from 0 to 1 onto a direction over the hemisphere around +Z,
+/// distributed by the cosine of its angle to +Z, like light scattered by a diffuse surface.
+/// Use an [`Onb`] to move it around some other direction.
+pub fn cosine_hemisphere(r1: Float, r2: Float) -> Vector3 {
+    let (x, y) = concentric_disk(r1, r2);
+    Vector3::new(x, y, (1. - x * x - y * y).max(0.).sqrt())
+}
@@ -0,0 +1,108 @@
+use std::ops::Mul;
+
+use super::{Float, Matrix, Quaternion, Ray, Vector3};
+
+/// A placement of something in space, which scales it uniformly, then rotates it, then moves
+/// it. Unlike a `Matrix`, composing and inverting transforms keeps them in this form, since the
+/// scale is the same along every axis. For uneven scaling or shearing, use a `Matrix` instead.
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Transform {
+    /// Where the origin is moved to.
+    pub translation: Vector3,
+
+    /// The rotation around the origin.
+    pub rotation: Quaternion,
+
+    /// The uniform scale factor.
+    pub scale: Float,
+}
+
+impl Transform {
+    /// Instantiate a new Transform from its translation, rotation and scale.
+    pub fn new(translation: Vector3, rotation: Quaternion, scale: Float) -> Self {
+        Self {
+            translation,
+            rotation: rotation.normalize(),
+            scale,
+        }
+    }
+
+    /// The identity transform, which doesn't move anything.
+    pub fn identity() -> Self {
+        Self::new(Vector3::default(), Quaternion::identity(), 1.)
+    }
+
+    /// Compose two transforms. The result applies `other` first, then `self`, like
+    /// placing an object (`other`) inside a group that is placed by `self`.
+    pub fn compose(self, other: Self) -> Self {
+        Self {
+            translation: self.transform_point(other.translation),
+            rotation: self.rotation * other.rotation,
+            scale: self.scale * other.scale,
+        }
+    }
+
+    /// Get the inverse of this transform, which undoes it.
+    pub fn inverse(self) -> Self {
+        let rotation = self.rotation.conjugate();
+        Self {
+            translation: -rotation.rotate(self.translation) / self.scale,
+            rotation,
+            scale: 1. / self.scale,
+        }
+    }
+
+    /// Transform a point.
+    pub fn transform_point(self, point: Vector3) -> Vector3 {
+        self.rotation.rotate(point * self.scale) + self.translation
+    }
+
+    /// Transform a direction, ignoring the translation.
+    pub fn transform_vector(self, vec: Vector3) -> Vector3 {
+        self.rotation.rotate(vec * self.scale)
+    }
+
+    /// Transform a surface normal. Since the scale is uniform, it is only rotated, and
+    /// stays normalized.
+    pub fn transform_normal(self, normal: Vector3) -> Vector3 {
+        self.rotation.rotate(normal)
+    }
+
+    /// Transform a ray. Its direction stays normalized, so distances along the transformed
+    /// ray are distances along the original ray multiplied by `scale`.
+    pub fn transform_ray(self, ray: &Ray) -> Ray {
+        Ray::new(
+            self.transform_point(ray.origin),
+            self.transform_normal(ray.direction),
+        )
+    }
+
+    /// Convert this transform to a matrix, for example to place a `Transformed` object.
+    pub fn to_matrix(self) -> Matrix {
+        Matrix::from(self.translation)
+            * self.rotation.to_matrix()
+            * Matrix::from_scale(Vector3::new(self.scale, self.scale, self.scale))
+    }
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl From<Transform> for Matrix {
+    fn from(transform: Transform) -> Self {
+        transform.to_matrix()
+    }
+}
+
+impl Mul for Transform {
+    type Output = Self;
+
+    /// Compose two transforms. The result applies `rhs` first, then `self`.
+    fn mul(self, rhs: Self) -> Self::Output {
+        self.compose(rhs)
+    }
+}
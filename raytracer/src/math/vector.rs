@@ -2,30 +2,31 @@ use std::ops::{Add, AddAssign, Div, Mul, MulAssign, Neg, Sub};
 
 use crate::material::Color;
 
-use super::{lerp, Axis, Lerp};
+use super::{lerp, Axis, Float, Lerp};
 
 pub const VECTOR_MAX: Vector3 = Vector3 {
-    x: f64::MAX,
-    y: f64::MAX,
-    z: f64::MAX,
+    x: Float::MAX,
+    y: Float::MAX,
+    z: Float::MAX,
 };
 pub const VECTOR_MIN: Vector3 = Vector3 {
-    x: f64::MIN,
-    y: f64::MIN,
-    z: f64::MIN,
+    x: Float::MIN,
+    y: Float::MIN,
+    z: Float::MIN,
 };
 
 /// A vector in 3D space.
 #[derive(Clone, Copy, PartialEq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Vector3 {
-    pub x: f64,
-    pub y: f64,
-    pub z: f64,
+    pub x: Float,
+    pub y: Float,
+    pub z: Float,
 }
 
 impl Vector3 {
     /// Instantiate a new Vector3.
-    pub fn new(x: f64, y: f64, z: f64) -> Self {
+    pub fn new(x: Float, y: Float, z: Float) -> Self {
         Self { x, y, z }
     }
 
@@ -57,7 +58,7 @@ impl Vector3 {
     }
 
     /// Find the dot product between two Vector3s.
-    pub fn dot(self, other: Self) -> f64 {
+    pub fn dot(self, other: Self) -> Float {
         self.x * other.x + self.y * other.y + self.z * other.z
     }
 
@@ -71,7 +72,7 @@ impl Vector3 {
     }
 
     /// Find the magnitude of this Vector3.
-    pub fn magnitude(self) -> f64 {
+    pub fn magnitude(self) -> Float {
         (self.x.powi(2) + self.y.powi(2) + self.z.powi(2)).sqrt()
     }
 
@@ -99,7 +100,7 @@ impl Vector3 {
     }
 
     /// Get an axis value from this vector.
-    pub fn axis(&self, axis: Axis) -> f64 {
+    pub fn axis(&self, axis: Axis) -> Float {
         match axis {
             Axis::X => self.x,
             Axis::Y => self.y,
@@ -108,9 +109,22 @@ impl Vector3 {
     }
 
     /// Get the angle between this vector and another.
-    pub fn angle(&self, other: Self) -> f64 {
+    pub fn angle(&self, other: Self) -> Float {
         (self.dot(other) / (self.magnitude() * other.magnitude())).acos()
     }
+
+    /// Get two unit vectors perpendicular to this (unit) vector and each other, such that
+    /// they and this vector make up a right-handed X, Y (this vector) and Z basis.
+    /// For the up vector, this is the usual X and Z axes.
+    pub fn basis(self) -> (Self, Self) {
+        let helper = if self.z.abs() < 0.999 {
+            Self::new(0., 0., 1.)
+        } else {
+            Self::new(1., 0., 0.)
+        };
+        let x = self.cross(helper).normalize();
+        (x, x.cross(self))
+    }
 }
 
 impl Add for Vector3 {
@@ -157,10 +171,10 @@ impl Mul for Vector3 {
     }
 }
 
-impl Mul<f64> for Vector3 {
+impl Mul<Float> for Vector3 {
     type Output = Self;
 
-    fn mul(self, rhs: f64) -> Self::Output {
+    fn mul(self, rhs: Float) -> Self::Output {
         Self {
             x: self.x * rhs,
             y: self.y * rhs,
@@ -169,8 +183,8 @@ impl Mul<f64> for Vector3 {
     }
 }
 
-impl MulAssign<f64> for Vector3 {
-    fn mul_assign(&mut self, rhs: f64) {
+impl MulAssign<Float> for Vector3 {
+    fn mul_assign(&mut self, rhs: Float) {
         self.x *= rhs;
         self.y *= rhs;
         self.z *= rhs;
@@ -189,10 +203,10 @@ impl Div for Vector3 {
     }
 }
 
-impl Div<f64> for Vector3 {
+impl Div<Float> for Vector3 {
     type Output = Self;
 
-    fn div(self, rhs: f64) -> Self::Output {
+    fn div(self, rhs: Float) -> Self::Output {
         Self {
             x: self.x / rhs,
             y: self.y / rhs,
@@ -216,15 +230,15 @@ impl Neg for Vector3 {
 impl From<Color> for Vector3 {
     fn from(color: Color) -> Self {
         Self {
-            x: color.r as f64 / 255.,
-            y: color.g as f64 / 255.,
-            z: color.b as f64 / 255.,
+            x: color.r as Float / 255.,
+            y: color.g as Float / 255.,
+            z: color.b as Float / 255.,
         }
     }
 }
 
 impl Lerp for Vector3 {
-    fn lerp(self, other: Self, t: f64) -> Self {
+    fn lerp(self, other: Self, t: Float) -> Self {
         Self {
             x: lerp(self.x, other.x, t),
             y: lerp(self.y, other.y, t),
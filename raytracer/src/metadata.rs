@@ -0,0 +1,56 @@
+//! Render metadata, key/value text describing how a render was made, which PNG files carry as
+//! `tEXt` chunks and EXR files as header attributes (see [`crate::exr`]).
+
+use std::{convert::TryFrom, time::Duration};
+
+use crate::scene::Scene;
+
+/// Describe how `scene` renders, for tracing a frame back to the settings that produced it:
+/// its resolution, sample counts and ray depth, followed by the scene's own
+/// [`metadata`](crate::scene::SceneOptions::metadata), and how long it took to render if known.
+pub fn describe(scene: &Scene, render_time: Option<Duration>) -> Vec<(String, String)> {
+    let (vw, vh) = scene.camera.frame_size();
+    let light_samples = scene
+        .lights
+        .iter()
+        .map(|light| format!("{} {}", light.kind(), light.samples()))
+        .collect::<Vec<_>>();
+
+    let mut entries = vec![
+        (String::from("Software"), String::from("raytracer")),
+        (String::from("Resolution"), format!("{}x{}", vw, vh)),
+        (String::from("Samples per pixel"), String::from("1")),
+        (String::from("Light samples"), light_samples.join(", ")),
+        (
+            String::from("Max ray depth"),
+            scene.options.max_ray_depth.to_string(),
+        ),
+    ];
+    entries.extend(scene.options.metadata.iter().cloned());
+    if let Some(time) = render_time {
+        entries.push((
+            String::from("Render time"),
+            format!("{:.3}s", time.as_secs_f32()),
+        ));
+    }
+
+    entries
+}
+
+/// The data of a PNG `tEXt` chunk holding a key and its value. Keys should be 1 to 79
+/// characters of Latin-1 text, and are shortened to fit; characters outside of Latin-1 are
+/// replaced with `?`.
+pub fn text_chunk(key: &str, value: &str) -> Vec<u8> {
+    let mut data = latin1(key);
+    data.truncate(79);
+    data.push(0);
+    data.extend(latin1(value));
+    data
+}
+
+/// Encode text as Latin-1, which is what `tEXt` chunks hold.
+fn latin1(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| u8::try_from(c as u32).unwrap_or(b'?'))
+        .collect()
+}
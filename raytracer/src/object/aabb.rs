@@ -1,7 +1,9 @@
 use crate::{
     acceleration,
+    checkpoint::Fingerprint,
     material::Material,
-    math::{Ray, Vector3},
+    math::{Float, Ray, Vector2, Vector3},
+    validate::Problem,
 };
 
 use super::{Hit, Intersect, SceneObject};
@@ -10,6 +12,7 @@ use super::{Hit, Intersect, SceneObject};
 /// It is used so that there is less memory overhead than
 /// a typical `Aabb`, which also must return material data.
 #[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct AabbIntersector {
     pub pos: Vector3,
     pub size: Vector3,
@@ -26,8 +29,8 @@ impl Intersect for AabbIntersector {
 
         let t1 = ray.inverse() * (-ro + (s * self.size));
         let t2 = ray.inverse() * (-ro - (s * self.size));
-        let tn = f64::max(f64::max(t1.x, t1.y), t1.z);
-        let tf = f64::min(f64::min(t2.x, t2.y), t2.z);
+        let tn = Float::max(Float::max(t1.x, t1.y), t1.z);
+        let tf = Float::min(Float::min(t2.x, t2.y), t2.z);
 
         if tn > tf || tf < 0. {
             return None;
@@ -46,23 +49,27 @@ impl Intersect for AabbIntersector {
 
         let pns = (Vector3::new(1., 1., 1.) - normal.abs()) * (pn - self.pos) / self.size;
 
+        // the tangent and bitangent follow increasing U and decreasing V on each face
         #[rustfmt::skip]
-        let uv: (f64, f64) = match normal {
-            Vector3 { y, .. } if y == 1. => (pns.x, pns.z),
-            Vector3 { y, .. } if y == -1. => (-pns.x, -pns.z),
-            Vector3 { x, .. } if x == 1. => (-pns.z, -pns.y),
-            Vector3 { x, .. } if x == -1. => (pns.z, -pns.y),
-            Vector3 { z, .. } if z == 1. => (pns.x, -pns.y),
-            Vector3 { z, .. } if z == -1. => (-pns.x, -pns.y),
-            _ => (0., 0.),
+        let (uv, tangent, bitangent): ((Float, Float), Vector3, Vector3) = match normal {
+            Vector3 { y, .. } if y == 1. => ((pns.x, pns.z), Vector3::new(1., 0., 0.), Vector3::new(0., 0., -1.)),
+            Vector3 { y, .. } if y == -1. => ((-pns.x, -pns.z), Vector3::new(-1., 0., 0.), Vector3::new(0., 0., 1.)),
+            Vector3 { x, .. } if x == 1. => ((-pns.z, -pns.y), Vector3::new(0., 0., -1.), Vector3::up()),
+            Vector3 { x, .. } if x == -1. => ((pns.z, -pns.y), Vector3::new(0., 0., 1.), Vector3::up()),
+            Vector3 { z, .. } if z == 1. => ((pns.x, -pns.y), Vector3::new(1., 0., 0.), Vector3::up()),
+            Vector3 { z, .. } if z == -1. => ((-pns.x, -pns.y), Vector3::new(-1., 0., 0.), Vector3::up()),
+            _ => ((0., 0.), Vector3::default(), Vector3::default()),
         };
 
-        Some(Hit::new(
-            normal,
-            (tn, pn),
-            (tf, pf),
-            (uv.0 as f32 * 0.5 + 0.5, uv.1 as f32 * 0.5 + 0.5),
-        ))
+        Some(
+            Hit::new(
+                normal,
+                (tn, pn),
+                (tf, pf),
+                Vector2::new(uv.0 as f32 * 0.5 + 0.5, uv.1 as f32 * 0.5 + 0.5),
+            )
+            .with_tangents(tangent, bitangent),
+        )
     }
 }
 
@@ -83,6 +90,7 @@ impl From<acceleration::Aabb> for AabbIntersector {
 
 /// An axis-aligned box, short for axis-aligned bounding box.
 #[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Aabb {
     intersector: AabbIntersector,
     pub material: Material,
@@ -124,4 +132,35 @@ impl SceneObject for Aabb {
     fn material(&self) -> &Material {
         &self.material
     }
+
+    fn material_mut(&mut self) -> &mut Material {
+        &mut self.material
+    }
+
+    fn kind(&self) -> &'static str {
+        "aabb"
+    }
+
+    fn clone_box(&self) -> Box<dyn SceneObject> {
+        Box::new(self.clone())
+    }
+
+    fn fingerprint(&self, hasher: &mut Fingerprint) {
+        hasher.write_debug(&(self.kind(), &self.intersector));
+        self.material.fingerprint(hasher);
+    }
+
+    fn validate(&self) -> Vec<Problem> {
+        let size = self.size();
+        if size.x <= 0. || size.y <= 0. || size.z <= 0. {
+            vec![Problem::ZeroSizeAabb]
+        } else {
+            Vec::new()
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    fn tagged(&self) -> crate::serialize::TaggedObject<'_> {
+        crate::serialize::TaggedObject::Aabb(self)
+    }
 }
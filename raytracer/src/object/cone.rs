@@ -0,0 +1,206 @@
+use std::f32::consts::PI;
+
+use crate::{
+    checkpoint::Fingerprint,
+    material::Material,
+    math::{Float, Ray, Vector2, Vector3},
+    scene::EPSILON,
+};
+
+use super::{Hit, Intersect, SceneObject};
+
+/// An intersection of a ray with one of the surfaces of a cone, in the cone's local space.
+#[derive(Clone, Copy)]
+struct ConeHit {
+    t: Float,
+    normal: Vector3,
+    uv: Vector2,
+    tangent: Vector3,
+    bitangent: Vector3,
+}
+
+/// A cone, capped at its base. If `top_radius` is not zero, it is a truncated cone (a frustum),
+/// capped at its top as well.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Cone {
+    /// The center of the base of the cone.
+    pub base: Vector3,
+
+    /// The direction from the base of the cone toward its apex.
+    pub axis: Vector3,
+
+    /// The distance from the base of the cone to its apex.
+    pub height: Float,
+
+    /// The radius of the base of the cone.
+    pub radius: Float,
+
+    /// The radius of the top of the cone, zero for a pointed cone.
+    pub top_radius: Float,
+
+    /// The material of the cone.
+    pub material: Material,
+}
+
+impl Cone {
+    /// Instantiate a new pointed, upright cone.
+    pub fn new(base: Vector3, radius: Float, height: Float, material: Material) -> Self {
+        Self {
+            base,
+            axis: Vector3::up(),
+            height,
+            radius,
+            top_radius: 0.,
+            material,
+        }
+    }
+
+    /// Point the cone along `axis`, from its base toward its apex.
+    pub fn with_axis(mut self, axis: Vector3) -> Self {
+        self.axis = axis.normalize();
+        self
+    }
+
+    /// Truncate the cone, giving its top a radius.
+    pub fn with_top_radius(mut self, top_radius: Float) -> Self {
+        self.top_radius = top_radius;
+        self
+    }
+
+    /// The point of the cone. If the cone is truncated, this is the center of its top.
+    pub fn apex(&self) -> Vector3 {
+        self.base + self.axis * self.height
+    }
+}
+
+impl Intersect for Cone {
+    fn intersect(&self, ray: &Ray) -> Option<Hit> {
+        let (ex, ez) = self.axis.basis();
+        let ey = self.axis;
+
+        // move the ray into the cone's local space, where the base is at the origin
+        let rel = ray.origin - self.base;
+        let (ox, oy, oz) = (rel.dot(ex), rel.dot(ey), rel.dot(ez));
+        let (dx, dy, dz) = (
+            ray.direction.dot(ex),
+            ray.direction.dot(ey),
+            ray.direction.dot(ez),
+        );
+
+        // the radius changes linearly with height, r(y) = radius + k * y
+        let k = (self.top_radius - self.radius) / self.height;
+        let r0 = self.radius + k * oy;
+
+        let mut hits = Vec::new();
+
+        // the side
+        let a = dx * dx + dz * dz - k * k * dy * dy;
+        let b = 2. * (ox * dx + oz * dz - r0 * k * dy);
+        let c = ox * ox + oz * oz - r0 * r0;
+        let discrim = b * b - 4. * a * c;
+        if a.abs() > EPSILON && discrim >= 0. {
+            let sq = discrim.sqrt();
+            for t in [(-b - sq) / (2. * a), (-b + sq) / (2. * a)] {
+                let y = oy + dy * t;
+                if t <= 0. || y < 0. || y > self.height {
+                    continue;
+                }
+
+                let (x, z) = (ox + dx * t, oz + dz * t);
+                let rho = (x * x + z * z).sqrt().max(EPSILON);
+                let normal = Vector3::new(x / rho, -k, z / rho).normalize();
+                let uv = Vector2::new(
+                    0.5 + x.atan2(z) as f32 / (PI * 2.),
+                    1. - (y / self.height) as f32,
+                );
+
+                // the tangent follows the direction of increasing U, around the axis
+                let tangent = Vector3::new(z / rho, 0., -x / rho);
+                hits.push(ConeHit {
+                    t,
+                    normal,
+                    uv,
+                    tangent,
+                    bitangent: normal.cross(tangent),
+                });
+            }
+        }
+
+        // the caps
+        if dy.abs() > EPSILON {
+            for (y, radius, normal) in [
+                (0., self.radius, Vector3::new(0., -1., 0.)),
+                (self.height, self.top_radius, Vector3::new(0., 1., 0.)),
+            ] {
+                let t = (y - oy) / dy;
+                let (x, z) = (ox + dx * t, oz + dz * t);
+                if t <= 0. || radius <= 0. || x * x + z * z > radius * radius {
+                    continue;
+                }
+
+                let uv = Vector2::new(
+                    0.5 + (x / radius) as f32 * 0.5,
+                    0.5 + (z / radius) as f32 * 0.5,
+                );
+                hits.push(ConeHit {
+                    t,
+                    normal,
+                    uv,
+                    tangent: Vector3::new(1., 0., 0.),
+                    bitangent: Vector3::new(0., 0., -1.),
+                });
+            }
+        }
+
+        let near = *hits.iter().min_by(|a, b| a.t.partial_cmp(&b.t).unwrap())?;
+        let far = hits.iter().map(|h| h.t).fold(near.t, Float::max);
+
+        // back into world space
+        let to_world = |v: Vector3| ex * v.x + ey * v.y + ez * v.z;
+        Some(
+            Hit::new(
+                to_world(near.normal),
+                (near.t, ray.along(near.t)),
+                (far, ray.along(far)),
+                near.uv,
+            )
+            .with_tangents(to_world(near.tangent), to_world(near.bitangent)),
+        )
+    }
+}
+
+impl SceneObject for Cone {
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn material_mut(&mut self) -> &mut Material {
+        &mut self.material
+    }
+
+    fn kind(&self) -> &'static str {
+        "cone"
+    }
+
+    fn clone_box(&self) -> Box<dyn SceneObject> {
+        Box::new(self.clone())
+    }
+
+    fn fingerprint(&self, hasher: &mut Fingerprint) {
+        hasher.write_debug(&(
+            self.kind(),
+            self.base,
+            self.axis,
+            self.height,
+            self.radius,
+            self.top_radius,
+        ));
+        self.material.fingerprint(hasher);
+    }
+
+    #[cfg(feature = "serde")]
+    fn tagged(&self) -> crate::serialize::TaggedObject<'_> {
+        crate::serialize::TaggedObject::Cone(self)
+    }
+}
@@ -0,0 +1,191 @@
+use crate::{
+    checkpoint::Fingerprint,
+    material::Material,
+    math::{Float, Ray, Vector3},
+    validate::Problem,
+};
+
+use super::{Hit, Intersect, ObjectStats, SceneObject};
+
+/// A boolean operation used to combine the two operands of a `Csg`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CsgOperation {
+    /// Everything inside either operand.
+    Union,
+
+    /// Everything inside both operands.
+    Intersection,
+
+    /// Everything inside the first operand, but not the second.
+    Difference,
+}
+
+/// A combination of two scene objects by a boolean operation (constructive solid geometry).
+///
+/// Each operand is treated as the solid between the near and far points of its hits, so the
+/// operands should be convex (spheres, AABBs, cones and the like). The operands' own materials
+/// are ignored, and the whole object is drawn with `material`.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Csg {
+    /// The operation combining the operands.
+    pub operation: CsgOperation,
+
+    /// The first operand.
+    pub a: Box<dyn SceneObject>,
+
+    /// The second operand. For a difference, this is the operand subtracted from `a`.
+    pub b: Box<dyn SceneObject>,
+
+    /// The material of the combined object.
+    pub material: Material,
+}
+
+impl Csg {
+    pub fn new(
+        operation: CsgOperation,
+        a: Box<dyn SceneObject>,
+        b: Box<dyn SceneObject>,
+        material: Material,
+    ) -> Self {
+        Self {
+            operation,
+            a,
+            b,
+            material,
+        }
+    }
+
+    /// Find the surface through which `ray` leaves `object` at `t`, with its normal facing the
+    /// inside of the object (toward the ray's origin).
+    fn exit_hit(object: &dyn SceneObject, ray: &Ray, t: Float) -> Option<Hit> {
+        // cast back toward the origin from just beyond the exit, so the exit becomes the near hit
+        let back = object.intersect(&Ray::new(ray.along(t + 1.), -ray.direction))?;
+
+        let mut hit = Hit::new(-back.normal, (t, ray.along(t)), (t, ray.along(t)), back.uv);
+        if let Some((tangent, bitangent)) = back.tangents {
+            hit = hit.with_tangents(tangent, bitangent);
+        }
+        Some(hit)
+    }
+}
+
+/// Move the far end of a hit to `t`.
+fn with_far(mut hit: Hit, t: Float, point: Vector3) -> Hit {
+    hit.far = t;
+    hit.vfar = point;
+    hit
+}
+
+impl Intersect for Csg {
+    fn intersect(&self, ray: &Ray) -> Option<Hit> {
+        let ha = self.a.intersect(ray);
+        let hb = self.b.intersect(ray);
+
+        match self.operation {
+            CsgOperation::Union => match (ha, hb) {
+                (Some(ha), Some(hb)) => {
+                    let (first, second) = if ha.near <= hb.near {
+                        (ha, hb)
+                    } else {
+                        (hb, ha)
+                    };
+
+                    // if the operands overlap, the solid continues to the end of both
+                    if second.near <= first.far && second.far > first.far {
+                        Some(with_far(first, second.far, second.vfar))
+                    } else {
+                        Some(first)
+                    }
+                }
+                (ha, hb) => ha.or(hb),
+            },
+            CsgOperation::Intersection => {
+                let (ha, hb) = (ha?, hb?);
+                let (far, vfar) = if ha.far <= hb.far {
+                    (ha.far, ha.vfar)
+                } else {
+                    (hb.far, hb.vfar)
+                };
+
+                // the surface is that of whichever operand is entered last
+                let entered = if ha.near >= hb.near { ha } else { hb };
+                if entered.near > far {
+                    return None;
+                }
+
+                Some(with_far(entered, far, vfar))
+            }
+            CsgOperation::Difference => {
+                let ha = ha?;
+                let hb = match hb {
+                    Some(hb) if hb.far > ha.near && hb.near < ha.far => hb,
+                    _ => return Some(ha),
+                };
+
+                // the second operand is entered inside the first, cutting it short (unless
+                // the part before it is behind the ray, like for rays leaving the surface)
+                if hb.near > ha.near && hb.near > 0. {
+                    return Some(with_far(ha, hb.near, hb.vnear));
+                }
+
+                // otherwise, the surface is where the ray leaves the second operand, if that
+                // is still inside the first
+                if hb.far < ha.far {
+                    let hit = Self::exit_hit(self.b.as_ref(), ray, hb.far)?;
+                    Some(with_far(hit, ha.far, ha.vfar))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    fn bvh_nodes_visited(&self, ray: &Ray) -> usize {
+        self.a.bvh_nodes_visited(ray) + self.b.bvh_nodes_visited(ray)
+    }
+}
+
+impl SceneObject for Csg {
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn material_mut(&mut self) -> &mut Material {
+        &mut self.material
+    }
+
+    fn kind(&self) -> &'static str {
+        "csg"
+    }
+
+    fn stats(&self) -> ObjectStats {
+        let mut stats = self.a.stats();
+        stats += self.b.stats();
+        stats.texture_bytes += self.material.texture_bytes();
+        stats
+    }
+
+    fn clone_box(&self) -> Box<dyn SceneObject> {
+        Box::new(self.clone())
+    }
+
+    fn fingerprint(&self, hasher: &mut Fingerprint) {
+        hasher.write_debug(&(self.kind(), self.operation));
+        self.a.fingerprint(hasher);
+        self.b.fingerprint(hasher);
+        self.material.fingerprint(hasher);
+    }
+
+    fn validate(&self) -> Vec<Problem> {
+        let mut problems = self.a.validate();
+        problems.extend(self.b.validate());
+        problems
+    }
+
+    #[cfg(feature = "serde")]
+    fn tagged(&self) -> crate::serialize::TaggedObject<'_> {
+        crate::serialize::TaggedObject::Csg(self)
+    }
+}
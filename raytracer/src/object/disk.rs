@@ -0,0 +1,120 @@
+use crate::{
+    checkpoint::Fingerprint,
+    material::Material,
+    math::{Float, Ray, Vector2, Vector3},
+    scene::EPSILON,
+    validate::{self, Problem},
+};
+
+use super::{Hit, Intersect, SceneObject};
+
+/// A flat, circular disk. If `inner_radius` is not zero, it has a hole in its center
+/// (an annulus).
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Disk {
+    /// The center of the disk.
+    pub origin: Vector3,
+
+    /// The normal of the disk.
+    pub normal: Vector3,
+
+    /// The radius of the disk.
+    pub radius: Float,
+
+    /// The radius of the hole in the center of the disk, zero for a solid disk.
+    pub inner_radius: Float,
+
+    /// The material of the disk.
+    pub material: Material,
+}
+
+impl Disk {
+    pub fn new(origin: Vector3, normal: Vector3, radius: Float, material: Material) -> Self {
+        Self {
+            origin,
+            normal: normal.normalize(),
+            radius,
+            inner_radius: 0.,
+            material,
+        }
+    }
+
+    /// Cut a hole of `inner_radius` out of the center of the disk.
+    pub fn with_inner_radius(mut self, inner_radius: Float) -> Self {
+        self.inner_radius = inner_radius;
+        self
+    }
+}
+
+impl Intersect for Disk {
+    fn intersect(&self, ray: &Ray) -> Option<Hit> {
+        let denom = self.normal.dot(ray.direction);
+        if denom.abs() <= EPSILON {
+            return None;
+        }
+
+        let t = (self.origin - ray.origin).dot(self.normal) / denom;
+        if t <= 0. {
+            return None;
+        }
+
+        let p = ray.along(t);
+        let (ex, ez) = self.normal.basis();
+        let (x, z) = ((p - self.origin).dot(ex), (p - self.origin).dot(ez));
+        let dist2 = x * x + z * z;
+        if dist2 > self.radius * self.radius || dist2 < self.inner_radius * self.inner_radius {
+            return None;
+        }
+
+        // the disk is mapped onto the square texture it is inscribed in
+        let uv = Vector2::new(
+            0.5 + (x / self.radius) as f32 * 0.5,
+            0.5 + (z / self.radius) as f32 * 0.5,
+        );
+
+        Some(Hit::new(self.normal * -denom.signum(), (t, p), (t, p), uv).with_tangents(ex, -ez))
+    }
+}
+
+impl SceneObject for Disk {
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn material_mut(&mut self) -> &mut Material {
+        &mut self.material
+    }
+
+    fn kind(&self) -> &'static str {
+        "disk"
+    }
+
+    fn clone_box(&self) -> Box<dyn SceneObject> {
+        Box::new(self.clone())
+    }
+
+    fn fingerprint(&self, hasher: &mut Fingerprint) {
+        hasher.write_debug(&(
+            self.kind(),
+            self.origin,
+            self.normal,
+            self.radius,
+            self.inner_radius,
+        ));
+        self.material.fingerprint(hasher);
+    }
+
+    fn validate(&self) -> Vec<Problem> {
+        if validate::is_unit(self.normal) {
+            Vec::new()
+        } else {
+            vec![Problem::UnnormalizedVector("normal")]
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    fn tagged(&self) -> crate::serialize::TaggedObject<'_> {
+        crate::serialize::TaggedObject::Disk(self)
+    }
+}
@@ -0,0 +1,103 @@
+#[cfg(feature = "simd")]
+use crate::acceleration::PACKET_SIZE;
+use crate::{
+    checkpoint::Fingerprint,
+    material::Material,
+    math::{Float, Ray},
+    validate::Problem,
+};
+
+use super::{Hit, Intersect, ObjectStats, RenderFlags, SceneObject};
+
+/// A scene object with its own [`RenderFlags`], for example to hide it from the camera while it
+/// still casts shadows.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Flagged<T: SceneObject> {
+    /// The object being flagged.
+    pub object: T,
+
+    /// Which kinds of rays can see the object.
+    pub flags: RenderFlags,
+}
+
+impl<T: SceneObject> Flagged<T> {
+    pub fn new(object: T, flags: RenderFlags) -> Self {
+        Self { object, flags }
+    }
+
+    /// Consumes this `Flagged`, returning the inner object.
+    pub fn into_inner(self) -> T {
+        self.object
+    }
+}
+
+impl<T: SceneObject> Intersect for Flagged<T> {
+    fn intersect(&self, ray: &Ray) -> Option<Hit> {
+        self.object.intersect(ray)
+    }
+
+    fn occludes(&self, ray: &Ray, max_t: Float) -> bool {
+        self.object.occludes(ray, max_t)
+    }
+
+    #[cfg(feature = "simd")]
+    fn intersect_packet(&self, rays: &[Ray; PACKET_SIZE]) -> [Option<Hit>; PACKET_SIZE] {
+        self.object.intersect_packet(rays)
+    }
+
+    fn bvh_nodes_visited(&self, ray: &Ray) -> usize {
+        self.object.bvh_nodes_visited(ray)
+    }
+}
+
+impl<T: SceneObject> SceneObject for Flagged<T> {
+    fn material(&self) -> &Material {
+        self.object.material()
+    }
+
+    fn material_mut(&mut self) -> &mut Material {
+        self.object.material_mut()
+    }
+
+    fn material_at(&self, hit: &Hit) -> &Material {
+        self.object.material_at(hit)
+    }
+
+    fn kind(&self) -> &'static str {
+        self.object.kind()
+    }
+
+    fn clone_box(&self) -> Box<dyn SceneObject> {
+        Box::new(Flagged::new(self.object.clone_box(), self.flags))
+    }
+
+    fn fingerprint(&self, hasher: &mut Fingerprint) {
+        hasher.write_debug(&self.flags);
+        self.object.fingerprint(hasher);
+    }
+
+    fn validate(&self) -> Vec<Problem> {
+        self.object.validate()
+    }
+
+    #[cfg(feature = "serde")]
+    fn tagged(&self) -> crate::serialize::TaggedObject<'_> {
+        crate::serialize::TaggedObject::Flagged {
+            object: &self.object,
+            flags: self.flags,
+        }
+    }
+
+    fn flags(&self) -> RenderFlags {
+        self.flags
+    }
+
+    fn opaque(&self) -> bool {
+        self.object.opaque()
+    }
+
+    fn stats(&self) -> ObjectStats {
+        self.object.stats()
+    }
+}
@@ -0,0 +1,168 @@
+use std::sync::Arc;
+
+#[cfg(feature = "simd")]
+use crate::acceleration::PACKET_SIZE;
+use crate::{
+    checkpoint::Fingerprint,
+    material::Material,
+    math::{Float, Quaternion, Ray, Transform, Vector3},
+    validate::Problem,
+};
+
+use super::{Hit, Intersect, Mesh, ObjectStats, SceneObject};
+
+/// A copy of a mesh placed somewhere in the scene, which shares its geometry (and its SBVH)
+/// with every other instance of the same mesh instead of duplicating it.
+///
+/// The mesh is placed by its `transform`. Faces with a material of their own in the mesh keep
+/// it, and every other face uses the instance's `material`.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Instance {
+    /// The shared mesh. It must already have its SBVH generated.
+    pub mesh: Arc<Mesh>,
+
+    /// The placement of the mesh in the scene.
+    pub transform: Transform,
+
+    /// The material of this instance, used in place of the mesh's own material.
+    pub material: Material,
+}
+
+impl Instance {
+    pub fn new(mesh: Arc<Mesh>, material: Material) -> Self {
+        Self {
+            mesh,
+            transform: Transform::identity(),
+            material,
+        }
+    }
+
+    /// Place the instance by `transform`.
+    pub fn with_transform(mut self, transform: Transform) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    /// Move the instance to `position`.
+    pub fn with_position(mut self, position: Vector3) -> Self {
+        self.transform.translation = position;
+        self
+    }
+
+    /// Rotate the instance by `rotation`.
+    pub fn with_rotation(mut self, rotation: Quaternion) -> Self {
+        self.transform.rotation = rotation.normalize();
+        self
+    }
+
+    /// Scale the instance by `scale`.
+    pub fn with_scale(mut self, scale: Float) -> Self {
+        self.transform.scale = scale;
+        self
+    }
+
+    /// Move a ray into the mesh's space. Since the scale is uniform, the direction stays
+    /// normalized and distances along it are just scaled.
+    fn local_ray(&self, ray: &Ray) -> Ray {
+        self.transform.inverse().transform_ray(ray)
+    }
+
+    /// Move a hit of a ray from `local_ray` back out into world space.
+    fn world_hit(&self, ray: &Ray, mut hit: Hit) -> Hit {
+        let transform = self.transform;
+        hit.near *= transform.scale;
+        hit.far *= transform.scale;
+        hit.vnear = ray.along(hit.near);
+        hit.vfar = ray.along(hit.far);
+        hit.normal = transform.transform_normal(hit.normal);
+        hit.tangents = hit
+            .tangents
+            .map(|(t, b)| (transform.transform_normal(t), transform.transform_normal(b)));
+
+        hit
+    }
+}
+
+impl Intersect for Instance {
+    fn intersect(&self, ray: &Ray) -> Option<Hit> {
+        let hit = self.mesh.intersect(&self.local_ray(ray))?;
+        Some(self.world_hit(ray, hit))
+    }
+
+    fn occludes(&self, ray: &Ray, max_t: Float) -> bool {
+        self.mesh
+            .occludes(&self.local_ray(ray), max_t / self.transform.scale)
+    }
+
+    #[cfg(feature = "simd")]
+    fn intersect_packet(&self, rays: &[Ray; PACKET_SIZE]) -> [Option<Hit>; PACKET_SIZE] {
+        let mut hits = self
+            .mesh
+            .intersect_packet(&rays.each_ref().map(|ray| self.local_ray(ray)));
+
+        std::array::from_fn(|i| hits[i].take().map(|hit| self.world_hit(&rays[i], hit)))
+    }
+
+    fn bvh_nodes_visited(&self, ray: &Ray) -> usize {
+        self.mesh.bvh_nodes_visited(&self.local_ray(ray))
+    }
+}
+
+impl SceneObject for Instance {
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn material_at(&self, hit: &Hit) -> &Material {
+        hit.face
+            .and_then(|face| self.mesh.tri_materials.get(face).copied().flatten())
+            .and_then(|index| self.mesh.face_materials.get(index))
+            .unwrap_or(&self.material)
+    }
+
+    fn material_mut(&mut self) -> &mut Material {
+        &mut self.material
+    }
+
+    fn kind(&self) -> &'static str {
+        "instance"
+    }
+
+    fn clone_box(&self) -> Box<dyn SceneObject> {
+        Box::new(self.clone())
+    }
+
+    fn fingerprint(&self, hasher: &mut Fingerprint) {
+        hasher.write_debug(&(self.kind(), self.transform));
+        self.mesh.fingerprint(hasher);
+        self.material.fingerprint(hasher);
+    }
+
+    fn validate(&self) -> Vec<Problem> {
+        self.mesh.validate()
+    }
+
+    #[cfg(feature = "serde")]
+    fn tagged(&self) -> crate::serialize::TaggedObject<'_> {
+        crate::serialize::TaggedObject::Instance(self)
+    }
+
+    fn opaque(&self) -> bool {
+        self.material.is_opaque() && self.mesh.face_materials.iter().all(Material::is_opaque)
+    }
+
+    /// The shared mesh's triangles and SBVH are counted again for every instance of it.
+    fn stats(&self) -> ObjectStats {
+        ObjectStats {
+            texture_bytes: self.material.texture_bytes()
+                + self
+                    .mesh
+                    .face_materials
+                    .iter()
+                    .map(Material::texture_bytes)
+                    .sum::<usize>(),
+            ..self.mesh.stats()
+        }
+    }
+}
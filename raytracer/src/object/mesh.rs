@@ -1,34 +1,150 @@
-use std::collections::HashMap;
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fs::{self, File},
+    io::{self, BufReader, BufWriter, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+};
 
 use crate::{
-    acceleration,
-    material::Material,
-    math::{Matrix, Ray, Vector3, VECTOR_MAX, VECTOR_MIN},
+    acceleration::{self, Accelerator},
+    checkpoint::Fingerprint,
+    material::{Color, ImageTexture, Material, Texture},
+    math::{Float, Quaternion, Ray, Transform, Vector2, Vector3, VECTOR_MAX, VECTOR_MIN},
     scene::EPSILON,
+    validate::{self, Problem},
 };
 
-use super::{Hit, Intersect, SceneObject};
+use noise::{NoiseFn, Perlin, Seedable};
+
+#[cfg(feature = "simd")]
+use crate::acceleration::{LaneMask, Lanes, RayPacket, PACKET_SIZE};
+#[cfg(feature = "simd")]
+use std::simd::prelude::*;
+
+use super::{ply, Hit, Intersect, ObjectStats, SceneObject};
 
-struct TriIntersect {
-    p: Vector3,
-    t: f64,
-    u: f32,
-    v: f32,
+/// The nearest two triangles a ray hits, found while traversing a mesh's SBVH.
+#[derive(Default)]
+struct NearestHits {
+    near: Option<(usize, TriIntersect)>,
+    far: Option<(usize, TriIntersect)>,
 }
 
-fn triangle_normal(v0: Vector3, v1: Vector3, v2: Vector3) -> Vector3 {
+impl NearestHits {
+    /// Whether triangle `i` is already one of the hits, since spatial splits can put a triangle
+    /// in more than one leaf.
+    fn seen(&self, i: usize) -> bool {
+        let is = |hit: &Option<(usize, TriIntersect)>| matches!(hit, Some((j, _)) if *j == i);
+        is(&self.near) || is(&self.far)
+    }
+
+    /// Keep a hit on triangle `i` if it is nearer than either of the hits so far.
+    fn insert(&mut self, i: usize, h: TriIntersect) {
+        match &self.near {
+            Some((_, n)) if n.t <= h.t => {
+                if !matches!(&self.far, Some((_, f)) if f.t <= h.t) {
+                    self.far = Some((i, h));
+                }
+            }
+            _ => {
+                self.far = self.near.take();
+                self.near = Some((i, h));
+            }
+        }
+    }
+
+    /// How far along the ray triangles can still be one of the nearest two.
+    fn max_t(&self) -> Float {
+        self.far
+            .as_ref()
+            .map(|(_, f)| f.t)
+            .unwrap_or(Float::INFINITY)
+    }
+}
+
+pub(super) struct TriIntersect {
+    pub p: Vector3,
+    pub t: Float,
+    pub u: f32,
+    pub v: f32,
+}
+
+pub(super) fn triangle_normal(v0: Vector3, v1: Vector3, v2: Vector3) -> Vector3 {
     let edge1 = v1 - v0;
     let edge2 = v2 - v0;
     edge1.cross(edge2).normalize()
 }
 
-fn triangle_intersect(v0: Vector3, v1: Vector3, v2: Vector3, ray: &Ray) -> Option<TriIntersect> {
+/// Build a material from an MTL material, starting from `base`. Texture paths are relative to
+/// `dir`, and textures that fail to load are ignored. The path of every texture referenced is
+/// added to `assets`.
+fn mtl_material(
+    mtl: &tobj::Material,
+    dir: &Path,
+    base: &Material,
+    assets: &mut Vec<PathBuf>,
+) -> Material {
+    let mut load = |path: &str| -> Option<Texture> {
+        if path.is_empty() {
+            return None;
+        }
+
+        let path = dir.join(path);
+        if !assets.contains(&path) {
+            assets.push(path.clone());
+        }
+        let image = image::open(path).ok()?;
+        Some(Texture::Image(ImageTexture::from_rgba(image.to_rgba8())))
+    };
+
+    let [r, g, b] = mtl.diffuse;
+    let mut material = base.clone();
+    material.texture = load(&mtl.diffuse_texture)
+        .map(Texture::decoded)
+        .unwrap_or_else(|| Texture::Solid(Color::newf(r as Float, g as Float, b as Float)));
+
+    // Blinn-Phong exponent to GGX roughness
+    if mtl.shininess > 0. {
+        material.roughness = (2. / (mtl.shininess as Float + 2.)).sqrt();
+    }
+
+    if mtl.dissolve < 1. {
+        material.transparency = 1. - mtl.dissolve as Float;
+    }
+
+    if mtl.optical_density != 1. {
+        material.ior = mtl.optical_density as Float;
+    }
+
+    if let Some(opacity) = load(&mtl.dissolve_texture) {
+        material.opacity = Some(opacity);
+    }
+
+    if let Some(normal_map) = load(&mtl.normal_texture) {
+        material.normal_map = Some(normal_map);
+    }
+
+    material
+}
+
+/// Intersect a ray with a triangle. If `cull_backfaces` is set, the triangle is only hit from
+/// its front, where its vertices wind counter-clockwise.
+pub(super) fn triangle_intersect(
+    v0: Vector3,
+    v1: Vector3,
+    v2: Vector3,
+    ray: &Ray,
+    cull_backfaces: bool,
+) -> Option<TriIntersect> {
     let edge1 = v1 - v0;
     let edge2 = v2 - v0;
 
+    // `a` is negative when the ray hits the back of the triangle
     let h = ray.direction.cross(edge2);
     let a = edge1.dot(h);
-    if a > -EPSILON && a < EPSILON {
+    if a < EPSILON && (cull_backfaces || a > -EPSILON) {
         return None;
     }
 
@@ -58,25 +174,82 @@ fn triangle_intersect(v0: Vector3, v1: Vector3, v2: Vector3, ray: &Ray) -> Optio
     }
 }
 
-fn triangle_intersect_uvs(
+#[cfg(feature = "simd")]
+fn cross_lanes(a: &[Lanes; 3], b: &[Lanes; 3]) -> [Lanes; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        -a[0] * b[2] + a[2] * b[0],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+#[cfg(feature = "simd")]
+fn dot_lanes(a: &[Lanes; 3], b: &[Lanes; 3]) -> Lanes {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+/// Intersect every ray of a packet with a triangle at once, like `triangle_intersect`. Returns
+/// which rays hit the triangle, and the `t`, `u` and `v` of each of their hits.
+#[cfg(feature = "simd")]
+pub(super) fn triangle_intersect_packet(
+    v0: Vector3,
+    v1: Vector3,
+    v2: Vector3,
+    packet: &RayPacket,
+    cull_backfaces: bool,
+) -> (LaneMask, Lanes, Lanes, Lanes) {
+    let splat = |v: Vector3| [Lanes::splat(v.x), Lanes::splat(v.y), Lanes::splat(v.z)];
+    let edge1 = splat(v1 - v0);
+    let edge2 = splat(v2 - v0);
+    let epsilon = Lanes::splat(EPSILON);
+
+    // `a` is negative where a ray hits the back of the triangle
+    let h = cross_lanes(&packet.direction, &edge2);
+    let a = dot_lanes(&edge1, &h);
+    let mut hit = if cull_backfaces {
+        a.simd_ge(epsilon)
+    } else {
+        a.simd_ge(epsilon) | a.simd_le(-epsilon)
+    };
+
+    let f = Lanes::splat(1.) / a;
+    let v0 = splat(v0);
+    let s = [
+        packet.origin[0] - v0[0],
+        packet.origin[1] - v0[1],
+        packet.origin[2] - v0[2],
+    ];
+    let u = f * dot_lanes(&s, &h);
+    hit &= u.simd_ge(Lanes::splat(0.)) & u.simd_le(Lanes::splat(1.));
+
+    let q = cross_lanes(&s, &edge1);
+    let v = f * dot_lanes(&packet.direction, &q);
+    hit &= v.simd_ge(Lanes::splat(0.)) & (u + v).simd_le(Lanes::splat(1.));
+
+    let t = f * dot_lanes(&edge2, &q);
+    hit &= t.simd_gt(epsilon);
+
+    (hit, t, u, v)
+}
+
+pub(super) fn triangle_intersect_uvs(
     v0: usize,
     v1: usize,
     v2: usize,
-    tc: &[(f32, f32)],
+    tc: &[Vector2],
     i: &TriIntersect,
-) -> (f32, f32) {
+) -> Vector2 {
     if tc.len() == 0 {
-        return (0., 0.);
+        return Vector2::new(0., 0.);
     }
 
-    let (a, b, c) = (&tc[v0], &tc[v1], &tc[v2]);
+    let (a, b, c) = (tc[v0], tc[v1], tc[v2]);
     let (iu, iv, iw) = (i.u, i.v, 1. - i.u - i.v);
-    let u = a.0 * iw + b.0 * iu + c.0 * iv;
-    let v = a.1 * iw + b.1 * iu + c.1 * iv;
-    (u.rem_euclid(1.), (1. - v).rem_euclid(1.))
+    let uv = a * iw + b * iu + c * iv;
+    Vector2::new(uv.x, 1. - uv.y)
 }
 
-fn triangle_intersect_normal(
+pub(super) fn triangle_intersect_normal(
     v0: usize,
     v1: usize,
     v2: usize,
@@ -85,9 +258,45 @@ fn triangle_intersect_normal(
 ) -> Vector3 {
     let (a, b, c) = (&ns[v0], &ns[v1], &ns[v2]);
     let (u, v, w) = (1. - i.u - i.v, i.u, i.v);
-    *a * u as f64 + *b * v as f64 + *c * w as f64
+    *a * u as Float + *b * v as Float + *c * w as Float
 }
 
+/// Find the tangent and bitangent of a triangle from its texture coordinates, pointing toward
+/// increasing U and toward the top of the texture. Returns `None` for degenerate UVs.
+pub(super) fn triangle_tangents(
+    (v0, v1, v2): (Vector3, Vector3, Vector3),
+    (t0, t1, t2): (Vector2, Vector2, Vector2),
+) -> Option<(Vector3, Vector3)> {
+    let (edge1, edge2) = (v1 - v0, v2 - v0);
+    let (e1, e2) = (t1 - t0, t2 - t0);
+    let (du1, dv1) = (e1.x as Float, e1.y as Float);
+    let (du2, dv2) = (e2.x as Float, e2.y as Float);
+
+    let det = du1 * dv2 - du2 * dv1;
+    if det.abs() < 1e-12 {
+        return None;
+    }
+
+    // OBJ texture coordinates have V pointing up, so the bitangent follows increasing V
+    let r = 1. / det;
+    Some((
+        (edge1 * dv2 - edge2 * dv1) * r,
+        (edge2 * du1 - edge1 * du2) * r,
+    ))
+}
+
+/// A named part of a mesh, like an object or group of an OBJ file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MeshPart {
+    /// The name of the part.
+    pub name: String,
+
+    /// The range of the part's triangles in the mesh's `tris`.
+    pub tris: Range<usize>,
+}
+
+#[derive(Clone)]
 pub struct Mesh {
     /// A list of unique vertices to use in the mesh.
     pub verts: Vec<Vector3>,
@@ -104,18 +313,55 @@ pub struct Mesh {
     pub tri_normals: Vec<[usize; 3]>,
 
     /// A list of each vertex's texture coordinates.
-    pub texcoords: Vec<(f32, f32)>,
+    pub texcoords: Vec<Vector2>,
 
     /// A list of each triangle vertex's texture coordinates, pointing to an index in `texcoords`.
     ///
     /// **Indices are shared with `tris`.**
     pub tri_texcoords: Vec<[usize; 3]>,
 
-    /// The material of this object.
+    /// A list of each vertex's color, which tints the material's texture. If this is empty,
+    /// the mesh is not tinted.
+    ///
+    /// **Indices are shared with `verts`.**
+    pub colors: Vec<Color>,
+
+    /// Whether to shade each triangle with its flat face normal, rather than interpolating
+    /// the normals of its vertices. Useful for low-poly models, even if they come with
+    /// smooth normals.
+    pub flat_shading: bool,
+
+    /// Whether to skip triangles hit from behind, which speeds up closed meshes and keeps
+    /// their interior faces from shadowing themselves. Rays can then never find where they
+    /// leave the mesh, so this should be off for transparent and refractive meshes.
+    pub backface_culling: bool,
+
+    /// The material of this object. It is used for every triangle without a material in `tri_materials`.
     pub material: Material,
 
+    /// Additional materials, for meshes that have more than one material.
+    pub face_materials: Vec<Material>,
+
+    /// The index into `face_materials` of each triangle's material, or `None` to use `material`.
+    /// If this is empty, every triangle uses `material`.
+    ///
+    /// **Indices are shared with `tris`.**
+    pub tri_materials: Vec<Option<usize>>,
+
+    /// The named parts of this mesh, which may not cover every triangle.
+    pub parts: Vec<MeshPart>,
+
     /// The SBVH acceleration structure of this mesh.
-    pub sbvh: Option<acceleration::TreeNode>,
+    pub sbvh: Option<acceleration::FlatBvh>,
+
+    /// The kd-tree acceleration structure of this mesh, which is used instead of the SBVH when
+    /// it's generated, see `generate_kdtree`.
+    pub kdtree: Option<acceleration::KdTree>,
+
+    /// The Embree BVH of this mesh, which is used instead of the SBVH when it's generated, see
+    /// `generate_embree`.
+    #[cfg(feature = "embree")]
+    pub embree: Option<acceleration::EmbreeBvh>,
 }
 
 impl Mesh {
@@ -127,84 +373,451 @@ impl Mesh {
             tri_normals: Vec::new(),
             texcoords: Vec::new(),
             tri_texcoords: Vec::new(),
+            colors: Vec::new(),
+            flat_shading: false,
+            backface_culling: false,
             material,
+            face_materials: Vec::new(),
+            tri_materials: Vec::new(),
+            parts: Vec::new(),
             sbvh: None,
+            kdtree: None,
+            #[cfg(feature = "embree")]
+            embree: None,
         }
     }
 
     pub fn from_obj(file_name: String, material: Material) -> Self {
-        let (models, _) = tobj::load_obj(
-            file_name,
+        Self::from_obj_with(file_name, material, |_, material| Some(material))
+    }
+
+    /// Load a mesh from an OBJ file, building per-face materials from its MTL library. Every
+    /// model (object or group) in the file is merged into the one mesh, and kept as one of its
+    /// `parts`.
+    ///
+    /// Each MTL material is passed through `hook` along with its name, which can modify or
+    /// replace it. Returning `None` discards it, so its faces use `material` instead.
+    pub fn from_obj_with<F>(file_name: String, material: Material, hook: F) -> Self
+    where
+        F: FnMut(&str, Material) -> Option<Material>,
+    {
+        Self::from_obj_with_assets(file_name, material, hook).0
+    }
+
+    /// Load a mesh from an OBJ file like [`Mesh::from_obj_with`], also returning the paths of
+    /// the other files it depends on: its MTL libraries and the textures they reference.
+    pub fn from_obj_with_assets<F>(
+        file_name: String,
+        material: Material,
+        mut hook: F,
+    ) -> (Self, Vec<PathBuf>)
+    where
+        F: FnMut(&str, Material) -> Option<Material>,
+    {
+        let base_dir = Path::new(&file_name)
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+
+        // load the OBJ like `tobj::load_obj` does, but keep track of the MTL libraries it loads
+        let libraries = RefCell::new(Vec::new());
+        let file = File::open(&file_name).expect("failed to open obj");
+        let (models, mtls) = tobj::load_obj_buf(
+            &mut BufReader::new(file),
             &tobj::LoadOptions {
                 triangulate: true,
                 ..Default::default()
             },
+            |library| {
+                let path = base_dir.join(library);
+                libraries.borrow_mut().push(path.clone());
+                tobj::load_mtl(path)
+            },
         )
         .expect("failed to parse obj");
+        let mut assets = libraries.into_inner();
 
-        let model = models.into_iter().next().unwrap();
-
-        // Gather all vertices
-        let mut verts = Vec::new();
-        let mut verts_iter = model.mesh.positions.into_iter().peekable();
-        while verts_iter.peek().is_some() {
-            let v = verts_iter.by_ref().take(3).collect::<Vec<_>>();
-            verts.push(Vector3::new(v[0] as f64, v[1] as f64, v[2] as f64));
+        // Build materials from the MTL library, if it could be loaded
+        let mut face_materials = Vec::new();
+        let mut mtl_indices = Vec::new();
+        for mtl in mtls.unwrap_or_default() {
+            let mtl_material = mtl_material(&mtl, &base_dir, &material, &mut assets);
+            let index = hook(&mtl.name, mtl_material).map(|mtl| {
+                face_materials.push(mtl);
+                face_materials.len() - 1
+            });
+            mtl_indices.push(index);
         }
 
-        // Gather all texcoords
-        let mut texcoords = Vec::new();
-        let mut texcoords_iter = model.mesh.texcoords.into_iter().peekable();
-        while texcoords_iter.peek().is_some() {
-            let tc = texcoords_iter.by_ref().take(2).collect::<Vec<_>>();
-            texcoords.push((tc[0], tc[1]))
-        }
+        // Models without normals or texcoords get flat normals and a blank texcoord if any
+        // other model has them, so that every triangle has one
+        let any_normals = models.iter().any(|m| !m.mesh.normal_indices.is_empty());
+        let any_texcoords = models.iter().any(|m| !m.mesh.texcoord_indices.is_empty());
+        let any_colors = models.iter().any(|m| !m.mesh.vertex_color.is_empty());
 
-        // Gather all normals
+        let mut verts = Vec::new();
+        let mut tris = Vec::new();
         let mut normals = Vec::new();
-        let mut normals_iter = model.mesh.normals.into_iter().peekable();
-        while normals_iter.peek().is_some() {
-            let ns = normals_iter.by_ref().take(3).collect::<Vec<_>>();
-            normals.push(Vector3::new(ns[0] as f64, ns[1] as f64, ns[2] as f64));
-        }
+        let mut normal_indices = Vec::new();
+        let mut texcoords = Vec::new();
+        let mut texcoord_indices = Vec::new();
+        let mut colors = Vec::new();
+        let mut parts = Vec::new();
+        let mut tri_materials = Vec::new();
+        let mut blank_texcoord = None;
 
-        // Gather all vertex indices (into triangles)
-        let mut tris = Vec::new();
-        let mut tris_iter = model.mesh.indices.into_iter().peekable();
-        while tris_iter.peek().is_some() {
-            let v = tris_iter.by_ref().take(3).collect::<Vec<_>>();
-            tris.push([v[0] as usize, v[1] as usize, v[2] as usize]);
-        }
+        // Merge every model in the file into this mesh
+        for model in models {
+            let (vert_offset, normal_offset, texcoord_offset) =
+                (verts.len(), normals.len(), texcoords.len());
+            let tri_offset = tris.len();
 
-        // Gather all texcoord indices
-        let mut texcoord_indices = Vec::new();
-        let mut texcoord_indices_iter = model.mesh.texcoord_indices.into_iter().peekable();
-        while texcoord_indices_iter.peek().is_some() {
-            let v = texcoord_indices_iter.by_ref().take(3).collect::<Vec<_>>();
-            texcoord_indices.push([v[0] as usize, v[1] as usize, v[2] as usize]);
-        }
+            // Gather all vertices
+            let mut verts_iter = model.mesh.positions.into_iter().peekable();
+            while verts_iter.peek().is_some() {
+                let v = verts_iter.by_ref().take(3).collect::<Vec<_>>();
+                verts.push(Vector3::new(v[0] as Float, v[1] as Float, v[2] as Float));
+            }
 
-        // Gather all normal indices
-        let mut normal_indices = Vec::new();
-        let mut normal_indices_iter = model.mesh.normal_indices.into_iter().peekable();
-        while normal_indices_iter.peek().is_some() {
-            let v = normal_indices_iter.by_ref().take(3).collect::<Vec<_>>();
-            normal_indices.push([v[0] as usize, v[1] as usize, v[2] as usize]);
+            // Gather all vertex colors, with white for models without them
+            if model.mesh.vertex_color.is_empty() {
+                if any_colors {
+                    colors.resize(verts.len(), Color::white());
+                }
+            } else {
+                let mut colors_iter = model.mesh.vertex_color.into_iter().peekable();
+                while colors_iter.peek().is_some() {
+                    let c = colors_iter.by_ref().take(3).collect::<Vec<_>>();
+                    colors.push(Color::newf(c[0] as Float, c[1] as Float, c[2] as Float));
+                }
+            }
+
+            // Gather all texcoords
+            let mut texcoords_iter = model.mesh.texcoords.into_iter().peekable();
+            while texcoords_iter.peek().is_some() {
+                let tc = texcoords_iter.by_ref().take(2).collect::<Vec<_>>();
+                texcoords.push(Vector2::new(tc[0], tc[1]))
+            }
+
+            // Gather all normals
+            let mut normals_iter = model.mesh.normals.into_iter().peekable();
+            while normals_iter.peek().is_some() {
+                let ns = normals_iter.by_ref().take(3).collect::<Vec<_>>();
+                normals.push(Vector3::new(ns[0] as Float, ns[1] as Float, ns[2] as Float));
+            }
+
+            // Gather all vertex indices (into triangles)
+            let mut tris_iter = model.mesh.indices.into_iter().peekable();
+            while tris_iter.peek().is_some() {
+                let v = tris_iter.by_ref().take(3).collect::<Vec<_>>();
+                tris.push([
+                    v[0] as usize + vert_offset,
+                    v[1] as usize + vert_offset,
+                    v[2] as usize + vert_offset,
+                ]);
+            }
+
+            // Gather all texcoord indices
+            if model.mesh.texcoord_indices.is_empty() {
+                if any_texcoords {
+                    let blank = *blank_texcoord.get_or_insert_with(|| {
+                        texcoords.push(Vector2::new(0., 0.));
+                        texcoords.len() - 1
+                    });
+                    texcoord_indices.resize(tris.len(), [blank; 3]);
+                }
+            } else {
+                let mut texcoord_indices_iter = model.mesh.texcoord_indices.into_iter().peekable();
+                while texcoord_indices_iter.peek().is_some() {
+                    let v = texcoord_indices_iter.by_ref().take(3).collect::<Vec<_>>();
+                    texcoord_indices.push([
+                        v[0] as usize + texcoord_offset,
+                        v[1] as usize + texcoord_offset,
+                        v[2] as usize + texcoord_offset,
+                    ]);
+                }
+            }
+
+            // Gather all normal indices
+            if model.mesh.normal_indices.is_empty() {
+                if any_normals {
+                    for tri in &tris[tri_offset..] {
+                        normals.push(triangle_normal(verts[tri[0]], verts[tri[1]], verts[tri[2]]));
+                        normal_indices.push([normals.len() - 1; 3]);
+                    }
+                }
+            } else {
+                let mut normal_indices_iter = model.mesh.normal_indices.into_iter().peekable();
+                while normal_indices_iter.peek().is_some() {
+                    let v = normal_indices_iter.by_ref().take(3).collect::<Vec<_>>();
+                    normal_indices.push([
+                        v[0] as usize + normal_offset,
+                        v[1] as usize + normal_offset,
+                        v[2] as usize + normal_offset,
+                    ]);
+                }
+            }
+
+            let face_material = model
+                .mesh
+                .material_id
+                .and_then(|id| mtl_indices.get(id).copied().flatten());
+            tri_materials.resize(tris.len(), face_material);
+
+            parts.push(MeshPart {
+                name: model.name,
+                tris: tri_offset..tris.len(),
+            });
         }
 
-        Self {
+        let mesh = Self {
+            tri_materials: if tri_materials.iter().any(Option::is_some) {
+                tri_materials
+            } else {
+                Vec::new()
+            },
             verts,
             tris,
             normals,
             tri_normals: normal_indices,
             texcoords,
             tri_texcoords: texcoord_indices,
+            colors,
+            flat_shading: false,
+            backface_culling: false,
             material,
+            face_materials,
+            parts,
             sbvh: None,
+            kdtree: None,
+            #[cfg(feature = "embree")]
+            embree: None,
+        };
+
+        (mesh, assets)
+    }
+
+    /// Load a mesh from a PLY file, in either its ASCII or binary formats, along with its
+    /// vertex normals, colors and texture coordinates if it has them. Faces with more than
+    /// three vertices are split into triangles.
+    pub fn from_ply(file_name: String, material: Material) -> io::Result<Self> {
+        let data = ply::read_ply(BufReader::new(File::open(file_name)?))?;
+
+        let mut tris = Vec::new();
+        for face in data.faces.iter() {
+            if face.iter().any(|&i| i >= data.verts.len()) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "invalid ply: face refers to a missing vertex",
+                ));
+            }
+
+            for i in 2..face.len() {
+                tris.push([face[0], face[i - 1], face[i]]);
+            }
+        }
+
+        let mut mesh = Self::new(material);
+
+        // normals, colors and texcoords are per vertex, so they share the vertex indices
+        if data.normals.len() == data.verts.len() {
+            mesh.normals = data.normals;
+            mesh.tri_normals = tris.clone();
+        }
+        if data.texcoords.len() == data.verts.len() {
+            mesh.texcoords = data.texcoords;
+            mesh.tri_texcoords = tris.clone();
+        }
+        if data.colors.len() == data.verts.len() {
+            mesh.colors = data.colors;
+        }
+
+        mesh.verts = data.verts;
+        mesh.tris = tris;
+        Ok(mesh)
+    }
+
+    /// Append the geometry of `other` to this mesh, remapping its indices, so that both can be
+    /// intersected through one SBVH instead of being separate scene objects.
+    ///
+    /// The triangles of `other` keep their materials: its face materials are added to this
+    /// mesh's, and its own `material` is added as a face material for the rest of its triangles.
+    /// If only one of the meshes has normals, texture coordinates or colors, the other's
+    /// triangles get flat normals, blank texture coordinates or white vertices. The SBVH must
+    /// be generated again afterwards.
+    pub fn append(&mut self, other: &Mesh) {
+        let need_normals = !self.tri_normals.is_empty() || !other.tri_normals.is_empty();
+        let need_texcoords = !self.tri_texcoords.is_empty() || !other.tri_texcoords.is_empty();
+        let need_colors = !self.colors.is_empty() || !other.colors.is_empty();
+
+        let fill = |mesh: &mut Mesh| {
+            if need_normals {
+                mesh.fill_flat_normals();
+            }
+            if need_texcoords {
+                mesh.fill_blank_texcoords();
+            }
+            if need_colors {
+                mesh.colors.resize(mesh.verts.len(), Color::white());
+            }
+        };
+
+        fill(self);
+
+        let tri_offset = self.tris.len();
+        let (vert_offset, normal_offset, texcoord_offset) =
+            (self.verts.len(), self.normals.len(), self.texcoords.len());
+        let offset = |tris: &[[usize; 3]], offset: usize| {
+            tris.iter()
+                .map(|t| [t[0] + offset, t[1] + offset, t[2] + offset])
+                .collect::<Vec<_>>()
+        };
+
+        self.verts.extend_from_slice(&other.verts);
+        self.tris.extend(offset(&other.tris, vert_offset));
+        self.normals.extend_from_slice(&other.normals);
+        self.tri_normals
+            .extend(offset(&other.tri_normals, normal_offset));
+        self.texcoords.extend_from_slice(&other.texcoords);
+        self.tri_texcoords
+            .extend(offset(&other.tri_texcoords, texcoord_offset));
+        self.colors.extend_from_slice(&other.colors);
+
+        fill(self);
+
+        // keep the materials of the other mesh's triangles
+        let material_offset = self.face_materials.len();
+        self.face_materials
+            .extend(other.face_materials.iter().cloned());
+        let other_material = self.add_material(other.material.clone());
+
+        self.tri_materials.resize(tri_offset, None);
+        self.tri_materials.extend((0..other.tris.len()).map(|i| {
+            match other.tri_materials.get(i).copied().flatten() {
+                Some(index) => Some(index + material_offset),
+                None => Some(other_material),
+            }
+        }));
+
+        self.parts.extend(other.parts.iter().map(|part| MeshPart {
+            name: part.name.clone(),
+            tris: part.tris.start + tri_offset..part.tris.end + tri_offset,
+        }));
+
+        self.sbvh = None;
+        self.kdtree = None;
+        #[cfg(feature = "embree")]
+        {
+            self.embree = None;
+        }
+    }
+
+    /// Merge `other` into this mesh, see [`Mesh::append`].
+    pub fn merge(mut self, other: &Mesh) -> Self {
+        self.append(other);
+        self
+    }
+
+    /// Give every triangle without normals its face normal.
+    fn fill_flat_normals(&mut self) {
+        for tri in self.tris[self.tri_normals.len()..].iter() {
+            self.normals.push(triangle_normal(
+                self.verts[tri[0]],
+                self.verts[tri[1]],
+                self.verts[tri[2]],
+            ));
+            self.tri_normals.push([self.normals.len() - 1; 3]);
+        }
+    }
+
+    /// Give every triangle without texture coordinates a blank one.
+    fn fill_blank_texcoords(&mut self) {
+        if self.tri_texcoords.len() < self.tris.len() {
+            self.texcoords.push(Vector2::new(0., 0.));
+            let blank = self.texcoords.len() - 1;
+            self.tri_texcoords.resize(self.tris.len(), [blank; 3]);
         }
     }
 
-    /// Recalculate the mesh's normals.
+    /// Get a part of this mesh by its name.
+    pub fn part(&self, name: &str) -> Option<&MeshPart> {
+        self.parts.iter().find(|part| part.name == name)
+    }
+
+    /// Set the material of every triangle of the part named `name`, see `set_tri_material`.
+    /// Returns whether the part exists.
+    pub fn set_part_material(&mut self, name: &str, material: Option<usize>) -> bool {
+        let tris = match self.part(name) {
+            Some(part) => part.tris.clone(),
+            None => return false,
+        };
+
+        for tri in tris {
+            self.set_tri_material(tri, material);
+        }
+        true
+    }
+
+    /// Remove every triangle that isn't in one of the parts named in `names`, for example to
+    /// show one part of a model on its own. The vertices are kept, so the part stays where it
+    /// was in the whole model. The SBVH must be generated again afterwards.
+    pub fn retain_parts(&mut self, names: &[&str]) {
+        // per-triangle data is either missing, or there for every triangle
+        fn keep<'a, T>(v: &'a [T], range: &Range<usize>) -> &'a [T] {
+            v.get(range.clone()).unwrap_or(&[])
+        }
+
+        let mut tris = Vec::new();
+        let mut tri_normals = Vec::new();
+        let mut tri_texcoords = Vec::new();
+        let mut tri_materials = Vec::new();
+        let mut parts = Vec::new();
+        for part in self
+            .parts
+            .iter()
+            .filter(|p| names.contains(&p.name.as_str()))
+        {
+            let start = tris.len();
+            tris.extend_from_slice(&self.tris[part.tris.clone()]);
+            tri_normals.extend_from_slice(keep(&self.tri_normals, &part.tris));
+            tri_texcoords.extend_from_slice(keep(&self.tri_texcoords, &part.tris));
+            tri_materials.extend_from_slice(keep(&self.tri_materials, &part.tris));
+            parts.push(MeshPart {
+                name: part.name.clone(),
+                tris: start..tris.len(),
+            });
+        }
+
+        self.tris = tris;
+        self.tri_normals = tri_normals;
+        self.tri_texcoords = tri_texcoords;
+        self.tri_materials = tri_materials;
+        self.parts = parts;
+        self.sbvh = None;
+        self.kdtree = None;
+        #[cfg(feature = "embree")]
+        {
+            self.embree = None;
+        }
+    }
+
+    /// Add a per-face material to this mesh, returning its index for use in `tri_materials`.
+    pub fn add_material(&mut self, material: Material) -> usize {
+        self.face_materials.push(material);
+        self.face_materials.len() - 1
+    }
+
+    /// Set the material of a triangle to one of `face_materials`, or `None` to use `material`.
+    pub fn set_tri_material(&mut self, tri: usize, material: Option<usize>) {
+        if self.tri_materials.len() < self.tris.len() {
+            self.tri_materials.resize(self.tris.len(), None);
+        }
+
+        self.tri_materials[tri] = material;
+    }
+
+    /// Recalculate the mesh's normals, replacing any it was loaded with by smooth vertex normals
+    /// averaged from the faces around each vertex.
     pub fn recalculate_normals(&mut self) {
         // Empty the normals and vert_normals vecs
         self.normals = Vec::new();
@@ -241,7 +854,7 @@ impl Mesh {
 
             // Insert the calculated normal into the normals Vec
             self.normals
-                .push((agg_norm / normals.len() as f64).normalize());
+                .push((agg_norm / normals.len() as Float).normalize());
 
             for tri in tris {
                 for n in 0..3 {
@@ -253,11 +866,87 @@ impl Mesh {
         }
     }
 
-    /// (Re)generate this mesh's SBVH.
-    pub fn generate_sbvh(&mut self) {
-        // Bake the mesh's triangles into a list of SBVH tris.
-        let tris = self
-            .tris
+    /// Recalculate the mesh's normals and regenerate its SBVH (or kd-tree, or Embree BVH), after
+    /// its vertices have been moved by `map_vertices` or `displace`.
+    pub fn recalculate(&mut self) {
+        self.recalculate_normals();
+        self.regenerate();
+    }
+
+    /// Rebuild whichever acceleration structure this mesh has, or an SBVH if it has none.
+    fn regenerate(&mut self) {
+        #[cfg(feature = "embree")]
+        if self.embree.is_some() {
+            return self.generate_embree();
+        }
+
+        if self.kdtree.is_some() {
+            self.generate_kdtree();
+        } else {
+            self.generate_sbvh();
+        }
+    }
+
+    /// Move every vertex of the mesh to the position given by `f`, for example to bend it or
+    /// ripple it into waves. The normals and SBVH are left stale, see `recalculate` and
+    /// `refit_sbvh`.
+    pub fn map_vertices<F>(&mut self, mut f: F)
+    where
+        F: FnMut(Vector3) -> Vector3,
+    {
+        self.verts.iter_mut().for_each(|v| *v = f(*v));
+    }
+
+    /// Push every vertex of the mesh out along its normal by the distance given by `f` (negative
+    /// distances push it in). The normals and SBVH are left stale, see `recalculate` and
+    /// `refit_sbvh`.
+    pub fn displace<F>(&mut self, mut f: F)
+    where
+        F: FnMut(Vector3) -> Float,
+    {
+        let normals = self.vertex_normals();
+        for (vert, normal) in self.verts.iter_mut().zip(normals) {
+            *vert += normal * f(*vert);
+        }
+    }
+
+    /// Displace the mesh by Perlin noise, sampled at its vertices' positions times `frequency`,
+    /// moving each vertex by up to `amplitude` along its normal.
+    #[allow(clippy::unnecessary_cast)]
+    pub fn displace_noise(&mut self, amplitude: Float, frequency: Float, seed: u32) {
+        let perlin = Perlin::new().set_seed(seed);
+        self.displace(|v| {
+            let p = v * frequency;
+            perlin.get([p.x as f64, p.y as f64, p.z as f64]) as Float * amplitude
+        });
+    }
+
+    /// The normal of each vertex, averaged from the faces around it and weighted by their area.
+    fn vertex_normals(&self) -> Vec<Vector3> {
+        let mut normals = vec![Vector3::default(); self.verts.len()];
+        for tri in self.tris.iter() {
+            let [v0, v1, v2] = [self.verts[tri[0]], self.verts[tri[1]], self.verts[tri[2]]];
+            let normal = (v1 - v0).cross(v2 - v0);
+            for &i in tri {
+                normals[i] += normal;
+            }
+        }
+
+        normals
+            .into_iter()
+            .map(|n| {
+                if n == Vector3::default() {
+                    n
+                } else {
+                    n.normalize()
+                }
+            })
+            .collect()
+    }
+
+    /// Bake the mesh's triangles into a list of acceleration structure tris.
+    fn acceleration_tris(&self) -> Vec<acceleration::Triangle> {
+        self.tris
             .iter()
             .map(|tri| {
                 acceleration::Triangle::new(
@@ -266,9 +955,164 @@ impl Mesh {
                     self.verts[tri[2]],
                 )
             })
-            .collect::<Vec<_>>();
+            .collect()
+    }
+
+    /// (Re)generate this mesh's SBVH, dropping its kd-tree (or Embree BVH) if it has one.
+    pub fn generate_sbvh(&mut self) {
+        self.sbvh = Some(acceleration::Sbvh::new(&self.acceleration_tris()).into());
+        self.kdtree = None;
+        #[cfg(feature = "embree")]
+        {
+            self.embree = None;
+        }
+    }
+
+    /// (Re)generate this mesh's kd-tree, which is traversed instead of an SBVH, and drop its
+    /// SBVH. Some meshes, like CAD models, trace much faster with a kd-tree, but most are faster
+    /// with an SBVH, so compare the two (see `Scene::render_bvh_heatmap`).
+    pub fn generate_kdtree(&mut self) {
+        self.kdtree = Some(acceleration::KdTree::new(&self.acceleration_tris()));
+        self.sbvh = None;
+        #[cfg(feature = "embree")]
+        {
+            self.embree = None;
+        }
+    }
+
+    /// (Re)generate this mesh's BVH with Embree, which is traversed instead of an SBVH, and drop
+    /// its SBVH and kd-tree. Embree builds much faster than an SBVH, which pays off on huge
+    /// meshes. See `acceleration::EmbreeBvh`.
+    #[cfg(feature = "embree")]
+    pub fn generate_embree(&mut self) {
+        self.embree = Some(acceleration::EmbreeBvh::new(&self.acceleration_tris()));
+        self.sbvh = None;
+        self.kdtree = None;
+    }
+
+    /// Statistics about the shape of this mesh's kd-tree (or Embree BVH) if it has one, or its
+    /// SBVH, or `None` if it has neither yet.
+    pub fn bvh_stats(&self) -> Option<acceleration::BvhStats> {
+        #[cfg(feature = "embree")]
+        if let Some(embree) = &self.embree {
+            return Some(embree.stats());
+        }
+
+        match (&self.kdtree, &self.sbvh) {
+            (Some(kdtree), _) => Some(kdtree.stats()),
+            (None, Some(sbvh)) => Some(sbvh.stats()),
+            (None, None) => None,
+        }
+    }
+
+    /// Call `f` with the triangles `ray` might hit, nearest first, with the kd-tree (or Embree
+    /// BVH) if this mesh has one, or the SBVH. See `Accelerator::traverse`.
+    fn traverse<F>(&self, ray: &Ray, f: F) -> usize
+    where
+        F: FnMut(usize) -> Float,
+    {
+        #[cfg(feature = "embree")]
+        if let Some(embree) = &self.embree {
+            return embree.traverse(ray, f);
+        }
+
+        match (&self.kdtree, &self.sbvh) {
+            (Some(kdtree), _) => kdtree.traverse(ray, f),
+            (None, Some(sbvh)) => sbvh.traverse(ray, f),
+            (None, None) => panic!("mesh has no acceleration structure, see `generate_sbvh`"),
+        }
+    }
+
+    /// A hash of this mesh's triangles and where its vertices are, after any transforms. Two
+    /// meshes with the same hash have the same SBVH.
+    #[allow(clippy::unnecessary_cast)]
+    pub fn geometry_hash(&self) -> u64 {
+        // FNV-1a, which is stable across runs and platforms unlike `DefaultHasher`
+        let mut hash = 0xcbf29ce484222325u64;
+        let mut write = |bytes: [u8; 8]| {
+            for byte in bytes {
+                hash = (hash ^ byte as u64).wrapping_mul(0x100000001b3);
+            }
+        };
+
+        // an SBVH built with one precision can't be used with the other
+        write((std::mem::size_of::<Float>() as u64).to_le_bytes());
+        write((self.verts.len() as u64).to_le_bytes());
+        for v in self.verts.iter() {
+            write((v.x as f64).to_bits().to_le_bytes());
+            write((v.y as f64).to_bits().to_le_bytes());
+            write((v.z as f64).to_bits().to_le_bytes());
+        }
+        write((self.tris.len() as u64).to_le_bytes());
+        for tri in self.tris.iter() {
+            for &i in tri {
+                write((i as u64).to_le_bytes());
+            }
+        }
+
+        hash
+    }
+
+    /// Like `generate_sbvh`, but cache the SBVH in `cache_dir`, keyed by `geometry_hash`. If this
+    /// mesh's SBVH was cached before, it is loaded instead of being built again. Otherwise it is
+    /// built and written to the cache, and any error doing so is returned, although the mesh
+    /// still gets its SBVH.
+    pub fn generate_sbvh_cached(&mut self, cache_dir: &Path) -> io::Result<()> {
+        let path = cache_dir.join(format!("{:016x}.sbvh", self.geometry_hash()));
+
+        // a missing, stale or corrupt cache file is simply rebuilt and overwritten
+        if let Ok(file) = File::open(&path) {
+            if let Ok(sbvh) =
+                acceleration::FlatBvh::read_from(&mut BufReader::new(file), self.tris.len())
+            {
+                self.sbvh = Some(sbvh);
+                return Ok(());
+            }
+        }
+
+        self.generate_sbvh();
+
+        // write to a temporary file first, so other processes never read a partial SBVH
+        fs::create_dir_all(cache_dir)?;
+        let temp = path.with_extension(format!("sbvh.{}", std::process::id()));
+        let sbvh = self.sbvh.as_ref().unwrap();
+        let result = File::create(&temp)
+            .and_then(|file| {
+                let mut writer = BufWriter::new(file);
+                sbvh.write_to(&mut writer)?;
+                writer.flush()
+            })
+            .and_then(|_| fs::rename(&temp, &path));
+        if result.is_err() {
+            let _ = fs::remove_file(&temp);
+        }
+        result
+    }
 
-        self.sbvh = Some(acceleration::Sbvh::new(&tris).into());
+    /// Update the bounding boxes of this mesh's SBVH to fit its vertices after they have moved
+    /// (by `shift`, `scale`, `map_vertices`, ...), without rebuilding it. This is much cheaper
+    /// than `generate_sbvh`, for example to animate a mesh every frame, but the SBVH gets less
+    /// efficient the further the vertices move from where it was built. The triangles themselves
+    /// must not have changed. If there is no SBVH yet, it is generated. A kd-tree (or an Embree
+    /// BVH) can't be refit, so it is regenerated instead.
+    pub fn refit_sbvh(&mut self) {
+        #[cfg(feature = "embree")]
+        if self.embree.is_some() {
+            return self.generate_embree();
+        }
+
+        if self.kdtree.is_some() {
+            return self.generate_kdtree();
+        }
+
+        let (verts, tris) = (&self.verts, &self.tris);
+        match self.sbvh.as_mut() {
+            Some(sbvh) => sbvh.refit(&|i| {
+                let tri = tris[i];
+                acceleration::Aabb::from_vecs(&[verts[tri[0]], verts[tri[1]], verts[tri[2]]])
+            }),
+            None => self.generate_sbvh(),
+        }
     }
 
     /// Shift all vertices by some vector.
@@ -293,148 +1137,208 @@ impl Mesh {
     }
 
     /// Scale all vertices by some vector.
-    pub fn scale(&mut self, delta: f64) {
+    pub fn scale(&mut self, delta: Float) {
         self.verts.iter_mut().for_each(|v| *v *= delta);
     }
 
-    /// Rotate the mesh in XYZ order.
-    pub fn rotate_xyz(&mut self, rot: Vector3) {
-        let rot = Matrix::from_euler_xyz(-rot.x, -rot.y, -rot.z);
+    /// Rotate the mesh around its origin.
+    pub fn rotate(&mut self, rotation: Quaternion) {
+        let rotation = rotation.normalize();
+        self.verts.iter_mut().for_each(|v| *v = rotation.rotate(*v));
+        self.normals
+            .iter_mut()
+            .for_each(|n| *n = rotation.rotate(*n));
+    }
 
-        for vert in self.verts.iter_mut() {
-            *vert = (rot * Matrix::from(*vert)).pos();
-        }
+    /// Place the mesh by `transform`, which scales, rotates and moves its vertices.
+    pub fn transform(&mut self, transform: Transform) {
+        self.verts
+            .iter_mut()
+            .for_each(|v| *v = transform.transform_point(*v));
+        self.normals
+            .iter_mut()
+            .for_each(|n| *n = transform.transform_normal(*n));
+    }
 
-        for norm in self.normals.iter_mut() {
-            *norm = (rot * Matrix::from(*norm)).pos();
-        }
+    /// Rotate the mesh in XYZ order.
+    pub fn rotate_xyz(&mut self, rot: Vector3) {
+        self.rotate(Quaternion::from_euler_xyz(-rot.x, -rot.y, -rot.z));
     }
 
     /// Rotate the mesh in ZYX order.
     pub fn rotate_zyx(&mut self, rot: Vector3) {
-        let rot = Matrix::from_euler_zyx(-rot.x, -rot.y, -rot.z);
+        self.rotate(Quaternion::from_euler_zyx(-rot.x, -rot.y, -rot.z));
+    }
+
+    /// The normal of a triangle at an intersection, either its face normal or the
+    /// interpolated normals of its vertices depending on `flat_shading`.
+    /// Build the hit of a ray from the nearest two triangles it hits.
+    fn nearest_hit(&self, hits: NearestHits) -> Option<Hit> {
+        // the far side of the hit is the next triangle along, if there is one
+        let (i, ti) = hits.near?;
+        let far_hit = hits.far.as_ref().map(|(_, f)| f).unwrap_or(&ti);
+        let mut hit = Hit::new(
+            self.hit_normal(i, &ti),
+            (ti.t, ti.p),
+            (far_hit.t, far_hit.p),
+            if self.tri_texcoords.len() > 0 {
+                triangle_intersect_uvs(
+                    self.tri_texcoords[i][0],
+                    self.tri_texcoords[i][1],
+                    self.tri_texcoords[i][2],
+                    &self.texcoords,
+                    &ti,
+                )
+            } else {
+                Vector2::new(0., 0.)
+            },
+        );
 
-        for vert in self.verts.iter_mut() {
-            *vert = (rot * Matrix::from(*vert)).pos();
+        // attach the nearest triangle and its tangents, for per-face materials and normal mapping
+        let t = &self.tris[i];
+        hit.face = Some(i);
+
+        if !self.colors.is_empty() {
+            let (w0, w1, w2) = ((1. - ti.u - ti.v) as Float, ti.u as Float, ti.v as Float);
+            let color = Vector3::from(self.colors[t[0]]) * w0
+                + Vector3::from(self.colors[t[1]]) * w1
+                + Vector3::from(self.colors[t[2]]) * w2;
+            hit = hit.with_color(color.into());
+        }
+        if self.tri_texcoords.is_empty() {
+            return Some(hit);
         }
 
-        for norm in self.normals.iter_mut() {
-            *norm = (rot * Matrix::from(*norm)).pos();
+        let tc = self.tri_texcoords[i];
+        match triangle_tangents(
+            (self.verts[t[0]], self.verts[t[1]], self.verts[t[2]]),
+            (
+                self.texcoords[tc[0]],
+                self.texcoords[tc[1]],
+                self.texcoords[tc[2]],
+            ),
+        ) {
+            Some((tangent, bitangent)) => Some(hit.with_tangents(tangent, bitangent)),
+            None => Some(hit),
         }
     }
 
-    fn sbvh_intersection(&self, node: &acceleration::TreeNode, ray: &Ray) -> Option<Vec<usize>> {
-        if !node.bounding().intersect(ray) {
-            return None;
+    fn hit_normal(&self, tri: usize, i: &TriIntersect) -> Vector3 {
+        if self.flat_shading {
+            let [v0, v1, v2] = self.tris[tri];
+            return triangle_normal(self.verts[v0], self.verts[v1], self.verts[v2]);
         }
 
-        match node {
-            acceleration::TreeNode::Branch { a, b, .. } => {
-                match (
-                    self.sbvh_intersection(a, ray),
-                    self.sbvh_intersection(b, ray),
+        let [n0, n1, n2] = self.tri_normals[tri];
+        triangle_intersect_normal(n0, n1, n2, &self.normals, i)
+    }
+}
+
+impl Mesh {
+    /// Find the nearest two triangles `ray` hits, and how many SBVH nodes were visited to find
+    /// them.
+    fn nearest_hits(&self, ray: &Ray) -> (NearestHits, usize) {
+        // intersect the triangles as the mesh is traversed, keeping the nearest two hits, and
+        // skipping anything beyond the second
+        let mut hits = NearestHits::default();
+        let visited = self.traverse(ray, |i| {
+            if !hits.seen(i) {
+                let t = &self.tris[i];
+                if let Some(h) = triangle_intersect(
+                    self.verts[t[0]],
+                    self.verts[t[1]],
+                    self.verts[t[2]],
+                    ray,
+                    self.backface_culling,
                 ) {
-                    (Some(av), Some(bv)) => Some(av.into_iter().chain(bv).collect()),
-                    (Some(av), None) => Some(av),
-                    (None, Some(bv)) => Some(bv),
-                    (None, None) => None,
+                    hits.insert(i, h);
                 }
             }
-            acceleration::TreeNode::Leaf { indices, .. } => Some(indices.clone()),
-        }
+
+            hits.max_t()
+        });
+
+        (hits, visited)
     }
 }
 
 impl Intersect for Mesh {
     fn intersect(&self, ray: &Ray) -> Option<Hit> {
-        assert!(self.sbvh.is_some());
+        self.nearest_hit(self.nearest_hits(ray).0)
+    }
 
-        let tris = match self.sbvh_intersection(self.sbvh.as_ref().unwrap(), ray) {
-            Some(v) => v,
-            None => return None,
+    #[cfg(feature = "simd")]
+    fn intersect_packet(&self, rays: &[Ray; PACKET_SIZE]) -> [Option<Hit>; PACKET_SIZE] {
+        // only SBVHs can be traversed in packets
+        if self.sbvh.is_none() {
+            return std::array::from_fn(|i| self.intersect(&rays[i]));
         }
-        .into_iter()
-        .map(|i| (i, &self.tris[i]))
-        .collect::<Vec<_>>();
+        assert!(self.sbvh.is_some());
 
-        if tris.is_empty() {
-            return None;
-        }
+        let packet = RayPacket::new(rays);
+        let mut hits: [NearestHits; PACKET_SIZE] = Default::default();
+        self.sbvh
+            .as_ref()
+            .unwrap()
+            .traverse_packet(&packet, |i, active| {
+                let t = &self.tris[i];
+                let (mask, ts, us, vs) = triangle_intersect_packet(
+                    self.verts[t[0]],
+                    self.verts[t[1]],
+                    self.verts[t[2]],
+                    &packet,
+                    self.backface_culling,
+                );
 
-        // find the triangles that intersect our ray
-        let mut intersected_tris = tris
-            .iter()
-            .filter_map(|(i, t)| {
-                triangle_intersect(self.verts[t[0]], self.verts[t[1]], self.verts[t[2]], ray)
-                    .map(|h| (i, t, h))
-            })
-            .collect::<Vec<_>>();
+                let mask = mask & active;
+                for (lane, hits) in hits.iter_mut().enumerate() {
+                    if mask.test(lane) && !hits.seen(i) {
+                        let h = TriIntersect {
+                            p: rays[lane].along(ts[lane]),
+                            t: ts[lane],
+                            u: us[lane] as f32,
+                            v: vs[lane] as f32,
+                        };
+                        hits.insert(i, h);
+                    }
+                }
 
-        // then sort them by nearness
-        intersected_tris.sort_by(|(_, _, ta), (_, _, tb)| {
-            ta.t.partial_cmp(&tb.t).unwrap_or(std::cmp::Ordering::Equal)
-        });
+                Lanes::from_array(std::array::from_fn(|lane| hits[lane].max_t()))
+            });
 
-        // return based on how many triangles we have
-        match intersected_tris.len() {
-            // no hits: return no hit
-            0 => None,
-
-            // one hit: return the only hit, where t_far is also t_near
-            1 => {
-                let t = &intersected_tris[0];
-                Some(Hit::new(
-                    triangle_intersect_normal(
-                        self.tri_normals[*t.0][0],
-                        self.tri_normals[*t.0][1],
-                        self.tri_normals[*t.0][2],
-                        &self.normals,
-                        &t.2,
+        hits.map(|hits| self.nearest_hit(hits))
+    }
+
+    fn occludes(&self, ray: &Ray, max_t: Float) -> bool {
+        let mut occluded = false;
+        self.traverse(ray, |i| {
+            if !occluded {
+                let t = &self.tris[i];
+                occluded = matches!(
+                    triangle_intersect(
+                        self.verts[t[0]],
+                        self.verts[t[1]],
+                        self.verts[t[2]],
+                        ray,
+                        self.backface_culling,
                     ),
-                    (t.2.t, t.2.p),
-                    (t.2.t, t.2.p),
-                    if self.tri_texcoords.len() > 0 {
-                        triangle_intersect_uvs(
-                            self.tri_texcoords[*t.0][0],
-                            self.tri_texcoords[*t.0][1],
-                            self.tri_texcoords[*t.0][2],
-                            &self.texcoords,
-                            &t.2,
-                        )
-                    } else {
-                        (0., 0.)
-                    },
-                ))
+                    Some(h) if h.t <= max_t
+                );
             }
 
-            // 2+ hits: return the first hit, but with a proper t_far
-            _ => {
-                let t = &intersected_tris[0];
-                let t1 = &intersected_tris[1];
-                Some(Hit::new(
-                    triangle_intersect_normal(
-                        self.tri_normals[*t.0][0],
-                        self.tri_normals[*t.0][1],
-                        self.tri_normals[*t.0][2],
-                        &self.normals,
-                        &t.2,
-                    ),
-                    (t.2.t, t.2.p),
-                    (t1.2.t, t1.2.p),
-                    if self.tri_texcoords.len() > 0 {
-                        triangle_intersect_uvs(
-                            self.tri_texcoords[*t.0][0],
-                            self.tri_texcoords[*t.0][1],
-                            self.tri_texcoords[*t.0][2],
-                            &self.texcoords,
-                            &t.2,
-                        )
-                    } else {
-                        (0., 0.)
-                    },
-                ))
+            // any hit will do, so stop looking once there is one
+            if occluded {
+                Float::NEG_INFINITY
+            } else {
+                max_t
             }
-        }
+        });
+
+        occluded
+    }
+
+    fn bvh_nodes_visited(&self, ray: &Ray) -> usize {
+        self.nearest_hits(ray).1
     }
 }
 
@@ -442,4 +1346,76 @@ impl SceneObject for Mesh {
     fn material(&self) -> &Material {
         &self.material
     }
+
+    fn material_at(&self, hit: &Hit) -> &Material {
+        hit.face
+            .and_then(|face| self.tri_materials.get(face).copied().flatten())
+            .and_then(|index| self.face_materials.get(index))
+            .unwrap_or(&self.material)
+    }
+
+    fn material_mut(&mut self) -> &mut Material {
+        &mut self.material
+    }
+
+    fn kind(&self) -> &'static str {
+        "mesh"
+    }
+
+    fn clone_box(&self) -> Box<dyn SceneObject> {
+        Box::new(self.clone())
+    }
+
+    fn fingerprint(&self, hasher: &mut Fingerprint) {
+        hasher.write_debug(&(
+            self.kind(),
+            self.geometry_hash(),
+            self.flat_shading,
+            self.backface_culling,
+        ));
+        hasher.write_debug(&(&self.normals, &self.tri_normals));
+        hasher.write_debug(&(&self.texcoords, &self.tri_texcoords));
+        hasher.write_debug(&(&self.colors, &self.tri_materials));
+        self.material.fingerprint(hasher);
+        for material in self.face_materials.iter() {
+            material.fingerprint(hasher);
+        }
+    }
+
+    fn validate(&self) -> Vec<Problem> {
+        let mut problems =
+            validate::check_triangles(&self.verts, self.tris.iter().copied(), self.normals.iter());
+        #[cfg(feature = "embree")]
+        let embree = self.embree.is_some();
+        #[cfg(not(feature = "embree"))]
+        let embree = false;
+        if self.sbvh.is_none() && self.kdtree.is_none() && !embree {
+            problems.push(Problem::MissingSbvh);
+        }
+        problems
+    }
+
+    #[cfg(feature = "serde")]
+    fn tagged(&self) -> crate::serialize::TaggedObject<'_> {
+        crate::serialize::TaggedObject::Mesh(self)
+    }
+
+    fn opaque(&self) -> bool {
+        self.material.is_opaque() && self.face_materials.iter().all(Material::is_opaque)
+    }
+
+    fn stats(&self) -> ObjectStats {
+        let bvh = self.bvh_stats();
+        ObjectStats {
+            triangles: self.tris.len(),
+            texture_bytes: self.material.texture_bytes()
+                + self
+                    .face_materials
+                    .iter()
+                    .map(Material::texture_bytes)
+                    .sum::<usize>(),
+            bvh_nodes: bvh.as_ref().map_or(0, |bvh| bvh.nodes),
+            bvh_bytes: bvh.map_or(0, |bvh| bvh.bytes),
+        }
+    }
 }
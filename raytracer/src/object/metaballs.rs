@@ -0,0 +1,240 @@
+use crate::{
+    checkpoint::Fingerprint,
+    material::Material,
+    math::{Float, Ray, Vector2, Vector3},
+};
+
+use super::{Hit, Intersect, SceneObject};
+
+/// One of the points of a `Metaballs` object.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Metaball {
+    /// The center of the metaball.
+    pub position: Vector3,
+
+    /// The distance at which the metaball no longer has any influence. The visible ball is
+    /// smaller than this, depending on the threshold of the `Metaballs`.
+    pub radius: Float,
+
+    /// How strongly this metaball contributes to the field. A negative weight carves into
+    /// the other metaballs instead.
+    pub weight: Float,
+}
+
+impl Metaball {
+    pub fn new(position: Vector3, radius: Float, weight: Float) -> Self {
+        Self {
+            position,
+            radius,
+            weight,
+        }
+    }
+
+    /// The strength of this metaball's field at `p`, which falls off smoothly from its weight at
+    /// its center to zero at its radius.
+    pub fn field(&self, p: Vector3) -> Float {
+        let rel = p - self.position;
+        let r2 = rel.dot(rel) / (self.radius * self.radius);
+        if r2 >= 1. {
+            return 0.;
+        }
+
+        self.weight * (1. - r2).powi(3)
+    }
+
+    /// The gradient of this metaball's field at `p`.
+    fn gradient(&self, p: Vector3) -> Vector3 {
+        let rel = p - self.position;
+        let rad2 = self.radius * self.radius;
+        let r2 = rel.dot(rel) / rad2;
+        if r2 >= 1. {
+            return Vector3::default();
+        }
+
+        rel * (self.weight * -6. * (1. - r2).powi(2) / rad2)
+    }
+}
+
+/// A blobby isosurface around a set of metaballs, whose fields are added together. The surface
+/// is wherever the total field equals the threshold, so nearby metaballs melt into each other.
+///
+/// Rays are intersected by marching through the reach of the metaballs in small steps, then
+/// refining the crossing of the surface.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Metaballs {
+    /// The metaballs making up the field.
+    pub balls: Vec<Metaball>,
+
+    /// The strength of the field at the surface.
+    pub threshold: Float,
+
+    /// How many steps to take along a ray per radius of the smallest metaball. More steps
+    /// find thinner features, but take longer.
+    pub steps_per_radius: u32,
+
+    /// The material of the surface.
+    pub material: Material,
+}
+
+impl Metaballs {
+    pub fn new(balls: Vec<Metaball>, threshold: Float, material: Material) -> Self {
+        Self {
+            balls,
+            threshold,
+            steps_per_radius: 16,
+            material,
+        }
+    }
+
+    /// Set how many steps to take along a ray per radius of the smallest metaball.
+    pub fn with_steps_per_radius(mut self, steps_per_radius: u32) -> Self {
+        self.steps_per_radius = steps_per_radius;
+        self
+    }
+
+    /// The total strength of the field at `p`.
+    pub fn field(&self, p: Vector3) -> Float {
+        self.balls.iter().map(|b| b.field(p)).sum()
+    }
+
+    fn inside(&self, p: Vector3) -> bool {
+        self.field(p) >= self.threshold
+    }
+
+    /// Find the span along `ray` within the reach of any metaball.
+    fn span(&self, ray: &Ray) -> Option<(Float, Float)> {
+        let mut span: Option<(Float, Float)> = None;
+        for ball in self.balls.iter() {
+            let oc = ray.origin - ball.position;
+            let b = oc.dot(ray.direction);
+            let discrim = b * b - (oc.dot(oc) - ball.radius * ball.radius);
+            if discrim < 0. {
+                continue;
+            }
+
+            let sq = discrim.sqrt();
+            let (t0, t1) = (-b - sq, -b + sq);
+            if t1 < 0. {
+                continue;
+            }
+
+            span = Some(match span {
+                Some((start, end)) => (start.min(t0), end.max(t1)),
+                None => (t0, t1),
+            });
+        }
+
+        span
+    }
+
+    /// Narrow down where the ray crosses the surface between `a` and `b`, which must be on
+    /// opposite sides of it.
+    fn refine(&self, ray: &Ray, mut a: Float, mut b: Float) -> Float {
+        let a_inside = self.inside(ray.along(a));
+        for _ in 0..16 {
+            let mid = (a + b) * 0.5;
+            if self.inside(ray.along(mid)) == a_inside {
+                a = mid;
+            } else {
+                b = mid;
+            }
+        }
+
+        (a + b) * 0.5
+    }
+
+    /// March along `ray` from `t` to `end` until the ray is `inside` the surface (or not),
+    /// returning where it crosses.
+    fn march(
+        &self,
+        ray: &Ray,
+        mut t: Float,
+        end: Float,
+        step: Float,
+        inside: bool,
+    ) -> Option<Float> {
+        while t < end {
+            let next = (t + step).min(end);
+            if self.inside(ray.along(next)) == inside {
+                return Some(self.refine(ray, t, next));
+            }
+
+            t = next;
+        }
+
+        None
+    }
+}
+
+impl Intersect for Metaballs {
+    fn intersect(&self, ray: &Ray) -> Option<Hit> {
+        let (start, end) = self.span(ray)?;
+        let min_radius = self
+            .balls
+            .iter()
+            .map(|b| b.radius)
+            .fold(Float::INFINITY, Float::min);
+        let step = min_radius / self.steps_per_radius.max(1) as Float;
+
+        // start a little way along the ray, so rays leaving the surface (like shadow rays)
+        // don't hit it again straight away
+        let start = start.max(step * 0.01);
+
+        // a ray starting inside the surface hits right away
+        let near = if self.inside(ray.along(start)) {
+            start
+        } else {
+            self.march(ray, start, end, step, true)?
+        };
+        let far = self.march(ray, near, end, step, false).unwrap_or(end);
+
+        let vnear = ray.along(near);
+        let gradient = self
+            .balls
+            .iter()
+            .fold(Vector3::default(), |g, b| g + b.gradient(vnear));
+
+        // the field grows toward the inside, so the normal points down the gradient
+        Some(Hit::new(
+            -gradient.normalize(),
+            (near, vnear),
+            (far, ray.along(far)),
+            Vector2::new(0., 0.),
+        ))
+    }
+}
+
+impl SceneObject for Metaballs {
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn material_mut(&mut self) -> &mut Material {
+        &mut self.material
+    }
+
+    fn kind(&self) -> &'static str {
+        "metaballs"
+    }
+
+    fn clone_box(&self) -> Box<dyn SceneObject> {
+        Box::new(self.clone())
+    }
+
+    fn fingerprint(&self, hasher: &mut Fingerprint) {
+        hasher.write_debug(&(
+            self.kind(),
+            &self.balls,
+            self.threshold,
+            self.steps_per_radius,
+        ));
+        self.material.fingerprint(hasher);
+    }
+
+    #[cfg(feature = "serde")]
+    fn tagged(&self) -> crate::serialize::TaggedObject<'_> {
+        crate::serialize::TaggedObject::Metaballs(self)
+    }
+}
@@ -1,17 +1,48 @@
 mod aabb;
+mod cone;
+mod csg;
+mod disk;
+mod flagged;
+mod instance;
 mod mesh;
+mod metaballs;
 mod plane;
+mod ply;
+mod quad;
+mod sdf;
 mod sphere;
+mod transformed;
+mod triangle;
 
+use std::ops::AddAssign;
+
+#[cfg(feature = "simd")]
+use crate::acceleration::PACKET_SIZE;
 use crate::{
-    material::Material,
-    math::{Ray, Vector3},
+    checkpoint::Fingerprint,
+    material::{Color, Material},
+    math::{Float, Ray, Vector2, Vector3},
+    scene::RayKind,
+    validate::Problem,
 };
 
+#[cfg(feature = "serde")]
+use crate::serialize::TaggedObject;
+
 pub use aabb::*;
+pub use cone::*;
+pub use csg::*;
+pub use disk::*;
+pub use flagged::*;
+pub use instance::*;
 pub use mesh::*;
+pub use metaballs::*;
 pub use plane::*;
+pub use quad::*;
+pub use sdf::*;
 pub use sphere::*;
+pub use transformed::*;
+pub use triangle::*;
 
 /// The result of a ray intersection, including hit location data and UV data.
 #[derive(Clone, Debug, PartialEq)]
@@ -20,27 +51,38 @@ pub struct Hit {
     pub normal: Vector3,
 
     /// The near t of the hit.
-    pub near: f64,
+    pub near: Float,
 
     /// The near point of the hit.
     pub vnear: Vector3,
 
     /// The far t of the hit.
-    pub far: f64,
+    pub far: Float,
 
     /// The far point of the hit.
     pub vfar: Vector3,
 
     /// The UV coordinates of the hit, for texture polling.
-    pub uv: (f32, f32),
+    pub uv: Vector2,
+
+    /// The tangent and bitangent of the surface at the hit, pointing toward increasing U and
+    /// toward the top of the texture respectively. Used for normal mapping.
+    pub tangents: Option<(Vector3, Vector3)>,
+
+    /// The index of the face that was hit, for objects made up of faces (like the triangles of a mesh).
+    pub face: Option<usize>,
+
+    /// The color of the surface at the hit (for example, interpolated from vertex colors),
+    /// which tints the color of the material.
+    pub color: Option<Color>,
 }
 
 impl Hit {
     pub fn new(
         normal: Vector3,
-        (near, vnear): (f64, Vector3),
-        (far, vfar): (f64, Vector3),
-        uv: (f32, f32),
+        (near, vnear): (Float, Vector3),
+        (far, vfar): (Float, Vector3),
+        uv: Vector2,
     ) -> Self {
         Self {
             normal,
@@ -49,6 +91,57 @@ impl Hit {
             far,
             vfar,
             uv,
+            tangents: None,
+            face: None,
+            color: None,
+        }
+    }
+
+    /// Attach a tangent and bitangent to this hit, see [`Hit::tangents`].
+    pub fn with_tangents(mut self, tangent: Vector3, bitangent: Vector3) -> Self {
+        self.tangents = Some((tangent, bitangent));
+        self
+    }
+
+    /// Attach a surface color to this hit, see [`Hit::color`].
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = Some(color);
+        self
+    }
+
+    /// Perturb the normal of this hit by a normal sampled from a tangent-space normal map.
+    ///
+    /// If the hit has no tangents, an arbitrary tangent frame around the normal is used.
+    pub fn apply_normal_map(&mut self, sample: Vector3) {
+        let n = self.normal;
+        let (t, b) = match self.tangents {
+            Some((t, b)) => {
+                // orthogonalize the tangent against the normal, keeping the bitangent's handedness
+                let t = (t - n * n.dot(t)).normalize();
+                let b_ortho = n.cross(t);
+                (
+                    t,
+                    if b_ortho.dot(b) < 0. {
+                        -b_ortho
+                    } else {
+                        b_ortho
+                    },
+                )
+            }
+            None => {
+                let axis = if n.y.abs() < 0.99 {
+                    Vector3::up()
+                } else {
+                    Vector3::right()
+                };
+                let t = axis.cross(n).normalize();
+                (t, n.cross(t))
+            }
+        };
+
+        let perturbed = (t * sample.x + b * sample.y + n * sample.z).normalize();
+        if !perturbed.x.is_nan() {
+            self.normal = perturbed;
         }
     }
 
@@ -57,14 +150,225 @@ impl Hit {
     }
 }
 
+/// Which kinds of rays can see a scene object, for tricks like lights that are hidden from the
+/// camera or objects that only show up in reflections.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RenderFlags {
+    /// Whether the object blocks light, casting shadows.
+    pub casts_shadows: bool,
+
+    /// Whether the object is seen directly by the camera.
+    pub visible_to_camera: bool,
+
+    /// Whether the object is seen in reflections and through refractions.
+    pub visible_in_reflections: bool,
+}
+
+impl RenderFlags {
+    /// Whether a ray cast for `kind` can hit an object with these flags.
+    pub fn visible_to(&self, kind: RayKind) -> bool {
+        match kind {
+            RayKind::Camera => self.visible_to_camera,
+            RayKind::Reflection => self.visible_in_reflections,
+            RayKind::Shadow => self.casts_shadows,
+        }
+    }
+}
+
+impl Default for RenderFlags {
+    fn default() -> Self {
+        Self {
+            casts_shadows: true,
+            visible_to_camera: true,
+            visible_in_reflections: true,
+        }
+    }
+}
+
+/// The size of a scene object, see [`SceneObject::stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ObjectStats {
+    /// The number of triangles in the object.
+    pub triangles: usize,
+
+    /// The memory taken by the texels of the object's textures, in bytes.
+    pub texture_bytes: usize,
+
+    /// The number of nodes in the object's acceleration structures.
+    pub bvh_nodes: usize,
+
+    /// The memory taken by the object's acceleration structures, in bytes.
+    pub bvh_bytes: usize,
+}
+
+impl AddAssign for ObjectStats {
+    fn add_assign(&mut self, rhs: Self) {
+        self.triangles += rhs.triangles;
+        self.texture_bytes += rhs.texture_bytes;
+        self.bvh_nodes += rhs.bvh_nodes;
+        self.bvh_bytes += rhs.bvh_bytes;
+    }
+}
+
 /// A trait that represents any type that can be intersected by a Ray.
 pub trait Intersect {
     /// Find the intersection, if any, between the ray provided and this shape.
     fn intersect(&self, ray: &Ray) -> Option<Hit>;
+
+    /// Whether the ray hits this shape anywhere up to `max_t` along it. Shapes can override
+    /// this to stop at the first hit they find, instead of finding the nearest one.
+    fn occludes(&self, ray: &Ray, max_t: Float) -> bool {
+        matches!(self.intersect(ray), Some(hit) if hit.near <= max_t)
+    }
+
+    /// Intersect a packet of rays with this shape. Shapes can override this to intersect the
+    /// rays together with SIMD instructions, instead of one by one.
+    #[cfg(feature = "simd")]
+    fn intersect_packet(&self, rays: &[Ray; PACKET_SIZE]) -> [Option<Hit>; PACKET_SIZE] {
+        std::array::from_fn(|i| self.intersect(&rays[i]))
+    }
+
+    /// How many nodes of this shape's acceleration structures `intersect` visits to find the
+    /// nearest hit of the ray, to see where the time tracing a scene goes. Shapes without one
+    /// visit none.
+    fn bvh_nodes_visited(&self, _ray: &Ray) -> usize {
+        0
+    }
 }
 
 /// A trait that represents any type that is a scene object, and can thus be viewed in the final render.
 pub trait SceneObject: Intersect + Send + Sync {
     /// Grab this scene object's material.
     fn material(&self) -> &Material;
+
+    /// Grab this scene object's material, mutably. Changing the material never
+    /// requires the object's geometry to be rebuilt.
+    fn material_mut(&mut self) -> &mut Material;
+
+    /// Grab the material of this scene object at a hit. Objects with more than one material
+    /// (for example, meshes with per-face materials) return the material of the part that was hit.
+    fn material_at(&self, _hit: &Hit) -> &Material {
+        self.material()
+    }
+
+    /// A short, human-readable name for the kind of this object, e.g. `"sphere"`.
+    fn kind(&self) -> &'static str;
+
+    /// A boxed copy of this object, so that scenes holding it can be cloned.
+    fn clone_box(&self) -> Box<dyn SceneObject>;
+
+    /// Which kinds of rays can see this object. Every object is visible to every ray, unless
+    /// it is wrapped in a [`Flagged`].
+    fn flags(&self) -> RenderFlags {
+        RenderFlags::default()
+    }
+
+    /// Whether every material of this object is opaque (see [`Material::is_opaque`]), so any
+    /// hit on it blocks shadow rays entirely.
+    fn opaque(&self) -> bool {
+        self.material().is_opaque()
+    }
+
+    /// How big this object is, to see what a scene is made of before rendering it. By default,
+    /// an object has no triangles or acceleration structures, just its material's textures.
+    fn stats(&self) -> ObjectStats {
+        ObjectStats {
+            texture_bytes: self.material().texture_bytes(),
+            ..ObjectStats::default()
+        }
+    }
+
+    /// Check this object for mistakes that would make it render wrong, or panic while it is
+    /// traced. See [`Scene::validate`](crate::scene::Scene::validate).
+    fn validate(&self) -> Vec<Problem> {
+        Vec::new()
+    }
+
+    /// Hash everything about this object that changes how it renders, so that a checkpoint of
+    /// one scene isn't resumed by another. By default, only its kind and material are hashed,
+    /// so objects should hash their shape too.
+    fn fingerprint(&self, hasher: &mut Fingerprint) {
+        hasher.write_debug(self.kind());
+        self.material().fingerprint(hasher);
+    }
+
+    /// This object, tagged with its kind so that it can be serialized (see
+    /// [`serialize`](crate::serialize)). Objects from outside this crate can't be.
+    #[cfg(feature = "serde")]
+    fn tagged(&self) -> TaggedObject<'_> {
+        TaggedObject::Unsupported(self.kind())
+    }
+}
+
+impl<T: Intersect + ?Sized> Intersect for Box<T> {
+    fn intersect(&self, ray: &Ray) -> Option<Hit> {
+        (**self).intersect(ray)
+    }
+
+    fn occludes(&self, ray: &Ray, max_t: Float) -> bool {
+        (**self).occludes(ray, max_t)
+    }
+
+    #[cfg(feature = "simd")]
+    fn intersect_packet(&self, rays: &[Ray; PACKET_SIZE]) -> [Option<Hit>; PACKET_SIZE] {
+        (**self).intersect_packet(rays)
+    }
+
+    fn bvh_nodes_visited(&self, ray: &Ray) -> usize {
+        (**self).bvh_nodes_visited(ray)
+    }
+}
+
+impl<T: SceneObject + ?Sized> SceneObject for Box<T> {
+    fn material(&self) -> &Material {
+        (**self).material()
+    }
+
+    fn material_mut(&mut self) -> &mut Material {
+        (**self).material_mut()
+    }
+
+    fn material_at(&self, hit: &Hit) -> &Material {
+        (**self).material_at(hit)
+    }
+
+    fn kind(&self) -> &'static str {
+        (**self).kind()
+    }
+
+    fn clone_box(&self) -> Box<dyn SceneObject> {
+        (**self).clone_box()
+    }
+
+    fn flags(&self) -> RenderFlags {
+        (**self).flags()
+    }
+
+    fn opaque(&self) -> bool {
+        (**self).opaque()
+    }
+
+    fn stats(&self) -> ObjectStats {
+        (**self).stats()
+    }
+
+    fn validate(&self) -> Vec<Problem> {
+        (**self).validate()
+    }
+
+    fn fingerprint(&self, hasher: &mut Fingerprint) {
+        (**self).fingerprint(hasher)
+    }
+
+    #[cfg(feature = "serde")]
+    fn tagged(&self) -> TaggedObject<'_> {
+        (**self).tagged()
+    }
+}
+
+impl Clone for Box<dyn SceneObject> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
 }
@@ -1,13 +1,16 @@
 use crate::{
+    checkpoint::Fingerprint,
     material::Material,
-    math::{Ray, Vector3},
+    math::{Ray, Vector2, Vector3},
     scene::EPSILON,
+    validate::{self, Problem},
 };
 
 use super::{Hit, Intersect, SceneObject};
 
 /// A plane.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Plane {
     /// The origin of the plane.
     pub origin: Vector3,
@@ -18,9 +21,9 @@ pub struct Plane {
     /// The plane's material.
     pub material: Material,
 
-    /// The unit by which UVs will be wrapped. For example,
-    /// when this value is 2, UVs will wrap every 2 units
-    /// in both axes.
+    /// The number of units spanned by one tile of UV space. For example,
+    /// when this value is 2, UVs go from 0 to 1 every 2 units
+    /// in both axes, past which textures wrap by their wrap mode.
     pub uv_wrap: f32,
 }
 
@@ -56,19 +59,23 @@ impl Intersect for Plane {
                 // TEMPORARY: use x and z coords to determine uvs
                 // in the future this should take into account the
                 // plane's normal
-                Some(Hit::new(
+                let hit = Hit::new(
                     self.normal * -denom.signum(),
                     (t, p),
                     (t, p),
                     if self.normal.y != 0. {
-                        (
-                            (p.x as f32 / self.uv_wrap).rem_euclid(1.),
-                            (p.z as f32 / self.uv_wrap).rem_euclid(1.),
-                        )
+                        Vector2::new(p.x as f32 / self.uv_wrap, p.z as f32 / self.uv_wrap)
                     } else {
-                        (0., 0.)
+                        Vector2::new(0., 0.)
                     },
-                ))
+                );
+
+                if self.normal.y != 0. {
+                    // U follows X, and V follows Z, which is toward the bottom of the texture
+                    Some(hit.with_tangents(Vector3::new(1., 0., 0.), Vector3::new(0., 0., -1.)))
+                } else {
+                    Some(hit)
+                }
             } else {
                 None
             }
@@ -82,4 +89,34 @@ impl SceneObject for Plane {
     fn material(&self) -> &Material {
         &self.material
     }
+
+    fn material_mut(&mut self) -> &mut Material {
+        &mut self.material
+    }
+
+    fn kind(&self) -> &'static str {
+        "plane"
+    }
+
+    fn clone_box(&self) -> Box<dyn SceneObject> {
+        Box::new(self.clone())
+    }
+
+    fn fingerprint(&self, hasher: &mut Fingerprint) {
+        hasher.write_debug(&(self.kind(), self.origin, self.normal, self.uv_wrap));
+        self.material.fingerprint(hasher);
+    }
+
+    fn validate(&self) -> Vec<Problem> {
+        if validate::is_unit(self.normal) {
+            Vec::new()
+        } else {
+            vec![Problem::UnnormalizedVector("normal")]
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    fn tagged(&self) -> crate::serialize::TaggedObject<'_> {
+        crate::serialize::TaggedObject::Plane(self)
+    }
 }
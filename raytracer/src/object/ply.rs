@@ -0,0 +1,299 @@
+use std::{
+    collections::VecDeque,
+    convert::TryInto,
+    io::{self, BufRead},
+};
+
+use crate::{
+    material::Color,
+    math::{Float, Vector2, Vector3},
+};
+
+/// The geometry read from a PLY file. Normals, colors and texcoords are per vertex, and are
+/// empty if the file doesn't have them.
+pub(super) struct PlyData {
+    pub verts: Vec<Vector3>,
+    pub normals: Vec<Vector3>,
+    pub colors: Vec<Color>,
+    pub texcoords: Vec<Vector2>,
+    pub faces: Vec<Vec<usize>>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+/// A numeric type that a PLY property can have.
+#[derive(Clone, Copy, PartialEq)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "char" | "int8" => Self::I8,
+            "uchar" | "uint8" => Self::U8,
+            "short" | "int16" => Self::I16,
+            "ushort" | "uint16" => Self::U16,
+            "int" | "int32" => Self::I32,
+            "uint" | "uint32" => Self::U32,
+            "float" | "float32" => Self::F32,
+            "double" | "float64" => Self::F64,
+            _ => return None,
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Self::I8 | Self::U8 => 1,
+            Self::I16 | Self::U16 => 2,
+            Self::I32 | Self::U32 | Self::F32 => 4,
+            Self::F64 => 8,
+        }
+    }
+
+    fn is_float(self) -> bool {
+        matches!(self, Self::F32 | Self::F64)
+    }
+}
+
+enum Property {
+    Scalar(String, Scalar),
+    List(String, Scalar, Scalar),
+}
+
+impl Property {
+    fn name(&self) -> &str {
+        match self {
+            Self::Scalar(name, _) | Self::List(name, _, _) => name,
+        }
+    }
+}
+
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("invalid ply: {}", message),
+    )
+}
+
+/// Reads the values of the body of a PLY file, one at a time, in either format.
+struct BodyReader<R> {
+    reader: R,
+    format: Format,
+    tokens: VecDeque<String>,
+}
+
+impl<R: BufRead> BodyReader<R> {
+    fn next(&mut self, scalar: Scalar) -> io::Result<Float> {
+        if self.format == Format::Ascii {
+            while self.tokens.is_empty() {
+                let mut line = String::new();
+                if self.reader.read_line(&mut line)? == 0 {
+                    return Err(invalid("unexpected end of file"));
+                }
+                self.tokens
+                    .extend(line.split_whitespace().map(str::to_owned));
+            }
+
+            let token = self.tokens.pop_front().unwrap();
+            return token.parse().map_err(|_| invalid("bad number"));
+        }
+
+        let mut buf = [0u8; 8];
+        let bytes = &mut buf[..scalar.size()];
+        self.reader.read_exact(bytes)?;
+
+        macro_rules! decode {
+            ($t:ty) => {{
+                let bytes = (&*bytes).try_into().unwrap();
+                (if self.format == Format::BinaryLittleEndian {
+                    <$t>::from_le_bytes(bytes)
+                } else {
+                    <$t>::from_be_bytes(bytes)
+                }) as Float
+            }};
+        }
+
+        Ok(match scalar {
+            Scalar::I8 => decode!(i8),
+            Scalar::U8 => decode!(u8),
+            Scalar::I16 => decode!(i16),
+            Scalar::U16 => decode!(u16),
+            Scalar::I32 => decode!(i32),
+            Scalar::U32 => decode!(u32),
+            Scalar::F32 => decode!(f32),
+            Scalar::F64 => decode!(Float),
+        })
+    }
+}
+
+/// Read the vertices and faces of a PLY file, in either its ASCII or binary formats.
+pub(super) fn read_ply<R: BufRead>(mut reader: R) -> io::Result<PlyData> {
+    // read the header, which is always text
+    let mut format = None;
+    let mut elements: Vec<Element> = Vec::new();
+    let mut line = String::new();
+    let mut first = true;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Err(invalid("missing end_header"));
+        }
+
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        if first {
+            if words != ["ply"] {
+                return Err(invalid("not a ply file"));
+            }
+            first = false;
+            continue;
+        }
+
+        match words.as_slice() {
+            ["format", kind, _] => {
+                format = Some(match *kind {
+                    "ascii" => Format::Ascii,
+                    "binary_little_endian" => Format::BinaryLittleEndian,
+                    "binary_big_endian" => Format::BinaryBigEndian,
+                    _ => return Err(invalid("unknown format")),
+                })
+            }
+            ["element", name, count] => elements.push(Element {
+                name: name.to_string(),
+                count: count.parse().map_err(|_| invalid("bad element count"))?,
+                properties: Vec::new(),
+            }),
+            ["property", "list", count, item, name] => {
+                let property = Property::List(
+                    name.to_string(),
+                    Scalar::parse(count).ok_or_else(|| invalid("unknown type"))?,
+                    Scalar::parse(item).ok_or_else(|| invalid("unknown type"))?,
+                );
+                elements
+                    .last_mut()
+                    .ok_or_else(|| invalid("property before element"))?
+                    .properties
+                    .push(property);
+            }
+            ["property", kind, name] => {
+                let property = Property::Scalar(
+                    name.to_string(),
+                    Scalar::parse(kind).ok_or_else(|| invalid("unknown type"))?,
+                );
+                elements
+                    .last_mut()
+                    .ok_or_else(|| invalid("property before element"))?
+                    .properties
+                    .push(property);
+            }
+            ["end_header"] => break,
+            _ => (), // comments and anything else we don't understand
+        }
+    }
+
+    let mut body = BodyReader {
+        reader,
+        format: format.ok_or_else(|| invalid("missing format"))?,
+        tokens: VecDeque::new(),
+    };
+
+    let mut data = PlyData {
+        verts: Vec::new(),
+        normals: Vec::new(),
+        colors: Vec::new(),
+        texcoords: Vec::new(),
+        faces: Vec::new(),
+    };
+
+    for element in elements.iter() {
+        let find = |names: &[&str]| {
+            element
+                .properties
+                .iter()
+                .position(|p| names.contains(&p.name()))
+        };
+        let (x, y, z) = (find(&["x"]), find(&["y"]), find(&["z"]));
+        let (nx, ny, nz) = (find(&["nx"]), find(&["ny"]), find(&["nz"]));
+        let (r, g, b) = (
+            find(&["red", "r"]),
+            find(&["green", "g"]),
+            find(&["blue", "b"]),
+        );
+        let (s, t) = (
+            find(&["s", "u", "texture_u", "texture_s"]),
+            find(&["t", "v", "texture_v", "texture_t"]),
+        );
+        let indices = find(&["vertex_indices", "vertex_index"]);
+
+        for _ in 0..element.count {
+            // read every property of the row, keeping lists for the faces
+            let mut values = Vec::with_capacity(element.properties.len());
+            let mut list = Vec::new();
+            for (i, property) in element.properties.iter().enumerate() {
+                match property {
+                    Property::Scalar(_, scalar) => values.push(body.next(*scalar)?),
+                    Property::List(_, count, item) => {
+                        let count = body.next(*count)? as usize;
+                        values.push(0.);
+                        for _ in 0..count {
+                            let value = body.next(*item)?;
+                            if Some(i) == indices {
+                                list.push(value as usize);
+                            }
+                        }
+                    }
+                }
+            }
+
+            match element.name.as_str() {
+                "vertex" => {
+                    if let (Some(x), Some(y), Some(z)) = (x, y, z) {
+                        data.verts
+                            .push(Vector3::new(values[x], values[y], values[z]));
+                    }
+                    if let (Some(nx), Some(ny), Some(nz)) = (nx, ny, nz) {
+                        data.normals
+                            .push(Vector3::new(values[nx], values[ny], values[nz]));
+                    }
+                    if let (Some(r), Some(g), Some(b)) = (r, g, b) {
+                        // colors are either bytes, or floats from 0 to 1
+                        let channel = |i: usize| match &element.properties[i] {
+                            Property::Scalar(_, scalar) if scalar.is_float() => {
+                                (values[i] * 255.).round().clamp(0., 255.) as u8
+                            }
+                            _ => values[i].clamp(0., 255.) as u8,
+                        };
+                        data.colors
+                            .push(Color::new(channel(r), channel(g), channel(b)));
+                    }
+                    if let (Some(s), Some(t)) = (s, t) {
+                        data.texcoords
+                            .push(Vector2::new(values[s] as f32, values[t] as f32));
+                    }
+                }
+                "face" => data.faces.push(list),
+                _ => (),
+            }
+        }
+    }
+
+    Ok(data)
+}
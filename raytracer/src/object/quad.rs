@@ -0,0 +1,132 @@
+use crate::{
+    checkpoint::Fingerprint,
+    lighting::AreaSurface,
+    material::Material,
+    math::{Ray, Vector2, Vector3},
+    scene::EPSILON,
+};
+
+use super::{Hit, Intersect, SceneObject};
+
+/// A finite, flat parallelogram (usually a rectangle), given by one corner and the two edges
+/// leaving it. Unlike a `Plane`, it is bounded.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Quad {
+    /// The corner of the quad where UV coordinates are (0, 0).
+    pub corner: Vector3,
+
+    /// The edge from `corner` along which U increases.
+    pub edge_u: Vector3,
+
+    /// The edge from `corner` along which V increases.
+    pub edge_v: Vector3,
+
+    /// The material of the quad.
+    pub material: Material,
+}
+
+impl Quad {
+    pub fn new(corner: Vector3, edge_u: Vector3, edge_v: Vector3, material: Material) -> Self {
+        Self {
+            corner,
+            edge_u,
+            edge_v,
+            material,
+        }
+    }
+
+    /// Instantiate a quad from its corners, laid out like those of `AreaSurface::Rectangle`:
+    /// the corner, the corner across `edge_u`, the corner across `edge_v`, then the opposite corner.
+    /// The opposite corner is ignored, as the quad is always a parallelogram.
+    pub fn from_corners(corners: [Vector3; 4], material: Material) -> Self {
+        Self::new(
+            corners[0],
+            corners[1] - corners[0],
+            corners[2] - corners[0],
+            material,
+        )
+    }
+
+    /// The corners of this quad, laid out like those of `AreaSurface::Rectangle`.
+    pub fn corners(&self) -> [Vector3; 4] {
+        [
+            self.corner,
+            self.corner + self.edge_u,
+            self.corner + self.edge_v,
+            self.corner + self.edge_u + self.edge_v,
+        ]
+    }
+
+    /// The normal of the front of this quad, following the right-hand rule from `edge_u` to `edge_v`.
+    pub fn normal(&self) -> Vector3 {
+        self.edge_u.cross(self.edge_v).normalize()
+    }
+
+    /// An area light surface covering this quad, so that an area light can be placed exactly on it.
+    pub fn area_surface(&self) -> AreaSurface {
+        AreaSurface::Rectangle(self.corners())
+    }
+}
+
+impl Intersect for Quad {
+    fn intersect(&self, ray: &Ray) -> Option<Hit> {
+        let n = self.edge_u.cross(self.edge_v);
+        let denom = n.dot(ray.direction);
+        if denom.abs() <= EPSILON {
+            return None;
+        }
+
+        let t = (self.corner - ray.origin).dot(n) / denom;
+        if t <= 0. {
+            return None;
+        }
+
+        // find the coordinates of the hit along each edge
+        let p = ray.along(t);
+        let q = p - self.corner;
+        let w = n / n.dot(n);
+        let (u, v) = (w.dot(q.cross(self.edge_v)), w.dot(self.edge_u.cross(q)));
+        if !(0. ..=1.).contains(&u) || !(0. ..=1.).contains(&v) {
+            return None;
+        }
+
+        Some(
+            Hit::new(
+                n.normalize() * -denom.signum(),
+                (t, p),
+                (t, p),
+                Vector2::new(u as f32, v as f32),
+            )
+            .with_tangents(self.edge_u.normalize(), -self.edge_v.normalize()),
+        )
+    }
+}
+
+impl SceneObject for Quad {
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn material_mut(&mut self) -> &mut Material {
+        &mut self.material
+    }
+
+    fn kind(&self) -> &'static str {
+        "quad"
+    }
+
+    fn clone_box(&self) -> Box<dyn SceneObject> {
+        Box::new(self.clone())
+    }
+
+    fn fingerprint(&self, hasher: &mut Fingerprint) {
+        hasher.write_debug(&(self.kind(), self.corner, self.edge_u, self.edge_v));
+        self.material.fingerprint(hasher);
+    }
+
+    #[cfg(feature = "serde")]
+    fn tagged(&self) -> crate::serialize::TaggedObject<'_> {
+        crate::serialize::TaggedObject::Quad(self)
+    }
+}
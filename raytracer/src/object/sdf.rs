@@ -0,0 +1,381 @@
+use std::{fmt, sync::Arc};
+
+use crate::{
+    checkpoint::Fingerprint,
+    material::Material,
+    math::{lerp, Float, Ray, Vector2, Vector3},
+};
+
+use super::{Hit, Intersect, SceneObject};
+
+/// A signed distance function, which gives the distance from a point to the nearest surface of
+/// a shape, negative inside the shape.
+///
+/// Shapes can be built from the primitives here (like `Distance::sphere`), combined with each
+/// other (like `Distance::smooth_union`) and modified (like `Distance::translate`), or written
+/// by hand. The distance should never be larger than the true distance, or rays may pass
+/// through the surface.
+///
+/// Shapes built from the primitives here carry a description of how they were built, since
+/// the function itself can't be compared or hashed (see [`Distance::description`]).
+#[derive(Clone)]
+pub struct Distance {
+    function: Arc<dyn Fn(Vector3) -> Float + Send + Sync>,
+    description: Option<Arc<str>>,
+}
+
+impl Distance {
+    /// Create a distance function from `f`, which maps a point to its signed distance. It has no
+    /// description, see [`with_description`](Self::with_description).
+    pub fn new<F>(f: F) -> Self
+    where
+        F: Fn(Vector3) -> Float + Send + Sync + 'static,
+    {
+        Self {
+            function: Arc::new(f),
+            description: None,
+        }
+    }
+
+    /// Describe this shape, so that shapes with the same description are taken to be the same
+    /// (see [`description`](Self::description)).
+    pub fn with_description(mut self, description: &str) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// A description of how this shape was built, which is the same for two shapes only if
+    /// their distance functions are, or `None` if it was written by hand (or built from a shape
+    /// that was) and never described. SDFs are fingerprinted by their descriptions, see
+    /// [`SceneObject::fingerprint`].
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    /// Describe a shape built by `name` from `args` and `parts`, if every part is described.
+    fn describe<A: fmt::Debug>(name: &str, args: A, parts: &[&Self]) -> Option<Arc<str>> {
+        let mut description = format!("{}({:?}", name, args);
+        for part in parts {
+            description += ", ";
+            description += part.description()?;
+        }
+        description += ")";
+        Some(description.into())
+    }
+
+    /// Create a distance function from `f` with `description`.
+    fn described<F>(description: Option<Arc<str>>, f: F) -> Self
+    where
+        F: Fn(Vector3) -> Float + Send + Sync + 'static,
+    {
+        Self {
+            description,
+            ..Self::new(f)
+        }
+    }
+
+    /// Get the signed distance from `p` to the shape.
+    pub fn at(&self, p: Vector3) -> Float {
+        (self.function)(p)
+    }
+
+    /// A sphere of `radius` around the origin.
+    pub fn sphere(radius: Float) -> Self {
+        let description = Self::describe("sphere", radius, &[]);
+        Self::described(description, move |p| p.magnitude() - radius)
+    }
+
+    /// A box around the origin, which extends `size` from its center on each axis.
+    pub fn cuboid(size: Vector3) -> Self {
+        let description = Self::describe("cuboid", size, &[]);
+        Self::described(description, move |p| {
+            let q = p.abs() - size;
+            let outside = Vector3::new(q.x.max(0.), q.y.max(0.), q.z.max(0.)).magnitude();
+            outside + q.x.max(q.y).max(q.z).min(0.)
+        })
+    }
+
+    /// A torus around the origin, lying flat in the XZ plane.
+    pub fn torus(major_radius: Float, minor_radius: Float) -> Self {
+        let description = Self::describe("torus", (major_radius, minor_radius), &[]);
+        Self::described(description, move |p| {
+            let ring = (p.x * p.x + p.z * p.z).sqrt() - major_radius;
+            (ring * ring + p.y * p.y).sqrt() - minor_radius
+        })
+    }
+
+    /// A capsule (a line segment with thickness) from `a` to `b`.
+    pub fn capsule(a: Vector3, b: Vector3, radius: Float) -> Self {
+        let description = Self::describe("capsule", (a, b, radius), &[]);
+        Self::described(description, move |p| {
+            let (pa, ba) = (p - a, b - a);
+            let h = (pa.dot(ba) / ba.dot(ba)).clamp(0., 1.);
+            (pa - ba * h).magnitude() - radius
+        })
+    }
+
+    /// An infinite plane through the origin, facing `normal`.
+    pub fn plane(normal: Vector3) -> Self {
+        let normal = normal.normalize();
+        let description = Self::describe("plane", normal, &[]);
+        Self::described(description, move |p| p.dot(normal))
+    }
+
+    /// Everything inside either shape.
+    pub fn union(self, other: Self) -> Self {
+        let description = Self::describe("union", (), &[&self, &other]);
+        Self::described(description, move |p| self.at(p).min(other.at(p)))
+    }
+
+    /// Everything inside both shapes.
+    pub fn intersection(self, other: Self) -> Self {
+        let description = Self::describe("intersection", (), &[&self, &other]);
+        Self::described(description, move |p| self.at(p).max(other.at(p)))
+    }
+
+    /// Everything inside this shape, but not `other`.
+    pub fn difference(self, other: Self) -> Self {
+        let description = Self::describe("difference", (), &[&self, &other]);
+        Self::described(description, move |p| self.at(p).max(-other.at(p)))
+    }
+
+    /// Everything inside either shape, blending them together where they are within
+    /// `smoothness` of each other.
+    pub fn smooth_union(self, other: Self, smoothness: Float) -> Self {
+        let description = Self::describe("smooth_union", smoothness, &[&self, &other]);
+        Self::described(description, move |p| {
+            let (a, b) = (self.at(p), other.at(p));
+            let h = (0.5 + 0.5 * (b - a) / smoothness).clamp(0., 1.);
+            lerp(b, a, h) - smoothness * h * (1. - h)
+        })
+    }
+
+    /// Everything inside both shapes, rounding off the edges where they meet by `smoothness`.
+    pub fn smooth_intersection(self, other: Self, smoothness: Float) -> Self {
+        let description = Self::describe("smooth_intersection", smoothness, &[&self, &other]);
+        Self::described(description, move |p| {
+            let (a, b) = (self.at(p), other.at(p));
+            let h = (0.5 - 0.5 * (b - a) / smoothness).clamp(0., 1.);
+            lerp(b, a, h) + smoothness * h * (1. - h)
+        })
+    }
+
+    /// Everything inside this shape, but not `other`, rounding off the edges of the cut by
+    /// `smoothness`.
+    pub fn smooth_difference(self, other: Self, smoothness: Float) -> Self {
+        let description = Self::describe("smooth_difference", smoothness, &[&self, &other]);
+        Self::described(description, move |p| {
+            let (a, b) = (self.at(p), -other.at(p));
+            let h = (0.5 - 0.5 * (b - a) / smoothness).clamp(0., 1.);
+            lerp(b, a, h) + smoothness * h * (1. - h)
+        })
+    }
+
+    /// Move the shape by `offset`.
+    pub fn translate(self, offset: Vector3) -> Self {
+        let description = Self::describe("translate", offset, &[&self]);
+        Self::described(description, move |p| self.at(p - offset))
+    }
+
+    /// Scale the shape around the origin by `factor`.
+    pub fn scale(self, factor: Float) -> Self {
+        let description = Self::describe("scale", factor, &[&self]);
+        Self::described(description, move |p| self.at(p / factor) * factor)
+    }
+
+    /// Round off the shape, growing it by `radius`.
+    pub fn round(self, radius: Float) -> Self {
+        let description = Self::describe("round", radius, &[&self]);
+        Self::described(description, move |p| self.at(p) - radius)
+    }
+
+    /// Hollow out the shape, leaving a shell `thickness` thick around its surface.
+    pub fn onion(self, thickness: Float) -> Self {
+        let description = Self::describe("onion", thickness, &[&self]);
+        Self::described(description, move |p| self.at(p).abs() - thickness)
+    }
+
+    /// Repeat the shape infinitely, once every `period` along each axis. The shape should fit
+    /// within one period around the origin. An axis with a period of zero is not repeated.
+    pub fn repeat(self, period: Vector3) -> Self {
+        let wrap = |x: Float, period: Float| {
+            if period > 0. {
+                x - period * (x / period).round()
+            } else {
+                x
+            }
+        };
+        let description = Self::describe("repeat", period, &[&self]);
+        Self::described(description, move |p| {
+            self.at(Vector3::new(
+                wrap(p.x, period.x),
+                wrap(p.y, period.y),
+                wrap(p.z, period.z),
+            ))
+        })
+    }
+}
+
+impl fmt::Debug for Distance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.description() {
+            Some(description) => write!(f, "Distance({})", description),
+            None => f.write_str("Distance"),
+        }
+    }
+}
+
+/// A shape given by a signed distance function, intersected by sphere tracing: stepping along
+/// the ray by the distance to the nearest surface until it is close enough to count as a hit.
+///
+/// Since a distance function has no texture coordinates, it is best textured with triplanar
+/// mapping.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Sdf {
+    /// The signed distance function of the shape.
+    pub distance: Distance,
+
+    /// The material of the shape.
+    pub material: Material,
+
+    /// The most steps to take along a ray before giving up on it.
+    pub max_steps: u32,
+
+    /// How far along a ray to look for the shape.
+    pub max_distance: Float,
+
+    /// How close to the surface a ray must get for it to count as a hit.
+    pub threshold: Float,
+}
+
+impl Sdf {
+    pub fn new(distance: Distance, material: Material) -> Self {
+        Self {
+            distance,
+            material,
+            max_steps: 256,
+            max_distance: 1000.,
+            threshold: 1e-4,
+        }
+    }
+
+    /// Set the most steps to take along a ray, trading speed for detail near edges.
+    pub fn with_max_steps(mut self, max_steps: u32) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    /// Set how far along a ray to look for the shape.
+    pub fn with_max_distance(mut self, max_distance: Float) -> Self {
+        self.max_distance = max_distance;
+        self
+    }
+
+    /// Set how close to the surface a ray must get for it to count as a hit.
+    pub fn with_threshold(mut self, threshold: Float) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// March along `ray` from `t` until the (signed) distance, multiplied by `sign`, drops below
+    /// the threshold. A sign of 1 finds where the ray enters the shape, and -1 where it leaves.
+    fn march(&self, ray: &Ray, mut t: Float, sign: Float) -> Option<Float> {
+        for _ in 0..self.max_steps {
+            let d = self.distance.at(ray.along(t)) * sign;
+            if d < self.threshold {
+                return Some(t);
+            }
+
+            t += d;
+            if t > self.max_distance {
+                return None;
+            }
+        }
+
+        None
+    }
+
+    /// Estimate the normal of the shape at `p` from the gradient of the distance function.
+    fn normal(&self, p: Vector3) -> Vector3 {
+        // sample the corners of a tetrahedron around the point
+        let h = self.threshold;
+        let ks = [
+            Vector3::new(1., -1., -1.),
+            Vector3::new(-1., -1., 1.),
+            Vector3::new(-1., 1., -1.),
+            Vector3::new(1., 1., 1.),
+        ];
+        ks.iter()
+            .fold(Vector3::default(), |n, &k| {
+                n + k * self.distance.at(p + k * h)
+            })
+            .normalize()
+    }
+}
+
+impl Intersect for Sdf {
+    fn intersect(&self, ray: &Ray) -> Option<Hit> {
+        // start a little way along the ray, so rays leaving the surface (like shadow rays)
+        // don't hit it again straight away
+        let start = self.threshold * 10.;
+
+        // a ray starting inside the shape hits right away
+        let near = if self.distance.at(ray.along(start)) < 0. {
+            0.
+        } else {
+            self.march(ray, start, 1.)?
+        };
+
+        // march through the inside of the shape to find where the ray leaves it
+        let far = self.march(ray, near + start, -1.).unwrap_or(near);
+
+        let vnear = ray.along(near);
+        let normal = self.normal(vnear);
+
+        Some(Hit::new(
+            normal,
+            (near, vnear),
+            (far, ray.along(far)),
+            Vector2::new(0., 0.),
+        ))
+    }
+}
+
+impl SceneObject for Sdf {
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn material_mut(&mut self) -> &mut Material {
+        &mut self.material
+    }
+
+    fn kind(&self) -> &'static str {
+        "sdf"
+    }
+
+    fn clone_box(&self) -> Box<dyn SceneObject> {
+        Box::new(self.clone())
+    }
+
+    fn fingerprint(&self, hasher: &mut Fingerprint) {
+        hasher.write_debug(&(
+            self.kind(),
+            self.max_steps,
+            self.max_distance,
+            self.threshold,
+        ));
+        // the distance function itself can't be hashed, only its description
+        match self.distance.description() {
+            Some(description) => hasher.write_debug(description),
+            None => hasher.write_unhashable("an SDF distance function without a description"),
+        }
+        self.material.fingerprint(hasher);
+    }
+
+    #[cfg(feature = "serde")]
+    fn tagged(&self) -> crate::serialize::TaggedObject<'_> {
+        crate::serialize::TaggedObject::Sdf(self)
+    }
+}
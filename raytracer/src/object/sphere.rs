@@ -1,27 +1,30 @@
 use std::f32::consts::PI;
 
 use crate::{
+    checkpoint::Fingerprint,
     material::Material,
-    math::{Ray, Vector3},
+    math::{Float, Ray, Vector2, Vector3},
 };
 
 use super::{Hit, Intersect, SceneObject};
 
-/// A sphere.
+/// A sphere. Its UV coordinates are an equirectangular (latitude/longitude) mapping, so a
+/// world map texture wraps around it with its center facing +Z.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Sphere {
     /// The origin of the sphere.
     pub origin: Vector3,
 
     /// The radius of the sphere.
-    pub radius: f64,
+    pub radius: Float,
 
     /// The material of the sphere.
     pub material: Material,
 }
 
 impl Sphere {
-    pub fn new(origin: Vector3, radius: f64, material: Material) -> Self {
+    pub fn new(origin: Vector3, radius: Float, material: Material) -> Self {
         Self {
             origin,
             radius,
@@ -52,12 +55,24 @@ impl Intersect for Sphere {
         let vtf = ray.along(t1);
         let norm = (vtn - self.origin).normalize();
 
-        let uv = (
+        // longitude wraps around the Y axis, with the seam at -Z, and latitude runs from the
+        // north pole at the top of the texture to the south pole at the bottom. The normal can
+        // stray just past the poles through rounding, which would make `asin` NaN
+        let uv = Vector2::new(
             0.5 + norm.x.atan2(norm.z) as f32 / (PI * 2.),
-            0.5 - norm.y.asin() as f32 / PI,
+            0.5 - norm.y.clamp(-1., 1.).asin() as f32 / PI,
         );
 
-        Some(Hit::new(norm, (t0, vtn), (t1, vtf), uv))
+        let hit = Hit::new(norm, (t0, vtn), (t1, vtf), uv);
+
+        // the tangent follows the direction of increasing longitude, and is undefined at the poles
+        let tangent = Vector3::new(norm.z, 0., -norm.x);
+        if tangent.magnitude() > 1e-6 {
+            let tangent = tangent.normalize();
+            Some(hit.with_tangents(tangent, norm.cross(tangent)))
+        } else {
+            Some(hit)
+        }
     }
 }
 
@@ -65,4 +80,26 @@ impl SceneObject for Sphere {
     fn material(&self) -> &Material {
         &self.material
     }
+
+    fn material_mut(&mut self) -> &mut Material {
+        &mut self.material
+    }
+
+    fn kind(&self) -> &'static str {
+        "sphere"
+    }
+
+    fn clone_box(&self) -> Box<dyn SceneObject> {
+        Box::new(self.clone())
+    }
+
+    fn fingerprint(&self, hasher: &mut Fingerprint) {
+        hasher.write_debug(&(self.kind(), self.origin, self.radius));
+        self.material.fingerprint(hasher);
+    }
+
+    #[cfg(feature = "serde")]
+    fn tagged(&self) -> crate::serialize::TaggedObject<'_> {
+        crate::serialize::TaggedObject::Sphere(self)
+    }
 }
@@ -0,0 +1,179 @@
+#[cfg(feature = "simd")]
+use crate::acceleration::PACKET_SIZE;
+use crate::{
+    checkpoint::Fingerprint,
+    material::Material,
+    math::{Float, Matrix, Ray, Vector3},
+    validate::Problem,
+};
+
+use super::{Hit, Intersect, ObjectStats, RenderFlags, SceneObject};
+
+/// A scene object moved, rotated and scaled by a transformation matrix, for example to turn
+/// a `Sphere` into an ellipsoid or an `Aabb` into a rotated box.
+///
+/// Rays are transformed into the object's own space to be intersected, and the hits are
+/// transformed back out.
+pub struct Transformed<T: SceneObject> {
+    /// The object being transformed.
+    pub object: T,
+
+    transform: Matrix,
+    inverse: Matrix,
+}
+
+impl<T: SceneObject> Transformed<T> {
+    /// Instantiate a new `Transformed` from an object and the transformation from its own
+    /// space into world space, which must be invertible.
+    pub fn new(object: T, transform: Matrix) -> Self {
+        Self {
+            object,
+            transform,
+            inverse: transform.inverse(),
+        }
+    }
+
+    /// Gets the transformation from the object's space into world space.
+    pub fn transform(&self) -> Matrix {
+        self.transform
+    }
+
+    /// Gets the transformation from world space into the object's space.
+    pub fn inverse(&self) -> Matrix {
+        self.inverse
+    }
+
+    /// Replace the transformation of the object.
+    pub fn set_transform(&mut self, transform: Matrix) {
+        self.transform = transform;
+        self.inverse = transform.inverse();
+    }
+
+    /// Consumes this `Transformed`, returning the inner object.
+    pub fn into_inner(self) -> T {
+        self.object
+    }
+
+    /// Move a ray into the object's space, returning it with the length its direction had
+    /// before being renormalized, which distances along the local ray are divided by to get
+    /// distances along the world ray.
+    fn local_ray(&self, ray: &Ray) -> (Ray, Float) {
+        let direction = self.inverse.transform_vector(ray.direction);
+        let length = direction.magnitude();
+        (
+            Ray::new(self.inverse.transform_point(ray.origin), direction / length),
+            length,
+        )
+    }
+
+    /// Transform a normal in the object's space into world space.
+    fn world_normal(&self, n: Vector3) -> Vector3 {
+        self.inverse.transform_normal(n).normalize()
+    }
+}
+
+impl<T: SceneObject> Intersect for Transformed<T> {
+    fn intersect(&self, ray: &Ray) -> Option<Hit> {
+        let (local, length) = self.local_ray(ray);
+        let hit = self.object.intersect(&local)?;
+
+        Some(self.world_hit(ray, hit, length))
+    }
+
+    fn occludes(&self, ray: &Ray, max_t: Float) -> bool {
+        let (local, length) = self.local_ray(ray);
+        self.object.occludes(&local, max_t * length)
+    }
+
+    #[cfg(feature = "simd")]
+    fn intersect_packet(&self, rays: &[Ray; PACKET_SIZE]) -> [Option<Hit>; PACKET_SIZE] {
+        let local = rays.each_ref().map(|ray| self.local_ray(ray));
+        let mut hits = self
+            .object
+            .intersect_packet(&local.each_ref().map(|(ray, _)| ray.clone()));
+
+        std::array::from_fn(|i| {
+            hits[i]
+                .take()
+                .map(|hit| self.world_hit(&rays[i], hit, local[i].1))
+        })
+    }
+
+    fn bvh_nodes_visited(&self, ray: &Ray) -> usize {
+        self.object.bvh_nodes_visited(&self.local_ray(ray).0)
+    }
+}
+
+impl<T: SceneObject> Transformed<T> {
+    /// Move a hit of a ray from `local_ray` back out into world space.
+    fn world_hit(&self, ray: &Ray, mut hit: Hit, length: Float) -> Hit {
+        hit.near /= length;
+        hit.far /= length;
+        hit.vnear = ray.along(hit.near);
+        hit.vfar = ray.along(hit.far);
+        hit.normal = self.world_normal(hit.normal);
+        hit.tangents = hit.tangents.map(|(t, b)| {
+            (
+                self.transform.transform_vector(t).normalize(),
+                self.transform.transform_vector(b).normalize(),
+            )
+        });
+
+        hit
+    }
+}
+
+impl<T: SceneObject> SceneObject for Transformed<T> {
+    fn material(&self) -> &Material {
+        self.object.material()
+    }
+
+    fn material_mut(&mut self) -> &mut Material {
+        self.object.material_mut()
+    }
+
+    fn material_at(&self, hit: &Hit) -> &Material {
+        self.object.material_at(hit)
+    }
+
+    fn kind(&self) -> &'static str {
+        self.object.kind()
+    }
+
+    fn clone_box(&self) -> Box<dyn SceneObject> {
+        Box::new(Transformed {
+            object: self.object.clone_box(),
+            transform: self.transform,
+            inverse: self.inverse,
+        })
+    }
+
+    fn fingerprint(&self, hasher: &mut Fingerprint) {
+        hasher.write_debug(&self.transform);
+        self.object.fingerprint(hasher);
+    }
+
+    fn validate(&self) -> Vec<Problem> {
+        self.object.validate()
+    }
+
+    #[cfg(feature = "serde")]
+    fn tagged(&self) -> crate::serialize::TaggedObject<'_> {
+        crate::serialize::TaggedObject::Transformed {
+            object: &self.object,
+            transform: self.transform,
+        }
+    }
+
+    fn flags(&self) -> RenderFlags {
+        self.object.flags()
+    }
+
+    fn opaque(&self) -> bool {
+        self.object.opaque()
+    }
+
+    fn stats(&self) -> ObjectStats {
+        self.object.stats()
+    }
+}
@@ -0,0 +1,136 @@
+use crate::{
+    checkpoint::Fingerprint,
+    material::{Color, Material},
+    math::{Float, Ray, Vector2, Vector3},
+    validate::{self, Problem},
+};
+
+use super::{
+    mesh::{
+        triangle_intersect, triangle_intersect_normal, triangle_intersect_uvs, triangle_normal,
+        triangle_tangents,
+    },
+    Hit, Intersect, SceneObject,
+};
+
+/// A single triangle, for small bits of geometry that don't need a whole `Mesh`.
+///
+/// Its vertices can optionally have their own normals (for smooth shading), texture
+/// coordinates and colors, which are interpolated across the triangle.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Triangle {
+    /// The vertices of the triangle.
+    pub verts: [Vector3; 3],
+
+    /// The normal at each vertex. If this is `None`, the triangle is flat, and its normal
+    /// follows the right-hand rule from the first to the last vertex.
+    pub normals: Option<[Vector3; 3]>,
+
+    /// The texture coordinates of each vertex, where V points up like in OBJ files.
+    pub uvs: Option<[Vector2; 3]>,
+
+    /// The color of each vertex, which tints the material's texture.
+    pub colors: Option<[Color; 3]>,
+
+    /// The material of the triangle.
+    pub material: Material,
+}
+
+impl Triangle {
+    pub fn new(verts: [Vector3; 3], material: Material) -> Self {
+        Self {
+            verts,
+            normals: None,
+            uvs: None,
+            colors: None,
+            material,
+        }
+    }
+
+    /// Give each vertex of the triangle a normal, for smooth shading.
+    pub fn with_normals(mut self, normals: [Vector3; 3]) -> Self {
+        self.normals = Some(normals.map(Vector3::normalize));
+        self
+    }
+
+    /// Give each vertex of the triangle texture coordinates.
+    pub fn with_uvs(mut self, uvs: [Vector2; 3]) -> Self {
+        self.uvs = Some(uvs);
+        self
+    }
+
+    /// Give each vertex of the triangle a color.
+    pub fn with_colors(mut self, colors: [Color; 3]) -> Self {
+        self.colors = Some(colors);
+        self
+    }
+}
+
+impl Intersect for Triangle {
+    fn intersect(&self, ray: &Ray) -> Option<Hit> {
+        let [v0, v1, v2] = self.verts;
+        let i = triangle_intersect(v0, v1, v2, ray, false)?;
+
+        let normal = match &self.normals {
+            Some(normals) => triangle_intersect_normal(0, 1, 2, normals, &i).normalize(),
+            None => triangle_normal(v0, v1, v2),
+        };
+        let uv = match &self.uvs {
+            Some(uvs) => triangle_intersect_uvs(0, 1, 2, uvs, &i),
+            None => Vector2::new(0., 0.),
+        };
+
+        let mut hit = Hit::new(normal, (i.t, i.p), (i.t, i.p), uv);
+
+        if let Some([c0, c1, c2]) = self.colors {
+            let (w0, w1, w2) = ((1. - i.u - i.v) as Float, i.u as Float, i.v as Float);
+            let color = Vector3::from(c0) * w0 + Vector3::from(c1) * w1 + Vector3::from(c2) * w2;
+            hit = hit.with_color(color.into());
+        }
+
+        if let Some([t0, t1, t2]) = self.uvs {
+            if let Some((tangent, bitangent)) = triangle_tangents((v0, v1, v2), (t0, t1, t2)) {
+                hit = hit.with_tangents(tangent, bitangent);
+            }
+        }
+
+        Some(hit)
+    }
+}
+
+impl SceneObject for Triangle {
+    fn material(&self) -> &Material {
+        &self.material
+    }
+
+    fn material_mut(&mut self) -> &mut Material {
+        &mut self.material
+    }
+
+    fn kind(&self) -> &'static str {
+        "triangle"
+    }
+
+    fn clone_box(&self) -> Box<dyn SceneObject> {
+        Box::new(self.clone())
+    }
+
+    fn fingerprint(&self, hasher: &mut Fingerprint) {
+        hasher.write_debug(&(self.kind(), self.verts, self.normals, self.uvs, self.colors));
+        self.material.fingerprint(hasher);
+    }
+
+    fn validate(&self) -> Vec<Problem> {
+        validate::check_triangles(
+            &self.verts,
+            std::iter::once([0, 1, 2]),
+            self.normals.iter().flatten(),
+        )
+    }
+
+    #[cfg(feature = "serde")]
+    fn tagged(&self) -> crate::serialize::TaggedObject<'_> {
+        crate::serialize::TaggedObject::Triangle(self)
+    }
+}
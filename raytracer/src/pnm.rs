@@ -0,0 +1,66 @@
+//! Writers for the plain PPM and PFM image formats. Both are simple enough to write by hand,
+//! and to read back from most image tools, which makes them handy for diffing renders or
+//! piping them into other programs.
+
+use std::io::{self, Write};
+
+use crate::material::Color;
+
+/// Write an image of `width` by `height` pixels to `writer` as a plain (ASCII) PPM file, one
+/// pixel per line, row by row from the top left. Being text, renders written this way can be
+/// compared with an ordinary diff.
+pub fn write_ppm<W: Write>(
+    writer: &mut W,
+    width: u32,
+    height: u32,
+    pixels: &[Color],
+) -> io::Result<()> {
+    check_size(width, height, pixels.len())?;
+
+    writeln!(writer, "P3")?;
+    writeln!(writer, "{} {}", width, height)?;
+    writeln!(writer, "255")?;
+    for color in pixels.iter() {
+        writeln!(writer, "{} {} {}", color.r, color.g, color.b)?;
+    }
+
+    Ok(())
+}
+
+/// Write an image of `width` by `height` pixels to `writer` as a PFM file, which stores each
+/// channel as a 32-bit float, so colors brighter than white are kept. Pixels are given row by
+/// row from the top left, though PFM stores its rows from the bottom up.
+pub fn write_pfm<W: Write>(
+    writer: &mut W,
+    width: u32,
+    height: u32,
+    pixels: &[[f32; 3]],
+) -> io::Result<()> {
+    check_size(width, height, pixels.len())?;
+
+    // a negative scale marks the data as little endian
+    writeln!(writer, "PF")?;
+    writeln!(writer, "{} {}", width, height)?;
+    writeln!(writer, "-1.0")?;
+
+    let mut line = Vec::with_capacity(width as usize * 12);
+    for row in pixels.chunks(width.max(1) as usize).rev() {
+        line.clear();
+        line.extend(row.iter().flatten().flat_map(|c| c.to_le_bytes()));
+        writer.write_all(&line)?;
+    }
+
+    Ok(())
+}
+
+/// Make sure there is exactly one pixel for every spot in the image.
+fn check_size(width: u32, height: u32, pixels: usize) -> io::Result<()> {
+    if pixels == width as usize * height as usize {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "image doesn't have a value for every pixel",
+        ))
+    }
+}
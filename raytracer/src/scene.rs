@@ -1,25 +1,122 @@
+use std::{
+    borrow::Cow,
+    collections::HashMap,
+    fmt,
+    fs::{self, File},
+    io::{self, BufWriter, Write},
+    ops::Range,
+    path::Path,
+    time::{Duration, Instant},
+};
+
 use rayon::prelude::*;
+use slotmap::{Key, SlotMap};
 
+#[cfg(feature = "simd")]
+use crate::acceleration::PACKET_SIZE;
 use crate::{
     camera::Camera,
-    lighting::Light,
-    material::Color,
-    math::{refraction_vec, Lerp, Ray, Vector3},
-    object::{Hit, SceneObject},
+    checkpoint::{Checkpoint, Fingerprint},
+    exr,
+    lighting::{cook_torrance, schlick_fresnel, Light, ObjectRef, DIELECTRIC_F0},
+    material::{linear_to_srgb, Color, ColorSpace, Material, Texture},
+    math::{refraction_vec, Float, Lerp, Ray, Vector3},
+    metadata,
+    object::{Hit, Intersect, ObjectStats, SceneObject},
+    pnm,
     skybox::{self, Skybox},
+    validate::{Issue, Location, Problem},
 };
 
 /// A very small value, close to zero, to prevent weird overlapping.
-pub const EPSILON: f64 = 0.00000000001;
+#[cfg(not(feature = "f32"))]
+pub const EPSILON: Float = 0.00000000001;
+
+/// A very small value, close to zero, to prevent weird overlapping. `f32` can't resolve
+/// distances nearly as small as `f64`, so this is much larger with the `f32` feature.
+#[cfg(feature = "f32")]
+pub const EPSILON: Float = 0.0001;
+
+/// The maximum number of cut out surfaces a ray can pass through on a single object.
+const MAX_CUTOUT_SKIPS: u32 = 32;
+
+/// How many rows of pixels streaming renders render at once, before writing them out.
+const STREAM_BLOCK_ROWS: i32 = 16;
+
+/// How much closer than a pixel away neighboring rays are when estimating texture footprints.
+const FOOTPRINT_PROBE_SCALE: f32 = 64.;
+
+/// What a ray is cast for, which decides the objects it can hit (see [`RenderFlags`](crate::object::RenderFlags)).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RayKind {
+    /// A ray from the camera, or continuing straight through a surface from one.
+    Camera,
+
+    /// A ray reflected or refracted off of a surface.
+    Reflection,
+
+    /// A ray toward a light, checking whether anything casts a shadow.
+    Shadow,
+}
+
+/// Which lighting to shade with, see [`Scene::render_light_group`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LightSelection<'a> {
+    /// Every light, the ambient light, emission and the skybox.
+    All,
+
+    /// Only the lights in a group. Lights with no group are in the `None` group, along with the
+    /// ambient light, emission and the skybox.
+    Group(Option<&'a str>),
+}
+
+impl LightSelection<'_> {
+    /// Whether the lighting that belongs to no group is included.
+    fn ungrouped(&self) -> bool {
+        matches!(self, Self::All | Self::Group(None))
+    }
+}
 
 /// Scene options. Defaults are provided.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct SceneOptions {
     /// The maximum number of bounces a ray can reflect/refract/etc. from an initial ray.
     pub max_ray_depth: u32,
 
     /// The ambient color of the scene.
     pub ambient: Color,
+
+    /// Whether or not to shade in linear color space. Colors (textures, lights, the skybox, ...)
+    /// are decoded from sRGB before shading, and the final color is encoded back to sRGB.
+    /// Without this, sRGB colors are shaded directly, which darkens and washes out textures.
+    pub linear_workflow: bool,
+
+    /// How far from a surface to start shadow rays, so that they don't hit the surface they
+    /// start on (shadow acne). Too big a bias detaches shadows from their objects
+    /// (peter-panning), so it should be tuned to the size of the scene.
+    pub shadow_bias: Float,
+
+    /// How much the shadow bias grows per unit of distance along the ray that hit the surface,
+    /// for large scenes where far surfaces have less floating point precision.
+    pub shadow_bias_scale: Float,
+
+    /// How many bits per channel [`Scene::render_to`] writes.
+    pub bit_depth: BitDepth,
+
+    /// Extra key/value text written into PNG and EXR renders, after what the renderer
+    /// describes itself (see [`metadata::describe`]), like the scene file it was built from.
+    pub metadata: Vec<(String, String)>,
+}
+
+/// How many bits each color channel of a saved render has. 16 bits keeps subtle gradients,
+/// like a clear sky, from banding, but only some formats (like PNG) can store them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BitDepth {
+    Eight,
+    Sixteen,
 }
 
 impl Default for SceneOptions {
@@ -27,13 +124,79 @@ impl Default for SceneOptions {
         Self {
             max_ray_depth: 4,
             ambient: Color::new(40, 40, 40),
+            linear_workflow: false,
+            shadow_bias: EPSILON,
+            shadow_bias_scale: 0.,
+            bit_depth: BitDepth::Eight,
+            metadata: Vec::new(),
         }
     }
 }
 
+slotmap::new_key_type! {
+    /// A handle to an object in a scene, as returned by [`Scene::add_object`].
+    ///
+    /// Handles stay valid while other objects are added and removed. Once its object is
+    /// removed, a handle is stale and finds nothing, even if a new object takes its place.
+    pub struct ObjectHandle;
+}
+
+/// A rectangular region of the rendered frame, in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Tile {
+    pub x: i32,
+    pub y: i32,
+    pub w: i32,
+    pub h: i32,
+}
+
+/// Statistics about what a scene is made of, see [`Scene::stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SceneStats {
+    /// The number of objects in the scene.
+    pub objects: usize,
+
+    /// The number of lights in the scene.
+    pub lights: usize,
+
+    /// The number of triangles in every object.
+    pub triangles: usize,
+
+    /// The memory taken by the texels of every texture, including the skybox's, in bytes.
+    pub texture_bytes: usize,
+
+    /// The number of nodes in the acceleration structures of every object.
+    pub bvh_nodes: usize,
+
+    /// The memory taken by the acceleration structures of every object, in bytes.
+    pub bvh_bytes: usize,
+}
+
+impl fmt::Display for SceneStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mib = |bytes: usize| bytes as f64 / (1024. * 1024.);
+        writeln!(f, "{} objects, {} lights", self.objects, self.lights)?;
+        writeln!(f, "{} triangles", self.triangles)?;
+        writeln!(f, "{:.1} MiB of textures", mib(self.texture_bytes))?;
+        write!(
+            f,
+            "{} BVH nodes in {:.1} MiB",
+            self.bvh_nodes,
+            mib(self.bvh_bytes)
+        )
+    }
+}
+
 /// A scene, which contains a list of objects, lights, and a camera to render from.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct Scene {
-    pub objects: Vec<Box<dyn SceneObject>>,
+    pub objects: SlotMap<ObjectHandle, Box<dyn SceneObject>>,
+
+    /// The handles of the objects that have names, see [`Scene::add_named_object`].
+    pub names: HashMap<String, ObjectHandle>,
+
     pub lights: Vec<Box<dyn Light>>,
     pub camera: Camera,
     pub skybox: Box<dyn Skybox>,
@@ -43,7 +206,8 @@ pub struct Scene {
 impl Default for Scene {
     fn default() -> Self {
         Self {
-            objects: Vec::new(),
+            objects: SlotMap::with_key(),
+            names: HashMap::new(),
             lights: Vec::new(),
             camera: Camera::default(),
             skybox: Box::new(skybox::Normal),
@@ -53,13 +217,211 @@ impl Default for Scene {
 }
 
 impl Scene {
-    /// Develop a list of objects that are struck by a ray.
-    pub fn cast_ray(&self, ray: &Ray) -> Vec<(&dyn SceneObject, Hit)> {
+    /// Add an object to the scene, returning a handle that can be used to modify it later.
+    pub fn add_object(&mut self, object: Box<dyn SceneObject>) -> ObjectHandle {
+        self.objects.insert(object)
+    }
+
+    /// Add an object to the scene under `name`, so that it can be found again later with
+    /// [`Scene::get_object`]. An object already using the name loses it.
+    pub fn add_named_object(
+        &mut self,
+        name: impl Into<String>,
+        object: Box<dyn SceneObject>,
+    ) -> ObjectHandle {
+        let handle = self.add_object(object);
+        self.names.insert(name.into(), handle);
+        handle
+    }
+
+    /// Give an object a name, returning the handle of the object that had the name before.
+    pub fn name_object(
+        &mut self,
+        handle: ObjectHandle,
+        name: impl Into<String>,
+    ) -> Option<ObjectHandle> {
+        self.names.insert(name.into(), handle)
+    }
+
+    /// Get the handle of an object by its name.
+    pub fn handle(&self, name: &str) -> Option<ObjectHandle> {
+        self.names.get(name).copied()
+    }
+
+    /// Get an object by its name.
+    pub fn get_object(&self, name: &str) -> Option<&dyn SceneObject> {
+        let handle = self.handle(name)?;
+        self.objects.get(handle).map(|o| o.as_ref())
+    }
+
+    /// Get an object by its name, mutably, for example to change its material between renders.
+    pub fn get_object_mut(&mut self, name: &str) -> Option<&mut Box<dyn SceneObject>> {
+        let handle = self.handle(name)?;
+        self.objects.get_mut(handle)
+    }
+
+    /// Remove an object by its name, returning it. The handles of other objects are unaffected.
+    pub fn remove_object(&mut self, name: &str) -> Option<Box<dyn SceneObject>> {
+        let handle = self.names.remove(name)?;
+        self.remove_object_by_handle(handle)
+    }
+
+    /// Remove an object by its handle, returning it. See [`Scene::remove_object`].
+    pub fn remove_object_by_handle(
+        &mut self,
+        handle: ObjectHandle,
+    ) -> Option<Box<dyn SceneObject>> {
+        let object = self.objects.remove(handle)?;
+        self.names.retain(|_, h| *h != handle);
+        Some(object)
+    }
+
+    /// The point to cast shadow rays from for `hit`, moved off of the surface by the shadow bias.
+    pub fn shadow_origin(&self, hit: &Hit) -> Vector3 {
+        let bias = self.options.shadow_bias + self.options.shadow_bias_scale * hit.near.abs();
+        hit.vnear + hit.normal * bias
+    }
+
+    /// Get the handle of an object in the scene, for example one struck by [`Scene::cast_ray`].
+    pub fn handle_of(&self, object: &dyn SceneObject) -> Option<ObjectHandle> {
+        self.objects
+            .iter()
+            .find(|(_, o)| std::ptr::addr_eq(o.as_ref(), object))
+            .map(|(handle, _)| handle)
+    }
+
+    /// The names of every named object in the scene.
+    pub fn object_names(&self) -> impl Iterator<Item = &str> {
+        self.names.keys().map(String::as_str)
+    }
+
+    /// Get the material of an object by its handle.
+    pub fn material(&self, handle: ObjectHandle) -> Option<&Material> {
+        self.objects.get(handle).map(|o| o.material())
+    }
+
+    /// Replace the material of an object by its handle, returning the old material.
+    ///
+    /// The object's geometry and acceleration structures are left untouched, so this
+    /// is cheap enough to be done between renders.
+    pub fn set_material(&mut self, handle: ObjectHandle, material: Material) -> Option<Material> {
+        self.objects
+            .get_mut(handle)
+            .map(|o| std::mem::replace(o.material_mut(), material))
+    }
+
+    /// Replace the texture of an object's material by its handle, returning the old texture.
+    pub fn set_texture(&mut self, handle: ObjectHandle, texture: Texture) -> Option<Texture> {
+        self.objects
+            .get_mut(handle)
+            .map(|o| std::mem::replace(&mut o.material_mut().texture, texture))
+    }
+
+    /// Gather statistics about the size of this scene, to see what a render will have to
+    /// trace before starting it. Meshes shared by several instances are counted once per
+    /// instance.
+    pub fn stats(&self) -> SceneStats {
+        let mut objects = ObjectStats::default();
+        for object in self.objects.values() {
+            objects += object.stats();
+        }
+
+        SceneStats {
+            objects: self.objects.len(),
+            lights: self.lights.len(),
+            triangles: objects.triangles,
+            texture_bytes: objects.texture_bytes + self.skybox.texture_bytes(),
+            bvh_nodes: objects.bvh_nodes,
+            bvh_bytes: objects.bvh_bytes,
+        }
+    }
+
+    /// Check every object and light of this scene for mistakes, like degenerate triangles,
+    /// unnormalized normals, meshes without an SBVH or lights linked to removed objects, that
+    /// would make it render wrong or panic partway through a render.
+    pub fn validate(&self) -> Vec<Issue> {
+        let objects = self.objects.iter().flat_map(|(handle, object)| {
+            let location = Location::Object(handle);
+            object
+                .validate()
+                .into_iter()
+                .map(move |problem| Issue { location, problem })
+        });
+        let lights = self.lights.iter().enumerate().flat_map(|(i, light)| {
+            let location = Location::Light(i);
+            let missing = light
+                .links()
+                .into_iter()
+                .flat_map(|links| links.missing(self))
+                .map(|r| match r {
+                    ObjectRef::Name(name) => Problem::MissingLinkedObject(format!("\"{}\"", name)),
+                    ObjectRef::Handle(handle) => {
+                        Problem::MissingLinkedObject(format!("{:?}", handle.data()))
+                    }
+                });
+            light
+                .validate()
+                .into_iter()
+                .chain(missing)
+                .map(move |problem| Issue { location, problem })
+        });
+
+        objects.chain(lights).collect()
+    }
+
+    /// Find the tiles of the frame (split into squares of `tile_size` pixels, at least 1) in
+    /// which an object is directly visible to the camera.
+    ///
+    /// Only primary rays are considered, so an object that is only seen through reflections
+    /// or refractions will not mark those tiles.
+    pub fn object_tiles(&self, handle: ObjectHandle, tile_size: i32) -> Vec<Tile> {
+        let target = match self.objects.get(handle) {
+            Some(o) => o.as_ref(),
+            None => return vec![],
+        };
+
+        let tile_size = tile_size.max(1);
+        let (fw, fh) = self.camera.frame_size();
+        let tiles = (0..fh)
+            .step_by(tile_size as usize)
+            .flat_map(|y| {
+                (0..fw).step_by(tile_size as usize).map(move |x| Tile {
+                    x,
+                    y,
+                    w: tile_size.min(fw - x),
+                    h: tile_size.min(fh - y),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        tiles
+            .into_par_iter()
+            .filter(|tile| {
+                (tile.y..tile.y + tile.h).any(|y| {
+                    (tile.x..tile.x + tile.w).any(|x| {
+                        let ray = self.frame_ray(x, y);
+                        matches!(
+                            self.cast_ray_once(&ray, RayKind::Camera),
+                            Some((o, _)) if std::ptr::addr_eq(o, target)
+                        )
+                    })
+                })
+            })
+            .collect()
+    }
+
+    /// Develop a list of objects that are struck by a ray, skipping objects that can't be
+    /// seen by a ray of `kind`.
+    pub fn cast_ray(&self, ray: &Ray, kind: RayKind) -> Vec<(&dyn SceneObject, Hit)> {
         let mut v = vec![];
 
         // iterate over every object in the scene and test for an intersection
-        for object in self.objects.iter() {
-            match object.intersect(ray) {
+        for object in self.objects.values() {
+            if !object.flags().visible_to(kind) {
+                continue;
+            }
+
+            match intersect_opaque(object.as_ref(), ray) {
                 Some(hit) => v.push((object.as_ref(), hit)),
                 None => continue,
             }
@@ -76,13 +438,189 @@ impl Scene {
     }
 
     /// Cast a ray and return one optional object.
-    pub fn cast_ray_once(&self, ray: &Ray) -> Option<(&dyn SceneObject, Hit)> {
-        let hit = self.cast_ray(ray);
+    pub fn cast_ray_once(&self, ray: &Ray, kind: RayKind) -> Option<(&dyn SceneObject, Hit)> {
+        let hit = self.cast_ray(ray, kind);
         hit.into_iter().next()
     }
 
+    /// Whether anything opaque (see [`SceneObject::opaque`]) that casts shadows blocks `ray`
+    /// within `max_distance`. This stops at the first blocker found, instead of finding and
+    /// sorting every hit along the ray. Transparent and cut-out objects are ignored, see
+    /// [`Scene::shadow_transmittance`].
+    pub fn occluded(&self, ray: &Ray, max_distance: Float) -> bool {
+        self.objects.values().any(|object| {
+            object.flags().visible_to(RayKind::Shadow)
+                && object.opaque()
+                && object.occludes(ray, max_distance)
+        })
+    }
+
+    /// Cast a packet of rays together, returning the first object each of them hits, like
+    /// [`Scene::cast_ray_once`].
+    #[cfg(feature = "simd")]
+    pub fn cast_packet(
+        &self,
+        rays: &[Ray; PACKET_SIZE],
+        kind: RayKind,
+    ) -> [Option<(&dyn SceneObject, Hit)>; PACKET_SIZE] {
+        let mut nearest: [Option<(&dyn SceneObject, Hit)>; PACKET_SIZE] = Default::default();
+        for object in self.objects.values() {
+            if !object.flags().visible_to(kind) {
+                continue;
+            }
+
+            let hits = object.intersect_packet(rays);
+            for (lane, hit) in IntoIterator::into_iter(hits).enumerate() {
+                // rays that hit a cut out part of the object carry on through it one at a time
+                let hit = match hit {
+                    Some(hit) if object.material_at(&hit).is_cut_out(hit.uv) => {
+                        intersect_opaque(object.as_ref(), &rays[lane])
+                    }
+                    hit => hit,
+                };
+
+                match hit {
+                    Some(hit) if !matches!(&nearest[lane], Some((_, n)) if n.near <= hit.near) => {
+                        nearest[lane] = Some((object.as_ref(), hit))
+                    }
+                    _ => (),
+                }
+            }
+        }
+
+        nearest
+    }
+
+    /// Find how much of each channel of light makes it along a shadow ray within `max_distance`.
+    /// Opaque objects block the light entirely, and transparent ones let some of it through,
+    /// tinted by their color, so colored glass casts colored shadows.
+    pub fn shadow_transmittance(&self, ray: &Ray, max_distance: Float) -> Vector3 {
+        if self.occluded(ray, max_distance) {
+            return Vector3::default();
+        }
+
+        // the order the remaining objects are hit in doesn't change how much light they let
+        // through, so there is no need to sort them
+        let mut transmittance = Vector3::new(1., 1., 1.);
+        for object in self.objects.values() {
+            if object.opaque() || !object.flags().visible_to(RayKind::Shadow) {
+                continue;
+            }
+
+            let hit = match intersect_opaque(object.as_ref(), ray) {
+                Some(hit) if hit.near <= max_distance => hit,
+                _ => continue,
+            };
+
+            // partially transparent textures only filter the light where they are opaque, so
+            // even a surface that isn't transparent lets light through where its alpha is low
+            let material = object.material_at(&hit);
+            let alpha = material.texture_alpha_at(hit.uv);
+            if material.transparency <= EPSILON && alpha >= 1. {
+                return Vector3::default();
+            }
+
+            let color =
+                self.texture_working_color(material.sample_color(hit.uv, 0.), &material.texture);
+            let filter = color * material.transparency;
+            transmittance = transmittance * Vector3::new(1., 1., 1.).lerp(filter, alpha);
+        }
+
+        transmittance
+    }
+
+    /// Estimate the size of the area, in UV coordinates, that a pixel covers where a ray hits an object.
+    /// This is found by intersecting the object with a neighboring ray, one pixel's angle away, in the
+    /// direction the surface is most stretched, so the footprint grows with distance and at grazing angles.
+    fn uv_footprint(&self, object: &dyn SceneObject, ray: &Ray, hit: &Hit) -> f32 {
+        let n = hit.normal;
+        let mut spread = n - ray.direction * n.dot(ray.direction);
+        if spread.magnitude() < EPSILON {
+            spread = ray.direction.cross(Vector3::up());
+        }
+
+        // tilt toward the viewer first, since tilting away can miss the object near its silhouette.
+        // the neighbor is much closer than a whole pixel away and scaled back up afterward, since
+        // UVs wrap around and a footprint larger than the wrap could not be measured otherwise
+        let angle = self.camera.pixel_angle() / FOOTPRINT_PROBE_SCALE as Float;
+        let spread = spread.normalize() * angle;
+        let other = object
+            .intersect(&Ray::new(ray.origin, (ray.direction - spread).normalize()))
+            .or_else(|| {
+                object.intersect(&Ray::new(ray.origin, (ray.direction + spread).normalize()))
+            });
+
+        match other {
+            Some(other) => {
+                // UVs wrap around, so take the shorter distance
+                let d = (other.uv - hit.uv).abs();
+                d.x.min(1. - d.x).max(d.y.min(1. - d.y)) * FOOTPRINT_PROBE_SCALE
+            }
+            None => 0.,
+        }
+    }
+
     /// Trace out a ray, getting its color.
     pub fn trace_ray(&self, ray: Ray, depth: u32) -> Color {
+        let color = self.shade(ray, depth, RayKind::Camera, LightSelection::All, 0.);
+        self.output_color(color)
+    }
+
+    /// Convert a color in the working color space to the color written to the output.
+    fn output_color(&self, color: Vector3) -> Color {
+        self.encoded_color(color).into()
+    }
+
+    /// Encode a color in the working color space the way it is written to 8 or 16-bit outputs,
+    /// clamped to 0-1 per channel, but without rounding it to a `Color`. Float outputs aren't
+    /// encoded, see [`linear_color`](Self::linear_color).
+    fn encoded_color(&self, color: Vector3) -> Vector3 {
+        if self.options.linear_workflow {
+            Vector3::new(
+                linear_to_srgb(color.x),
+                linear_to_srgb(color.y),
+                linear_to_srgb(color.z),
+            )
+        } else {
+            Vector3::new(
+                color.x.clamp(0., 1.),
+                color.y.clamp(0., 1.),
+                color.z.clamp(0., 1.),
+            )
+        }
+    }
+
+    /// Convert a color (0-1 per channel) encoded in `space` to the color space shading happens in,
+    /// which is linear if `SceneOptions::linear_workflow` is set.
+    fn working_color(&self, color: Vector3, space: ColorSpace) -> Vector3 {
+        if self.options.linear_workflow {
+            space.to_linear(color)
+        } else {
+            color
+        }
+    }
+
+    /// Convert a color sampled from `texture` to the working color space. Decoded textures are
+    /// already linear, so without the linear workflow they are encoded back to sRGB instead.
+    fn texture_working_color(&self, color: Vector3, texture: &Texture) -> Vector3 {
+        match (texture.is_decoded(), self.options.linear_workflow) {
+            (true, true) => color,
+            (true, false) => ColorSpace::Srgb.from_linear(color),
+            (false, _) => self.working_color(color, texture.color_space()),
+        }
+    }
+
+    /// Trace out a ray, getting its color in the working color space, which can be brighter
+    /// than white. `sky_roughness` is the roughness of the surface the ray reflected off of, which blurs
+    /// the skybox it sees.
+    fn shade(
+        &self,
+        ray: Ray,
+        depth: u32,
+        kind: RayKind,
+        lights: LightSelection,
+        sky_roughness: Float,
+    ) -> Vector3 {
         // Things to study:
         // How can we optimize the object lookup process? There
         // are many methods documented online of how to accelerate
@@ -94,46 +632,146 @@ impl Scene {
         // unique scene object for every ray. This is slow, but for
         // scenes of only a few objects, it's not really a problem.
 
-        let (object, hit) = match self.cast_ray_once(&ray) {
+        let hit = self.cast_ray_once(&ray, kind);
+        self.shade_hit(ray, hit, depth, kind, lights, sky_roughness)
+    }
+
+    /// Shade a ray that has already been cast, where `hit` is the first object it hit, if any.
+    fn shade_hit(
+        &self,
+        ray: Ray,
+        hit: Option<(&dyn SceneObject, Hit)>,
+        depth: u32,
+        kind: RayKind,
+        lights: LightSelection,
+        sky_roughness: Float,
+    ) -> Vector3 {
+        let (object, mut hit) = match hit {
             Some(r) => r,
-            None => return self.skybox.ray_color(&ray),
+            None if !lights.ungrouped() => return Vector3::default(),
+            None => {
+                let sky = self.skybox.ray_color_rough(&ray, sky_roughness).into();
+                return self.working_color(sky, ColorSpace::Srgb) * self.skybox.intensity();
+            }
         };
+        let material = object.material_at(&hit);
 
-        let mut color: Vector3 = object.material().texture.at(hit.uv).into();
-        let base_color = color.clone();
+        let mut color: Vector3 = match (&material.texture, material.triplanar) {
+            (Texture::Solid(color), _) => (*color).into(),
+            (Texture::VertexColor, _) => hit.color.unwrap_or_else(Color::white).into(),
+            (_, Some(triplanar)) => {
+                let footprint = hit.near * self.camera.pixel_angle();
+                material.triplanar_color(&triplanar, hit.vnear, hit.normal, footprint)
+            }
+            (_, None) => {
+                let footprint = self.uv_footprint(object, &ray, &hit);
+                material.sample_color(hit.uv, footprint)
+            }
+        };
+
+        color = self.texture_working_color(color, &material.texture);
+        // vertex colors tint every other texture
+        let tint = hit
+            .color
+            .filter(|_| !matches!(material.texture, Texture::VertexColor));
+        if let Some(tint) = tint {
+            color = color * self.working_color(tint.into(), ColorSpace::Srgb);
+        }
 
-        if object.material().emissivity == 1. {
-            return color.into();
+        if let Some(normal) = material.normal_at(hit.uv) {
+            hit.apply_normal_map(normal);
         }
 
+        let base_color = color.clone();
+
         // Calculate light influences
-        let mut sum_vecs: Vector3 = self.options.ambient.into();
+        let (roughness, metallic) = (material.roughness, material.metallic);
+        let ambient = if lights.ungrouped() {
+            self.working_color(self.options.ambient.into(), ColorSpace::Srgb)
+        } else {
+            Vector3::default()
+        };
+
+        // the clearcoat reflects some light before it reaches the base layer
+        let cos_view = (-ray.direction).dot(hit.normal).abs();
+        let clearcoat = material.clearcoat;
+        let coat_fresnel = clearcoat * schlick_fresnel(DIELECTRIC_F0, cos_view);
+
+        // a thin film tints reflections of the base layer depending on the view angle
+        let film = material.iridescence(cos_view);
+        let mut sum_vecs = base_color * ambient * (1. - metallic) * (1. - coat_fresnel);
+        // only look up the object if some light needs it for its links
+        let handle = if self.lights.iter().any(|l| l.links().is_some()) {
+            self.handle_of(object)
+        } else {
+            None
+        };
         for light in self.lights.iter() {
-            let lcol: Vector3 = light.color().to_owned().into();
+            if let LightSelection::Group(group) = lights {
+                if light.group() != group {
+                    continue;
+                }
+            }
+
+            if let (Some(links), Some(handle)) = (light.links(), handle) {
+                if !links.affects(self, handle) {
+                    continue;
+                }
+            }
+
+            let lcol = self.working_color((*light.color()).into(), ColorSpace::Srgb);
             let shading = light.shading(&ray, &hit, self);
+            if shading.visibility <= 0. {
+                continue;
+            }
+
+            // color from diffuse/specular, using the Cook-Torrance BRDF
+            let brdf = cook_torrance(
+                hit.normal,
+                -ray.direction,
+                shading.direction,
+                base_color,
+                roughness,
+                metallic,
+            );
+            let radiance = lcol * shading.tint * (shading.intensity * shading.visibility);
 
-            // color from diffuse/specular
-            let diffuse = lcol * shading.diffuse;
-            let specular = lcol * (shading.specular * light.specular_strength());
+            let mut lit = brdf.diffuse + brdf.specular * film * light.specular_strength();
 
-            sum_vecs += (diffuse + specular) * shading.intensity;
+            if clearcoat > EPSILON {
+                let coat = cook_torrance(
+                    hit.normal,
+                    -ray.direction,
+                    shading.direction,
+                    Vector3::default(),
+                    material.clearcoat_roughness,
+                    0.,
+                );
+                lit = lit * (1. - coat_fresnel)
+                    + coat.specular * (clearcoat * light.specular_strength());
+            }
+
+            sum_vecs += lit * radiance;
         }
 
-        color = color * sum_vecs;
+        color = sum_vecs;
 
-        let (reflectiveness, transparency, ior) = (
-            object.material().reflectiveness,
-            object.material().transparency,
-            object.material().ior,
-        );
+        let (reflectiveness, transparency, ior) =
+            (material.reflectiveness, material.transparency, material.ior);
         if transparency > EPSILON && depth < self.options.max_ray_depth {
             // if the IOR is just one, we can continue by getting the ray color
             // at the opposite end of this object
             let mut transparency_color = color;
             if ior == 1. {
-                let thru = self.trace_ray(Ray::new(hit.vfar, ray.direction), depth + 1);
+                let thru = self.shade(
+                    Ray::new(hit.vfar, ray.direction),
+                    depth + 1,
+                    kind,
+                    lights,
+                    sky_roughness,
+                );
 
-                transparency_color = thru.into();
+                transparency_color = thru;
             } else {
                 // find the angle between the incidence and the normal
                 // the higher the IOR, the higher the new ray should tend toward the normal
@@ -151,11 +789,14 @@ impl Scene {
                             ior,
                             1.,
                         ) {
-                            let ref_col = self.trace_ray(
+                            let ref_col = self.shade(
                                 Ray::new(ref_hit.vfar + exit_ref_vec * EPSILON, exit_ref_vec),
                                 depth + 1,
+                                RayKind::Reflection,
+                                lights,
+                                0.,
                             );
-                            transparency_color = ref_col.into();
+                            transparency_color = ref_col;
                         }
                     }
                 }
@@ -166,14 +807,17 @@ impl Scene {
                 // we raise this to a power of two so that edge reflections are much more strong than center reflections
                 let dot = (-ray.direction).dot(hit.normal).powi(2);
 
-                let reflected = self.trace_ray(
+                let reflected = self.shade(
                     ray.reflect(hit.vnear + hit.normal * EPSILON, hit.normal),
                     depth + 1,
+                    RayKind::Reflection,
+                    lights,
+                    roughness,
                 );
 
                 // mix in the reflected color highest at the edges
                 // TODO: incorporate `reflectiveness` here
-                transparency_color = transparency_color.lerp(reflected.into(), 1. - dot);
+                transparency_color = transparency_color.lerp(reflected * film, 1. - dot);
             }
 
             color = color.lerp(transparency_color, transparency);
@@ -187,80 +831,753 @@ impl Scene {
             // hit object so as not to cause any weird overlap
 
             // TODO: should I incorporate some rendering techniques like fresnel to fade or amplify the edges?
-            let reflected = self.trace_ray(
+            let reflected = self.shade(
                 ray.reflect(hit.vnear + hit.normal * EPSILON, hit.normal),
                 depth + 1,
+                RayKind::Reflection,
+                lights,
+                roughness,
             );
 
-            color = color.lerp(reflected.into(), reflectiveness);
+            color = color.lerp(reflected * film, reflectiveness);
+        }
+
+        // reflect the scene off of the clearcoat, strongest at grazing angles. The layers
+        // below were already dimmed by the light the coat reflects, so this adds on top
+        if clearcoat > EPSILON && depth < self.options.max_ray_depth {
+            let reflected = self.shade(
+                ray.reflect(hit.vnear + hit.normal * EPSILON, hit.normal),
+                depth + 1,
+                RayKind::Reflection,
+                lights,
+                material.clearcoat_roughness,
+            );
+
+            color += reflected * coat_fresnel;
         }
 
         // todo: fog
 
-        let emissivity = object.material().emissivity;
-        if emissivity > 0. {
-            color.lerp(base_color, emissivity).into()
-        } else {
-            color.into()
+        // add the light emitted by this object
+        let emissivity = material.emissivity;
+        if emissivity > 0. && lights.ungrouped() {
+            let emission = material.emission.as_ref().unwrap_or(&material.texture);
+            let emitted = self.texture_working_color(material.emitted_at(hit.uv), emission);
+            color += emitted * emissivity;
+        }
+
+        // blend partially transparent textures with whatever is behind them
+        let alpha = material.texture_alpha_at(hit.uv);
+        if alpha < 1. && depth < self.options.max_ray_depth {
+            let behind = self.shade(
+                Ray::new(hit.vnear + ray.direction * EPSILON, ray.direction),
+                depth + 1,
+                kind,
+                lights,
+                sky_roughness,
+            );
+            color = behind.lerp(color, alpha);
         }
+
+        // bright bounces carry their energy on, colors are only clamped once they're encoded
+        color
     }
 
     /// Trace out a pixel, where top-left of the image is (0, 0).
     /// This function is run many times in parallel.
     pub fn trace_pixel(&self, x: i32, y: i32) -> Color {
-        let ray = Ray::new(
-            self.camera.origin,
-            self.camera.direction_at(x as f64, y as f64),
-        );
+        self.trace_pixel_from(self.camera.origin, x, y)
+    }
+
+    /// Trace out a pixel as if the camera were placed at `origin`.
+    pub fn trace_pixel_from(&self, origin: Vector3, x: i32, y: i32) -> Color {
+        let ray = Ray::new(origin, self.camera.direction_at(x as Float, y as Float));
 
         self.trace_ray(ray, 0)
     }
 
+    /// Get the primary ray of a pixel of the final frame, where top-left of the frame is (0, 0).
+    /// If the camera is rendering in stereo, this will be cast from the proper eye.
+    pub fn frame_ray(&self, x: i32, y: i32) -> Ray {
+        match self.camera.stereo {
+            Some(stereo) => {
+                let (eye, ex, ey) = stereo.eye_at(x, y, self.camera.vw, self.camera.vh);
+                Ray::new(
+                    self.camera.eye_origin(eye, stereo.ipd),
+                    self.camera.direction_at(ex as Float, ey as Float),
+                )
+            }
+            None => Ray::new(
+                self.camera.origin,
+                self.camera.direction_at(x as Float, y as Float),
+            ),
+        }
+    }
+
+    /// Trace out a packet of camera rays together, getting their colors.
+    #[cfg(feature = "simd")]
+    fn trace_packet(&self, rays: &[Ray; PACKET_SIZE]) -> [Color; PACKET_SIZE] {
+        let mut hits = self.cast_packet(rays, RayKind::Camera);
+        std::array::from_fn(|i| {
+            let color = self.shade_hit(
+                rays[i].clone(),
+                hits[i].take(),
+                0,
+                RayKind::Camera,
+                LightSelection::All,
+                0.,
+            );
+            self.output_color(color)
+        })
+    }
+
+    /// Render the image out as a list of Colors, tracing the camera rays of neighboring pixels
+    /// in packets, since they mostly hit the same parts of the scene.
+    #[cfg(feature = "simd")]
+    fn render_packets(&self, rows: Range<i32>) -> Vec<Color> {
+        let (vw, _) = self.camera.frame_size();
+        let row_packets = (vw as usize).div_ceil(PACKET_SIZE);
+
+        (0..row_packets * rows.len())
+            .into_par_iter()
+            .flat_map_iter(|i| {
+                let x = (i % row_packets * PACKET_SIZE) as i32;
+                let y = rows.start + (i / row_packets) as i32;
+
+                // the last packet of a row can hang off the edge, so repeat its last pixel
+                let rays = std::array::from_fn(|j| self.frame_ray((x + j as i32).min(vw - 1), y));
+                IntoIterator::into_iter(self.trace_packet(&rays)).take((vw - x) as usize)
+            })
+            .collect()
+    }
+
+    /// Trace out a pixel of the final frame, where top-left of the frame is (0, 0).
+    /// If the camera is rendering in stereo, this will trace from the proper eye.
+    pub fn trace_frame_pixel(&self, x: i32, y: i32) -> Color {
+        self.trace_ray(self.frame_ray(x, y), 0)
+    }
+
     /// Render the image out as a list of Colors.
     pub fn render(&self) -> Vec<Color> {
-        let (vw, vh) = (self.camera.vw, self.camera.vh);
-
-        // Thanks to Rayon, parallelizing the raytracer is
-        // outrageously simple. Rayon provides "parallel iterators",
-        // which largely reflect the Rust trait `Iterator`, except
-        // they are handled by Rayon's global thread scheduler,
-        // which means they intelligently are scheduled to be
-        // run by different CPU cores, all on a balanced load.
-        // Initially, I'd written some code to chunk off pixels
-        // in the image by some arbitrary configurable number and
-        // use a thread scheduler to eat away these entire chunks,
-        // aggregate the results into a map and write it back to
-        // the main core, which worked, but this solution is MUCH
-        // cleaner because you have the beauty of a well-maintained
-        // and researched Rust library developed by very smart people
-        // who have optimized for this specific case.
-        //
-        // https://en.wikipedia.org/wiki/Embarrassingly_parallel
-        (0..(vw * vh))
-            .into_par_iter() // Look at that! Just create a range and parallelize it instantly. Beautiful!
-            .map(|i| self.trace_pixel(i % vw, i / vw))
-            .collect::<Vec<_>>()
-
-        // We will need more complexity here later if we want to
-        // add a live preview as the image renders.
-    }
-
-    /// Render the image out to the desired save file.
+        let (_, vh) = self.camera.frame_size();
+        self.render_rows(0..vh)
+    }
+
+    /// Render some of the rows of the image out as a list of Colors, row by row, where the top
+    /// row is 0.
+    pub fn render_rows(&self, rows: Range<i32>) -> Vec<Color> {
+        #[cfg(feature = "simd")]
+        return self.render_packets(rows);
+
+        #[cfg(not(feature = "simd"))]
+        {
+            let (vw, _) = self.camera.frame_size();
+
+            // Thanks to Rayon, parallelizing the raytracer is
+            // outrageously simple. Rayon provides "parallel iterators",
+            // which largely reflect the Rust trait `Iterator`, except
+            // they are handled by Rayon's global thread scheduler,
+            // which means they intelligently are scheduled to be
+            // run by different CPU cores, all on a balanced load.
+            // Initially, I'd written some code to chunk off pixels
+            // in the image by some arbitrary configurable number and
+            // use a thread scheduler to eat away these entire chunks,
+            // aggregate the results into a map and write it back to
+            // the main core, which worked, but this solution is MUCH
+            // cleaner because you have the beauty of a well-maintained
+            // and researched Rust library developed by very smart people
+            // who have optimized for this specific case.
+            //
+            // https://en.wikipedia.org/wiki/Embarrassingly_parallel
+            (rows.start * vw..rows.end * vw)
+                .into_par_iter() // Look at that! Just create a range and parallelize it instantly. Beautiful!
+                .map(|i| self.trace_frame_pixel(i % vw, i / vw))
+                .collect::<Vec<_>>()
+
+            // We will need more complexity here later if we want to
+            // add a live preview as the image renders.
+        }
+    }
+
+    /// Render the image into an in-memory image buffer, for applications that display or
+    /// process frames themselves rather than saving them to a file.
+    pub fn render_image(&self) -> image::RgbImage {
+        self.frame_image(&self.render())
+    }
+
+    /// Render the image into an in-memory buffer of linear float colors, which aren't clamped
+    /// to white, like the color channels of [`render_to_exr`](Self::render_to_exr).
+    pub fn render_image_float(&self) -> image::ImageBuffer<image::Rgb<f32>, Vec<f32>> {
+        self.float_image(LightSelection::All)
+    }
+
+    /// Render the image into a buffer of linear float colors, lit by only some of the lighting.
+    fn float_image(&self, lights: LightSelection) -> image::ImageBuffer<image::Rgb<f32>, Vec<f32>> {
+        let (vw, vh) = self.camera.frame_size();
+
+        let pixels = (0..(vw * vh))
+            .into_par_iter()
+            .flat_map_iter(|i| {
+                let ray = self.frame_ray(i % vw, i / vw);
+                let color = self.shade(ray, 0, RayKind::Camera, lights, 0.);
+                let color = self.linear_color(color);
+                [color.x as f32, color.y as f32, color.z as f32]
+            })
+            .collect::<Vec<_>>();
+
+        image::ImageBuffer::from_raw(vw as u32, vh as u32, pixels).unwrap()
+    }
+
+    /// The names of the light groups of the scene's lights, see [`crate::lighting::Grouped`].
+    pub fn light_groups(&self) -> Vec<&str> {
+        let mut groups = Vec::new();
+        for group in self.lights.iter().filter_map(|l| l.group()) {
+            if !groups.contains(&group) {
+                groups.push(group);
+            }
+        }
+
+        groups
+    }
+
+    /// Render the image lit by only the lights in `group`, into a buffer of linear float colors
+    /// like [`render_image_float`](Self::render_image_float). With `None`, it is lit by the
+    /// lights in no group, along with the ambient light, emission and the skybox. Since they
+    /// aren't clamped, the renders of every group add up to the full float render (with the
+    /// linear workflow), so the lighting can be rebalanced afterwards without rendering again.
+    pub fn render_light_group(
+        &self,
+        group: Option<&str>,
+    ) -> image::ImageBuffer<image::Rgb<f32>, Vec<f32>> {
+        self.float_image(LightSelection::Group(group))
+    }
+
+    /// Render the image lit by only the lights in `group` (see
+    /// [`render_light_group`](Self::render_light_group)) out to an OpenEXR file of linear color,
+    /// so the files of every group can be added together.
+    pub fn render_light_group_to_exr(&self, group: Option<&str>, path: &str) -> io::Result<()> {
+        let image = self.render_light_group(group);
+        let (vw, vh) = image.dimensions();
+
+        let mut layers = [Vec::new(), Vec::new(), Vec::new()];
+        for pixel in image.pixels() {
+            for (layer, &c) in layers.iter_mut().zip(pixel.0.iter()) {
+                layer.push(c);
+            }
+        }
+        let layers = ["R", "G", "B"]
+            .iter()
+            .copied()
+            .zip(layers.iter().map(|v| v.as_slice()))
+            .collect::<Vec<_>>();
+
+        let mut writer = BufWriter::new(File::create(path)?);
+        exr::write(&mut writer, vw, vh, &layers, &[])?;
+        writer.flush()
+    }
+
+    /// Render a heat map of how many BVH nodes (see [`Intersect::bvh_nodes_visited`]) each
+    /// camera ray visits, from black through blue and green to red at the most visited pixel,
+    /// to find the meshes that are slow to trace. Returns the frame and the most nodes any
+    /// pixel visited.
+    pub fn render_bvh_heatmap(&self) -> (Vec<Color>, usize) {
+        let (vw, vh) = self.camera.frame_size();
+
+        let counts = (0..(vw * vh))
+            .into_par_iter()
+            .map(|i| {
+                let ray = self.frame_ray(i % vw, i / vw);
+                self.objects
+                    .values()
+                    .filter(|object| object.flags().visible_to(RayKind::Camera))
+                    .map(|object| object.bvh_nodes_visited(&ray))
+                    .sum::<usize>()
+            })
+            .collect::<Vec<_>>();
+
+        let max = counts.iter().copied().max().unwrap_or_default();
+        let ramp = [Color::black(), Color::blue(), Color::green(), Color::red()];
+        let frame = counts
+            .into_iter()
+            .map(|count| {
+                let t = count as Float / max.max(1) as Float * (ramp.len() - 1) as Float;
+                let i = (t as usize).min(ramp.len() - 2);
+                ramp[i].lerp(ramp[i + 1], t - i as Float)
+            })
+            .collect();
+
+        (frame, max)
+    }
+
+    /// Re-render a single tile of a previously rendered frame in place. `frame` is expected
+    /// to be the output of [`render`](Self::render) for this scene's camera.
+    pub fn render_tile(&self, tile: Tile, frame: &mut [Color]) {
+        let (fw, _) = self.camera.frame_size();
+
+        let colors = (0..(tile.w * tile.h))
+            .into_par_iter()
+            .map(|i| self.trace_frame_pixel(tile.x + i % tile.w, tile.y + i / tile.w))
+            .collect::<Vec<_>>();
+
+        for (i, color) in colors.into_iter().enumerate() {
+            let (x, y) = (tile.x + i as i32 % tile.w, tile.y + i as i32 / tile.w);
+            frame[(y * fw + x) as usize] = color;
+        }
+    }
+
+    /// Render the image out to the desired save file, with as many bits per channel as
+    /// `SceneOptions::bit_depth` asks for.
     pub fn render_to(&self, path: &str, format: image::ImageFormat) {
-        let rendered = self.render();
+        let start = Instant::now();
+        let image = match self.options.bit_depth {
+            BitDepth::Eight => image::DynamicImage::ImageRgb8(self.render_image()),
+            BitDepth::Sixteen => image::DynamicImage::ImageRgb16(self.render_16()),
+        };
+        self.save_image(&image, path, format, Some(start.elapsed()));
+    }
+
+    /// Render the image with 16 bits per channel, keeping the shaded colors at full precision
+    /// until they are written rather than rounding them to a `Color`.
+    fn render_16(&self) -> image::ImageBuffer<image::Rgb<u16>, Vec<u16>> {
+        let (_, fh) = self.camera.frame_size();
+        self.image_16(&self.encoded_rows(0..fh))
+    }
+
+    /// Lay out a frame of encoded colors (see [`encoded_color`](Self::encoded_color)) as an
+    /// image with 16 bits per channel.
+    fn image_16(&self, frame: &[Vector3]) -> image::ImageBuffer<image::Rgb<u16>, Vec<u16>> {
+        let (fw, fh) = self.camera.frame_size();
+
+        let mut imgbuf: image::ImageBuffer<image::Rgb<u16>, Vec<u16>> =
+            image::ImageBuffer::new(fw as u32, fh as u32);
+        for (i, color) in frame.iter().enumerate() {
+            imgbuf.put_pixel(
+                i as u32 % fw as u32,
+                i as u32 / fw as u32,
+                image::Rgb([
+                    sixteen_bit(color.x),
+                    sixteen_bit(color.y),
+                    sixteen_bit(color.z),
+                ]),
+            );
+        }
+
+        imgbuf
+    }
+
+    /// Render some of the rows of the image, encoded the way they are written to the output
+    /// but not yet rounded to a `Color` (see [`encoded_color`](Self::encoded_color)).
+    fn encoded_rows(&self, rows: Range<i32>) -> Vec<Vector3> {
+        let mut colors = self.shaded_rows(rows);
+        for color in colors.iter_mut() {
+            *color = self.encoded_color(*color);
+        }
+        colors
+    }
+
+    /// Render some of the rows of the image, in the working color space.
+    fn shaded_rows(&self, rows: Range<i32>) -> Vec<Vector3> {
+        let (vw, _) = self.camera.frame_size();
+
+        (rows.start * vw..rows.end * vw)
+            .into_par_iter()
+            .map(|i| {
+                let ray = self.frame_ray(i % vw, i / vw);
+                self.shade(ray, 0, RayKind::Camera, LightSelection::All, 0.)
+            })
+            .collect()
+    }
+
+    /// Render the image out like [`render_to`](Self::render_to), a block of rows at a time,
+    /// saving the rows rendered so far to the checkpoint file at `checkpoint` every `interval`.
+    /// If the checkpoint file already holds part of this render, the render resumes from it
+    /// rather than starting over. The checkpoint file is removed once the image is saved.
+    ///
+    /// **Note:** A checkpoint is only resumed if the scene's objects, lights, camera, skybox,
+    /// options and [`metadata`](crate::metadata) are the same. Function textures and SDFs whose
+    /// distance functions have no [`description`](crate::object::Distance::description) can't
+    /// be compared, so scenes with them can't be checkpointed, and this fails with
+    /// `io::ErrorKind::InvalidInput` before rendering anything.
+    pub fn render_to_resumable(
+        &self,
+        path: &str,
+        format: image::ImageFormat,
+        checkpoint: &Path,
+        interval: Duration,
+    ) -> io::Result<()> {
+        let start = Instant::now();
+        let (vw, vh) = self.camera.frame_size();
+
+        let fingerprint = self.fingerprint().map_err(|what| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("can't checkpoint a scene with {}", what),
+            )
+        })?;
+        let mut progress = match Checkpoint::load(checkpoint) {
+            Ok(progress)
+                if progress.fingerprint == fingerprint
+                    && (progress.width, progress.height) == (vw as u32, vh as u32) =>
+            {
+                progress
+            }
+            _ => Checkpoint::new(fingerprint, vw as u32, vh as u32),
+        };
+        progress
+            .colors
+            .truncate(progress.rows() as usize * vw as usize);
+
+        let mut saved = Instant::now();
+        let resumed = progress.rows() as i32;
+        for rows in self.row_blocks().filter(|rows| rows.end > resumed) {
+            let rows = rows.start.max(resumed)..rows.end;
+            progress.colors.extend(self.shaded_rows(rows));
+
+            if saved.elapsed() >= interval {
+                progress.save(checkpoint)?;
+                saved = Instant::now();
+            }
+        }
+
+        let image = match self.options.bit_depth {
+            BitDepth::Eight => {
+                let frame = progress
+                    .colors
+                    .iter()
+                    .map(|&c| self.output_color(c))
+                    .collect::<Vec<_>>();
+                image::DynamicImage::ImageRgb8(self.frame_image(&frame))
+            }
+            BitDepth::Sixteen => {
+                let frame = progress
+                    .colors
+                    .iter()
+                    .map(|&c| self.encoded_color(c))
+                    .collect::<Vec<_>>();
+                image::DynamicImage::ImageRgb16(self.image_16(&frame))
+            }
+        };
+        self.save_image(&image, path, format, Some(start.elapsed()));
+
+        match fs::remove_file(checkpoint) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    /// A hash of the scene's metadata without its render time (see [`metadata::describe`]), its
+    /// options, camera, objects, lights and skybox, which identifies a render well enough to
+    /// tell whether a checkpoint belongs to it, or the first thing in the scene that can't be
+    /// hashed (see [`Fingerprint::write_unhashable`]).
+    fn fingerprint(&self) -> Result<u64, &'static str> {
+        let mut hasher = Fingerprint::new();
+        for (key, value) in metadata::describe(self, None).iter() {
+            hasher.write_debug(&(key, value));
+        }
+        hasher.write_debug(&self.options);
+        hasher.write_debug(&self.camera);
+
+        for object in self.objects.values() {
+            object.fingerprint(&mut hasher);
+        }
+        // light links refer to objects by name
+        let mut names = self.names.iter().collect::<Vec<_>>();
+        names.sort_by_key(|(name, _)| *name);
+        hasher.write_debug(&names);
+        for light in self.lights.iter() {
+            light.fingerprint(&mut hasher);
+        }
+        self.skybox.fingerprint(&mut hasher);
+
+        match hasher.unhashable() {
+            Some(what) => Err(what),
+            None => Ok(hasher.finish()),
+        }
+    }
+
+    /// Render the image out to a PNG file a block of rows at a time, writing each block before
+    /// rendering the next, so that very large renders don't need the whole frame in memory.
+    /// Like [`render_to`](Self::render_to), it has as many bits per channel as
+    /// `SceneOptions::bit_depth` asks for.
+    pub fn render_to_png_streaming(&self, path: &str) -> io::Result<()> {
+        let start = Instant::now();
+        let mut writer = self.png_writer(path, self.options.bit_depth)?;
+
+        let mut stream = writer.stream_writer();
+        for rows in self.row_blocks() {
+            let bytes = match self.options.bit_depth {
+                BitDepth::Eight => self
+                    .render_rows(rows)
+                    .iter()
+                    .flat_map(|c| [c.r, c.g, c.b])
+                    .collect::<Vec<_>>(),
+                BitDepth::Sixteen => self
+                    .encoded_rows(rows)
+                    .iter()
+                    .flat_map(|c| [c.x, c.y, c.z])
+                    .flat_map(|c| sixteen_bit(c).to_be_bytes())
+                    .collect(),
+            };
+            stream.write_all(&bytes)?;
+        }
+        stream.finish()?;
+
+        // the metadata goes after the image data, once the render time is known
+        self.write_png_metadata(&mut writer, Some(start.elapsed()))
+    }
+
+    /// Render the image out to an OpenEXR file a block of rows at a time, like
+    /// [`render_to_png_streaming`](Self::render_to_png_streaming). The file is the same as one
+    /// from [`render_to_exr`](Self::render_to_exr), except its metadata can't include the render
+    /// time, since the header is written first.
+    pub fn render_to_exr_streaming(&self, path: &str, channels: exr::Channels) -> io::Result<()> {
+        let (vw, vh) = self.camera.frame_size();
+
+        let metadata = metadata::describe(self, None);
+        let attributes = metadata
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .collect::<Vec<_>>();
+
+        let file = BufWriter::new(File::create(path)?);
+        let names = channels.names();
+        let mut writer = exr::Writer::new(file, vw as u32, vh as u32, &names, &attributes)?;
+        for rows in self.row_blocks() {
+            let values = self.exr_rows(rows, channels);
+            let values = values.iter().map(|v| v.as_slice()).collect::<Vec<_>>();
+            writer.write_lines(&values)?;
+        }
+
+        writer.finish()?.flush()
+    }
+
+    /// The blocks of rows that streaming renders render at once, from the top down.
+    fn row_blocks(&self) -> impl Iterator<Item = Range<i32>> {
+        let (_, vh) = self.camera.frame_size();
+        (0..vh)
+            .step_by(STREAM_BLOCK_ROWS as usize)
+            .map(move |y| y..(y + STREAM_BLOCK_ROWS).min(vh))
+    }
+
+    /// Render the image out to an OpenEXR file at `path`, with 32-bit float channels of linear
+    /// color, so highlights brighter than white survive to be graded or composited later.
+    /// Alpha is 1 where the camera sees an object and 0 where it sees the skybox, and
+    /// `channels` picks which extra channels to write along with them.
+    pub fn render_to_exr(&self, path: &str, channels: exr::Channels) -> io::Result<()> {
+        let start = Instant::now();
+        let (vw, vh) = self.camera.frame_size();
+
+        let values = self.exr_rows(0..vh, channels);
+        let layers = channels
+            .names()
+            .into_iter()
+            .zip(values.iter().map(|v| v.as_slice()))
+            .collect::<Vec<_>>();
+        let metadata = metadata::describe(self, Some(start.elapsed()));
+        let attributes = metadata
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .collect::<Vec<_>>();
+
+        let mut writer = BufWriter::new(File::create(path)?);
+        exr::write(&mut writer, vw as u32, vh as u32, &layers, &attributes)?;
+        writer.flush()
+    }
+
+    /// Render some of the rows of the image as the channels of an EXR render, in the order of
+    /// [`exr::Channels::names`].
+    fn exr_rows(&self, rows: Range<i32>, channels: exr::Channels) -> Vec<Vec<f32>> {
+        let (vw, _) = self.camera.frame_size();
+
+        let pixels = (rows.start * vw..rows.end * vw)
+            .into_par_iter()
+            .map(|i| {
+                let ray = self.frame_ray(i % vw, i / vw);
+                let hit = self.cast_ray_once(&ray, RayKind::Camera);
+                let surface = hit.as_ref().map(|(_, hit)| (hit.near, hit.normal));
+                let color = self.shade_hit(ray, hit, 0, RayKind::Camera, LightSelection::All, 0.);
+                (self.linear_color(color), surface)
+            })
+            .collect::<Vec<_>>();
+
+        fn channel<T>(pixels: &[T], f: impl Fn(&T) -> Float) -> Vec<f32> {
+            pixels.iter().map(|p| f(p) as f32).collect()
+        }
+        let mut layers = vec![
+            channel(&pixels, |(c, _)| c.x),
+            channel(&pixels, |(c, _)| c.y),
+            channel(&pixels, |(c, _)| c.z),
+            channel(&pixels, |(_, s)| if s.is_some() { 1. } else { 0. }),
+        ];
+        if channels.depth {
+            layers.push(channel(&pixels, |(_, s)| {
+                s.map_or(Float::INFINITY, |s| s.0)
+            }));
+        }
+        if channels.normal {
+            layers.push(channel(&pixels, |(_, s)| s.map_or(0., |s| s.1.x)));
+            layers.push(channel(&pixels, |(_, s)| s.map_or(0., |s| s.1.y)));
+            layers.push(channel(&pixels, |(_, s)| s.map_or(0., |s| s.1.z)));
+        }
+
+        layers
+    }
+
+    /// Render the image out to a plain PPM file (see [`pnm::write_ppm`]).
+    pub fn render_to_ppm(&self, path: &str) -> io::Result<()> {
+        let (vw, vh) = self.camera.frame_size();
+
+        let mut writer = BufWriter::new(File::create(path)?);
+        pnm::write_ppm(&mut writer, vw as u32, vh as u32, &self.render())?;
+        writer.flush()
+    }
+
+    /// Render the image out to a PFM file, with linear colors that aren't clamped to white,
+    /// like the color channels of [`render_to_exr`](Self::render_to_exr).
+    pub fn render_to_pfm(&self, path: &str) -> io::Result<()> {
+        let image = self.render_image_float();
+        let pixels = image.pixels().map(|p| p.0).collect::<Vec<_>>();
+
+        let mut writer = BufWriter::new(File::create(path)?);
+        pnm::write_pfm(&mut writer, image.width(), image.height(), &pixels)?;
+        writer.flush()
+    }
+
+    /// Convert a color in the working color space to linear, for float outputs, keeping colors
+    /// brighter than white.
+    fn linear_color(&self, color: Vector3) -> Vector3 {
+        // without the linear workflow, colors are shaded in sRGB and written to 8 and 16-bit
+        // outputs as they are, so they are decoded here to match those in a linear output
+        if self.options.linear_workflow {
+            color
+        } else {
+            ColorSpace::Srgb.to_linear(color)
+        }
+    }
+
+    /// Save a frame rendered from this scene, like one from [`render`](Self::render), to the
+    /// desired save file.
+    pub fn save_frame(&self, frame: &[Color], path: &str, format: image::ImageFormat) {
+        let image = image::DynamicImage::ImageRgb8(self.frame_image(frame));
+        self.save_image(&image, path, format, None);
+    }
+
+    /// Save an image to the desired save file. PNGs also get the render's metadata (see
+    /// [`metadata::describe`]), including `render_time` if it is known.
+    fn save_image(
+        &self,
+        image: &image::DynamicImage,
+        path: &str,
+        format: image::ImageFormat,
+        render_time: Option<Duration>,
+    ) {
+        if format == image::ImageFormat::Png {
+            self.save_png(image, path, render_time).unwrap();
+        } else {
+            image.save_with_format(path, format).unwrap();
+        }
+    }
+
+    /// Save an image to a PNG file, with the render's metadata.
+    fn save_png(
+        &self,
+        image: &image::DynamicImage,
+        path: &str,
+        render_time: Option<Duration>,
+    ) -> io::Result<()> {
+        let (data, bit_depth) = match image {
+            image::DynamicImage::ImageRgb8(image) => {
+                (Cow::Borrowed(image.as_raw().as_slice()), BitDepth::Eight)
+            }
+            // PNG stores 16-bit channels big endian
+            image::DynamicImage::ImageRgb16(image) => (
+                image
+                    .as_raw()
+                    .iter()
+                    .flat_map(|c| c.to_be_bytes())
+                    .collect(),
+                BitDepth::Sixteen,
+            ),
+            image => (Cow::Owned(image.to_rgb8().into_raw()), BitDepth::Eight),
+        };
+
+        let mut writer = self.png_writer(path, bit_depth)?;
+        self.write_png_metadata(&mut writer, render_time)?;
+        writer.write_image_data(&data)?;
+        Ok(())
+    }
+
+    /// Start writing a PNG file the size of the frame, with its header written.
+    fn png_writer(
+        &self,
+        path: &str,
+        bit_depth: BitDepth,
+    ) -> io::Result<png::Writer<BufWriter<File>>> {
+        let (vw, vh) = self.camera.frame_size();
+
+        let file = BufWriter::new(File::create(path)?);
+        let mut encoder = png::Encoder::new(file, vw as u32, vh as u32);
+        encoder.set_color(png::ColorType::RGB);
+        encoder.set_depth(match bit_depth {
+            BitDepth::Eight => png::BitDepth::Eight,
+            BitDepth::Sixteen => png::BitDepth::Sixteen,
+        });
+        Ok(encoder.write_header()?)
+    }
+
+    /// Write the render's metadata (see [`metadata::describe`]) to a PNG file as `tEXt`
+    /// chunks, including `render_time` if it is known.
+    fn write_png_metadata<W: Write>(
+        &self,
+        writer: &mut png::Writer<W>,
+        render_time: Option<Duration>,
+    ) -> io::Result<()> {
+        for (key, value) in metadata::describe(self, render_time).iter() {
+            writer.write_chunk(*b"tEXt", &metadata::text_chunk(key, value))?;
+        }
+        Ok(())
+    }
+
+    /// Lay out a frame rendered from this scene as an image.
+    fn frame_image(&self, frame: &[Color]) -> image::RgbImage {
+        let (fw, fh) = self.camera.frame_size();
 
         // spit out an image
-        let mut imgbuf: image::RgbImage =
-            image::ImageBuffer::new(self.camera.vw as u32, self.camera.vh as u32);
+        let mut imgbuf: image::RgbImage = image::ImageBuffer::new(fw as u32, fh as u32);
 
-        for (i, color) in rendered.into_iter().enumerate() {
+        for (i, color) in frame.iter().enumerate() {
             imgbuf.put_pixel(
-                i as u32 % self.camera.vw as u32,
-                i as u32 / self.camera.vw as u32,
+                i as u32 % fw as u32,
+                i as u32 / fw as u32,
                 image::Rgb([color.r, color.g, color.b]),
             );
         }
 
-        imgbuf.save_with_format(path, format).unwrap();
+        imgbuf
+    }
+}
+
+/// Encode a channel of a color, 0-1, as 16 bits.
+fn sixteen_bit(c: Float) -> u16 {
+    (c.clamp(0., 1.) * 65535.).round() as u16
+}
+
+/// Intersect a ray with an object, continuing the ray past any hits where the object's
+/// material is cut out (see [`Material::is_cut_out`]).
+fn intersect_opaque(object: &dyn SceneObject, ray: &Ray) -> Option<Hit> {
+    let mut hit = object.intersect(ray)?;
+
+    let mut offset = 0.;
+    for _ in 0..MAX_CUTOUT_SKIPS {
+        if !object.material_at(&hit).is_cut_out(hit.uv) {
+            hit.near += offset;
+            hit.far += offset;
+            return Some(hit);
+        }
+
+        offset += hit.near.max(0.) + EPSILON;
+        hit = object.intersect(&Ray::new(ray.along(offset), ray.direction))?;
     }
+
+    None
 }
@@ -1,10 +1,11 @@
 use std::{collections::HashMap, iter::Peekable, vec::IntoIter};
 
 use lazy_static::lazy_static;
-use raytracer::material::Color;
 use thiserror::Error;
 
-use crate::tokenize::{Op, Sep, Token};
+use crate::{material::Color, math::Float};
+
+use super::tokenize::{Op, Position, Sep, StringPart, Token};
 
 #[allow(dead_code)]
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -15,6 +16,8 @@ enum Associativity {
 
 lazy_static! {
     static ref OP_PRECEDENCE: HashMap<Op, u8> = vec![
+        (Op::Neg, 4),
+        (Op::Not, 4),
         (Op::Mul, 3),
         (Op::Div, 3),
         (Op::Mod, 3),
@@ -45,14 +48,17 @@ lazy_static! {
 /// An error while parsing to the AST.
 #[derive(Debug, Error)]
 pub enum AstError {
-    #[error("expected '{0}', got '{1:?}'")]
-    UnexpectedToken(String, Token),
+    #[error("{2}: expected '{0}', got '{1:?}'")]
+    UnexpectedToken(String, Token, Position),
+
+    #[error("{0}: expected more tokens, got end")]
+    UnexpectedEof(Position),
 
-    #[error("expected more tokens, got end")]
-    UnexpectedEof,
+    #[error("{0}: error parsing arithmetic expression")]
+    ArithmeticError(Position),
 
-    #[error("error parsing arithmetic expression")]
-    ArithmeticError,
+    #[error("{1}: {0} outside of a loop")]
+    OutsideLoop(String, Position),
 
     #[error("too many closing parenthesis")]
     ArithmeticExcessCloseParensError(Option<Node>),
@@ -77,6 +83,14 @@ pub enum Node {
         value: Box<Node>,
     },
 
+    /// Assignment to a key of a dictionary stored in a variable, like `dict.key = value`. The
+    /// `path` is the keys to follow from the variable, the last of which is set.
+    DictAssign {
+        name: String,
+        path: Vec<String>,
+        value: Box<Node>,
+    },
+
     /// A for loop.
     For {
         var: String,
@@ -85,6 +99,16 @@ pub enum Node {
         body: Vec<Node>,
     },
 
+    /// A for-each loop, over the items of an array.
+    ForEach {
+        var: String,
+        array: Box<Node>,
+        body: Vec<Node>,
+    },
+
+    /// A while loop, which runs its body for as long as its condition is truthy.
+    While { cond: Box<Node>, body: Vec<Node> },
+
     /// An if statement.
     If {
         /// The condition-body pairs.
@@ -97,13 +121,22 @@ pub enum Node {
     /// A function declaration.
     Function {
         name: String,
-        params: Vec<String>,
+        params: Vec<Param>,
         body: Vec<Node>,
     },
 
     /// A return statement.
     Return(Box<Node>),
 
+    /// A break statement, which exits the loop it is in.
+    Break,
+
+    /// A continue statement, which skips to the next iteration of the loop it is in.
+    Continue,
+
+    /// An include statement, naming an SDL file whose contents are spliced in its place.
+    Include(String),
+
     /// A dictionary. It acts as a map whose keys are identifiers and whose values are more AST nodes.
     Dictionary(HashMap<String, Node>),
 
@@ -116,8 +149,11 @@ pub enum Node {
     /// A string.
     String(String),
 
+    /// An interpolated string, the values of whose parts are joined together.
+    Interpolated(Vec<Node>),
+
     /// A number.
-    Number(f64),
+    Number(Float),
 
     /// A vector.
     Vector(Box<Node>, Box<Node>, Box<Node>),
@@ -137,10 +173,21 @@ pub enum Node {
     /// A scope terminator.
     ScopeTerminator,
 
+    /// The position in the source of the statement that follows.
+    Position(Position),
+
     // Array actions
     /// An array access.
     ArrayAccess(Box<Node>, Box<Node>),
 
+    /// A slice of an array, from an inclusive start index to an exclusive end index. A missing
+    /// index is the start or end of the array.
+    ArraySlice(Box<Node>, Option<Box<Node>>, Option<Box<Node>>),
+
+    // Dictionary actions
+    /// A dictionary access, by key.
+    DictAccess(Box<Node>, String),
+
     // Arithmetic
     /// The addition of two nodes.
     Add(Box<Node>, Box<Node>),
@@ -176,7 +223,13 @@ pub enum Node {
     /// Less than comparison of two nodes.
     LtEq(Box<Node>, Box<Node>),
 
+    /// The negation of a node.
+    Neg(Box<Node>),
+
     // Logic
+    /// Logical NOT of a node.
+    Not(Box<Node>),
+
     /// Logical AND of two nodes.
     And(Box<Node>, Box<Node>),
 
@@ -184,6 +237,15 @@ pub enum Node {
     Or(Box<Node>, Box<Node>),
 }
 
+/// A parameter of a function declaration.
+#[derive(Debug, Clone)]
+pub struct Param {
+    pub name: String,
+
+    /// The value of the parameter when no argument is given for it, if it is optional.
+    pub default: Option<Node>,
+}
+
 /// A kind of node *value*, rather than just any node. Used to allow functions to specify
 /// their parameter types.
 #[derive(Debug, Clone, PartialEq)]
@@ -203,7 +265,13 @@ pub enum NodeKind {
 /// a root AST node, which is composed of many more AST nodes.
 #[derive(Debug)]
 pub struct AstParser {
-    tokens: Peekable<IntoIter<Token>>,
+    tokens: Peekable<IntoIter<(Token, Position)>>,
+
+    /// The position of the last token read.
+    position: Position,
+
+    /// How many loops the scope being parsed is nested in, within the function it is in.
+    loops: usize,
 }
 
 impl AstParser {
@@ -211,9 +279,11 @@ impl AstParser {
     // after all, the only other thing they could be is keywords
 
     /// Construct a new AST parser from a list of tokens from the tokenizer.
-    pub fn new(tokens: Vec<Token>) -> Self {
+    pub fn new(tokens: Vec<(Token, Position)>) -> Self {
         Self {
             tokens: tokens.into_iter().peekable(),
+            position: Position::START,
+            loops: 0,
         }
     }
 
@@ -224,6 +294,7 @@ impl AstParser {
             Err(AstError::UnexpectedToken(
                 "not a closing brace".into(),
                 Token::Sep(Sep::BraceClose),
+                self.position,
             ))
         } else {
             Ok(Node::Root(body))
@@ -237,6 +308,8 @@ impl AstParser {
         while let Ok(token) = self.next() {
             match token {
                 Token::Identifier(identifier) => {
+                    nodes.push(Node::Position(self.position));
+
                     // once we read the identifier, we have to consider some cases:
                     // 1. the user is trying to create a scene object
                     // 2. the user is trying to set the value of a variable
@@ -250,22 +323,33 @@ impl AstParser {
                                     return Err(AstError::UnexpectedToken(
                                         "an identifier".into(),
                                         t,
+                                        self.position,
                                     ))
                                 }
                             };
 
                             self.read_expecting(Token::Identifier("in".into()))?;
 
+                            // either a range, `from to to`, or an array to loop over the items of
                             let from = self.parse_value(true)?;
+                            if let Some(Token::Sep(Sep::BraceOpen)) = self.peek() {
+                                self.next()?;
+                                let body = self.parse_loop_body()?;
+
+                                nodes.push(Node::ForEach {
+                                    var: ident,
+                                    array: Box::new(from),
+                                    body,
+                                });
+
+                                continue;
+                            }
+
                             self.read_expecting(Token::Identifier("to".into()))?;
                             let to = self.parse_value(true)?;
 
                             self.read_expecting(Token::Sep(Sep::BraceOpen))?;
-                            let body = self.parse_scope()?;
-                            match body.last() {
-                                Some(Node::ScopeTerminator) => (),
-                                _ => return Err(AstError::UnexpectedEof),
-                            }
+                            let body = self.parse_loop_body()?;
 
                             nodes.push(Node::For {
                                 var: ident,
@@ -276,6 +360,19 @@ impl AstParser {
 
                             continue;
                         }
+                        "while" => {
+                            let condition = self.parse_value(true)?;
+
+                            self.read_expecting(Token::Sep(Sep::BraceOpen))?;
+                            let body = self.parse_loop_body()?;
+
+                            nodes.push(Node::While {
+                                cond: Box::new(condition),
+                                body,
+                            });
+
+                            continue;
+                        }
                         "if" => {
                             let condition = self.parse_value(true)?;
 
@@ -284,14 +381,14 @@ impl AstParser {
                             let body = self.parse_scope()?;
                             match body.last() {
                                 Some(Node::ScopeTerminator) => (),
-                                _ => return Err(AstError::UnexpectedEof),
+                                _ => return Err(AstError::UnexpectedEof(self.position)),
                             }
 
                             let mut cond_bodies = vec![(Box::new(condition), body)];
                             let mut else_body = None;
 
                             loop {
-                                match self.tokens.peek() {
+                                match self.peek() {
                                     Some(Token::Identifier(i)) if i == "else" => {
                                         self.next()?;
                                         match self.next()? {
@@ -302,7 +399,11 @@ impl AstParser {
                                                 let body = self.parse_scope()?;
                                                 match body.last() {
                                                     Some(Node::ScopeTerminator) => (),
-                                                    _ => return Err(AstError::UnexpectedEof),
+                                                    _ => {
+                                                        return Err(AstError::UnexpectedEof(
+                                                            self.position,
+                                                        ))
+                                                    }
                                                 }
 
                                                 cond_bodies.push((Box::new(condition), body));
@@ -311,7 +412,11 @@ impl AstParser {
                                                 let body = self.parse_scope()?;
                                                 match body.last() {
                                                     Some(Node::ScopeTerminator) => (),
-                                                    _ => return Err(AstError::UnexpectedEof),
+                                                    _ => {
+                                                        return Err(AstError::UnexpectedEof(
+                                                            self.position,
+                                                        ))
+                                                    }
                                                 }
 
                                                 let _ = else_body.insert(body);
@@ -321,6 +426,7 @@ impl AstParser {
                                                 return Err(AstError::UnexpectedToken(
                                                     "`if` or opening brace".into(),
                                                     t,
+                                                    self.position,
                                                 ))
                                             }
                                         }
@@ -343,6 +449,7 @@ impl AstParser {
                                     return Err(AstError::UnexpectedToken(
                                         "an identifier".into(),
                                         t,
+                                        self.position,
                                     ))
                                 }
                             };
@@ -364,29 +471,61 @@ impl AstParser {
                                     return Err(AstError::UnexpectedToken(
                                         "an identifier".into(),
                                         t,
+                                        self.position,
                                     ))
                                 }
                             };
 
                             self.read_expecting(Token::Sep(Sep::ParensOpen))?;
 
-                            let params = self.read_list(
-                                |s| match s.next() {
-                                    Ok(Token::Identifier(i)) => Ok(i),
-                                    Err(e) => Err(e),
-                                    Ok(t) => {
-                                        Err(AstError::UnexpectedToken("an identifer".into(), t))
+                            // parameters are identifiers, optionally with a default value, like
+                            // `radius: 1`
+                            let mut params = Vec::new();
+                            loop {
+                                // if we hit the close token, stop the loop early
+                                if let Some(Token::Sep(Sep::ParensClose)) = self.peek() {
+                                    self.next()?;
+                                    break;
+                                }
+
+                                let name = self.read_identifier()?;
+                                let (default, ct) = match self.peek() {
+                                    Some(Token::Sep(Sep::Colon)) => {
+                                        self.next()?;
+                                        match self.parse_value(true) {
+                                            Ok(v) => (Some(v), true),
+                                            Err(AstError::ArithmeticExcessCloseParensError(
+                                                Some(v),
+                                            )) => (Some(v), false),
+                                            Err(e) => return Err(e),
+                                        }
                                     }
-                                },
-                                |s| s.read_sep(Sep::Comma),
-                                Token::Sep(Sep::ParensClose),
-                            )?;
+                                    _ => (None, true),
+                                };
+                                params.push(Param { name, default });
+
+                                if !ct {
+                                    break;
+                                }
+
+                                // if we hit the close token, stop the loop, just like before
+                                if let Some(Token::Sep(Sep::ParensClose)) = self.peek() {
+                                    self.next()?;
+                                    break;
+                                }
+
+                                // if the next token wasn't the close token, expect the delimiter
+                                self.read_sep(Sep::Comma)?;
+                            }
 
+                            // loops outside of a function can't be broken out of from inside it
                             self.read_expecting(Token::Sep(Sep::BraceOpen))?;
+                            let loops = std::mem::take(&mut self.loops);
                             let body = self.parse_scope()?;
+                            self.loops = loops;
                             match body.last() {
                                 Some(Node::ScopeTerminator) => (),
-                                _ => return Err(AstError::UnexpectedEof),
+                                _ => return Err(AstError::UnexpectedEof(self.position)),
                             }
 
                             nodes.push(Node::Function {
@@ -402,28 +541,68 @@ impl AstParser {
 
                             continue;
                         }
+                        "break" | "continue" => {
+                            if self.loops == 0 {
+                                return Err(AstError::OutsideLoop(identifier, self.position));
+                            }
+
+                            nodes.push(match identifier.as_str() {
+                                "break" => Node::Break,
+                                _ => Node::Continue,
+                            });
+
+                            continue;
+                        }
+                        "include" => {
+                            match self.next()? {
+                                Token::String(path) => nodes.push(Node::Include(path)),
+                                t => {
+                                    return Err(AstError::UnexpectedToken(
+                                        "a file name".into(),
+                                        t,
+                                        self.position,
+                                    ))
+                                }
+                            }
+
+                            continue;
+                        }
                         _ => (),
                     }
 
-                    match self.tokens.peek() {
+                    match self.peek() {
                         Some(Token::Op(Op::Assign)) => {
-                            self.tokens.next();
+                            self.next()?;
                             nodes.push(Node::Assign {
                                 name: identifier,
                                 declare: false,
                                 value: Box::new(self.parse_value(true)?),
                             })
                         }
+                        Some(Token::Sep(Sep::Dot)) => {
+                            let mut path = Vec::new();
+                            while let Some(Token::Sep(Sep::Dot)) = self.peek() {
+                                self.next()?;
+                                path.push(self.read_identifier()?);
+                            }
+
+                            self.read_expecting(Token::Op(Op::Assign))?;
+                            nodes.push(Node::DictAssign {
+                                name: identifier,
+                                path,
+                                value: Box::new(self.parse_value(true)?),
+                            })
+                        }
                         Some(Token::Sep(Sep::BraceOpen)) => {
                             nodes.push(self.read_object(identifier)?)
                         }
                         Some(Token::Sep(Sep::ParensOpen)) => {
-                            self.tokens.next();
+                            self.next()?;
                             let mut v = Vec::new();
 
                             loop {
                                 // if we hit the close token, stop the loop early
-                                if let Some(t) = self.tokens.peek() {
+                                if let Some(t) = self.peek() {
                                     if t == &Token::Sep(Sep::ParensClose) {
                                         self.next()?;
                                         break;
@@ -445,7 +624,7 @@ impl AstParser {
                                 }
 
                                 // if we hit the close token, stop the loop, just like before
-                                if let Some(t) = self.tokens.peek() {
+                                if let Some(t) = self.peek() {
                                     if t == &Token::Sep(Sep::ParensClose) {
                                         self.next()?;
                                         break;
@@ -459,10 +638,12 @@ impl AstParser {
                             nodes.push(Node::Call(identifier, v));
                         }
                         Some(_) => {
+                            let t = self.next()?;
                             return Err(AstError::UnexpectedToken(
                                 String::from("something valid in a scope"),
-                                self.tokens.next().unwrap(),
-                            ))
+                                t,
+                                self.position,
+                            ));
                         }
                         _ => (),
                     }
@@ -475,6 +656,7 @@ impl AstParser {
                     return Err(AstError::UnexpectedToken(
                         "something usable in a scope, or a scope terminator".into(),
                         t,
+                        self.position,
                     ))
                 }
             }
@@ -483,6 +665,19 @@ impl AstParser {
         Ok(nodes)
     }
 
+    /// Parse the body of a loop, after its opening brace, in which break and continue
+    /// statements can be used.
+    fn parse_loop_body(&mut self) -> Result<Vec<Node>, AstError> {
+        self.loops += 1;
+        let body = self.parse_scope()?;
+        self.loops -= 1;
+
+        match body.last() {
+            Some(Node::ScopeTerminator) => Ok(body),
+            _ => Err(AstError::UnexpectedEof(self.position)),
+        }
+    }
+
     /// Parse any "value": effectively an expression that has some value.
     fn parse_value(&mut self, logic: bool) -> Result<Node, AstError> {
         let mut op_stack: Vec<Token> = vec![];
@@ -490,15 +685,23 @@ impl AstParser {
 
         macro_rules! lr_op {
             ($n:ident, $out:ident) => {{
-                let b = $out.pop().unwrap();
-                let a = $out.pop().unwrap();
+                let b = $out.pop().ok_or(AstError::ArithmeticError(self.position))?;
+                let a = $out.pop().ok_or(AstError::ArithmeticError(self.position))?;
                 $out.push(Node::$n(Box::new(a), Box::new(b)));
             }};
         }
 
+        macro_rules! un_op {
+            ($n:ident, $out:ident) => {{
+                let a = $out.pop().ok_or(AstError::ArithmeticError(self.position))?;
+                $out.push(Node::$n(Box::new(a)));
+            }};
+        }
+
         macro_rules! match_op_nolog {
             ($top:expr, $out:ident) => {
                 match $top {
+                    Token::Op(Op::Neg) => un_op!(Neg, $out),
                     Token::Op(Op::Add) => lr_op!(Add, $out),
                     Token::Op(Op::Sub) => lr_op!(Sub, $out),
                     Token::Op(Op::Mul) => lr_op!(Mul, $out),
@@ -512,6 +715,8 @@ impl AstParser {
         macro_rules! match_op {
             ($top:expr, $out:ident) => {
                 match $top {
+                    Token::Op(Op::Neg) => un_op!(Neg, $out),
+                    Token::Op(Op::Not) => un_op!(Not, $out),
                     Token::Op(Op::Add) => lr_op!(Add, $out),
                     Token::Op(Op::Sub) => lr_op!(Sub, $out),
                     Token::Op(Op::Mul) => lr_op!(Mul, $out),
@@ -531,8 +736,7 @@ impl AstParser {
         }
 
         let mut last_op = true;
-        loop {
-            let peeking = self.tokens.peek().ok_or(AstError::UnexpectedEof)?;
+        while let Some(peeking) = self.peek() {
             match peeking {
                 Token::Number(_) => {
                     if !last_op {
@@ -562,6 +766,20 @@ impl AstParser {
 
                     out_queue.push(Node::String(s));
                 }
+                Token::Interpolated(_) => {
+                    if !last_op {
+                        break;
+                    } else {
+                        last_op = false;
+                    }
+
+                    let parts = match self.next()? {
+                        Token::Interpolated(parts) => parts,
+                        _ => unreachable!(),
+                    };
+
+                    out_queue.push(Self::read_interpolated(parts)?);
+                }
                 Token::Boolean(_) => {
                     if !last_op {
                         break;
@@ -601,7 +819,7 @@ impl AstParser {
                         let mut v = Vec::new();
                         loop {
                             // if we hit the close token, stop the loop early
-                            if let Some(t) = self.tokens.peek() {
+                            if let Some(t) = self.peek() {
                                 if t == &Token::Sep(Sep::BracketClose) {
                                     self.next()?;
                                     break;
@@ -623,7 +841,7 @@ impl AstParser {
                             }
 
                             // if we hit the close token, stop the loop, just like before
-                            if let Some(t) = self.tokens.peek() {
+                            if let Some(t) = self.peek() {
                                 if t == &Token::Sep(Sep::BracketClose) {
                                     self.next()?;
                                     break;
@@ -637,12 +855,37 @@ impl AstParser {
                         out_queue.push(Node::Array(v));
                     } else {
                         self.next()?;
-                        let index = self.parse_value(true)?;
-                        self.read_sep(Sep::BracketClose)?;
                         let indexing = out_queue.pop().unwrap();
-                        out_queue.push(Node::ArrayAccess(Box::new(indexing), Box::new(index)));
+
+                        // either `[index]`, or a slice like `[from..to]`, `[from..]` or `[..to]`
+                        let from = match self.peek() {
+                            Some(Token::Op(Op::RangeExclusive)) => None,
+                            _ => Some(Box::new(self.parse_value(true)?)),
+                        };
+                        match (from, self.peek()) {
+                            (from, Some(Token::Op(Op::RangeExclusive))) => {
+                                self.next()?;
+                                let to = match self.peek() {
+                                    Some(Token::Sep(Sep::BracketClose)) => None,
+                                    _ => Some(Box::new(self.parse_value(true)?)),
+                                };
+                                self.read_sep(Sep::BracketClose)?;
+                                out_queue.push(Node::ArraySlice(Box::new(indexing), from, to));
+                            }
+                            (Some(index), _) => {
+                                self.read_sep(Sep::BracketClose)?;
+                                out_queue.push(Node::ArrayAccess(Box::new(indexing), index));
+                            }
+                            (None, _) => unreachable!(),
+                        }
                     }
                 }
+                Token::Sep(Sep::Dot) if !last_op => {
+                    self.next()?;
+                    let key = self.read_identifier()?;
+                    let dict = out_queue.pop().unwrap();
+                    out_queue.push(Node::DictAccess(Box::new(dict), key));
+                }
                 Token::Op(Op::Lt) if last_op => {
                     last_op = false;
 
@@ -661,7 +904,7 @@ impl AstParser {
                         _ => unreachable!(),
                     };
 
-                    match self.tokens.peek() {
+                    match self.peek() {
                         Some(Token::Sep(Sep::ParensOpen)) => {
                             self.next()?;
 
@@ -669,7 +912,7 @@ impl AstParser {
 
                             loop {
                                 // if we hit the close token, stop the loop early
-                                if let Some(t) = self.tokens.peek() {
+                                if let Some(t) = self.peek() {
                                     if t == &Token::Sep(Sep::ParensClose) {
                                         self.next()?;
                                         break;
@@ -691,7 +934,7 @@ impl AstParser {
                                 }
 
                                 // if we hit the close token, stop the loop, just like before
-                                if let Some(t) = self.tokens.peek() {
+                                if let Some(t) = self.peek() {
                                     if t == &Token::Sep(Sep::ParensClose) {
                                         self.next()?;
                                         break;
@@ -707,6 +950,16 @@ impl AstParser {
                         _ => out_queue.push(Node::Identifier(ident)),
                     }
                 }
+                // a minus or not in place of an operand is a unary operator, which applies to the
+                // operand after it, so it is pushed without popping any other operators
+                Token::Op(Op::Sub) if last_op => {
+                    self.next()?;
+                    op_stack.push(Token::Op(Op::Neg));
+                }
+                Token::Op(Op::Not) if last_op && logic => {
+                    self.next()?;
+                    op_stack.push(Token::Op(Op::Not));
+                }
                 Token::Op(op) => {
                     // this token is an operator, match it further
                     let matches = match op {
@@ -805,7 +1058,7 @@ impl AstParser {
                     if let Some(Token::Sep(Sep::ParensOpen)) = op_stack.last() {
                         op_stack.pop();
                     } else {
-                        return Err(AstError::ArithmeticError);
+                        return Err(AstError::ArithmeticError(self.position));
                     }
                 }
                 _ => {
@@ -816,7 +1069,7 @@ impl AstParser {
 
         while !op_stack.is_empty() {
             if let Some(Token::Sep(Sep::ParensOpen)) = op_stack.last() {
-                return Err(AstError::ArithmeticError);
+                return Err(AstError::ArithmeticError(self.position));
             } else {
                 if logic {
                     match_op!(op_stack.pop().unwrap(), out_queue);
@@ -826,13 +1079,39 @@ impl AstParser {
             }
         }
 
-        Ok(out_queue.into_iter().next().unwrap())
+        out_queue
+            .into_iter()
+            .next()
+            .ok_or(AstError::UnexpectedEof(self.position))
+    }
+
+    /// Parse the parts of an interpolated string, each expression of which must be a single value.
+    fn read_interpolated(parts: Vec<StringPart>) -> Result<Node, AstError> {
+        let mut nodes = vec![];
+        for part in parts {
+            match part {
+                StringPart::Text(text) => nodes.push(Node::String(text)),
+                StringPart::Expr(tokens) => {
+                    let mut parser = Self::new(tokens);
+                    nodes.push(parser.parse_value(true)?);
+                    if let Ok(t) = parser.next() {
+                        return Err(AstError::UnexpectedToken(
+                            "a closing brace in an interpolated string".into(),
+                            t,
+                            parser.position,
+                        ));
+                    }
+                }
+            }
+        }
+
+        Ok(Node::Interpolated(nodes))
     }
 
     /// Read a scene object.
     ///
     /// An example scene object:
-    /// ```
+    /// ```text
     /// sphere {
     ///     position: <1, 2, 3>,
     ///     radius: 4,
@@ -863,7 +1142,7 @@ impl AstParser {
     /// Read a dictionary.
     ///
     /// An example dictionary:
-    /// ```
+    /// ```text
     /// {
     ///     key: value,
     ///     key: "value",
@@ -872,6 +1151,7 @@ impl AstParser {
     ///         a: "you can nest dictionaries!"
     ///     }
     /// }
+    /// ```
     fn read_dict(&mut self) -> Result<Node, AstError> {
         // we assume the opening brace has already been read
 
@@ -883,11 +1163,12 @@ impl AstParser {
                         return Err(AstError::UnexpectedToken(
                             "a key-value or closing brace".into(),
                             t,
+                            s.position,
                         ))
                     }
                 };
 
-                if let Some(Token::Sep(Sep::Colon)) = s.tokens.peek() {
+                if let Some(Token::Sep(Sep::Colon)) = s.peek() {
                     s.next()?;
                     Ok((key, s.parse_value(true)?))
                 } else {
@@ -904,7 +1185,7 @@ impl AstParser {
     /// Read a vector.
     ///
     /// An example vector:
-    /// ```
+    /// ```text
     /// <1.1, 2.4, 6.7>
     /// ```
     fn read_vector(&mut self) -> Result<Node, AstError> {
@@ -918,6 +1199,18 @@ impl AstParser {
         Ok(Node::Vector(Box::new(x), Box::new(y), Box::new(z)))
     }
 
+    /// Read an identifier from the token stream.
+    fn read_identifier(&mut self) -> Result<String, AstError> {
+        match self.next()? {
+            Token::Identifier(i) => Ok(i),
+            t => Err(AstError::UnexpectedToken(
+                "an identifier".into(),
+                t,
+                self.position,
+            )),
+        }
+    }
+
     /// Read from the token stream, expecting a token.
     /// Errors with `AstError::UnexpectedToken` if any other token is received.
     fn read_expecting(&mut self, token: Token) -> Result<(), AstError> {
@@ -925,7 +1218,11 @@ impl AstParser {
         if got == token {
             Ok(())
         } else {
-            Err(AstError::UnexpectedToken(format!("{}", token), got.clone()))
+            Err(AstError::UnexpectedToken(
+                format!("{}", token),
+                got.clone(),
+                self.position,
+            ))
         }
     }
 
@@ -952,7 +1249,7 @@ impl AstParser {
 
         loop {
             // if we hit the close token, stop the loop early
-            if let Some(t) = self.tokens.peek() {
+            if let Some(t) = self.peek() {
                 if t == &close_token {
                     self.next()?;
                     break;
@@ -963,7 +1260,7 @@ impl AstParser {
             v.push(item(self)?);
 
             // if we hit the close token, stop the loop, just like before
-            if let Some(t) = self.tokens.peek() {
+            if let Some(t) = self.peek() {
                 if t == &close_token {
                     self.next()?;
                     break;
@@ -977,8 +1274,18 @@ impl AstParser {
         Ok(v)
     }
 
+    /// Peek at the next token in the token stream.
+    fn peek(&mut self) -> Option<&Token> {
+        self.tokens.peek().map(|(token, _)| token)
+    }
+
     /// Advance the token stream, or error with `AstError::UnexpectedEof`.
     fn next(&mut self) -> Result<Token, AstError> {
-        self.tokens.next().ok_or(AstError::UnexpectedEof)
+        let (token, position) = self
+            .tokens
+            .next()
+            .ok_or(AstError::UnexpectedEof(self.position))?;
+        self.position = position;
+        Ok(token)
     }
 }
@@ -1,4 +1,4 @@
-use crate::{
+use super::{
     ast::NodeKind,
     interpret::{InterpretError, Interpreter, Value},
 };
@@ -33,7 +33,9 @@ impl Function {
         interpreter: &mut Interpreter,
         args: Vec<Value>,
     ) -> Option<Result<Value, InterpretError>> {
-        if args.iter().zip(self.arg_types.iter()).any(|(a, b)| a != b) {
+        if args.len() != self.arg_types.len()
+            || args.iter().zip(self.arg_types.iter()).any(|(a, b)| a != b)
+        {
             return None;
         }
 
@@ -0,0 +1,3270 @@
+use std::{
+    cmp::Ordering,
+    collections::{hash_map::Entry, HashMap},
+    convert::TryFrom,
+    fs::File,
+    io::{self, Read, Seek},
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::Arc,
+};
+
+use image::DynamicImage;
+use lazy_static::lazy_static;
+use noise::{NoiseFn, OpenSimplex, Perlin};
+use rand::Rng;
+use slotmap::SlotMap;
+use thiserror::Error;
+
+use crate::{
+    camera,
+    lighting::{self, AreaSurface, MeshSurface},
+    material::{
+        presets, Color, ColorSpace, ImageTexture, Material, Texture, TextureFilter, Triplanar,
+        UvTransform, WrapMode,
+    },
+    math::{consts, remap, Float, Lerp, Matrix, Quaternion, Transform, Vector2, Vector3},
+    object::{self, Distance},
+    scene::{BitDepth, ObjectHandle, Scene},
+    skybox,
+};
+
+use super::{
+    ast::{self, AstError, AstParser, NodeKind},
+    function::Function,
+    reference::RefObject,
+    tokenize::{Position, TokenizeError, Tokenizer},
+};
+
+macro_rules! optional_property {
+    ($self:ident, $scene:ident, $properties:ident, $name:literal, $k:ident) => {
+        $self
+            .optional_property($scene, &mut $properties, $name, ast::NodeKind::$k)?
+            .map(|v| unwrap_variant!(v, Value::$k))
+    };
+}
+
+macro_rules! required_property {
+    ($self:ident, $scene:ident, $properties:ident, $name:literal, $k:ident) => {
+        match optional_property!($self, $scene, $properties, $name, $k) {
+            Some(a) => a,
+            _ => return Err(InterpretError::RequiredPropertyMissing($name)),
+        }
+    };
+}
+
+macro_rules! unwrap_variant {
+    ($matching:expr, $variant:path) => {
+        match $matching {
+            $variant(a) => a,
+            _ => panic!("unwrapped variant {}", stringify!($variant)),
+        }
+    };
+}
+
+/// An interpreter error.
+#[derive(Debug, Error)]
+pub enum InterpretError {
+    #[error("{0}: {1}")]
+    At(Position, Box<InterpretError>),
+
+    #[error("{0}, {1}")]
+    InFile(String, Box<InterpretError>),
+
+    #[error("tokenizer error: {0}")]
+    Tokenizer(#[from] TokenizeError),
+
+    #[error("ast error: {0}")]
+    Ast(#[from] AstError),
+
+    #[error("failed to read {0}: {1}")]
+    SourceRead(String, io::Error),
+
+    #[error("{0} includes itself")]
+    IncludeCycle(String),
+
+    #[error("there are too many definitions of the {0} object, expecting zero or one")]
+    NonUniqueObject(&'static str),
+
+    #[error("unknown scene object/scene definition {0}")]
+    UnknownObject(String),
+
+    #[error("property {0} must be specified (it is not optional)")]
+    RequiredPropertyMissing(&'static str),
+
+    #[error("materials object must be a dictionary")]
+    InvalidMaterials,
+
+    #[error("invalid args to function call")]
+    InvalidCallArgs,
+
+    #[error("expected {0} args, got {1}")]
+    InvalidArgCount(usize, usize),
+
+    #[error("no argument given for parameter {0}, which has no default value")]
+    MissingArgument(String),
+
+    #[error("unknown shading {0}, expecting smooth or flat")]
+    UnknownShading(String),
+
+    #[error("unknown accelerator {0}, expecting sbvh, kdtree, or embree with the embree feature")]
+    UnknownAccelerator(String),
+
+    #[error("unknown attenuation {0}, expecting inverse_square, linear or an exponent")]
+    UnknownAttenuation(String),
+
+    #[error("mesh part must be a string or an array of strings")]
+    InvalidMeshPart,
+
+    #[error("unknown mesh part {0}")]
+    UnknownMeshPart(String),
+
+    #[error("failed to load mesh {0}: {1}")]
+    MeshLoad(String, io::Error),
+
+    #[error("failed to cache SBVH in {0}: {1}")]
+    SbvhCache(String, io::Error),
+
+    #[error("generic image error {0}")]
+    ImageError(#[from] image::ImageError),
+
+    #[error("no function by the name {0}")]
+    UnknownFunction(String),
+
+    #[error("no variable defined by the name {0}")]
+    UndefinedVariable(String),
+
+    #[error("cannot convert AST node to a definite type")]
+    NonValueNode,
+
+    #[error("bad comparison")]
+    BadComparison,
+
+    #[error("attempt to perform logic on non-boolean types")]
+    BadLogic,
+
+    #[error("attempt to negate a value that is not a number or a vector")]
+    BadNegation,
+
+    #[error("attempt to index a variable that is not an array")]
+    NonArrayVariable,
+
+    #[error("attempt to loop over a value that is not an array")]
+    NonArrayLoop,
+
+    #[error("attempt to index an array with a non-number value")]
+    NonNumberIndex,
+
+    #[error("index out of bounds")]
+    IndexOutOfBounds,
+
+    #[error("attempt to access a key of a value that is not a dictionary")]
+    NonDictionaryValue,
+
+    #[error("no key {0} in dictionary")]
+    UnknownKey(String),
+
+    #[error("cannot convert string {0:?} to a number")]
+    InvalidNumber(String),
+
+    #[error("invalid reference")]
+    InvalidReference,
+
+    #[error("property {0} must be an array of exactly 3 {1}")]
+    InvalidTriangleProperty(&'static str, &'static str),
+
+    #[error("unknown stereo layout {0}, expecting side_by_side or top_bottom")]
+    UnknownStereoLayout(String),
+
+    #[error("unknown texture filter {0}, expecting nearest or bilinear")]
+    UnknownTextureFilter(String),
+
+    #[error("unknown texture wrap mode {0}, expecting repeat, clamp or mirror")]
+    UnknownWrapMode(String),
+
+    #[error("unknown texture color space {0}, expecting srgb or linear")]
+    UnknownColorSpace(String),
+
+    #[error(
+        "each metaball must be a dictionary with a position, a radius and optionally a weight"
+    )]
+    InvalidMetaball,
+
+    #[error("sdf shapes must be dictionaries, with the kind of shape as `shape`")]
+    InvalidSdfShape,
+
+    #[error("unknown sdf shape {0}, expecting sphere, box, torus, capsule, plane, union, intersection or difference")]
+    UnknownSdfShape(String),
+
+    #[error("transform must be a dictionary, given to a scene object")]
+    InvalidTransform,
+
+    #[error("casts_shadows, visible_to_camera and visible_in_reflections can only be given to a scene object")]
+    InvalidRenderFlags,
+
+    #[error("name can only be given to a scene object")]
+    InvalidObjectName,
+
+    #[error("include and exclude must be arrays of object names or ids, given to a light")]
+    InvalidLightLink,
+
+    #[error("no object with id {0} has been declared before the light linking it")]
+    UnknownObjectId(usize),
+
+    #[error("group can only be given to a light")]
+    InvalidLightGroup,
+
+    #[error("cubemap faces must be an array of six filenames of square images, all the same size")]
+    InvalidCubemapFaces,
+
+    #[error("unknown csg operation {0}, expecting union, intersection or difference")]
+    UnknownCsgOperation(String),
+
+    #[error("csg operands must be dictionaries describing a single scene object, with its name as `object`")]
+    InvalidCsgOperand,
+
+    #[error("unknown material preset {0}, expecting one of {}", presets::NAMES.join(", "))]
+    UnknownMaterialPreset(String),
+
+    #[error("unsupported bit depth {0}, expecting 8 or 16")]
+    UnsupportedBitDepth(Float),
+}
+
+impl InterpretError {
+    /// Attach the position of the statement this error happened in, unless it already has the
+    /// position of a statement nested in that one.
+    fn at(self, position: Option<Position>) -> Self {
+        match (self, position) {
+            (Self::At(position, e), _) => Self::At(position, e),
+            (e, Some(position)) => Self::At(position, Box::new(e)),
+            (e, None) => e,
+        }
+    }
+
+    /// Attach the path of the included file this error happened in, unless it already has the
+    /// path of a file included by that one.
+    fn in_file(self, path: &str) -> Self {
+        match self {
+            Self::InFile(..) => self,
+            e => Self::InFile(path.to_owned(), Box::new(e)),
+        }
+    }
+}
+
+/// A definite value, which has been reduced from
+/// an AST node that was a literal, a call, or a variable.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// The unit type. Represents no data.
+    Unit,
+
+    /// A string.
+    String(String),
+
+    /// A number.
+    Number(Float),
+
+    /// A vector.
+    Vector(Vector3),
+
+    /// A color.
+    Color(Color),
+
+    /// A boolean.
+    Boolean(bool),
+
+    /// A dictionary.
+    Dictionary(HashMap<String, Value>),
+
+    /// An array.
+    Array(Vec<Value>),
+
+    /// A reference to a reference object.
+    Ref(Rc<slotmap::DefaultKey>, NodeKind),
+}
+
+impl From<Value> for ast::Node {
+    fn from(v: Value) -> Self {
+        match v {
+            Value::Unit => Self::Unit,
+            Value::String(s) => Self::String(s),
+            Value::Number(n) => Self::Number(n),
+            Value::Vector(v) => Self::Vector(
+                Box::new(Self::Number(v.x)),
+                Box::new(Self::Number(v.y)),
+                Box::new(Self::Number(v.z)),
+            ),
+            Value::Color(c) => Self::Color(c),
+            Value::Boolean(b) => Self::Boolean(b),
+            Value::Dictionary(m) => {
+                Self::Dictionary(m.into_iter().map(|(k, v)| (k, v.into())).collect())
+            }
+            Value::Array(a) => Self::Array(a.into_iter().map(Into::<ast::Node>::into).collect()),
+            Value::Ref(_, _) => unimplemented!(), // TODO
+        }
+    }
+}
+
+impl Value {
+    fn from_node(
+        interpreter: &mut Interpreter,
+        scene: &mut Scene,
+        node: ast::Node,
+    ) -> Result<Self, InterpretError> {
+        macro_rules! cmp_inner {
+            ($a:expr, $b:expr, $($variant:ident),+$(,)?) => {
+                match ($a, $b) {
+                    $((Value::$variant(a), Value::$variant(b)) => a.partial_cmp(&b),)+
+                    _ => return Err(InterpretError::BadComparison),
+                }
+            }
+        }
+
+        let value = match node {
+            ast::Node::Identifier(name) => interpreter
+                .variable_value(&name)
+                .ok_or(InterpretError::UndefinedVariable(name))?,
+            ast::Node::Call(name, args) => interpreter.call_func(scene, name, args)?,
+            ast::Node::String(s) => Self::String(s),
+            ast::Node::Interpolated(parts) => {
+                let mut string = String::new();
+                for part in parts {
+                    let value = Self::from_node(interpreter, scene, part)?;
+                    string.push_str(&interpreter.stringify(&value));
+                }
+                Self::String(string)
+            }
+            ast::Node::Number(n) => Self::Number(n),
+            ast::Node::Vector(x, y, z) => {
+                let x = Self::from_node(interpreter, scene, *x)?;
+                let y = Self::from_node(interpreter, scene, *y)?;
+                let z = Self::from_node(interpreter, scene, *z)?;
+                Self::Vector(Vector3::new(
+                    unwrap_variant!(x, Self::Number),
+                    unwrap_variant!(y, Self::Number),
+                    unwrap_variant!(z, Self::Number),
+                ))
+            }
+            ast::Node::Color(c) => Self::Color(c),
+            ast::Node::Boolean(b) => Self::Boolean(b),
+            ast::Node::Dictionary(m) => Self::Dictionary(
+                m.into_iter()
+                    .filter_map(|(k, v)| {
+                        Value::from_node(interpreter, scene, v).ok().map(|v| (k, v))
+                    })
+                    .collect(),
+            ),
+            ast::Node::Array(a) => {
+                let ref_obj = RefObject::Array(
+                    a.into_iter()
+                        .filter_map(|v| Value::from_node(interpreter, scene, v).ok())
+                        .collect(),
+                );
+                Self::Ref(interpreter.new_ref_obj(ref_obj), NodeKind::Array)
+            }
+            ast::Node::ArrayAccess(ident, index) => {
+                let index = Self::from_node(interpreter, scene, *index)?;
+                let array = Self::from_node(interpreter, scene, *ident)?;
+                match array {
+                    Value::Ref(key, NodeKind::Array) => {
+                        // get the reference object from the interpreter's RO storage
+                        let ro = interpreter
+                            .ref_objects
+                            .get(*key)
+                            .ok_or(InterpretError::InvalidReference)?;
+
+                        match ro {
+                            RefObject::Array(a) => {
+                                let index = match index {
+                                    Value::Number(i) => array_index(i, a.len())
+                                        .filter(|&i| i < a.len())
+                                        .ok_or(InterpretError::IndexOutOfBounds)?,
+                                    _ => return Err(InterpretError::NonNumberIndex),
+                                };
+                                a[index].to_owned()
+                            }
+                        }
+                    }
+                    _ => return Err(InterpretError::NonArrayVariable),
+                }
+            }
+            ast::Node::ArraySlice(ident, from, to) => {
+                let from = from
+                    .map(|n| Self::from_node(interpreter, scene, *n))
+                    .transpose()?;
+                let to = to
+                    .map(|n| Self::from_node(interpreter, scene, *n))
+                    .transpose()?;
+                let array = Self::from_node(interpreter, scene, *ident)?;
+                let slice = match array {
+                    Value::Ref(key, NodeKind::Array) => {
+                        let ro = interpreter
+                            .ref_objects
+                            .get(*key)
+                            .ok_or(InterpretError::InvalidReference)?;
+
+                        match ro {
+                            RefObject::Array(a) => {
+                                let bound = |value, default| match value {
+                                    Some(Value::Number(i)) => array_index(i, a.len())
+                                        .filter(|&i| i <= a.len())
+                                        .ok_or(InterpretError::IndexOutOfBounds),
+                                    Some(_) => Err(InterpretError::NonNumberIndex),
+                                    None => Ok(default),
+                                };
+                                let from = bound(from, 0)?;
+                                let to = bound(to, a.len())?;
+                                a.get(from..to)
+                                    .ok_or(InterpretError::IndexOutOfBounds)?
+                                    .to_vec()
+                            }
+                        }
+                    }
+                    _ => return Err(InterpretError::NonArrayVariable),
+                };
+                Self::Ref(
+                    interpreter.new_ref_obj(RefObject::Array(slice)),
+                    NodeKind::Array,
+                )
+            }
+            ast::Node::DictAccess(dict, key) => match Self::from_node(interpreter, scene, *dict)? {
+                Value::Dictionary(mut m) => {
+                    m.remove(&key).ok_or(InterpretError::UnknownKey(key))?
+                }
+                _ => return Err(InterpretError::NonDictionaryValue),
+            },
+            // arithmetic operators
+            ast::Node::Add(a, b) => Self::from_node(
+                interpreter,
+                scene,
+                ast::Node::Call(String::from("add"), vec![*a, *b]),
+            )?,
+            ast::Node::Sub(a, b) => Self::from_node(
+                interpreter,
+                scene,
+                ast::Node::Call(String::from("sub"), vec![*a, *b]),
+            )?,
+            ast::Node::Mul(a, b) => Self::from_node(
+                interpreter,
+                scene,
+                ast::Node::Call(String::from("mul"), vec![*a, *b]),
+            )?,
+            ast::Node::Div(a, b) => Self::from_node(
+                interpreter,
+                scene,
+                ast::Node::Call(String::from("div"), vec![*a, *b]),
+            )?,
+            ast::Node::Mod(a, b) => Self::from_node(
+                interpreter,
+                scene,
+                ast::Node::Call(String::from("mod"), vec![*a, *b]),
+            )?,
+            // Comparison
+            ast::Node::Eq(a, b) => Self::Boolean(
+                Self::from_node(interpreter, scene, *a)?
+                    == Self::from_node(interpreter, scene, *b)?,
+            ),
+            ast::Node::Neq(a, b) => Self::Boolean(
+                Self::from_node(interpreter, scene, *a)?
+                    != Self::from_node(interpreter, scene, *b)?,
+            ),
+            ast::Node::Gt(a, b) => Self::Boolean(matches!(
+                cmp_inner!(
+                    Self::from_node(interpreter, scene, *a)?,
+                    Self::from_node(interpreter, scene, *b)?,
+                    String,
+                    Number,
+                    Boolean,
+                ),
+                Some(Ordering::Greater),
+            )),
+            ast::Node::Lt(a, b) => Self::Boolean(matches!(
+                cmp_inner!(
+                    Self::from_node(interpreter, scene, *a)?,
+                    Self::from_node(interpreter, scene, *b)?,
+                    String,
+                    Number,
+                    Boolean,
+                ),
+                Some(Ordering::Less),
+            )),
+            ast::Node::GtEq(a, b) => {
+                let ordering = cmp_inner!(
+                    Self::from_node(interpreter, scene, *a)?,
+                    Self::from_node(interpreter, scene, *b)?,
+                    String,
+                    Number,
+                    Boolean,
+                );
+
+                Self::Boolean(match ordering {
+                    Some(Ordering::Greater) | Some(Ordering::Equal) => true,
+                    _ => false,
+                })
+            }
+            ast::Node::LtEq(a, b) => {
+                let ordering = cmp_inner!(
+                    Self::from_node(interpreter, scene, *a)?,
+                    Self::from_node(interpreter, scene, *b)?,
+                    String,
+                    Number,
+                    Boolean,
+                );
+
+                Self::Boolean(match ordering {
+                    Some(Ordering::Less) | Some(Ordering::Equal) => true,
+                    _ => false,
+                })
+            }
+            ast::Node::Neg(a) => match Self::from_node(interpreter, scene, *a)? {
+                Self::Number(n) => Self::Number(-n),
+                Self::Vector(v) => Self::Vector(-v),
+                _ => return Err(InterpretError::BadNegation),
+            },
+            ast::Node::Not(a) => match Self::from_node(interpreter, scene, *a)? {
+                Self::Boolean(a) => Self::Boolean(!a),
+                _ => return Err(InterpretError::BadLogic),
+            },
+            ast::Node::And(a, b) => Self::Boolean(match Self::from_node(interpreter, scene, *a)? {
+                Self::Boolean(a) => match Self::from_node(interpreter, scene, *b)? {
+                    Self::Boolean(b) => a && b,
+                    _ => return Err(InterpretError::BadLogic),
+                },
+                _ => return Err(InterpretError::BadLogic),
+            }),
+            ast::Node::Or(a, b) => Self::Boolean(match Self::from_node(interpreter, scene, *a)? {
+                Self::Boolean(a) => match Self::from_node(interpreter, scene, *b)? {
+                    Self::Boolean(b) => a || b,
+                    _ => return Err(InterpretError::BadLogic),
+                },
+                _ => return Err(InterpretError::BadLogic),
+            }),
+            _ => return Err(InterpretError::NonValueNode),
+        };
+
+        Ok(value)
+    }
+
+    fn from_nodes(
+        interpreter: &mut Interpreter,
+        scene: &mut Scene,
+        nodes: Vec<ast::Node>,
+    ) -> Result<Vec<Self>, InterpretError> {
+        let mut values = vec![];
+        for node in nodes.into_iter() {
+            values.push(Value::from_node(interpreter, scene, node)?);
+        }
+        Ok(values)
+    }
+
+    fn is_truthy(&self) -> bool {
+        match self {
+            Self::Boolean(b) => *b,
+            Self::Number(n) => n != &0. && !n.is_nan(),
+            Self::Unit => false,
+            _ => true,
+        }
+    }
+}
+
+/// Resolve an index into an array of length `len`, where negative indices count back from the end
+/// of the array. This is `None` for indices before the start of the array, but not for those past
+/// its end.
+fn array_index(index: Float, len: usize) -> Option<usize> {
+    let index = index.floor() as isize;
+    if index < 0 {
+        len.checked_sub(index.unsigned_abs())
+    } else {
+        Some(index as usize)
+    }
+}
+
+impl PartialEq<ast::NodeKind> for Value {
+    fn eq(&self, other: &ast::NodeKind) -> bool {
+        match (self, other) {
+            (_, ast::NodeKind::Any) => true,
+            (Self::Dictionary(_), ast::NodeKind::Dictionary) => true,
+            (Self::String(_), ast::NodeKind::String) => true,
+            (Self::Number(_), ast::NodeKind::Number) => true,
+            (Self::Vector(_), ast::NodeKind::Vector) => true,
+            (Self::Color(_), ast::NodeKind::Color) => true,
+            (Self::Boolean(_), ast::NodeKind::Boolean) => true,
+            (Self::Ref(_, kind), k) if kind == k => true,
+            _ => false,
+        }
+    }
+}
+
+/// A user-defined function.
+#[derive(Debug, Clone)]
+struct UserFunction {
+    params: Vec<ast::Param>,
+    body: Vec<ast::Node>,
+}
+
+/// A scope is a wrapper around a dictionary from identifier
+/// to AST node. The AST node is expected to be fully reduced.
+#[derive(Default)]
+struct Scope {
+    vars: HashMap<String, Value>,
+    funcs: HashMap<String, UserFunction>,
+}
+
+/// How running a statement changes which statement runs next.
+enum Flow {
+    /// Go on to the next statement.
+    Next,
+
+    /// Return a value from the function the statement is in.
+    Return(Value),
+
+    /// Break out of the loop the statement is in.
+    Break,
+
+    /// Skip to the next iteration of the loop the statement is in.
+    Continue,
+}
+
+/// The image cache, that is, a map between file names and loaded images.
+type ImageCache = HashMap<String, DynamicImage>;
+
+/// The mesh cache, that is, a map between file names and loaded meshes shared by instances,
+/// along with the other asset files each mesh was loaded from.
+type MeshCache = HashMap<String, (Arc<object::Mesh>, Vec<String>)>;
+
+/// The interpreter is the general runtime for the SDL interpreter. It is responsible for storing
+/// AST data, scene data, and interpreting the AST at scene construction time to develop the
+/// scene.
+pub struct Interpreter {
+    /// The root node.
+    root: ast::Node,
+
+    /// The image cache, so images do not have to be re-loaded each time their path is referenced.
+    images: ImageCache,
+
+    /// The mesh cache, so that every instance of a model shares one copy of its geometry.
+    meshes: MeshCache,
+
+    /// The scope stack.
+    scope_stack: Vec<Scope>,
+
+    /// Every asset file path (images, models, ...) the interpreter has loaded, in the order
+    /// they were first referenced.
+    assets: Vec<String>,
+
+    /// Every SDL file path spliced into the source by an include statement.
+    includes: Vec<String>,
+
+    /// A list of object names that have been declared.
+    object_names: Vec<String>,
+
+    /// The handle of the object most recently added to the scene, so the rest of its
+    /// declaration can wrap, name or take back the object.
+    last_object: Option<ObjectHandle>,
+
+    /// The handles of the objects declared so far, in order, so that SDL can refer to them by id.
+    object_handles: Vec<ObjectHandle>,
+
+    /// All reference objects, scattered in memory.
+    ref_objects: SlotMap<slotmap::DefaultKey, RefObject>,
+
+    /// All reference object references, reference-counted so they can be cleaned up when no longer referenced by anything.
+    refs: Vec<Rc<slotmap::DefaultKey>>,
+
+    /// The perlin noise generator for the interpreter.
+    perlin: Perlin,
+
+    /// The simplex noise generator for the interpreter.
+    simplex: OpenSimplex,
+
+    /// The directory mesh SBVHs are cached in, if any, so they are not rebuilt every run.
+    sbvh_cache: Option<PathBuf>,
+
+    /// Errors that didn't stop the scene from being constructed, like failing to cache an SBVH.
+    warnings: Vec<InterpretError>,
+}
+
+impl Interpreter {
+    /// Create a new interpreter. This will instantiate a `Tokenizer` and tokenize the input, as well
+    /// as instantiate an `AstParser` and parse the tokenized input. From there, the interpreter
+    /// can operate on the root AST node.
+    ///
+    /// Files included by the source are resolved against the working directory.
+    pub fn new<R: Read + Seek>(reader: R) -> Result<Self, InterpretError> {
+        Self::with_source(reader, Path::new(""), vec![])
+    }
+
+    /// Create a new interpreter from the SDL file at `path`. Files it includes are resolved
+    /// against the directory it is in.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, InterpretError> {
+        let path = path.as_ref();
+        let source_read = |e| InterpretError::SourceRead(path.display().to_string(), e);
+        let canonical = path.canonicalize().map_err(source_read)?;
+        let file = File::open(path).map_err(source_read)?;
+        let dir = canonical
+            .parent()
+            .unwrap_or_else(|| Path::new(""))
+            .to_owned();
+        Self::with_source(file, &dir, vec![canonical])
+    }
+
+    /// Create a new interpreter, where `dir` is the directory include paths are relative to and
+    /// `including` is the file being read, if it is known.
+    fn with_source<R: Read + Seek>(
+        reader: R,
+        dir: &Path,
+        mut including: Vec<PathBuf>,
+    ) -> Result<Self, InterpretError> {
+        // inject constants into the global namespace
+        let stack = vec![Scope {
+            vars: vec![
+                (String::from("PI"), Value::Number(consts::PI)),
+                (String::from("TAU"), Value::Number(consts::TAU)),
+                (String::from("E"), Value::Number(consts::E)),
+                (String::from("t"), Value::Number(0.)),
+            ]
+            .into_iter()
+            .collect(),
+            funcs: HashMap::new(),
+        }];
+
+        let mut includes = Vec::new();
+        let root = Self::parse(reader, 0, dir, &mut including, &mut includes)?;
+
+        Ok(Interpreter {
+            root: ast::Node::Root(root),
+            images: HashMap::new(),
+            meshes: HashMap::new(),
+            scope_stack: stack,
+            assets: Vec::new(),
+            includes,
+            object_names: Vec::new(),
+            last_object: None,
+            object_handles: Vec::new(),
+            ref_objects: SlotMap::new(),
+            refs: Vec::new(),
+            perlin: Perlin::new(),
+            simplex: OpenSimplex::new(),
+            sbvh_cache: None,
+            warnings: Vec::new(),
+        })
+    }
+
+    /// Tokenize and parse SDL source into the nodes of its root scope, splicing in the files it
+    /// includes. `file` is which file the source is (see [`Position::file`]), `including` is the
+    /// stack of files being included, to catch include cycles, and every file included is added
+    /// to `includes`.
+    fn parse<R: Read + Seek>(
+        reader: R,
+        file: usize,
+        dir: &Path,
+        including: &mut Vec<PathBuf>,
+        includes: &mut Vec<String>,
+    ) -> Result<Vec<ast::Node>, InterpretError> {
+        let tokens = Tokenizer::in_file(reader, file).tokenize()?;
+        match AstParser::new(tokens).parse_root()? {
+            ast::Node::Root(nodes) => Self::splice_includes(nodes, dir, including, includes),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Replace every include statement in `nodes`, and in the bodies of the statements and
+    /// functions in them, with the nodes of the file it includes.
+    fn splice_includes(
+        nodes: Vec<ast::Node>,
+        dir: &Path,
+        including: &mut Vec<PathBuf>,
+        includes: &mut Vec<String>,
+    ) -> Result<Vec<ast::Node>, InterpretError> {
+        macro_rules! splice {
+            ($body:expr) => {
+                Self::splice_includes($body, dir, including, includes)?
+            };
+        }
+
+        let mut spliced = Vec::with_capacity(nodes.len());
+        for node in nodes {
+            spliced.push(match node {
+                ast::Node::Include(path) => {
+                    let path = dir.join(path);
+                    let name = path.display().to_string();
+                    let source_read = |e| InterpretError::SourceRead(name.clone(), e);
+                    let canonical = path.canonicalize().map_err(source_read)?;
+                    if including.contains(&canonical) {
+                        return Err(InterpretError::IncludeCycle(name));
+                    }
+
+                    let file = File::open(&path).map_err(source_read)?;
+                    let dir = canonical
+                        .parent()
+                        .unwrap_or_else(|| Path::new(""))
+                        .to_owned();
+                    let index = match includes.iter().position(|include| *include == name) {
+                        Some(index) => index,
+                        None => {
+                            includes.push(name.clone());
+                            includes.len() - 1
+                        }
+                    };
+
+                    including.push(canonical);
+                    let nodes = Self::parse(file, index + 1, &dir, including, includes)
+                        .map_err(|e| e.in_file(&name))?;
+                    including.pop();
+
+                    spliced.extend(nodes);
+                    continue;
+                }
+                ast::Node::For {
+                    var,
+                    from,
+                    to,
+                    body,
+                } => ast::Node::For {
+                    var,
+                    from,
+                    to,
+                    body: splice!(body),
+                },
+                ast::Node::ForEach { var, array, body } => ast::Node::ForEach {
+                    var,
+                    array,
+                    body: splice!(body),
+                },
+                ast::Node::While { cond, body } => ast::Node::While {
+                    cond,
+                    body: splice!(body),
+                },
+                ast::Node::If {
+                    cond_bodies,
+                    else_body,
+                } => ast::Node::If {
+                    cond_bodies: cond_bodies
+                        .into_iter()
+                        .map(|(cond, body)| Ok((cond, splice!(body))))
+                        .collect::<Result<_, InterpretError>>()?,
+                    else_body: match else_body {
+                        Some(body) => Some(splice!(body)),
+                        None => None,
+                    },
+                },
+                ast::Node::Function { name, params, body } => ast::Node::Function {
+                    name,
+                    params,
+                    body: splice!(body),
+                },
+                node => node,
+            });
+        }
+
+        Ok(spliced)
+    }
+
+    /// Cache the SBVHs of meshes in the directory `dir`, or stop caching them if it is `None`.
+    pub fn set_sbvh_cache(&mut self, dir: Option<PathBuf>) {
+        self.sbvh_cache = dir;
+    }
+
+    /// Set a global variable (a var in the base of the scope stack).
+    pub fn set_global(&mut self, identifier: String, value: Value) {
+        self.scope_stack[0].vars.insert(identifier, value);
+    }
+
+    /// Get every asset file path the interpreter has loaded while constructing the scene.
+    pub fn assets(&self) -> &[String] {
+        &self.assets
+    }
+
+    /// Get the errors that didn't stop the last run from constructing the scene, like failing
+    /// to write an SBVH to the cache, so they can be reported.
+    pub fn warnings(&self) -> &[InterpretError] {
+        &self.warnings
+    }
+
+    /// Get every SDL file path the source includes, directly or through other included files.
+    /// Since they are part of the source, a new interpreter must be created if they change.
+    pub fn includes(&self) -> &[String] {
+        &self.includes
+    }
+
+    /// Invalidate any cached data loaded from the asset file at `path`, so that it is reloaded
+    /// from disk the next time the scene is constructed.
+    pub fn invalidate_asset(&mut self, path: &Path) {
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_owned());
+        let is_other = |file: &String| {
+            Path::new(file)
+                .canonicalize()
+                .unwrap_or_else(|_| PathBuf::from(file))
+                != path
+        };
+        self.images.retain(|file, _| is_other(file));
+        self.meshes
+            .retain(|file, (_, assets)| is_other(file) && assets.iter().all(is_other));
+    }
+
+    /// Generate the SBVH of a mesh, using the SBVH cache if there is one. The mesh gets its
+    /// SBVH even if caching it fails.
+    fn generate_sbvh(cache: Option<&Path>, mesh: &mut object::Mesh) -> Result<(), InterpretError> {
+        match cache {
+            Some(dir) => mesh
+                .generate_sbvh_cached(dir)
+                .map_err(|e| InterpretError::SbvhCache(dir.display().to_string(), e)),
+            None => {
+                mesh.generate_sbvh();
+                Ok(())
+            }
+        }
+    }
+
+    /// Record that an asset file path has been referenced.
+    fn track_asset(&mut self, path: &str) {
+        if !self.assets.iter().any(|a| a == path) {
+            self.assets.push(path.to_owned());
+        }
+    }
+
+    /// Start execution of the interpreter.
+    pub fn run(mut self) -> Result<Scene, InterpretError> {
+        let root = match self.root {
+            ast::Node::Root(root) => root,
+            _ => unreachable!(),
+        };
+
+        // this is so that `self` is not fully destructed
+        // and we can continue to pass it to methods
+        // that receive `&mut self`
+        self.root = ast::Node::Root(vec![]);
+
+        // instantiate a new scene
+        let mut scene = Scene::default();
+
+        // match nodes that can be in the root node
+        self.run_scope(&mut scene, root)
+            .map_err(|e| self.locate(e))?;
+
+        Ok(scene)
+    }
+
+    /// Start execution of the interpreter, cloning the root node.
+    /// Use this if you are going to continually reuse the interpreter.
+    pub fn run_cloned(&mut self) -> Result<Scene, InterpretError> {
+        let root = match self.root.clone() {
+            ast::Node::Root(root) => root,
+            _ => unreachable!(),
+        };
+
+        // generate a scene
+        let mut scene = Scene::default();
+
+        // reset state
+        self.object_names = vec![];
+        self.last_object = None;
+        self.object_handles = vec![];
+        self.assets = vec![];
+        self.warnings = vec![];
+        self.refs = vec![];
+        self.ref_objects = SlotMap::new();
+
+        // execute the scene
+        self.run_scope(&mut scene, root)
+            .map_err(|e| self.locate(e))?;
+
+        Ok(scene)
+    }
+
+    /// Attach the path of the included file an error happened in, if it wasn't the source.
+    fn locate(&self, e: InterpretError) -> InterpretError {
+        match e {
+            InterpretError::At(position, _) if position.file > 0 => {
+                e.in_file(&self.includes[position.file - 1])
+            }
+            e => e,
+        }
+    }
+
+    /// Run the statements of a scope, until one of them returns, breaks or continues.
+    fn run_scope(
+        &mut self,
+        scene: &mut Scene,
+        nodes: Vec<ast::Node>,
+    ) -> Result<Flow, InterpretError> {
+        let mut position = None;
+        for node in nodes.into_iter() {
+            if let ast::Node::Position(p) = node {
+                position = Some(p);
+                continue;
+            }
+
+            match self.run_statement(scene, node) {
+                Ok(Flow::Next) => (),
+                Ok(flow) => return Ok(flow),
+                Err(e) => return Err(e.at(position)),
+            }
+        }
+
+        Ok(Flow::Next)
+    }
+
+    /// Run a single statement.
+    fn run_statement(
+        &mut self,
+        scene: &mut Scene,
+        node: ast::Node,
+    ) -> Result<Flow, InterpretError> {
+        match node {
+            ast::Node::Assign {
+                name,
+                declare,
+                value,
+            } => {
+                let value = Value::from_node(self, scene, *value)?;
+                if declare {
+                    // set in the top-most stack
+                    self.scope_stack
+                        .last_mut()
+                        .unwrap()
+                        .vars
+                        .insert(name, value);
+                } else {
+                    // assign to existing variable in nearest scope, or
+                    // set it globally
+                    for (i, scope) in self.scope_stack.iter_mut().enumerate().rev() {
+                        match scope.vars.entry(name.clone()) {
+                            Entry::Occupied(mut ent) => {
+                                ent.insert(value);
+                                break;
+                            }
+                            Entry::Vacant(ent) if i == 0 => {
+                                ent.insert(value);
+                                break;
+                            }
+                            _ => (),
+                        }
+                    }
+                }
+            }
+            ast::Node::DictAssign { name, path, value } => {
+                let value = Value::from_node(self, scene, *value)?;
+
+                // find the variable in the nearest scope, and follow the path of keys to
+                // the dictionary to set the last key of
+                let mut dict = self
+                    .scope_stack
+                    .iter_mut()
+                    .rev()
+                    .find_map(|scope| scope.vars.get_mut(&name))
+                    .ok_or(InterpretError::UndefinedVariable(name))?;
+                let (last, keys) = path.split_last().unwrap();
+                for key in keys {
+                    dict = match dict {
+                        Value::Dictionary(m) => m
+                            .get_mut(key)
+                            .ok_or_else(|| InterpretError::UnknownKey(key.clone()))?,
+                        _ => return Err(InterpretError::NonDictionaryValue),
+                    };
+                }
+
+                match dict {
+                    Value::Dictionary(m) => {
+                        m.insert(last.clone(), value);
+                    }
+                    _ => return Err(InterpretError::NonDictionaryValue),
+                }
+            }
+            ast::Node::For {
+                var,
+                from,
+                to,
+                body,
+            } => {
+                let from = unwrap_variant!(Value::from_node(self, scene, *from)?, Value::Number)
+                    .floor() as i32;
+                let to = unwrap_variant!(Value::from_node(self, scene, *to)?, Value::Number).floor()
+                    as i32;
+
+                for i in from..to {
+                    // push a new scope to the stack with the index variable
+                    self.scope_stack.push(Scope {
+                        vars: vec![(var.clone(), Value::Number(i as Float))]
+                            .into_iter()
+                            .collect(),
+                        funcs: HashMap::new(),
+                    });
+
+                    // run the scope body
+                    let flow = self.run_scope(scene, body.clone())?;
+
+                    // pop the scope from the stack
+                    self.pop_scope();
+
+                    match flow {
+                        Flow::Break => break,
+                        Flow::Return(value) => return Ok(Flow::Return(value)),
+                        Flow::Next | Flow::Continue => (),
+                    }
+                }
+            }
+            ast::Node::ForEach { var, array, body } => {
+                // loop over a copy of the items, so the body can change the array
+                let items = match Value::from_node(self, scene, *array)? {
+                    Value::Ref(key, NodeKind::Array) => match self.ref_objects.get(*key) {
+                        Some(RefObject::Array(a)) => a.clone(),
+                        None => return Err(InterpretError::InvalidReference),
+                    },
+                    _ => return Err(InterpretError::NonArrayLoop),
+                };
+
+                for item in items {
+                    self.scope_stack.push(Scope {
+                        vars: vec![(var.clone(), item)].into_iter().collect(),
+                        funcs: HashMap::new(),
+                    });
+                    let flow = self.run_scope(scene, body.clone())?;
+                    self.pop_scope();
+
+                    match flow {
+                        Flow::Break => break,
+                        Flow::Return(value) => return Ok(Flow::Return(value)),
+                        Flow::Next | Flow::Continue => (),
+                    }
+                }
+            }
+            ast::Node::While { cond, body } => {
+                while Value::from_node(self, scene, (*cond).clone())?.is_truthy() {
+                    self.scope_stack.push(Scope::default());
+                    let flow = self.run_scope(scene, body.clone())?;
+                    self.pop_scope();
+
+                    match flow {
+                        Flow::Break => break,
+                        Flow::Return(value) => return Ok(Flow::Return(value)),
+                        Flow::Next | Flow::Continue => (),
+                    }
+                }
+            }
+            ast::Node::If {
+                cond_bodies,
+                else_body,
+            } => {
+                let mut run_body = None;
+                for (cond, body) in cond_bodies.into_iter() {
+                    if Value::from_node(self, scene, *cond)?.is_truthy() {
+                        let _ = run_body.insert(body);
+                        break;
+                    }
+                }
+                if let Some(body) = run_body.or(else_body) {
+                    self.scope_stack.push(Scope::default());
+                    let flow = self.run_scope(scene, body)?;
+                    self.pop_scope();
+
+                    // a break, continue or return in the body applies to the loop or function
+                    // this is in
+                    return Ok(flow);
+                }
+            }
+            ast::Node::Function { name, params, body } => {
+                self.scope_stack
+                    .last_mut()
+                    .unwrap()
+                    .funcs
+                    .insert(name, UserFunction { params, body });
+            }
+            ast::Node::Return(value) => {
+                return Ok(Flow::Return(Value::from_node(self, scene, *value)?));
+            }
+            ast::Node::Break => return Ok(Flow::Break),
+            ast::Node::Continue => return Ok(Flow::Continue),
+            ast::Node::Call(name, args) => {
+                self.call_func(scene, name, args)?;
+            }
+            ast::Node::Object {
+                name,
+                mut properties,
+            } => {
+                let object_count = scene.objects.len();
+                let light_count = scene.lights.len();
+
+                match name.as_str() {
+                    // one-time scene properties
+                    "scene" => {
+                        if self.object_names.iter().any(|n| n.as_str() == "scene") {
+                            return Err(InterpretError::NonUniqueObject("scene"));
+                        }
+
+                        let max_ray_depth =
+                            optional_property!(self, scene, properties, "max_ray_depth", Number)
+                                .map(|f| f as u32);
+                        let ambient = optional_property!(self, scene, properties, "ambient", Color);
+
+                        if let Some(mrd) = max_ray_depth {
+                            scene.options.max_ray_depth = mrd;
+                        }
+
+                        if let Some(ambient) = ambient {
+                            scene.options.ambient = ambient;
+                        }
+
+                        if let Some(linear_workflow) =
+                            optional_property!(self, scene, properties, "linear_workflow", Boolean)
+                        {
+                            scene.options.linear_workflow = linear_workflow;
+                        }
+
+                        if let Some(bias) =
+                            optional_property!(self, scene, properties, "shadow_bias", Number)
+                        {
+                            scene.options.shadow_bias = bias;
+                        }
+
+                        if let Some(scale) =
+                            optional_property!(self, scene, properties, "shadow_bias_scale", Number)
+                        {
+                            scene.options.shadow_bias_scale = scale;
+                        }
+
+                        if let Some(depth) =
+                            optional_property!(self, scene, properties, "bit_depth", Number)
+                        {
+                            scene.options.bit_depth = match depth as u32 {
+                                8 => BitDepth::Eight,
+                                16 => BitDepth::Sixteen,
+                                _ => return Err(InterpretError::UnsupportedBitDepth(depth)),
+                            };
+                        }
+                    }
+                    "camera" => {
+                        if self.object_names.iter().any(|n| n.as_str() == "camera") {
+                            return Err(InterpretError::NonUniqueObject("camera"));
+                        }
+
+                        let vw = optional_property!(self, scene, properties, "vw", Number)
+                            .map(|f| f as i32);
+                        let vh = optional_property!(self, scene, properties, "vh", Number)
+                            .map(|f| f as i32);
+                        let origin = optional_property!(self, scene, properties, "origin", Vector);
+                        let yaw = optional_property!(self, scene, properties, "yaw", Number);
+                        let pitch = optional_property!(self, scene, properties, "pitch", Number);
+                        let fov = optional_property!(self, scene, properties, "fov", Number);
+                        let stereo = optional_property!(self, scene, properties, "stereo", String);
+                        let ipd = optional_property!(self, scene, properties, "ipd", Number);
+                        let k1 = optional_property!(self, scene, properties, "k1", Number);
+                        let k2 = optional_property!(self, scene, properties, "k2", Number);
+
+                        if let Some(vw) = vw {
+                            scene.camera.vw = vw;
+                        }
+                        if let Some(vh) = vh {
+                            scene.camera.vh = vh;
+                        }
+                        if let Some(origin) = origin {
+                            scene.camera.origin = origin;
+                        }
+                        if let Some(yaw) = yaw {
+                            scene.camera.yaw = yaw;
+                        }
+                        if let Some(pitch) = pitch {
+                            scene.camera.pitch = pitch;
+                        }
+                        if let Some(fov) = fov {
+                            scene.camera.set_fov(fov);
+                        }
+                        if let Some(stereo) = stereo {
+                            let default = camera::Stereo::default();
+                            let layout = match stereo.as_str() {
+                                "side_by_side" => camera::StereoLayout::SideBySide,
+                                "top_bottom" => camera::StereoLayout::TopBottom,
+                                _ => return Err(InterpretError::UnknownStereoLayout(stereo)),
+                            };
+
+                            scene.camera.stereo = Some(camera::Stereo {
+                                ipd: ipd.unwrap_or(default.ipd),
+                                layout,
+                            });
+                        }
+                        if k1.is_some() || k2.is_some() {
+                            scene.camera.distortion = Some(camera::Distortion {
+                                k1: k1.unwrap_or(0.),
+                                k2: k2.unwrap_or(0.),
+                            });
+                        }
+                    }
+                    "skybox" => {
+                        if self.object_names.iter().any(|n| n.as_str() == "skybox") {
+                            return Err(InterpretError::NonUniqueObject("skybox"));
+                        }
+
+                        let t = required_property!(self, scene, properties, "type", String);
+
+                        match t.as_str() {
+                            "normal" => scene.skybox = Box::new(skybox::Normal),
+                            "solid" => {
+                                let color =
+                                    required_property!(self, scene, properties, "color", Color);
+                                scene.skybox = Box::new(skybox::Solid(color));
+                            }
+                            "gradient" => {
+                                let default = skybox::Gradient::default();
+                                let zenith =
+                                    optional_property!(self, scene, properties, "zenith", Color);
+                                let horizon =
+                                    optional_property!(self, scene, properties, "horizon", Color);
+                                let ground =
+                                    optional_property!(self, scene, properties, "ground", Color);
+                                scene.skybox = Box::new(skybox::Gradient {
+                                    zenith: zenith.unwrap_or(default.zenith),
+                                    horizon: horizon.unwrap_or(default.horizon),
+                                    ground: ground.unwrap_or(default.ground),
+                                });
+                            }
+                            "atmosphere" => {
+                                let default = skybox::Atmosphere::default();
+                                let vector =
+                                    required_property!(self, scene, properties, "vector", Vector)
+                                        .normalize();
+                                let turbidity = optional_property!(
+                                    self,
+                                    scene,
+                                    properties,
+                                    "turbidity",
+                                    Number
+                                );
+                                let exposure =
+                                    optional_property!(self, scene, properties, "exposure", Number);
+                                let sun_radius = optional_property!(
+                                    self,
+                                    scene,
+                                    properties,
+                                    "sun_radius",
+                                    Number
+                                );
+                                let ground =
+                                    optional_property!(self, scene, properties, "ground", Color);
+                                scene.skybox = Box::new(skybox::Atmosphere {
+                                    vector,
+                                    turbidity: turbidity.unwrap_or(default.turbidity),
+                                    exposure: exposure.unwrap_or(default.exposure),
+                                    sun_radius: sun_radius.unwrap_or(default.sun_radius),
+                                    ground: ground.unwrap_or(default.ground),
+                                });
+                            }
+                            "cubemap" => {
+                                let image =
+                                    optional_property!(self, scene, properties, "image", String);
+                                let faces =
+                                    optional_property!(self, scene, properties, "faces", Array);
+
+                                let cubemap = match (image, faces) {
+                                    (Some(filename), _) => {
+                                        skybox::Cubemap::new(self.load_image(filename)?.to_rgb8())
+                                    }
+                                    (None, Some(faces)) => {
+                                        let faces = faces
+                                            .into_iter()
+                                            .map(|face| match face {
+                                                Value::String(filename) => {
+                                                    Ok(self.load_image(filename)?.to_rgb8())
+                                                }
+                                                _ => Err(InterpretError::InvalidCubemapFaces),
+                                            })
+                                            .collect::<Result<Vec<_>, _>>()?;
+                                        let size = faces.first().map(|f| f.dimensions());
+                                        let square = faces.iter().all(|f| {
+                                            Some(f.dimensions()) == size && f.width() == f.height()
+                                        });
+                                        let [xpos, xneg, ypos, yneg, zpos, zneg] =
+                                            <[_; 6]>::try_from(faces)
+                                                .ok()
+                                                .filter(|_| square)
+                                                .ok_or(InterpretError::InvalidCubemapFaces)?;
+                                        skybox::Cubemap::from_faces(
+                                            xpos, xneg, ypos, yneg, zpos, zneg,
+                                        )
+                                    }
+                                    (None, None) => {
+                                        return Err(InterpretError::RequiredPropertyMissing(
+                                            "image",
+                                        ))
+                                    }
+                                };
+
+                                scene.skybox = Box::new(cubemap);
+                            }
+                            _ => return Err(InterpretError::InvalidMaterials),
+                        }
+
+                        // any skybox can be spun around to frame the background
+                        let yaw = optional_property!(self, scene, properties, "yaw", Number);
+                        let rotation =
+                            optional_property!(self, scene, properties, "rotate_xyz", Vector);
+                        if yaw.is_some() || rotation.is_some() {
+                            let rotation = rotation.unwrap_or_default();
+                            let rotation = Matrix::from_euler_xyz(
+                                rotation.x,
+                                rotation.y + yaw.unwrap_or(0.),
+                                rotation.z,
+                            );
+                            let sky =
+                                std::mem::replace(&mut scene.skybox, Box::new(skybox::Normal));
+                            scene.skybox = Box::new(skybox::Rotated::new(sky, rotation));
+                        }
+
+                        // and balanced against the scene's lights
+                        if let Some(intensity) =
+                            optional_property!(self, scene, properties, "intensity", Number)
+                        {
+                            let sky =
+                                std::mem::replace(&mut scene.skybox, Box::new(skybox::Normal));
+                            scene.skybox = Box::new(skybox::Scaled::new(sky, intensity));
+                        }
+                    }
+
+                    // objects
+                    "aabb" | "box" => {
+                        let pos = required_property!(self, scene, properties, "position", Vector);
+                        let size = required_property!(self, scene, properties, "size", Vector);
+                        let material = self.read_material(scene, &mut properties)?;
+
+                        self.add_object(scene, Box::new(object::Aabb::new(pos, size, material)));
+                    }
+                    "mesh" => {
+                        let transform = self.read_placement(scene, &mut properties)?;
+                        let material = self.read_material(scene, &mut properties)?;
+
+                        let mut mesh = if properties.contains_key("obj") {
+                            let obj = required_property!(self, scene, properties, "obj", String);
+                            self.track_asset(&obj);
+
+                            let use_mtl =
+                                optional_property!(self, scene, properties, "use_mtl", Boolean)
+                                    .unwrap_or(true);
+                            let (mesh, assets) =
+                                object::Mesh::from_obj_with_assets(obj, material, |_, mtl| {
+                                    use_mtl.then_some(mtl)
+                                });
+                            for asset in assets {
+                                self.track_asset(&asset.display().to_string());
+                            }
+                            mesh
+                        } else if properties.contains_key("ply") {
+                            let ply = required_property!(self, scene, properties, "ply", String);
+                            self.track_asset(&ply);
+
+                            object::Mesh::from_ply(ply.clone(), material)
+                                .map_err(|e| InterpretError::MeshLoad(ply, e))?
+                        } else {
+                            let mut mesh = object::Mesh::new(material);
+
+                            let verts = required_property!(self, scene, properties, "verts", Array)
+                                .into_iter()
+                                .filter_map(|v| match v {
+                                    Value::Vector(v) => Some(v),
+                                    _ => None,
+                                })
+                                .collect::<Vec<_>>();
+
+                            let mut tris =
+                                required_property!(self, scene, properties, "tris", Array)
+                                    .into_iter()
+                                    .filter_map(|v| match v {
+                                        Value::Number(n) => Some(n as usize),
+                                        _ => None,
+                                    });
+
+                            let mut normals = Vec::new();
+                            let mut normals_grouped = Vec::new();
+                            if properties.contains_key("normals") {
+                                normals =
+                                    required_property!(self, scene, properties, "normals", Array)
+                                        .into_iter()
+                                        .filter_map(|v| match v {
+                                            Value::Vector(v) => Some(v),
+                                            _ => None,
+                                        })
+                                        .collect::<Vec<_>>();
+
+                                let mut normal_indices = required_property!(
+                                    self,
+                                    scene,
+                                    properties,
+                                    "normal_indices",
+                                    Array
+                                )
+                                .into_iter()
+                                .filter_map(|v| match v {
+                                    Value::Number(n) => Some(n as usize),
+                                    _ => None,
+                                });
+
+                                loop {
+                                    let v0 = match normal_indices.next() {
+                                        Some(v) => v,
+                                        None => break,
+                                    };
+                                    let v1 = match normal_indices.next() {
+                                        Some(v) => v,
+                                        None => break,
+                                    };
+                                    let v2 = match normal_indices.next() {
+                                        Some(v) => v,
+                                        None => break,
+                                    };
+                                    normals_grouped.push([v0, v1, v2]);
+                                }
+                            }
+
+                            let mut tris_grouped = Vec::new();
+                            loop {
+                                let v0 = match tris.next() {
+                                    Some(v) => v,
+                                    None => break,
+                                };
+                                let v1 = match tris.next() {
+                                    Some(v) => v,
+                                    None => break,
+                                };
+                                let v2 = match tris.next() {
+                                    Some(v) => v,
+                                    None => break,
+                                };
+                                tris_grouped.push([v0, v1, v2]);
+                            }
+
+                            if tris_grouped.is_empty() {
+                                return Ok(Flow::Next);
+                            }
+
+                            mesh.verts = verts;
+                            mesh.tris = tris_grouped;
+                            mesh.normals = normals;
+                            mesh.tri_normals = normals_grouped;
+
+                            mesh
+                        };
+
+                        if transform.scale != 1. {
+                            mesh.scale(transform.scale);
+                        }
+
+                        // recentering happens between scaling and the rest of the placement
+                        if optional_property!(self, scene, properties, "recenter", Boolean)
+                            .unwrap_or(true)
+                        {
+                            mesh.center();
+                        }
+
+                        mesh.transform(Transform {
+                            scale: 1.,
+                            ..transform
+                        });
+
+                        // parts are picked out after the whole model is placed, so they stay
+                        // where they were in it
+                        let part = match self.optional_property(
+                            scene,
+                            &mut properties,
+                            "part",
+                            ast::NodeKind::Any,
+                        )? {
+                            Some(Value::Ref(key, _)) => {
+                                self.ref_objects.get(*key).cloned().map(Value::from)
+                            }
+                            part => part,
+                        };
+                        let parts = match part {
+                            Some(Value::String(part)) => Some(vec![part]),
+                            Some(Value::Array(parts)) => Some(
+                                parts
+                                    .into_iter()
+                                    .map(|part| match part {
+                                        Value::String(part) => Ok(part),
+                                        _ => Err(InterpretError::InvalidMeshPart),
+                                    })
+                                    .collect::<Result<Vec<_>, _>>()?,
+                            ),
+                            Some(_) => return Err(InterpretError::InvalidMeshPart),
+                            None => None,
+                        };
+                        if let Some(parts) = parts {
+                            if let Some(missing) = parts.iter().find(|p| mesh.part(p).is_none()) {
+                                return Err(InterpretError::UnknownMeshPart(missing.clone()));
+                            }
+                            mesh.retain_parts(
+                                &parts.iter().map(String::as_str).collect::<Vec<_>>(),
+                            );
+                        }
+
+                        let displace =
+                            optional_property!(self, scene, properties, "displace", Number);
+                        if let Some(amplitude) = displace {
+                            let frequency = optional_property!(
+                                self,
+                                scene,
+                                properties,
+                                "displace_frequency",
+                                Number
+                            )
+                            .unwrap_or(1.);
+                            let seed = optional_property!(
+                                self,
+                                scene,
+                                properties,
+                                "displace_seed",
+                                Number
+                            )
+                            .unwrap_or(0.);
+                            mesh.displace_noise(amplitude, frequency, seed as u32);
+                        }
+
+                        if mesh.normals.is_empty()
+                            || displace.is_some()
+                            || optional_property!(
+                                self,
+                                scene,
+                                properties,
+                                "recalculate_normals",
+                                Boolean
+                            )
+                            .unwrap_or(false)
+                        {
+                            mesh.recalculate_normals();
+                        }
+
+                        if let Some(shading) =
+                            optional_property!(self, scene, properties, "shading", String)
+                        {
+                            mesh.flat_shading = match shading.as_str() {
+                                "smooth" => false,
+                                "flat" => true,
+                                _ => return Err(InterpretError::UnknownShading(shading)),
+                            };
+                        }
+
+                        mesh.backface_culling = optional_property!(
+                            self,
+                            scene,
+                            properties,
+                            "backface_culling",
+                            Boolean
+                        )
+                        .unwrap_or(false);
+
+                        if let Some(materials) = properties.remove("materials") {
+                            let materials = match materials {
+                                ast::Node::Array(materials) => materials,
+                                _ => return Err(InterpretError::InvalidMaterials),
+                            };
+                            for material in materials {
+                                let material = self.material_from_node(scene, material)?;
+                                mesh.add_material(material);
+                            }
+
+                            let tri_materials =
+                                required_property!(self, scene, properties, "tri_materials", Array);
+                            for (tri, index) in
+                                tri_materials.into_iter().take(mesh.tris.len()).enumerate()
+                            {
+                                match index {
+                                    Value::Number(n) if n >= 0. => {
+                                        mesh.set_tri_material(tri, Some(n as usize))
+                                    }
+                                    _ => mesh.set_tri_material(tri, None),
+                                }
+                            }
+                        }
+
+                        let accelerator =
+                            optional_property!(self, scene, properties, "accelerator", String)
+                                .unwrap_or_else(|| String::from("sbvh"));
+                        match accelerator.as_str() {
+                            "sbvh" => {
+                                if let Err(e) =
+                                    Self::generate_sbvh(self.sbvh_cache.as_deref(), &mut mesh)
+                                {
+                                    self.warnings.push(e);
+                                }
+                            }
+                            "kdtree" => mesh.generate_kdtree(),
+                            #[cfg(feature = "embree")]
+                            "embree" => mesh.generate_embree(),
+                            _ => return Err(InterpretError::UnknownAccelerator(accelerator)),
+                        }
+                        self.add_object(scene, Box::new(mesh));
+                    }
+                    "instance" => {
+                        let obj = required_property!(self, scene, properties, "obj", String);
+                        let transform = self.read_placement(scene, &mut properties)?;
+                        let material = self.read_material(scene, &mut properties)?;
+                        self.track_asset(&obj);
+
+                        let cache = self.sbvh_cache.as_deref();
+                        let warnings = &mut self.warnings;
+                        let (mesh, assets) = self
+                            .meshes
+                            .entry(obj)
+                            .or_insert_with_key(|obj| {
+                                let (mut mesh, assets) = object::Mesh::from_obj_with_assets(
+                                    obj.clone(),
+                                    Material::default(),
+                                    |_, mtl| Some(mtl),
+                                );
+                                mesh.center();
+                                if mesh.normals.is_empty() {
+                                    mesh.recalculate_normals();
+                                }
+                                if let Err(e) = Self::generate_sbvh(cache, &mut mesh) {
+                                    warnings.push(e);
+                                }
+                                let assets = assets.iter().map(|a| a.display().to_string());
+                                (Arc::new(mesh), assets.collect())
+                            })
+                            .clone();
+                        for asset in assets {
+                            self.track_asset(&asset);
+                        }
+
+                        self.add_object(
+                            scene,
+                            Box::new(
+                                object::Instance::new(mesh, material).with_transform(transform),
+                            ),
+                        );
+                    }
+                    "sdf" => {
+                        let shape = match properties.remove("shape") {
+                            Some(node) => self.read_distance(scene, node)?,
+                            None => return Err(InterpretError::RequiredPropertyMissing("shape")),
+                        };
+                        let max_steps =
+                            optional_property!(self, scene, properties, "max_steps", Number);
+                        let max_distance =
+                            optional_property!(self, scene, properties, "max_distance", Number);
+                        let threshold =
+                            optional_property!(self, scene, properties, "threshold", Number);
+                        let material = self.read_material(scene, &mut properties)?;
+
+                        let mut sdf = object::Sdf::new(shape, material);
+                        if let Some(max_steps) = max_steps {
+                            sdf = sdf.with_max_steps(max_steps as u32);
+                        }
+                        if let Some(max_distance) = max_distance {
+                            sdf = sdf.with_max_distance(max_distance);
+                        }
+                        if let Some(threshold) = threshold {
+                            sdf = sdf.with_threshold(threshold);
+                        }
+
+                        self.add_object(scene, Box::new(sdf));
+                    }
+                    "metaballs" => {
+                        let balls = required_property!(self, scene, properties, "balls", Array)
+                            .into_iter()
+                            .map(|ball| {
+                                let ball = match ball {
+                                    Value::Dictionary(ball) => ball,
+                                    _ => return Err(InterpretError::InvalidMetaball),
+                                };
+                                match (ball.get("position"), ball.get("radius"), ball.get("weight"))
+                                {
+                                    (
+                                        Some(Value::Vector(position)),
+                                        Some(Value::Number(radius)),
+                                        None,
+                                    ) => Ok(object::Metaball::new(*position, *radius, 1.)),
+                                    (
+                                        Some(Value::Vector(position)),
+                                        Some(Value::Number(radius)),
+                                        Some(Value::Number(weight)),
+                                    ) => Ok(object::Metaball::new(*position, *radius, *weight)),
+                                    _ => Err(InterpretError::InvalidMetaball),
+                                }
+                            })
+                            .collect::<Result<Vec<_>, _>>()?;
+                        let threshold =
+                            optional_property!(self, scene, properties, "threshold", Number)
+                                .unwrap_or(0.5);
+                        let steps_per_radius =
+                            optional_property!(self, scene, properties, "steps_per_radius", Number);
+                        let material = self.read_material(scene, &mut properties)?;
+
+                        let mut metaballs = object::Metaballs::new(balls, threshold, material);
+                        if let Some(steps) = steps_per_radius {
+                            metaballs = metaballs.with_steps_per_radius(steps as u32);
+                        }
+
+                        self.add_object(scene, Box::new(metaballs));
+                    }
+                    "disk" => {
+                        let origin = required_property!(self, scene, properties, "origin", Vector);
+                        let normal = optional_property!(self, scene, properties, "normal", Vector)
+                            .unwrap_or_else(Vector3::up);
+                        let radius = required_property!(self, scene, properties, "radius", Number);
+                        let inner_radius =
+                            optional_property!(self, scene, properties, "inner_radius", Number)
+                                .unwrap_or(0.);
+                        let material = self.read_material(scene, &mut properties)?;
+
+                        self.add_object(
+                            scene,
+                            Box::new(
+                                object::Disk::new(origin, normal, radius, material)
+                                    .with_inner_radius(inner_radius),
+                            ),
+                        );
+                    }
+                    "quad" => {
+                        let material = self.read_material(scene, &mut properties)?;
+
+                        // either a corner and two edges, or corners like a rectangle area light
+                        let quad = if properties.contains_key("c00") {
+                            object::Quad::from_corners(
+                                [
+                                    required_property!(self, scene, properties, "c00", Vector),
+                                    required_property!(self, scene, properties, "c01", Vector),
+                                    required_property!(self, scene, properties, "c10", Vector),
+                                    Vector3::default(),
+                                ],
+                                material,
+                            )
+                        } else {
+                            object::Quad::new(
+                                required_property!(self, scene, properties, "corner", Vector),
+                                required_property!(self, scene, properties, "edge_u", Vector),
+                                required_property!(self, scene, properties, "edge_v", Vector),
+                                material,
+                            )
+                        };
+
+                        self.add_object(scene, Box::new(quad));
+                    }
+                    "plane" => {
+                        let origin = required_property!(self, scene, properties, "origin", Vector);
+                        let normal = optional_property!(self, scene, properties, "normal", Vector)
+                            .unwrap_or_else(|| Vector3::new(0., 1., 0.))
+                            .normalize();
+                        let uv_wrap =
+                            optional_property!(self, scene, properties, "uv_wrap", Number)
+                                .map(|f| f as f32)
+                                .unwrap_or(1.);
+                        let material = self.read_material(scene, &mut properties)?;
+
+                        self.add_object(
+                            scene,
+                            Box::new(object::Plane {
+                                origin,
+                                normal,
+                                material,
+                                uv_wrap,
+                            }),
+                        );
+                    }
+                    "sphere" => {
+                        let pos = required_property!(self, scene, properties, "position", Vector);
+                        let radius = required_property!(self, scene, properties, "radius", Number);
+                        let material = self.read_material(scene, &mut properties)?;
+
+                        self.add_object(
+                            scene,
+                            Box::new(object::Sphere::new(pos, radius, material)),
+                        );
+                    }
+                    "cone" => {
+                        let pos = required_property!(self, scene, properties, "position", Vector);
+                        let radius = required_property!(self, scene, properties, "radius", Number);
+                        let height = required_property!(self, scene, properties, "height", Number);
+                        let top_radius =
+                            optional_property!(self, scene, properties, "top_radius", Number)
+                                .unwrap_or(0.);
+                        let axis = optional_property!(self, scene, properties, "axis", Vector)
+                            .unwrap_or_else(Vector3::up);
+                        let material = self.read_material(scene, &mut properties)?;
+
+                        self.add_object(
+                            scene,
+                            Box::new(
+                                object::Cone::new(pos, radius, height, material)
+                                    .with_axis(axis)
+                                    .with_top_radius(top_radius),
+                            ),
+                        );
+                    }
+
+                    "triangle" => {
+                        let verts = required_property!(self, scene, properties, "verts", Array);
+                        let normals = optional_property!(self, scene, properties, "normals", Array);
+                        let uvs = optional_property!(self, scene, properties, "uvs", Array);
+                        let colors = optional_property!(self, scene, properties, "colors", Array);
+                        let material = self.read_material(scene, &mut properties)?;
+
+                        let vectors = |name, values: Vec<Value>| {
+                            let vectors = values
+                                .into_iter()
+                                .filter_map(|v| match v {
+                                    Value::Vector(v) => Some(v),
+                                    _ => None,
+                                })
+                                .collect::<Vec<_>>();
+                            <[Vector3; 3]>::try_from(vectors).map_err(|_| {
+                                InterpretError::InvalidTriangleProperty(name, "vectors")
+                            })
+                        };
+
+                        let mut triangle =
+                            object::Triangle::new(vectors("verts", verts)?, material);
+                        if let Some(normals) = normals {
+                            triangle = triangle.with_normals(vectors("normals", normals)?);
+                        }
+                        if let Some(uvs) = uvs {
+                            let uvs = vectors("uvs", uvs)?;
+                            triangle = triangle
+                                .with_uvs(uvs.map(|uv| Vector2::new(uv.x as f32, uv.y as f32)));
+                        }
+                        if let Some(colors) = colors {
+                            let colors = colors
+                                .into_iter()
+                                .filter_map(|v| match v {
+                                    Value::Color(c) => Some(c),
+                                    _ => None,
+                                })
+                                .collect::<Vec<_>>();
+                            let colors = <[Color; 3]>::try_from(colors).map_err(|_| {
+                                InterpretError::InvalidTriangleProperty("colors", "colors")
+                            })?;
+                            triangle = triangle.with_colors(colors);
+                        }
+
+                        self.add_object(scene, Box::new(triangle));
+                    }
+
+                    "csg" => {
+                        let operation =
+                            match required_property!(self, scene, properties, "operation", String)
+                                .as_str()
+                            {
+                                "union" => object::CsgOperation::Union,
+                                "intersection" => object::CsgOperation::Intersection,
+                                "difference" => object::CsgOperation::Difference,
+                                op => {
+                                    return Err(InterpretError::UnknownCsgOperation(op.to_owned()))
+                                }
+                            };
+                        let a = self.csg_operand(scene, properties.remove("a"))?;
+                        let b = self.csg_operand(scene, properties.remove("b"))?;
+                        let material = self.read_material(scene, &mut properties)?;
+
+                        self.add_object(
+                            scene,
+                            Box::new(object::Csg::new(operation, a, b, material)),
+                        );
+                    }
+
+                    // lights
+                    "point_light" | "pointlight" => {
+                        let default = lighting::Point::default();
+
+                        let color = optional_property!(self, scene, properties, "color", Color);
+                        let intensity =
+                            optional_property!(self, scene, properties, "intensity", Number);
+                        let specular_strength = optional_property!(
+                            self,
+                            scene,
+                            properties,
+                            "specular_strength",
+                            Number
+                        );
+                        let position =
+                            required_property!(self, scene, properties, "position", Vector);
+                        let max_distance =
+                            optional_property!(self, scene, properties, "max_distance", Number);
+
+                        let radius = optional_property!(self, scene, properties, "radius", Number);
+                        let samples =
+                            optional_property!(self, scene, properties, "samples", Number);
+
+                        let attenuation = self.read_attenuation(scene, &mut properties)?;
+
+                        let light = lighting::Point {
+                            color: color.unwrap_or(default.color),
+                            intensity: intensity.unwrap_or(default.intensity),
+                            specular_strength: specular_strength
+                                .unwrap_or(default.specular_strength),
+                            position,
+                            max_distance: max_distance.unwrap_or(default.max_distance),
+                            attenuation: attenuation.unwrap_or(default.attenuation),
+                            radius: radius.unwrap_or(default.radius),
+                            samples: samples.map(|f| f as u32).unwrap_or(default.samples),
+                        };
+
+                        scene.lights.push(Box::new(light));
+                    }
+                    "spot_light" | "spotlight" => {
+                        let default = lighting::Spot::default();
+
+                        let color = optional_property!(self, scene, properties, "color", Color);
+                        let intensity =
+                            optional_property!(self, scene, properties, "intensity", Number);
+                        let specular_strength = optional_property!(
+                            self,
+                            scene,
+                            properties,
+                            "specular_strength",
+                            Number
+                        );
+                        let position =
+                            required_property!(self, scene, properties, "position", Vector);
+                        let direction = match (
+                            optional_property!(self, scene, properties, "direction", Vector),
+                            optional_property!(self, scene, properties, "target", Vector),
+                        ) {
+                            (Some(direction), None) => direction,
+                            (None, Some(target)) => target - position,
+                            _ => {
+                                return Err(InterpretError::RequiredPropertyMissing(
+                                    "one of direction, target",
+                                ))
+                            }
+                        };
+                        let inner_angle =
+                            optional_property!(self, scene, properties, "inner_angle", Number);
+                        let outer_angle =
+                            optional_property!(self, scene, properties, "outer_angle", Number);
+                        let falloff =
+                            optional_property!(self, scene, properties, "falloff", Number);
+                        let max_distance =
+                            optional_property!(self, scene, properties, "max_distance", Number);
+
+                        let attenuation = self.read_attenuation(scene, &mut properties)?;
+
+                        let light = lighting::Spot {
+                            color: color.unwrap_or(default.color),
+                            intensity: intensity.unwrap_or(default.intensity),
+                            specular_strength: specular_strength
+                                .unwrap_or(default.specular_strength),
+                            position,
+                            direction: direction.normalize(),
+                            inner_angle: inner_angle.unwrap_or(default.inner_angle),
+                            outer_angle: outer_angle.unwrap_or(default.outer_angle),
+                            falloff: falloff.unwrap_or(default.falloff),
+                            max_distance: max_distance.unwrap_or(default.max_distance),
+                            attenuation: attenuation.unwrap_or(default.attenuation),
+                        };
+
+                        scene.lights.push(Box::new(light));
+                    }
+                    "sun" | "sun_light" | "sunlight" => {
+                        let default = lighting::Sun::default();
+
+                        let color = optional_property!(self, scene, properties, "color", Color);
+                        let intensity =
+                            optional_property!(self, scene, properties, "intensity", Number);
+                        let specular_strength = optional_property!(
+                            self,
+                            scene,
+                            properties,
+                            "specular_strength",
+                            Number
+                        );
+                        let vector = required_property!(self, scene, properties, "vector", Vector)
+                            .normalize();
+                        let shadows =
+                            optional_property!(self, scene, properties, "shadows", Boolean);
+                        let shadow_coefficient = optional_property!(
+                            self,
+                            scene,
+                            properties,
+                            "shadow_coefficient",
+                            Number
+                        );
+                        let angular_radius =
+                            optional_property!(self, scene, properties, "angular_radius", Number);
+                        let samples =
+                            optional_property!(self, scene, properties, "samples", Number);
+
+                        let light = lighting::Sun {
+                            color: color.unwrap_or(default.color),
+                            intensity: intensity.unwrap_or(default.intensity),
+                            specular_strength: specular_strength
+                                .unwrap_or(default.specular_strength),
+                            vector,
+                            shadows: shadows.unwrap_or(default.shadows),
+                            shadow_coefficient: shadow_coefficient
+                                .unwrap_or(default.shadow_coefficient),
+                            angular_radius: angular_radius.unwrap_or(default.angular_radius),
+                            samples: samples.map(|s| s as u32).unwrap_or(default.samples),
+                        };
+
+                        scene.lights.push(Box::new(light));
+                    }
+                    "area_light" | "arealight" => {
+                        let default = lighting::Area::default();
+
+                        let color = optional_property!(self, scene, properties, "color", Color);
+                        let intensity =
+                            optional_property!(self, scene, properties, "intensity", Number);
+                        let specular_strength = optional_property!(
+                            self,
+                            scene,
+                            properties,
+                            "specular_strength",
+                            Number
+                        );
+                        let surface =
+                            match required_property!(self, scene, properties, "surface", String)
+                                .as_str()
+                            {
+                                "sphere" => AreaSurface::Sphere(
+                                    required_property!(self, scene, properties, "position", Vector),
+                                    required_property!(self, scene, properties, "radius", Number),
+                                ),
+                                "rectangle" => AreaSurface::Rectangle([
+                                    required_property!(self, scene, properties, "c00", Vector),
+                                    required_property!(self, scene, properties, "c01", Vector),
+                                    required_property!(self, scene, properties, "c10", Vector),
+                                    required_property!(self, scene, properties, "c11", Vector),
+                                ]),
+                                "mesh" => {
+                                    let obj =
+                                        required_property!(self, scene, properties, "obj", String);
+                                    self.track_asset(&obj);
+
+                                    let (mut mesh, assets) = object::Mesh::from_obj_with_assets(
+                                        obj,
+                                        Material::default(),
+                                        |_, mtl| Some(mtl),
+                                    );
+                                    for asset in assets {
+                                        self.track_asset(&asset.display().to_string());
+                                    }
+                                    if let Some(scale) =
+                                        optional_property!(self, scene, properties, "scale", Number)
+                                    {
+                                        mesh.scale(scale);
+                                    }
+                                    mesh.center();
+                                    if let Some(position) = optional_property!(
+                                        self, scene, properties, "position", Vector
+                                    ) {
+                                        mesh.shift(position);
+                                    }
+
+                                    AreaSurface::Mesh(MeshSurface::from_mesh(&mesh))
+                                }
+                                "capsule" | "tube" | "line" => AreaSurface::Capsule(
+                                    required_property!(self, scene, properties, "a", Vector),
+                                    required_property!(self, scene, properties, "b", Vector),
+                                    optional_property!(self, scene, properties, "radius", Number)
+                                        .unwrap_or(0.),
+                                ),
+                                _ => return Err(InterpretError::InvalidMaterials),
+                            };
+                        let iterations =
+                            optional_property!(self, scene, properties, "iterations", Number);
+                        let max_distance =
+                            optional_property!(self, scene, properties, "max_distance", Number);
+
+                        let attenuation = self.read_attenuation(scene, &mut properties)?;
+
+                        let light = lighting::Area {
+                            color: color.unwrap_or(default.color),
+                            intensity: intensity.unwrap_or(default.intensity),
+                            specular_strength: specular_strength
+                                .unwrap_or(default.specular_strength),
+                            surface,
+                            iterations: iterations.map(|f| f as u32).unwrap_or(default.iterations),
+                            max_distance: max_distance.unwrap_or(default.max_distance),
+                            attenuation: attenuation.unwrap_or(default.attenuation),
+                        };
+
+                        scene.lights.push(Box::new(light));
+                    }
+                    _ => return Err(InterpretError::UnknownObject(name.clone())),
+                }
+
+                // any scene object can be moved, rotated and scaled by a transform
+                if let Some(transform) = properties.remove("transform") {
+                    if scene.objects.len() != object_count + 1 {
+                        return Err(InterpretError::InvalidTransform);
+                    }
+
+                    let transform = self.read_transform(scene, transform)?;
+                    let object = self.take_last_object(scene);
+                    self.add_object(scene, Box::new(object::Transformed::new(object, transform)));
+                }
+
+                // and hidden from some kinds of rays
+                let casts_shadows =
+                    optional_property!(self, scene, properties, "casts_shadows", Boolean);
+                let visible_to_camera =
+                    optional_property!(self, scene, properties, "visible_to_camera", Boolean);
+                let visible_in_reflections =
+                    optional_property!(self, scene, properties, "visible_in_reflections", Boolean);
+                if casts_shadows.is_some()
+                    || visible_to_camera.is_some()
+                    || visible_in_reflections.is_some()
+                {
+                    if scene.objects.len() != object_count + 1 {
+                        return Err(InterpretError::InvalidRenderFlags);
+                    }
+
+                    let flags = object::RenderFlags {
+                        casts_shadows: casts_shadows.unwrap_or(true),
+                        visible_to_camera: visible_to_camera.unwrap_or(true),
+                        visible_in_reflections: visible_in_reflections.unwrap_or(true),
+                    };
+                    let object = self.take_last_object(scene);
+                    self.add_object(scene, Box::new(object::Flagged::new(object, flags)));
+                }
+
+                // and named, to be found by the host application
+                if let Some(object_name) =
+                    optional_property!(self, scene, properties, "name", String)
+                {
+                    if scene.objects.len() != object_count + 1 {
+                        return Err(InterpretError::InvalidObjectName);
+                    }
+
+                    if let Some(handle) = self.last_object {
+                        scene.name_object(handle, object_name);
+                    }
+                }
+
+                // lights can be linked to only light some objects
+                if let Some(links) = self.read_light_links(scene, &mut properties)? {
+                    if scene.lights.len() != light_count + 1 {
+                        return Err(InterpretError::InvalidLightLink);
+                    }
+
+                    let light = scene.lights.pop().unwrap();
+                    scene
+                        .lights
+                        .push(Box::new(lighting::Linked::new(light, links)));
+                }
+
+                // and grouped, to have their lighting rendered separately
+                if let Some(group) = optional_property!(self, scene, properties, "group", String) {
+                    if scene.lights.len() != light_count + 1 {
+                        return Err(InterpretError::InvalidLightGroup);
+                    }
+
+                    let light = scene.lights.pop().unwrap();
+                    scene
+                        .lights
+                        .push(Box::new(lighting::Grouped::new(light, group)));
+                }
+
+                self.object_names.push(name.clone());
+                if scene.objects.len() == object_count + 1 {
+                    self.object_handles.extend(self.last_object);
+                }
+            }
+            _ => (),
+        }
+
+        Ok(Flow::Next)
+    }
+
+    /// Build one operand of a `csg` object from a dictionary of object properties, with the
+    /// name of the object under `object`.
+    fn csg_operand(
+        &mut self,
+        scene: &mut Scene,
+        node: Option<ast::Node>,
+    ) -> Result<Box<dyn object::SceneObject>, InterpretError> {
+        let mut properties = match node {
+            Some(ast::Node::Dictionary(map)) => map,
+            _ => return Err(InterpretError::InvalidCsgOperand),
+        };
+        let name = match optional_property!(self, scene, properties, "object", String) {
+            Some(name) => name,
+            None => return Err(InterpretError::InvalidCsgOperand),
+        };
+
+        // declare the operand like any other object, then take it back out of the scene
+        let count = scene.objects.len();
+        self.run_scope(scene, vec![ast::Node::Object { name, properties }])?;
+        if scene.objects.len() != count + 1 {
+            return Err(InterpretError::InvalidCsgOperand);
+        }
+        self.object_names.pop();
+        self.object_handles.pop();
+
+        // the operand isn't in the scene by itself, so it can't keep a name
+        Ok(self.take_last_object(scene))
+    }
+
+    /// Add an object to the scene, keeping its handle for the rest of its declaration.
+    fn add_object(&mut self, scene: &mut Scene, object: Box<dyn object::SceneObject>) {
+        self.last_object = Some(scene.add_object(object));
+    }
+
+    /// Take the object most recently added back out of the scene, along with its name.
+    fn take_last_object(&mut self, scene: &mut Scene) -> Box<dyn object::SceneObject> {
+        let handle = self.last_object.take().unwrap();
+        scene.remove_object_by_handle(handle).unwrap()
+    }
+
+    /// Load an image from a file, or reuse it if it was already loaded.
+    fn load_image(&mut self, path: String) -> Result<&image::DynamicImage, InterpretError> {
+        self.track_asset(&path);
+
+        Ok(match self.images.entry(path) {
+            Entry::Occupied(buf) => buf.into_mut(),
+            Entry::Vacant(ent) => {
+                let img = image::open(ent.key())?;
+                ent.insert(img)
+            }
+        })
+    }
+
+    /// Read how a light falls off with distance from its `attenuation` property, which is either
+    /// `"inverse_square"`, `"linear"` or the exponent of the distance.
+    fn read_attenuation(
+        &mut self,
+        scene: &mut Scene,
+        properties: &mut HashMap<String, ast::Node>,
+    ) -> Result<Option<lighting::Attenuation>, InterpretError> {
+        Ok(
+            match self.optional_property(scene, properties, "attenuation", ast::NodeKind::Any)? {
+                Some(Value::String(s)) => Some(match s.as_str() {
+                    "inverse_square" => lighting::Attenuation::InverseSquare,
+                    "linear" => lighting::Attenuation::Linear,
+                    _ => return Err(InterpretError::UnknownAttenuation(s)),
+                }),
+                Some(Value::Number(e)) => Some(lighting::Attenuation::Exponent(e)),
+                Some(_) => return Err(InterpretError::UnknownAttenuation(String::new())),
+                None => None,
+            },
+        )
+    }
+
+    /// Read which objects a light affects from its `include` and `exclude` properties, arrays of
+    /// object names or ids (the order objects were declared in, counting from 0).
+    fn read_light_links(
+        &mut self,
+        scene: &mut Scene,
+        properties: &mut HashMap<String, ast::Node>,
+    ) -> Result<Option<lighting::LightLinks>, InterpretError> {
+        let include = self
+            .optional_property(scene, properties, "include", ast::NodeKind::Array)?
+            .map(|v| unwrap_variant!(v, Value::Array));
+        let exclude = self
+            .optional_property(scene, properties, "exclude", ast::NodeKind::Array)?
+            .map(|v| unwrap_variant!(v, Value::Array));
+        if include.is_none() && exclude.is_none() {
+            return Ok(None);
+        }
+
+        let refs = |values: Option<Vec<Value>>| {
+            values
+                .unwrap_or_default()
+                .into_iter()
+                .map(|v| match v {
+                    Value::String(name) => Ok(lighting::ObjectRef::Name(name)),
+                    Value::Number(id) if id >= 0. => self
+                        .object_handles
+                        .get(id as usize)
+                        .map(|&handle| lighting::ObjectRef::Handle(handle))
+                        .ok_or(InterpretError::UnknownObjectId(id as usize)),
+                    _ => Err(InterpretError::InvalidLightLink),
+                })
+                .collect::<Result<Vec<_>, _>>()
+        };
+
+        Ok(Some(lighting::LightLinks {
+            include: refs(include)?,
+            exclude: refs(exclude)?,
+        }))
+    }
+
+    /// Read a rotation given by either `rotate_xyz` or `rotate_zyx`, both Euler angles in
+    /// radians, into a quaternion.
+    fn read_rotation(
+        &mut self,
+        scene: &mut Scene,
+        properties: &mut HashMap<String, ast::Node>,
+    ) -> Result<Quaternion, InterpretError> {
+        let rotate_xyz = self
+            .optional_property(scene, properties, "rotate_xyz", ast::NodeKind::Vector)?
+            .map(|v| unwrap_variant!(v, Value::Vector));
+        let rotate_zyx = self
+            .optional_property(scene, properties, "rotate_zyx", ast::NodeKind::Vector)?
+            .map(|v| unwrap_variant!(v, Value::Vector));
+        match (rotate_xyz, rotate_zyx) {
+            (Some(_), Some(_)) => Err(InterpretError::RequiredPropertyMissing(
+                "one of rotate_xyz, rotate_zyx, not duplicates",
+            )),
+            (Some(r), None) => Ok(Quaternion::from_euler_xyz(-r.x, -r.y, -r.z)),
+            (None, Some(r)) => Ok(Quaternion::from_euler_zyx(-r.x, -r.y, -r.z)),
+            (None, None) => Ok(Quaternion::identity()),
+        }
+    }
+
+    /// Read the placement of a mesh or instance from its `position`, uniform `scale` and
+    /// rotation properties.
+    fn read_placement(
+        &mut self,
+        scene: &mut Scene,
+        properties: &mut HashMap<String, ast::Node>,
+    ) -> Result<Transform, InterpretError> {
+        let position = self
+            .optional_property(scene, properties, "position", ast::NodeKind::Vector)?
+            .map_or_else(Vector3::default, |v| unwrap_variant!(v, Value::Vector));
+        let scale = self
+            .optional_property(scene, properties, "scale", ast::NodeKind::Number)?
+            .map_or(1., |v| unwrap_variant!(v, Value::Number));
+        let rotation = self.read_rotation(scene, properties)?;
+        Ok(Transform::new(position, rotation, scale))
+    }
+
+    /// Read a transformation matrix from a `transform` dictionary. The object is scaled, then
+    /// rotated, then moved.
+    fn read_transform(
+        &mut self,
+        scene: &mut Scene,
+        node: ast::Node,
+    ) -> Result<Matrix, InterpretError> {
+        let mut map = match node {
+            ast::Node::Dictionary(map) => map,
+            _ => return Err(InterpretError::InvalidTransform),
+        };
+
+        let position = optional_property!(self, scene, map, "position", Vector).unwrap_or_default();
+        let scale = match self.optional_property(scene, &mut map, "scale", ast::NodeKind::Any)? {
+            Some(Value::Number(n)) => Vector3::new(n, n, n),
+            Some(Value::Vector(v)) => v,
+            Some(_) => return Err(InterpretError::InvalidTransform),
+            None => Vector3::new(1., 1., 1.),
+        };
+        let rotation = self.read_rotation(scene, &mut map)?;
+
+        Ok(Matrix::from(position) * rotation.to_matrix() * Matrix::from_scale(scale))
+    }
+
+    /// Read a signed distance function from a dictionary naming its kind as `shape`, which may
+    /// itself combine other shapes.
+    fn read_distance(
+        &mut self,
+        scene: &mut Scene,
+        node: ast::Node,
+    ) -> Result<Distance, InterpretError> {
+        let mut map = match node {
+            ast::Node::Dictionary(map) => map,
+            _ => return Err(InterpretError::InvalidSdfShape),
+        };
+        let shape = match optional_property!(self, scene, map, "shape", String) {
+            Some(shape) => shape,
+            None => return Err(InterpretError::InvalidSdfShape),
+        };
+
+        let mut distance = match shape.as_str() {
+            "sphere" => Distance::sphere(required_property!(self, scene, map, "radius", Number)),
+            "box" | "cuboid" => {
+                Distance::cuboid(required_property!(self, scene, map, "size", Vector))
+            }
+            "torus" => Distance::torus(
+                required_property!(self, scene, map, "major_radius", Number),
+                required_property!(self, scene, map, "minor_radius", Number),
+            ),
+            "capsule" => Distance::capsule(
+                required_property!(self, scene, map, "a", Vector),
+                required_property!(self, scene, map, "b", Vector),
+                required_property!(self, scene, map, "radius", Number),
+            ),
+            "plane" => Distance::plane(
+                optional_property!(self, scene, map, "normal", Vector).unwrap_or_else(Vector3::up),
+            ),
+            "union" | "intersection" | "difference" => {
+                let a = match map.remove("a") {
+                    Some(node) => self.read_distance(scene, node)?,
+                    None => return Err(InterpretError::RequiredPropertyMissing("a")),
+                };
+                let b = match map.remove("b") {
+                    Some(node) => self.read_distance(scene, node)?,
+                    None => return Err(InterpretError::RequiredPropertyMissing("b")),
+                };
+
+                match (
+                    shape.as_str(),
+                    optional_property!(self, scene, map, "smoothness", Number),
+                ) {
+                    ("union", Some(k)) => a.smooth_union(b, k),
+                    ("intersection", Some(k)) => a.smooth_intersection(b, k),
+                    ("difference", Some(k)) => a.smooth_difference(b, k),
+                    ("union", None) => a.union(b),
+                    ("intersection", None) => a.intersection(b),
+                    _ => a.difference(b),
+                }
+            }
+            _ => return Err(InterpretError::UnknownSdfShape(shape)),
+        };
+
+        // modifiers that can be applied to any shape
+        if let Some(radius) = optional_property!(self, scene, map, "round", Number) {
+            distance = distance.round(radius);
+        }
+        if let Some(thickness) = optional_property!(self, scene, map, "onion", Number) {
+            distance = distance.onion(thickness);
+        }
+        if let Some(factor) = optional_property!(self, scene, map, "scale", Number) {
+            distance = distance.scale(factor);
+        }
+        if let Some(period) = optional_property!(self, scene, map, "repeat", Vector) {
+            distance = distance.repeat(period);
+        }
+        if let Some(position) = optional_property!(self, scene, map, "position", Vector) {
+            distance = distance.translate(position);
+        }
+
+        Ok(distance)
+    }
+
+    /// Read the `material` property of an object, defaulting if it is missing.
+    fn read_material(
+        &mut self,
+        scene: &mut Scene,
+        properties: &mut HashMap<String, ast::Node>,
+    ) -> Result<Material, InterpretError> {
+        match properties.remove("material") {
+            Some(node) => self.material_from_node(scene, node),
+            None => Ok(Material::default()),
+        }
+    }
+
+    /// Read a material from a dictionary node.
+    fn material_from_node(
+        &mut self,
+        scene: &mut Scene,
+        node: ast::Node,
+    ) -> Result<Material, InterpretError> {
+        match node {
+            ast::Node::Dictionary(mut map) => {
+                // properties that aren't given are taken from the preset, if any
+                let base = match optional_property!(self, scene, map, "preset", String) {
+                    Some(name) => presets::by_name(&name)
+                        .ok_or(InterpretError::UnknownMaterialPreset(name))?,
+                    None => Material {
+                        ior: 1.5,
+                        ..Default::default()
+                    },
+                };
+
+                let reflectiveness = optional_property!(self, scene, map, "reflectiveness", Number)
+                    .unwrap_or(base.reflectiveness);
+                let transparency = optional_property!(self, scene, map, "transparency", Number)
+                    .unwrap_or(base.transparency);
+                let ior = optional_property!(self, scene, map, "ior", Number).unwrap_or(base.ior);
+                let emissivity = optional_property!(self, scene, map, "emissivity", Number)
+                    .unwrap_or(base.emissivity);
+                let roughness = optional_property!(self, scene, map, "roughness", Number)
+                    .unwrap_or(base.roughness);
+                let metallic = optional_property!(self, scene, map, "metallic", Number)
+                    .unwrap_or(base.metallic);
+                let clearcoat = optional_property!(self, scene, map, "clearcoat", Number)
+                    .unwrap_or(base.clearcoat);
+                let clearcoat_roughness =
+                    optional_property!(self, scene, map, "clearcoat_roughness", Number)
+                        .unwrap_or(base.clearcoat_roughness);
+                let thin_film_thickness =
+                    optional_property!(self, scene, map, "thin_film_thickness", Number)
+                        .unwrap_or(base.thin_film_thickness);
+                let thin_film_ior = optional_property!(self, scene, map, "thin_film_ior", Number)
+                    .unwrap_or(base.thin_film_ior);
+
+                let default_uv = base.uv_transform;
+                let uv_offset = optional_property!(self, scene, map, "uv_offset", Vector);
+                let uv_scale = optional_property!(self, scene, map, "uv_scale", Vector);
+                let uv_rotation = optional_property!(self, scene, map, "uv_rotation", Number);
+                let uv_transform = UvTransform {
+                    offset: uv_offset
+                        .map(|v| Vector2::new(v.x as f32, v.y as f32))
+                        .unwrap_or(default_uv.offset),
+                    scale: uv_scale
+                        .map(|v| Vector2::new(v.x as f32, v.y as f32))
+                        .unwrap_or(default_uv.scale),
+                    rotation: uv_rotation.map(|r| r as f32).unwrap_or(default_uv.rotation),
+                };
+
+                let texture_filter = optional_property!(self, scene, map, "texture_filter", String);
+                let mipmaps = optional_property!(self, scene, map, "mipmaps", Boolean);
+                let texture_wrap = optional_property!(self, scene, map, "texture_wrap", String);
+                let texture_color_space =
+                    optional_property!(self, scene, map, "texture_color_space", String);
+
+                let triplanar = optional_property!(self, scene, map, "triplanar", Boolean);
+                let triplanar_scale =
+                    optional_property!(self, scene, map, "triplanar_scale", Number);
+                let triplanar_sharpness =
+                    optional_property!(self, scene, map, "triplanar_sharpness", Number);
+                let triplanar = match triplanar {
+                    Some(true) => {
+                        let default = Triplanar::default();
+                        Some(Triplanar {
+                            scale: triplanar_scale.unwrap_or(default.scale),
+                            sharpness: triplanar_sharpness.unwrap_or(default.sharpness),
+                        })
+                    }
+                    Some(false) => None,
+                    None => base.triplanar,
+                };
+
+                let mut texture = match map.remove("texture") {
+                    Some(node) => self.read_texture(scene, node)?,
+                    None => base.texture,
+                };
+                if let Texture::Image(image) = &mut texture {
+                    if let Some(filter) = texture_filter {
+                        image.filter = match filter.as_str() {
+                            "nearest" => TextureFilter::Nearest,
+                            "bilinear" => TextureFilter::Bilinear,
+                            _ => return Err(InterpretError::UnknownTextureFilter(filter)),
+                        };
+                    }
+                    if let Some(wrap) = texture_wrap {
+                        image.wrap = match wrap.as_str() {
+                            "repeat" => WrapMode::Repeat,
+                            "clamp" => WrapMode::Clamp,
+                            "mirror" => WrapMode::Mirror,
+                            _ => return Err(InterpretError::UnknownWrapMode(wrap)),
+                        };
+                    }
+                    if let Some(color_space) = texture_color_space {
+                        image.color_space = match color_space.as_str() {
+                            "srgb" => ColorSpace::Srgb,
+                            "linear" => ColorSpace::Linear,
+                            _ => return Err(InterpretError::UnknownColorSpace(color_space)),
+                        };
+                    }
+                    // decode color textures once, before their mipmaps are averaged
+                    let mut decoded = image.clone().decoded();
+                    if mipmaps == Some(true) {
+                        decoded = decoded.with_mipmaps();
+                    }
+                    *image = decoded;
+                }
+                let emission = match map.remove("emission") {
+                    Some(node) => Some(self.read_texture(scene, node)?.decoded()),
+                    None => base.emission,
+                };
+                let opacity = match map.remove("opacity") {
+                    Some(node) => Some(self.read_texture(scene, node)?),
+                    None => base.opacity,
+                };
+                let alpha_cutoff = optional_property!(self, scene, map, "alpha_cutoff", Number)
+                    .unwrap_or(base.alpha_cutoff);
+                let normal_map = match map.remove("normal_map") {
+                    Some(node) => Some(self.read_texture(scene, node)?),
+                    None => base.normal_map,
+                };
+
+                Ok(Material {
+                    texture,
+                    uv_transform,
+                    triplanar,
+                    reflectiveness,
+                    transparency,
+                    ior,
+                    emissivity,
+                    emission,
+                    roughness,
+                    metallic,
+                    clearcoat,
+                    clearcoat_roughness,
+                    thin_film_thickness,
+                    thin_film_ior,
+                    opacity,
+                    alpha_cutoff,
+                    normal_map,
+                })
+            }
+            _ => Err(InterpretError::InvalidMaterials),
+        }
+    }
+
+    /// Read a texture from a call node.
+    ///
+    /// A texture can be `solid(color(r, g, b))`, `checkerboard(color(r, g, b), color(r, g, b))`,
+    /// `image("path")` or `vertex_color()`.
+    fn read_texture(
+        &mut self,
+        scene: &mut Scene,
+        node: ast::Node,
+    ) -> Result<Texture, InterpretError> {
+        match node {
+            ast::Node::Call(name, args) => match name.as_str() {
+                "solid" => {
+                    let value = Value::from_nodes(self, scene, args)?;
+                    let args = self.deconstruct_args(value, &[ast::NodeKind::Color])?;
+                    Ok(Texture::Solid(unwrap_variant!(args[0], Value::Color)))
+                }
+                "checkerboard" => {
+                    let value = Value::from_nodes(self, scene, args)?;
+                    let args = self
+                        .deconstruct_args(value, &[ast::NodeKind::Color, ast::NodeKind::Color])?;
+
+                    Ok(Texture::Checkerboard(
+                        unwrap_variant!(args[0], Value::Color),
+                        unwrap_variant!(args[1], Value::Color),
+                    ))
+                }
+                "vertex_color" => {
+                    let value = Value::from_nodes(self, scene, args)?;
+                    self.deconstruct_args(value, &[])?;
+                    Ok(Texture::VertexColor)
+                }
+                "image" => {
+                    let value = Value::from_nodes(self, scene, args)?;
+                    let args = self.deconstruct_args(value, &[ast::NodeKind::String])?;
+                    let path = unwrap_variant!(args.into_iter().next().unwrap(), Value::String);
+
+                    let img = self.load_image(path)?.to_rgba8();
+                    Ok(Texture::Image(ImageTexture::from_rgba(img)))
+                }
+                _ => Err(InterpretError::InvalidCallArgs),
+            },
+            _ => Err(InterpretError::InvalidCallArgs),
+        }
+    }
+
+    /// Call a named function with some arguments.
+    /// Its result is another node that can be used as other values.
+    fn call_func(
+        &mut self,
+        scene: &mut Scene,
+        name: String,
+        args: Vec<ast::Node>,
+    ) -> Result<Value, InterpretError> {
+        macro_rules! float_func {
+            ($n:ident) => {
+                |_, v| Ok(Value::Number(unwrap_variant!(v[0], Value::Number).$n()))
+            };
+        }
+
+        // the noise generators take and return `f64`s, whatever `Float` is
+        macro_rules! noise_func {
+            ($noise:ident, $n:literal) => {
+                |s, v| {
+                    let point: [f64; $n] =
+                        std::array::from_fn(|i| unwrap_variant!(v[i], Value::Number) as f64);
+                    Ok(Value::Number(s.$noise.get(point) as Float))
+                }
+            };
+        }
+
+        macro_rules! vector_func {
+            ($n:ident, $r:ident) => {
+                |_, v| Ok(Value::$r(unwrap_variant!(v[0], Value::Vector).$n()))
+            };
+            ($n:ident, $r:ident,) => {
+                |_, v| {
+                    Ok(Value::$r(
+                        unwrap_variant!(v[0], Value::Vector)
+                            .$n(unwrap_variant!(v[1], Value::Vector)),
+                    ))
+                }
+            };
+        }
+
+        lazy_static! {
+            static ref FUNCTIONS: Vec<Function> = vec![
+                // floating point operators
+                Function::new(&["add"], &[NodeKind::Number, NodeKind::Number], |_, v| Ok(Value::Number(unwrap_variant!(v[0], Value::Number) + unwrap_variant!(v[1], Value::Number)))),
+                Function::new(&["sub"], &[NodeKind::Number, NodeKind::Number], |_, v| Ok(Value::Number(unwrap_variant!(v[0], Value::Number) - unwrap_variant!(v[1], Value::Number)))),
+                Function::new(&["mul"], &[NodeKind::Number, NodeKind::Number], |_, v| Ok(Value::Number(unwrap_variant!(v[0], Value::Number) * unwrap_variant!(v[1], Value::Number)))),
+                Function::new(&["div"], &[NodeKind::Number, NodeKind::Number], |_, v| Ok(Value::Number(unwrap_variant!(v[0], Value::Number) / unwrap_variant!(v[1], Value::Number)))),
+                Function::new(&["mod"], &[NodeKind::Number, NodeKind::Number], |_, v| Ok(Value::Number(unwrap_variant!(v[0], Value::Number) % unwrap_variant!(v[1], Value::Number)))),
+                Function::new(&["pow"], &[NodeKind::Number, NodeKind::Number], |_, v| Ok(Value::Number(unwrap_variant!(v[0], Value::Number).powf(unwrap_variant!(v[1], Value::Number))))),
+
+                // vector operators
+                Function::new(&["add"], &[NodeKind::Vector, NodeKind::Vector], |_, v| Ok(Value::Vector(unwrap_variant!(v[0], Value::Vector) + unwrap_variant!(v[1], Value::Vector)))),
+                Function::new(&["sub"], &[NodeKind::Vector, NodeKind::Vector], |_, v| Ok(Value::Vector(unwrap_variant!(v[0], Value::Vector) - unwrap_variant!(v[1], Value::Vector)))),
+                Function::new(&["mul"], &[NodeKind::Vector, NodeKind::Vector], |_, v| Ok(Value::Vector(unwrap_variant!(v[0], Value::Vector) * unwrap_variant!(v[1], Value::Vector)))),
+                Function::new(&["div"], &[NodeKind::Vector, NodeKind::Vector], |_, v| Ok(Value::Vector(unwrap_variant!(v[0], Value::Vector) / unwrap_variant!(v[1], Value::Vector)))),
+
+                // vector (*|/) floating point
+                Function::new(&["mul"], &[NodeKind::Vector, NodeKind::Number], |_, v| Ok(Value::Vector(unwrap_variant!(v[0], Value::Vector) * unwrap_variant!(v[1], Value::Number)))),
+                Function::new(&["div"], &[NodeKind::Vector, NodeKind::Number], |_, v| Ok(Value::Vector(unwrap_variant!(v[0], Value::Vector) / unwrap_variant!(v[1], Value::Number)))),
+
+                // string operators
+                Function::new(&["add"], &[NodeKind::String, NodeKind::String], |_, v| Ok(Value::String(format!("{}{}", unwrap_variant!(&v[0], Value::String), unwrap_variant!(&v[1], Value::String))))),
+
+                // array operations
+                Function::new(&["push"], &[NodeKind::Array, NodeKind::Any], |s, v| {
+                    let mut v = v.into_iter();
+                    let key = match v.next().unwrap() {
+                        Value::Ref(k, _) => *k,
+                        _ => unreachable!(),
+                    };
+
+                    match s.ref_objects.get_mut(key) {
+                        Some(RefObject::Array(a)) => a.push(v.next().unwrap()),
+                        _ => return Err(InterpretError::InvalidReference),
+                    }
+
+                    Ok(Value::Unit)
+                }),
+                Function::new(&["set"], &[NodeKind::Array, NodeKind::Number, NodeKind::Any], |s, v| {
+                    let mut v = v.into_iter();
+                    let key = match v.next().unwrap() {
+                        Value::Ref(k, _) => *k,
+                        _ => unreachable!(),
+                    };
+
+                    match s.ref_objects.get_mut(key) {
+                        Some(RefObject::Array(a)) => {
+                            let index = unwrap_variant!(v.next().unwrap(), Value::Number) as usize;
+                            let value = v.next().unwrap();
+                            if index == a.len() {
+                                a.push(value);
+                            } else {
+                                a[index] = value;
+                            }
+                        }
+                        _ => return Err(InterpretError::InvalidReference),
+                    }
+
+                    Ok(Value::Unit)
+                }),
+                Function::new(&["pop"], &[NodeKind::Array], |s, v| {
+                    let mut v = v.into_iter();
+                    let key = match v.next().unwrap() {
+                        Value::Ref(k, _) => *k,
+                        _ => unreachable!(),
+                    };
+
+                    match s.ref_objects.get_mut(key) {
+                        Some(RefObject::Array(a)) => a.pop().ok_or(InterpretError::IndexOutOfBounds),
+                        _ => Err(InterpretError::InvalidReference),
+                    }
+                }),
+                Function::new(&["insert"], &[NodeKind::Array, NodeKind::Number, NodeKind::Any], |s, v| {
+                    let mut v = v.into_iter();
+                    let key = match v.next().unwrap() {
+                        Value::Ref(k, _) => *k,
+                        _ => unreachable!(),
+                    };
+
+                    match s.ref_objects.get_mut(key) {
+                        Some(RefObject::Array(a)) => {
+                            let index = array_index(unwrap_variant!(v.next().unwrap(), Value::Number), a.len())
+                                .filter(|&i| i <= a.len())
+                                .ok_or(InterpretError::IndexOutOfBounds)?;
+                            a.insert(index, v.next().unwrap());
+                        }
+                        _ => return Err(InterpretError::InvalidReference),
+                    }
+
+                    Ok(Value::Unit)
+                }),
+                Function::new(&["remove"], &[NodeKind::Array, NodeKind::Number], |s, v| {
+                    let mut v = v.into_iter();
+                    let key = match v.next().unwrap() {
+                        Value::Ref(k, _) => *k,
+                        _ => unreachable!(),
+                    };
+
+                    match s.ref_objects.get_mut(key) {
+                        Some(RefObject::Array(a)) => {
+                            let index = array_index(unwrap_variant!(v.next().unwrap(), Value::Number), a.len())
+                                .filter(|&i| i < a.len())
+                                .ok_or(InterpretError::IndexOutOfBounds)?;
+                            Ok(a.remove(index))
+                        }
+                        _ => Err(InterpretError::InvalidReference),
+                    }
+                }),
+                Function::new(&["len"], &[NodeKind::Array], |s, v| {
+                    let mut v = v.into_iter();
+                    let key = match v.next().unwrap() {
+                        Value::Ref(k, _) => *k,
+                        _ => unreachable!(),
+                    };
+
+                    match s.ref_objects.get(key) {
+                        Some(RefObject::Array(a)) => Ok(Value::Number(a.len() as Float)),
+                        _ => return Err(InterpretError::InvalidReference),
+                    }
+                }),
+
+                // string functions
+                Function::new(&["len"], &[NodeKind::String], |_, v| {
+                    Ok(Value::Number(unwrap_variant!(&v[0], Value::String).chars().count() as Float))
+                }),
+                Function::new(&["split"], &[NodeKind::String, NodeKind::String], |s, v| {
+                    let parts = unwrap_variant!(&v[0], Value::String)
+                        .split(unwrap_variant!(&v[1], Value::String).as_str())
+                        .map(|p| Value::String(p.to_owned()))
+                        .collect();
+                    Ok(Value::Ref(s.new_ref_obj(RefObject::Array(parts)), NodeKind::Array))
+                }),
+                Function::new(&["substr"], &[NodeKind::String, NodeKind::Number], |_, v| {
+                    let string = unwrap_variant!(&v[0], Value::String);
+                    let len = string.chars().count();
+                    let start = array_index(unwrap_variant!(v[1], Value::Number), len)
+                        .filter(|&i| i <= len)
+                        .ok_or(InterpretError::IndexOutOfBounds)?;
+                    Ok(Value::String(string.chars().skip(start).collect()))
+                }),
+                Function::new(&["substr"], &[NodeKind::String, NodeKind::Number, NodeKind::Number], |_, v| {
+                    let string = unwrap_variant!(&v[0], Value::String);
+                    let len = string.chars().count();
+                    let start = array_index(unwrap_variant!(v[1], Value::Number), len)
+                        .filter(|&i| i <= len)
+                        .ok_or(InterpretError::IndexOutOfBounds)?;
+                    let count = unwrap_variant!(v[2], Value::Number).max(0.) as usize;
+                    Ok(Value::String(string.chars().skip(start).take(count).collect()))
+                }),
+                Function::new(&["upper"], &[NodeKind::String], |_, v| Ok(Value::String(unwrap_variant!(&v[0], Value::String).to_uppercase()))),
+                Function::new(&["lower"], &[NodeKind::String], |_, v| Ok(Value::String(unwrap_variant!(&v[0], Value::String).to_lowercase()))),
+                Function::new(&["contains"], &[NodeKind::String, NodeKind::String], |_, v| {
+                    Ok(Value::Boolean(
+                        unwrap_variant!(&v[0], Value::String).contains(unwrap_variant!(&v[1], Value::String).as_str()),
+                    ))
+                }),
+                Function::new(&["str"], &[NodeKind::Any], |s, v| Ok(Value::String(s.stringify(&v[0])))),
+                Function::new(&["number"], &[NodeKind::String], |_, v| {
+                    let string = unwrap_variant!(&v[0], Value::String);
+                    string
+                        .trim()
+                        .parse()
+                        .map(Value::Number)
+                        .map_err(|_| InterpretError::InvalidNumber(string.clone()))
+                }),
+
+                // printing
+                Function::new(&["print"], &[NodeKind::String], |_, v| {
+                    println!("{}", unwrap_variant!(&v[0], Value::String));
+                    Ok(Value::Unit)
+                }),
+                Function::new(&["print"], &[NodeKind::Number], |_, v| {
+                    println!("{}", unwrap_variant!(&v[0], Value::Number));
+                    Ok(Value::Unit)
+                }),
+                Function::new(&["print"], &[NodeKind::Boolean], |_, v| {
+                    println!("{}", unwrap_variant!(&v[0], Value::Boolean));
+                    Ok(Value::Unit)
+                }),
+                Function::new(&["print"], &[NodeKind::Array], |_, v| {
+                    println!("{:?}", unwrap_variant!(&v[0], Value::Array));
+                    Ok(Value::Unit)
+                }),
+
+                // constructors
+                Function::new(&["color", "rgb"], &[NodeKind::Number, NodeKind::Number, NodeKind::Number], |_, v| {
+                    Ok(Value::Color(Color::new(
+                        unwrap_variant!(v[0], Value::Number) as u8,
+                        unwrap_variant!(v[1], Value::Number) as u8,
+                        unwrap_variant!(v[2], Value::Number) as u8,
+                    )))
+                }),
+                Function::new(&["gray"], &[NodeKind::Number], |_, v| {
+                    let comp = unwrap_variant!(v[0], Value::Number) as u8;
+                    Ok(Value::Color(Color::new(comp, comp, comp)))
+                }),
+                Function::new(&["hsv"], &[NodeKind::Number, NodeKind::Number, NodeKind::Number], |_, v| {
+                    let (h, s, v) = (
+                        unwrap_variant!(v[0], Value::Number) % 360.,
+                        unwrap_variant!(v[1], Value::Number),
+                        unwrap_variant!(v[2], Value::Number),
+                    );
+
+                    let c = v * s;
+                    let x = c * (1. - (h / 60. % 2. - 1.).abs());
+                    let m = v - c;
+
+                    let (r, g, b) = if (0. ..60.).contains(&h) {
+                        (c, x, 0.)
+                    } else if (60. ..120.).contains(&h) {
+                        (x, c, 0.)
+                    } else if (120. ..180.).contains(&h) {
+                        (0., c, x)
+                    } else if (180. ..240.).contains(&h) {
+                        (0., x, c)
+                    } else if (240. ..300.).contains(&h) {
+                        (x, 0., c)
+                    } else if (300. ..360.).contains(&h) {
+                        (c, 0., x)
+                    } else {
+                        unreachable!();
+                    };
+
+                    Ok(Value::Color(Color::new(
+                        ((r + m) * 255.) as u8,
+                        ((g + m) * 255.) as u8,
+                        ((b + m) * 255.) as u8,
+                    )))
+                }),
+
+                // floating point functions
+                Function::new(&["sin"], &[NodeKind::Number], float_func!(sin)),
+                Function::new(&["cos"], &[NodeKind::Number], float_func!(cos)),
+                Function::new(&["tan"], &[NodeKind::Number], float_func!(tan)),
+                Function::new(&["asin"], &[NodeKind::Number], float_func!(asin)),
+                Function::new(&["acos"], &[NodeKind::Number], float_func!(acos)),
+                Function::new(&["atan"], &[NodeKind::Number], float_func!(atan)),
+                Function::new(&["abs"], &[NodeKind::Number], float_func!(abs)),
+                Function::new(&["sqrt"], &[NodeKind::Number], float_func!(sqrt)),
+                Function::new(&["floor"], &[NodeKind::Number], float_func!(floor)),
+                Function::new(&["ceil"], &[NodeKind::Number], float_func!(ceil)),
+                Function::new(&["rad"], &[NodeKind::Number], float_func!(to_radians)),
+                Function::new(&["deg"], &[NodeKind::Number], float_func!(to_degrees)),
+                Function::new(&["random"], &[NodeKind::Number, NodeKind::Number], |_, v| {
+                    Ok(Value::Number(rand::thread_rng().gen_range(
+                        unwrap_variant!(v[0], Value::Number)
+                            ..=unwrap_variant!(v[1], Value::Number),
+                    )))
+                }),
+                Function::new(&["lerp"], &[NodeKind::Number, NodeKind::Number, NodeKind::Number], |_, v| {
+                    Ok(Value::Number(Lerp::lerp(
+                        unwrap_variant!(v[0], Value::Number),
+                        unwrap_variant!(v[1], Value::Number),
+                        unwrap_variant!(v[2], Value::Number),
+                    )))
+                }),
+                Function::new(&["min"], &[NodeKind::Number, NodeKind::Number], |_, v| Ok(Value::Number(unwrap_variant!(v[0], Value::Number).min(unwrap_variant!(v[1], Value::Number))))),
+                Function::new(&["max"], &[NodeKind::Number, NodeKind::Number], |_, v| Ok(Value::Number(unwrap_variant!(v[0], Value::Number).max(unwrap_variant!(v[1], Value::Number))))),
+                Function::new(&["remap"], &[NodeKind::Number, NodeKind::Number, NodeKind::Number, NodeKind::Number, NodeKind::Number], |_, v| {
+                    let x = unwrap_variant!(v[0], Value::Number);
+                    let a = unwrap_variant!(v[1], Value::Number)..unwrap_variant!(v[2], Value::Number);
+                    let b = unwrap_variant!(v[3], Value::Number)..unwrap_variant!(v[4], Value::Number);
+                    Ok(Value::Number(remap(x, a, b)))
+                }),
+
+                // vector functions
+                Function::new(&["normalize"], &[NodeKind::Vector], vector_func!(normalize, Vector)),
+                Function::new(&["magnitude"], &[NodeKind::Vector], vector_func!(magnitude, Number)),
+                Function::new(&["angle"], &[NodeKind::Vector], vector_func!(angle, Number,)),
+                Function::new(&["lerp"], &[NodeKind::Vector, NodeKind::Vector, NodeKind::Number], |_, v| {
+                    Ok(Value::Vector(
+                        unwrap_variant!(v[0], Value::Vector).lerp(
+                            unwrap_variant!(v[1], Value::Vector),
+                            unwrap_variant!(v[2], Value::Number),
+                        ))
+                    )
+                }),
+
+                // noise gen functions
+                Function::new(&["perlin"], &[NodeKind::Number, NodeKind::Number], noise_func!(perlin, 2)),
+                Function::new(&["perlin"], &[NodeKind::Number, NodeKind::Number, NodeKind::Number], noise_func!(perlin, 3)),
+
+                Function::new(&["simplex"], &[NodeKind::Number, NodeKind::Number], noise_func!(simplex, 2)),
+                Function::new(&["simplex"], &[NodeKind::Number, NodeKind::Number, NodeKind::Number], noise_func!(simplex, 3)),
+            ];
+        }
+
+        let values = Value::from_nodes(self, scene, args)?;
+
+        for func in FUNCTIONS
+            .iter()
+            .filter(|f| f.names.contains(&name.as_str()))
+        {
+            if let Some(r) = func.try_eval(self, values.clone()) {
+                return r;
+            }
+        }
+
+        let func = self
+            .scope_stack
+            .iter()
+            .rev()
+            .filter_map(|s| s.funcs.iter().find(|(n, _)| n == &&name).map(|(_, f)| f))
+            .next()
+            .cloned();
+
+        if let Some(func) = func {
+            if values.len() > func.params.len() {
+                return Err(InterpretError::InvalidArgCount(
+                    func.params.len(),
+                    values.len(),
+                ));
+            }
+
+            // make a new scope, inject the parameter values, and run the body. parameters
+            // without an argument take their default value, which can use the parameters
+            // before them
+            self.scope_stack.push(Scope::default());
+            let mut values = values.into_iter();
+            for param in func.params.iter() {
+                let value = match (values.next(), &param.default) {
+                    (Some(value), _) => value,
+                    (None, Some(default)) => Value::from_node(self, scene, default.clone())?,
+                    (None, None) => {
+                        return Err(InterpretError::MissingArgument(param.name.clone()))
+                    }
+                };
+                self.scope_stack
+                    .last_mut()
+                    .unwrap()
+                    .vars
+                    .insert(param.name.clone(), value);
+            }
+
+            let flow = self.run_scope(scene, func.body.clone())?;
+            self.pop_scope();
+
+            return Ok(match flow {
+                Flow::Return(value) => value,
+                _ => Value::Unit,
+            });
+        }
+
+        return Err(InterpretError::UnknownFunction(name));
+    }
+
+    /// Deconstruct a list of arguments based on `NodeKind`s.
+    fn deconstruct_args(
+        &self,
+        args: Vec<Value>,
+        dest: &[ast::NodeKind],
+    ) -> Result<Vec<Value>, InterpretError> {
+        // first, confirm that both lengths are identical
+        if args.len() != dest.len() {
+            return Err(InterpretError::InvalidArgCount(dest.len(), args.len()));
+        }
+
+        let mut out = Vec::new();
+
+        macro_rules! match_kinds {
+            ($nk:ident, $v:ident, $o:ident, $($t:ident),+,) => {
+                match $nk {
+                    ast::NodeKind::Any => $o.push($v),
+                    $(
+                        ast::NodeKind::$t => {
+                            if matches!($v, Value::$t(_)) {
+                                $o.push($v)
+                            }
+                        }
+                    ),+
+                }
+            }
+        }
+
+        // now iterate through each dest arg and compare with the arg we have
+        for (node_kind, value) in dest.into_iter().zip(args.into_iter()) {
+            match_kinds!(
+                node_kind, value, out, Boolean, Color, Dictionary, Number, String, Vector, Array,
+            );
+        }
+
+        // we return `args` again if and only if they match the intended destination
+        // now the receiver can forcibly unwrap each variant
+        Ok(out)
+    }
+
+    /// Fetch an optional property out of a properties dictionary.
+    fn optional_property(
+        &mut self,
+        scene: &mut Scene,
+        properties: &mut HashMap<String, ast::Node>,
+        name: &'static str,
+        kind: ast::NodeKind,
+    ) -> Result<Option<Value>, InterpretError> {
+        macro_rules! match_kinds {
+            ($nk:ident, $v:ident, $($t:ident),+,) => {
+                match $nk {
+                    ast::NodeKind::Any => Ok(Some($v)),
+                    $(
+                        ast::NodeKind::$t => {
+                            match $v {
+                                Value::Ref(rc, _) => {
+                                    match self.ref_objects.get(*rc) {
+                                        Some(ro) => {
+                                            let val = ro.clone().into();
+                                            if matches!(val, Value::$t(_)) {
+                                                Ok(Some(val))
+                                            } else {
+                                                Err(InterpretError::InvalidCallArgs)
+                                            }
+                                        }
+                                        None => Err(InterpretError::InvalidCallArgs),
+                                    }
+                                }
+                                Value::$t(_) => Ok(Some($v)),
+                                _ => Err(InterpretError::InvalidCallArgs),
+                            }
+                        }
+                    ),+
+                }
+            }
+        }
+
+        match properties.remove(name) {
+            Some(node) => {
+                let value = Value::from_node(self, scene, node)?;
+                match_kinds!(
+                    kind, value, Boolean, Color, Dictionary, Number, String, Vector, Array,
+                )
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Convert a value to a string, as it is written in an interpolated string.
+    fn stringify(&self, value: &Value) -> String {
+        match value {
+            Value::Unit => String::from("()"),
+            Value::String(s) => s.clone(),
+            Value::Number(n) => n.to_string(),
+            Value::Vector(v) => format!("<{}, {}, {}>", v.x, v.y, v.z),
+            Value::Color(c) => format!("color({}, {}, {})", c.r, c.g, c.b),
+            Value::Boolean(b) => b.to_string(),
+            Value::Dictionary(m) => {
+                let mut keys = m.keys().collect::<Vec<_>>();
+                keys.sort();
+                let entries = keys
+                    .into_iter()
+                    .map(|k| format!("{}: {}", k, self.stringify(&m[k])))
+                    .collect::<Vec<_>>();
+                format!("{{{}}}", entries.join(", "))
+            }
+            Value::Array(a) => format!(
+                "[{}]",
+                a.iter()
+                    .map(|v| self.stringify(v))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Value::Ref(key, _) => match self.ref_objects.get(**key) {
+                Some(ro) => self.stringify(&ro.clone().into()),
+                None => String::from("<invalid reference>"),
+            },
+        }
+    }
+
+    /// Gets the value of a variable, somewhere along the stack, moving backwards.
+    /// This clones the value of the variable.
+    fn variable_value(&self, identifier: &String) -> Option<Value> {
+        for scope in self.scope_stack.iter().rev() {
+            if let Some(value) = scope.vars.get(identifier) {
+                return Some(value.to_owned());
+            }
+        }
+
+        None
+    }
+
+    /// Insert a new reference object into memory.
+    fn new_ref_obj(&mut self, obj: RefObject) -> Rc<slotmap::DefaultKey> {
+        let key = self.ref_objects.insert(obj);
+        let rc = Rc::new(key);
+        self.refs.push(Rc::clone(&rc));
+        rc
+    }
+
+    /// Pop the scope at the top of the scope stack.
+    /// This will also clean up unused reference objects.
+    fn pop_scope(&mut self) {
+        // pop from the top of the scope stack
+        drop(self.scope_stack.pop());
+
+        // now look through all of our ref objects and kill any with only one count
+        let ref_objects = &mut self.ref_objects;
+        self.refs.retain(|rc| {
+            if Rc::strong_count(rc) <= 1 {
+                ref_objects.remove(**rc);
+                false
+            } else {
+                true
+            }
+        });
+    }
+}
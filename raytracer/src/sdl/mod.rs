@@ -0,0 +1,24 @@
+//! The raytracer's scene description language (SDL): its tokenizer, AST and interpreter, so
+//! that applications can load `.sdl` scene files themselves. See the `sdl` crate's README for
+//! the language itself.
+
+use std::io::{Read, Seek};
+
+use crate::scene::Scene;
+
+mod ast;
+mod function;
+mod interpret;
+mod reference;
+mod tokenize;
+
+pub use ast::{AstError, NodeKind};
+pub use interpret::{InterpretError, Interpreter, Value};
+pub use tokenize::{Position, TokenizeError};
+
+/// Load a scene from SDL source. Relative asset and include paths are resolved against the
+/// working directory. For more control, like setting globals, caching SBVHs or resolving includes
+/// against the directory of a file, use an [`Interpreter`].
+pub fn load_scene<R: Read + Seek>(reader: R) -> Result<Scene, InterpretError> {
+    Interpreter::new(reader)?.run()
+}
@@ -1,4 +1,4 @@
-use crate::interpret::Value;
+use super::interpret::Value;
 
 /// A reference object value.
 #[derive(Debug, Clone)]
@@ -1,20 +1,69 @@
 use std::{
     fmt::{self, Display, Formatter},
-    io::{self, Read, Seek, SeekFrom},
+    io::{self, Cursor, Read, Seek, SeekFrom},
 };
 
 use thiserror::Error;
 
+use crate::math::Float;
+
 #[derive(Debug, Error)]
 pub enum TokenizeError {
     #[error("generic IO error: {0}")]
     Io(#[from] io::Error),
 
-    #[error("unexpected character {0}")]
-    UnexpectedCharacter(char),
+    #[error("{1}: unexpected character {0}")]
+    UnexpectedCharacter(char, Position),
+
+    #[error("{0}: error parsing number")]
+    NumberParseError(Position),
+
+    #[error("{0}: unclosed brace in interpolated string")]
+    UnclosedInterpolation(Position),
+}
+
+/// A position in SDL source, counting lines and columns from 1.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
 
-    #[error("error parsing number")]
-    NumberParseError,
+    /// The file the position is in: 0 for the source itself, or one more than the index of a
+    /// file it includes in [`Interpreter::includes`](super::Interpreter::includes).
+    pub file: usize,
+}
+
+impl Position {
+    /// The position of the first character of the source.
+    pub const START: Self = Self {
+        line: 1,
+        column: 1,
+        file: 0,
+    };
+
+    /// Make a position relative to the start of some text absolute, given the position the text
+    /// starts at.
+    fn relative_to(self, origin: Position) -> Self {
+        if self.line == 1 {
+            Self {
+                line: origin.line,
+                column: origin.column + self.column - 1,
+                file: origin.file,
+            }
+        } else {
+            Self {
+                line: origin.line + self.line - 1,
+                column: self.column,
+                file: origin.file,
+            }
+        }
+    }
+}
+
+impl Display for Position {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
 }
 
 /// An operator.
@@ -44,6 +93,10 @@ pub enum Op {
     Not,
     And,
     Or,
+
+    /// Unary minus. The tokenizer never produces this, since a `-` is only unary depending on
+    /// what comes before it, which the AST parser decides.
+    Neg,
 }
 
 /// A separator.
@@ -57,6 +110,17 @@ pub enum Sep {
     ParensClose,
     BracketOpen,
     BracketClose,
+    Dot,
+}
+
+/// A part of an interpolated string.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StringPart {
+    /// Literal text.
+    Text(String),
+
+    /// The tokens of an expression between braces, whose value is inserted into the string.
+    Expr(Vec<(Token, Position)>),
 }
 
 /// A token, some significant piece of data to be parsed by the AST.
@@ -74,8 +138,11 @@ pub enum Token {
     /// A string. Any text that is surrounded by quotes. Supports quote escaping.
     String(String),
 
+    /// A string with expressions between braces in it, like `"frame_{t}.png"`.
+    Interpolated(Vec<StringPart>),
+
     /// A number. Decimals optional.
-    Number(f64),
+    Number(Float),
 
     /// A boolean.
     Boolean(bool),
@@ -92,6 +159,7 @@ impl Display for Token {
             Self::Sep(Sep::ParensClose) => write!(f, ")"),
             Self::Sep(Sep::BracketOpen) => write!(f, "["),
             Self::Sep(Sep::BracketClose) => write!(f, "]"),
+            Self::Sep(Sep::Dot) => write!(f, "."),
 
             Self::Op(Op::Add) => write!(f, "+"),
             Self::Op(Op::Sub) => write!(f, "-"),
@@ -113,9 +181,29 @@ impl Display for Token {
             Self::Op(Op::And) => write!(f, "&&"),
             Self::Op(Op::Or) => write!(f, "||"),
             Self::Op(Op::Not) => write!(f, "!"),
+            Self::Op(Op::Neg) => write!(f, "-"),
 
             Self::Identifier(ident) => write!(f, "{}", ident),
             Self::String(str) => write!(f, "\"{}\"", str),
+            Self::Interpolated(parts) => {
+                write!(f, "\"")?;
+                for part in parts {
+                    match part {
+                        StringPart::Text(text) => write!(f, "{}", text)?,
+                        StringPart::Expr(tokens) => {
+                            write!(f, "{{")?;
+                            for (i, (token, _)) in tokens.iter().enumerate() {
+                                if i > 0 {
+                                    write!(f, " ")?;
+                                }
+                                write!(f, "{}", token)?;
+                            }
+                            write!(f, "}}")?;
+                        }
+                    }
+                }
+                write!(f, "\"")
+            }
             Self::Number(num) => write!(f, "{}", num),
             Self::Boolean(bool) => write!(f, "{}", if *bool { "true" } else { "false" }),
         }
@@ -138,18 +226,54 @@ impl From<Sep> for Token {
 /// parsed by the AST.
 pub struct Tokenizer<R: Read + Seek> {
     reader: R,
+
+    /// The offsets in the reader each line read so far starts at.
+    line_starts: Vec<u64>,
+
+    /// The position the reader starts at, for tokenizing text in the middle of other source.
+    origin: Position,
+
+    /// The position of the token being read.
+    start: Position,
 }
 
 impl<R: Read + Seek> Tokenizer<R> {
     pub fn new(reader: R) -> Self {
-        Tokenizer { reader }
+        Self::with_origin(reader, Position::START)
+    }
+
+    /// Instantiate a tokenizer of the `file`th file, whose positions are in that file (see
+    /// [`Position::file`]).
+    pub fn in_file(reader: R, file: usize) -> Self {
+        Self::with_origin(
+            reader,
+            Position {
+                file,
+                ..Position::START
+            },
+        )
+    }
+
+    /// Instantiate a tokenizer of text that starts at `origin` in some other source.
+    fn with_origin(reader: R, origin: Position) -> Self {
+        Tokenizer {
+            reader,
+            line_starts: vec![],
+            origin,
+            start: origin,
+        }
     }
 
-    /// Tokenize from the reader, converting to a `Result<Vec<Token>, TokenizeError>`.
-    pub fn tokenize(mut self) -> Result<Vec<Token>, TokenizeError> {
+    /// Tokenize from the reader, converting to a list of tokens and the positions they start at.
+    pub fn tokenize(mut self) -> Result<Vec<(Token, Position)>, TokenizeError> {
         let mut tokens = vec![];
+        let mut positions = vec![];
+
+        self.line_starts = vec![self.reader.stream_position()?];
 
         while let Ok(c) = self.peek_next() {
+            self.start = self.position()?;
+
             match c {
                 // whitespace: ignore
                 _ if c.is_whitespace() => self.skip()?,
@@ -162,6 +286,7 @@ impl<R: Read + Seek> Tokenizer<R> {
                         // handle keywords
                         "true" | "yes" => tokens.push(Token::Boolean(true)),
                         "false" | "no" => tokens.push(Token::Boolean(false)),
+                        "not" => tokens.push(Token::Op(Op::Not)),
 
                         // otherwise, this is a normal identifier
                         _ => tokens.push(Token::Identifier(ident)),
@@ -169,7 +294,7 @@ impl<R: Read + Seek> Tokenizer<R> {
                 }
 
                 // a quote: string
-                '"' => tokens.push(Token::String(self.read_string()?)),
+                '"' => tokens.push(self.read_string()?),
 
                 // a number: number
                 '0'..='9' => tokens.push(Token::Number(self.read_number(false)?)),
@@ -291,7 +416,7 @@ impl<R: Read + Seek> Tokenizer<R> {
                             tokens.push(Token::Op(Op::And));
                             self.skip()?;
                         }
-                        x => return Err(TokenizeError::UnexpectedCharacter(x)),
+                        x => return Err(TokenizeError::UnexpectedCharacter(x, self.start)),
                     }
                 }
                 '|' => {
@@ -301,22 +426,42 @@ impl<R: Read + Seek> Tokenizer<R> {
                             tokens.push(Token::Op(Op::And));
                             self.skip()?;
                         }
-                        x => return Err(TokenizeError::UnexpectedCharacter(x)),
+                        x => return Err(TokenizeError::UnexpectedCharacter(x, self.start)),
                     }
                 }
                 '.' => {
                     self.skip()?;
-                    match self.next()? {
-                        '.' => tokens.push(Op::RangeExclusive.into()),
-                        x => return Err(TokenizeError::UnexpectedCharacter(x)),
+                    match self.peek_next() {
+                        Ok('.') => {
+                            tokens.push(Op::RangeExclusive.into());
+                            self.skip()?;
+                        }
+                        _ => tokens.push(Sep::Dot.into()),
                     }
                 }
 
-                x => return Err(TokenizeError::UnexpectedCharacter(x)),
+                x => return Err(TokenizeError::UnexpectedCharacter(x, self.start)),
+            }
+
+            // every character read above is part of at most one token
+            if tokens.len() > positions.len() {
+                positions.push(self.start);
             }
         }
 
-        Ok(tokens)
+        Ok(tokens.into_iter().zip(positions).collect())
+    }
+
+    /// The position of the next character in the reader.
+    fn position(&mut self) -> Result<Position, io::Error> {
+        let offset = self.reader.stream_position()?;
+        let line = self.line_starts.partition_point(|&start| start <= offset);
+        Ok(Position {
+            line,
+            column: (offset - self.line_starts[line - 1]) as usize + 1,
+            ..Position::START
+        }
+        .relative_to(self.origin))
     }
 
     /// Read an identifier, which is just an alphanumeric bit of text.
@@ -327,19 +472,35 @@ impl<R: Read + Seek> Tokenizer<R> {
             .collect())
     }
 
-    /// Read a string, which is two quotations surrounding any amount of text.
-    fn read_string(&mut self) -> Result<String, TokenizeError> {
+    /// Read a string, which is two quotations surrounding any amount of text. Expressions between
+    /// unescaped braces are tokenized, making this an interpolated string.
+    fn read_string(&mut self) -> Result<Token, TokenizeError> {
         // skip the initial quotation
         self.skip()?;
 
         let mut escape = false;
         let mut string = String::new();
+        let mut parts = vec![];
 
         while let Ok(c) = self.next() {
             match c {
                 '"' if !escape => break,
                 '\\' if !escape => escape = true,
-                'n' if escape => string.push('\n'),
+                'n' if escape => {
+                    string.push('\n');
+                    escape = false;
+                }
+                '{' if !escape => {
+                    if !string.is_empty() {
+                        parts.push(StringPart::Text(std::mem::take(&mut string)));
+                    }
+                    let origin = self.position()?;
+                    let expr = self.read_interpolation()?;
+                    parts.push(StringPart::Expr(
+                        Tokenizer::with_origin(Cursor::new(expr.into_bytes()), origin)
+                            .tokenize()?,
+                    ));
+                }
                 c => {
                     string.push(c);
                     escape = false;
@@ -347,11 +508,48 @@ impl<R: Read + Seek> Tokenizer<R> {
             }
         }
 
-        Ok(string)
+        if parts.is_empty() {
+            Ok(Token::String(string))
+        } else {
+            if !string.is_empty() {
+                parts.push(StringPart::Text(string));
+            }
+            Ok(Token::Interpolated(parts))
+        }
+    }
+
+    /// Read the source of an expression in an interpolated string, up to its closing brace. The
+    /// opening brace has already been read.
+    fn read_interpolation(&mut self) -> Result<String, TokenizeError> {
+        let mut depth = 0;
+        let mut in_string = false;
+        let mut escape = false;
+        let mut expr = String::new();
+
+        loop {
+            let c = self
+                .next()
+                .map_err(|_| TokenizeError::UnclosedInterpolation(self.start))?;
+            match c {
+                // strings in the expression may have braces of their own
+                '"' if !escape => in_string = !in_string,
+                '\\' if in_string && !escape => {
+                    escape = true;
+                    expr.push(c);
+                    continue;
+                }
+                '{' if !in_string => depth += 1,
+                '}' if !in_string && depth == 0 => return Ok(expr),
+                '}' if !in_string => depth -= 1,
+                _ => (),
+            }
+            escape = false;
+            expr.push(c);
+        }
     }
 
-    /// Read a number, which is an f64. Decimal optional.
-    fn read_number(&mut self, def_negative: bool) -> Result<f64, TokenizeError> {
+    /// Read a number, which is a `Float`. Decimal optional.
+    fn read_number(&mut self, def_negative: bool) -> Result<Float, TokenizeError> {
         let negative = if def_negative {
             true
         } else {
@@ -373,8 +571,15 @@ impl<R: Read + Seek> Tokenizer<R> {
             };
 
             match c {
-                '.' if dec_seen => return Err(TokenizeError::UnexpectedCharacter('.')),
-                '.' if !dec_seen => dec_seen = true,
+                '.' if dec_seen => return Err(TokenizeError::UnexpectedCharacter('.', self.start)),
+                '.' if !dec_seen => {
+                    // a range operator right after a number, like `1..3`, isn't a decimal point
+                    if let Ok('.') = self.peek_next() {
+                        self.back()?;
+                        break;
+                    }
+                    dec_seen = true;
+                }
                 '0'..='9' => {
                     if dec_seen {
                         post_dec.push(c);
@@ -390,7 +595,7 @@ impl<R: Read + Seek> Tokenizer<R> {
         }
 
         match (pre_dec.is_empty(), post_dec.is_empty()) {
-            (true, true) => return Err(TokenizeError::NumberParseError),
+            (true, true) => return Err(TokenizeError::NumberParseError(self.start)),
             (true, false) => pre_dec.push('0'),
             (false, true) => post_dec.push('0'),
             _ => (),
@@ -398,14 +603,24 @@ impl<R: Read + Seek> Tokenizer<R> {
 
         format!("{}.{}", pre_dec, post_dec)
             .parse()
-            .map(|n: f64| if negative { -n } else { n })
-            .map_err(|_| TokenizeError::NumberParseError)
+            .map(|n: Float| if negative { -n } else { n })
+            .map_err(|_| TokenizeError::NumberParseError(self.start))
     }
 
     /// Read the next character in the reader, an `Option<char>`.
     fn next(&mut self) -> Result<char, io::Error> {
         let mut byte = [0u8];
-        self.reader.read_exact(&mut byte).map(|_| byte[0] as char)
+        self.reader.read_exact(&mut byte)?;
+
+        // keep track of where lines start, the first time their newline is read
+        if byte[0] == b'\n' {
+            let start = self.reader.stream_position()?;
+            if self.line_starts.last().is_none_or(|&last| start > last) {
+                self.line_starts.push(start);
+            }
+        }
+
+        Ok(byte[0] as char)
     }
 
     /// Skips the next character in the reader.
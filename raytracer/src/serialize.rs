@@ -0,0 +1,543 @@
+//! Serialization of scenes with [serde](https://serde.rs), behind the `serde` feature, so that
+//! a [`Scene`](crate::scene::Scene) can be saved, diffed and exchanged as JSON, RON or any
+//! other format serde supports, without going through the SDL.
+//!
+//! Objects, lights and skyboxes are trait objects, so each is written with a `type` field
+//! naming its kind, like `"type": "sphere"`, alongside its own fields. Wrappers like
+//! [`Flagged`] nest the object they wrap. Only the kinds in this crate can be serialized:
+//! serializing any other implementation of the traits fails, as does serializing a
+//! [`FunctionTexture`] or an SDF [`Distance`], since closures can't be written out.
+//!
+//! Acceleration structures and other derived data aren't written. Meshes get their SBVH (or
+//! kd-tree) rebuilt when they are read back, and image textures and cubemaps their mipmaps.
+
+use serde::{de::Error as _, ser::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    lighting::{Area, Grouped, Light, LightLinks, Linked, Point, Spot, Sun},
+    material::{
+        Color, ColorSpace, FunctionTexture, ImageTexture, Material, TextureFilter, WrapMode,
+    },
+    math::{Float, Matrix, Vector2, Vector3},
+    object::{
+        Aabb, Cone, Csg, Disk, Distance, Flagged, Instance, Mesh, MeshPart, Metaballs, Plane, Quad,
+        RenderFlags, SceneObject, Sdf, Sphere, Transformed, Triangle,
+    },
+    skybox::{Atmosphere, Cubemap, Gradient, Normal, Rotated, Scaled, Skybox, Solid},
+};
+
+/// A scene object, tagged with its kind for serialization. See [`SceneObject::tagged`].
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TaggedObject<'a> {
+    Sphere(&'a Sphere),
+    Plane(&'a Plane),
+    Disk(&'a Disk),
+    Quad(&'a Quad),
+    Triangle(&'a Triangle),
+    Cone(&'a Cone),
+    Aabb(&'a Aabb),
+    Metaballs(&'a Metaballs),
+    Mesh(&'a Mesh),
+    Instance(&'a Instance),
+    Csg(&'a Csg),
+    Sdf(&'a Sdf),
+    Flagged {
+        object: &'a dyn SceneObject,
+        flags: RenderFlags,
+    },
+    Transformed {
+        object: &'a dyn SceneObject,
+        transform: Matrix,
+    },
+
+    /// An object of a kind this crate doesn't know how to serialize.
+    #[serde(serialize_with = "unsupported_object")]
+    Unsupported(&'static str),
+}
+
+/// The owned counterpart of `TaggedObject`, to deserialize objects into.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum AnyObject {
+    Sphere(Sphere),
+    Plane(Plane),
+    Disk(Disk),
+    Quad(Quad),
+    Triangle(Triangle),
+    Cone(Cone),
+    Aabb(Aabb),
+    Metaballs(Metaballs),
+    Mesh(Box<Mesh>),
+    Instance(Instance),
+    Csg(Csg),
+    Sdf(Sdf),
+    Flagged {
+        object: Box<dyn SceneObject>,
+        flags: RenderFlags,
+    },
+    Transformed {
+        object: Box<dyn SceneObject>,
+        transform: Matrix,
+    },
+}
+
+impl From<AnyObject> for Box<dyn SceneObject> {
+    fn from(object: AnyObject) -> Self {
+        match object {
+            AnyObject::Sphere(o) => Box::new(o),
+            AnyObject::Plane(o) => Box::new(o),
+            AnyObject::Disk(o) => Box::new(o),
+            AnyObject::Quad(o) => Box::new(o),
+            AnyObject::Triangle(o) => Box::new(o),
+            AnyObject::Cone(o) => Box::new(o),
+            AnyObject::Aabb(o) => Box::new(o),
+            AnyObject::Metaballs(o) => Box::new(o),
+            AnyObject::Mesh(o) => o,
+            AnyObject::Instance(o) => Box::new(o),
+            AnyObject::Csg(o) => Box::new(o),
+            AnyObject::Sdf(o) => Box::new(o),
+            AnyObject::Flagged { object, flags } => Box::new(Flagged::new(object, flags)),
+            AnyObject::Transformed { object, transform } => {
+                Box::new(Transformed::new(object, transform))
+            }
+        }
+    }
+}
+
+impl Serialize for dyn SceneObject + '_ {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.tagged().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Box<dyn SceneObject> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        AnyObject::deserialize(deserializer).map(Into::into)
+    }
+}
+
+fn unsupported_object<S: Serializer>(kind: &&'static str, _: S) -> Result<S::Ok, S::Error> {
+    Err(S::Error::custom(format!(
+        "{} objects can't be serialized",
+        kind
+    )))
+}
+
+/// A light, tagged with its kind for serialization. See [`Light::tagged`].
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TaggedLight<'a> {
+    Point(&'a Point),
+    Sun(&'a Sun),
+    Spot(&'a Spot),
+    Area(&'a Area),
+    Linked {
+        light: &'a dyn Light,
+        links: &'a LightLinks,
+    },
+    Grouped {
+        light: &'a dyn Light,
+        group: &'a str,
+    },
+
+    /// A light of a kind this crate doesn't know how to serialize.
+    #[serde(serialize_with = "unsupported_light")]
+    Unsupported(&'static str),
+}
+
+/// The owned counterpart of `TaggedLight`, to deserialize lights into.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum AnyLight {
+    Point(Point),
+    Sun(Sun),
+    Spot(Spot),
+    Area(Area),
+    Linked {
+        light: Box<dyn Light>,
+        links: LightLinks,
+    },
+    Grouped {
+        light: Box<dyn Light>,
+        group: String,
+    },
+}
+
+impl From<AnyLight> for Box<dyn Light> {
+    fn from(light: AnyLight) -> Self {
+        match light {
+            AnyLight::Point(l) => Box::new(l),
+            AnyLight::Sun(l) => Box::new(l),
+            AnyLight::Spot(l) => Box::new(l),
+            AnyLight::Area(l) => Box::new(l),
+            AnyLight::Linked { light, links } => Box::new(Linked::new(light, links)),
+            AnyLight::Grouped { light, group } => Box::new(Grouped::new(light, group)),
+        }
+    }
+}
+
+impl Serialize for dyn Light + '_ {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.tagged().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Box<dyn Light> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        AnyLight::deserialize(deserializer).map(Into::into)
+    }
+}
+
+fn unsupported_light<S: Serializer>(kind: &&'static str, _: S) -> Result<S::Ok, S::Error> {
+    Err(S::Error::custom(format!(
+        "{} lights can't be serialized",
+        kind
+    )))
+}
+
+/// A skybox, tagged with its kind for serialization. See [`Skybox::tagged`].
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum TaggedSkybox<'a> {
+    Solid(&'a Solid),
+    Normal,
+    Gradient(&'a Gradient),
+    Atmosphere(&'a Atmosphere),
+    Cubemap(&'a Cubemap),
+    Scaled {
+        skybox: &'a dyn Skybox,
+        intensity: Float,
+    },
+    Rotated {
+        skybox: &'a dyn Skybox,
+        rotation: Matrix,
+    },
+
+    /// A skybox of a kind this crate doesn't know how to serialize.
+    #[serde(serialize_with = "unsupported_skybox")]
+    Unsupported,
+}
+
+/// The owned counterpart of `TaggedSkybox`, to deserialize skyboxes into.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum AnySkybox {
+    Solid(Solid),
+    Normal,
+    Gradient(Gradient),
+    Atmosphere(Atmosphere),
+    Cubemap(Cubemap),
+    Scaled {
+        skybox: Box<dyn Skybox>,
+        intensity: Float,
+    },
+    Rotated {
+        skybox: Box<dyn Skybox>,
+        rotation: Matrix,
+    },
+}
+
+impl From<AnySkybox> for Box<dyn Skybox> {
+    fn from(skybox: AnySkybox) -> Self {
+        match skybox {
+            AnySkybox::Solid(s) => Box::new(s),
+            AnySkybox::Normal => Box::new(Normal),
+            AnySkybox::Gradient(s) => Box::new(s),
+            AnySkybox::Atmosphere(s) => Box::new(s),
+            AnySkybox::Cubemap(s) => Box::new(s),
+            AnySkybox::Scaled { skybox, intensity } => Box::new(Scaled::new(skybox, intensity)),
+            AnySkybox::Rotated { skybox, rotation } => Box::new(Rotated::new(skybox, rotation)),
+        }
+    }
+}
+
+impl Serialize for dyn Skybox + '_ {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.tagged().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Box<dyn Skybox> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        AnySkybox::deserialize(deserializer).map(Into::into)
+    }
+}
+
+fn unsupported_skybox<S: Serializer>(_: S) -> Result<S::Ok, S::Error> {
+    Err(S::Error::custom("this kind of skybox can't be serialized"))
+}
+
+/// The data of a `Mesh`, without its acceleration structures.
+#[derive(Serialize)]
+struct MeshRef<'a> {
+    verts: &'a [Vector3],
+    tris: &'a [[usize; 3]],
+    normals: &'a [Vector3],
+    tri_normals: &'a [[usize; 3]],
+    texcoords: &'a [Vector2],
+    tri_texcoords: &'a [[usize; 3]],
+    colors: &'a [Color],
+    flat_shading: bool,
+    backface_culling: bool,
+    material: &'a Material,
+    face_materials: &'a [Material],
+    tri_materials: &'a [Option<usize>],
+    parts: &'a [MeshPart],
+
+    /// Whether the mesh is traced with a kd-tree rather than an SBVH.
+    kdtree: bool,
+
+    /// Whether the mesh is traced with an Embree BVH rather than an SBVH.
+    #[cfg(feature = "embree")]
+    embree: bool,
+}
+
+/// The owned counterpart of `MeshRef`, to deserialize meshes into.
+#[derive(Deserialize)]
+struct MeshData {
+    verts: Vec<Vector3>,
+    tris: Vec<[usize; 3]>,
+    #[serde(default)]
+    normals: Vec<Vector3>,
+    #[serde(default)]
+    tri_normals: Vec<[usize; 3]>,
+    #[serde(default)]
+    texcoords: Vec<Vector2>,
+    #[serde(default)]
+    tri_texcoords: Vec<[usize; 3]>,
+    #[serde(default)]
+    colors: Vec<Color>,
+    #[serde(default)]
+    flat_shading: bool,
+    #[serde(default)]
+    backface_culling: bool,
+    #[serde(default)]
+    material: Material,
+    #[serde(default)]
+    face_materials: Vec<Material>,
+    #[serde(default)]
+    tri_materials: Vec<Option<usize>>,
+    #[serde(default)]
+    parts: Vec<MeshPart>,
+    #[serde(default)]
+    kdtree: bool,
+    #[cfg(feature = "embree")]
+    #[serde(default)]
+    embree: bool,
+}
+
+impl Serialize for Mesh {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        MeshRef {
+            verts: &self.verts,
+            tris: &self.tris,
+            normals: &self.normals,
+            tri_normals: &self.tri_normals,
+            texcoords: &self.texcoords,
+            tri_texcoords: &self.tri_texcoords,
+            colors: &self.colors,
+            flat_shading: self.flat_shading,
+            backface_culling: self.backface_culling,
+            material: &self.material,
+            face_materials: &self.face_materials,
+            tri_materials: &self.tri_materials,
+            parts: &self.parts,
+            kdtree: self.kdtree.is_some(),
+            #[cfg(feature = "embree")]
+            embree: self.embree.is_some(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Mesh {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let data = MeshData::deserialize(deserializer)?;
+
+        // indices out of range would panic when the mesh is traced, rather than here
+        let in_range = |tris: &[[usize; 3]], len: usize| tris.iter().flatten().all(|&i| i < len);
+        if !in_range(&data.tris, data.verts.len()) {
+            return Err(D::Error::custom("mesh triangle refers to a missing vertex"));
+        }
+        if !in_range(&data.tri_normals, data.normals.len()) {
+            return Err(D::Error::custom("mesh triangle refers to a missing normal"));
+        }
+        if !in_range(&data.tri_texcoords, data.texcoords.len()) {
+            return Err(D::Error::custom(
+                "mesh triangle refers to missing texture coordinates",
+            ));
+        }
+        if data
+            .tri_materials
+            .iter()
+            .flatten()
+            .any(|&i| i >= data.face_materials.len())
+        {
+            return Err(D::Error::custom(
+                "mesh triangle refers to a missing material",
+            ));
+        }
+
+        let mut mesh = Mesh {
+            verts: data.verts,
+            tris: data.tris,
+            normals: data.normals,
+            tri_normals: data.tri_normals,
+            texcoords: data.texcoords,
+            tri_texcoords: data.tri_texcoords,
+            colors: data.colors,
+            flat_shading: data.flat_shading,
+            backface_culling: data.backface_culling,
+            material: data.material,
+            face_materials: data.face_materials,
+            tri_materials: data.tri_materials,
+            parts: data.parts,
+            sbvh: None,
+            kdtree: None,
+            #[cfg(feature = "embree")]
+            embree: None,
+        };
+        #[cfg(feature = "embree")]
+        if data.embree {
+            mesh.generate_embree();
+            return Ok(mesh);
+        }
+        if data.kdtree {
+            mesh.generate_kdtree();
+        } else {
+            mesh.generate_sbvh();
+        }
+
+        Ok(mesh)
+    }
+}
+
+/// The pixels of an 8-bit image, row by row from the top left.
+#[derive(Serialize, Deserialize)]
+struct ImageData<T> {
+    width: u32,
+    height: u32,
+    pixels: T,
+}
+
+impl ImageData<Vec<u8>> {
+    /// Get the image back, checking that there is a value for every channel of every pixel.
+    fn into_image<P, E>(self) -> Result<image::ImageBuffer<P, Vec<u8>>, E>
+    where
+        P: image::Pixel<Subpixel = u8> + 'static,
+        E: serde::de::Error,
+    {
+        image::ImageBuffer::from_raw(self.width, self.height, self.pixels)
+            .ok_or_else(|| E::custom("image doesn't have a value for every pixel"))
+    }
+}
+
+/// The data of an `ImageTexture`, without its mipmaps.
+#[derive(Serialize)]
+struct ImageTextureRef<'a> {
+    image: ImageData<&'a [u8]>,
+    mipmaps: bool,
+    decoded: bool,
+    filter: TextureFilter,
+    wrap: WrapMode,
+    color_space: ColorSpace,
+}
+
+/// The owned counterpart of `ImageTextureRef`, to deserialize image textures into.
+#[derive(Deserialize)]
+struct ImageTextureData {
+    image: ImageData<Vec<u8>>,
+    mipmaps: bool,
+    #[serde(default)]
+    decoded: bool,
+    filter: TextureFilter,
+    wrap: WrapMode,
+    color_space: ColorSpace,
+}
+
+impl Serialize for ImageTexture {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let image = self.image();
+        ImageTextureRef {
+            image: ImageData {
+                width: image.width(),
+                height: image.height(),
+                pixels: image.as_raw(),
+            },
+            mipmaps: self.has_mipmaps(),
+            decoded: self.is_decoded(),
+            filter: self.filter,
+            wrap: self.wrap,
+            color_space: self.color_space,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for ImageTexture {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let data = ImageTextureData::deserialize(deserializer)?;
+        let mut texture = ImageTexture::from_rgba(data.image.into_image()?)
+            .with_filter(data.filter)
+            .with_wrap(data.wrap)
+            .with_color_space(data.color_space);
+        if data.decoded {
+            texture = texture.decoded();
+        }
+        if data.mipmaps {
+            texture = texture.with_mipmaps();
+        }
+
+        Ok(texture)
+    }
+}
+
+impl Serialize for FunctionTexture {
+    fn serialize<S: Serializer>(&self, _: S) -> Result<S::Ok, S::Error> {
+        Err(S::Error::custom("function textures can't be serialized"))
+    }
+}
+
+impl<'de> Deserialize<'de> for FunctionTexture {
+    fn deserialize<D: Deserializer<'de>>(_: D) -> Result<Self, D::Error> {
+        Err(D::Error::custom("function textures can't be deserialized"))
+    }
+}
+
+impl Serialize for Distance {
+    fn serialize<S: Serializer>(&self, _: S) -> Result<S::Ok, S::Error> {
+        Err(S::Error::custom("distance functions can't be serialized"))
+    }
+}
+
+impl<'de> Deserialize<'de> for Distance {
+    fn deserialize<D: Deserializer<'de>>(_: D) -> Result<Self, D::Error> {
+        Err(D::Error::custom("distance functions can't be deserialized"))
+    }
+}
+
+impl Serialize for Cubemap {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let image = self.image();
+        ImageData {
+            width: image.width(),
+            height: image.height(),
+            pixels: image.as_raw().as_slice(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Cubemap {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let data = ImageData::<Vec<u8>>::deserialize(deserializer)?;
+        if data.width / 4 != data.height / 3 {
+            return Err(D::Error::custom(
+                "cubemap image isn't a cross of square faces",
+            ));
+        }
+
+        Ok(Cubemap::new(data.into_image()?))
+    }
+}
@@ -1,35 +1,479 @@
 use crate::{
+    checkpoint::Fingerprint,
+    lighting::Sun,
     material::Color,
-    math::{blerp, Ray, Vector3},
+    math::{blerp, Float, Lerp, Matrix, Ray, Vector2, Vector3},
     object::{AabbIntersector, Hit, Intersect},
 };
 
+#[cfg(feature = "serde")]
+use crate::serialize::TaggedSkybox;
+
 /// Any type that can be used as a skybox for a scene.
 pub trait Skybox: Send + Sync {
     /// The color a ray should produce for the skybox.
     fn ray_color(&self, ray: &Ray) -> Color;
+
+    /// The color a ray reflected off of a surface of `roughness` (from 0 to 1) should produce
+    /// for the skybox, blurred for rougher surfaces. Skyboxes without sharp details don't need
+    /// to blur, so by default this is just the ray's color.
+    fn ray_color_rough(&self, ray: &Ray, _roughness: Float) -> Color {
+        self.ray_color(ray)
+    }
+
+    /// How much to brighten (or darken) the skybox's colors by, to balance it against the
+    /// scene's lights. See [`Scaled`].
+    fn intensity(&self) -> Float {
+        1.
+    }
+
+    /// The memory taken by the texels of this skybox's images, in bytes. Procedural skyboxes
+    /// have none.
+    fn texture_bytes(&self) -> usize {
+        0
+    }
+
+    /// A boxed copy of this skybox, so that scenes holding it can be cloned.
+    fn clone_box(&self) -> Box<dyn Skybox>;
+
+    /// Hash everything about this skybox that changes how it renders, so that a checkpoint of
+    /// one scene isn't resumed by another. By default, only its intensity is hashed.
+    fn fingerprint(&self, hasher: &mut Fingerprint) {
+        hasher.write_debug(&self.intensity());
+    }
+
+    /// This skybox, tagged with its kind so that it can be serialized (see
+    /// [`serialize`](crate::serialize)). Skyboxes from outside this crate can't be.
+    #[cfg(feature = "serde")]
+    fn tagged(&self) -> TaggedSkybox<'_> {
+        TaggedSkybox::Unsupported
+    }
+}
+
+impl<T: Skybox + ?Sized> Skybox for Box<T> {
+    fn ray_color(&self, ray: &Ray) -> Color {
+        (**self).ray_color(ray)
+    }
+
+    fn ray_color_rough(&self, ray: &Ray, roughness: Float) -> Color {
+        (**self).ray_color_rough(ray, roughness)
+    }
+
+    fn intensity(&self) -> Float {
+        (**self).intensity()
+    }
+
+    fn texture_bytes(&self) -> usize {
+        (**self).texture_bytes()
+    }
+
+    fn clone_box(&self) -> Box<dyn Skybox> {
+        (**self).clone_box()
+    }
+
+    fn fingerprint(&self, hasher: &mut Fingerprint) {
+        (**self).fingerprint(hasher)
+    }
+
+    #[cfg(feature = "serde")]
+    fn tagged(&self) -> TaggedSkybox<'_> {
+        (**self).tagged()
+    }
+}
+
+impl Clone for Box<dyn Skybox> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+/// A skybox brightened or darkened by an intensity factor.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Scaled<S: Skybox> {
+    /// The skybox being scaled.
+    pub skybox: S,
+
+    /// The factor the skybox's colors are multiplied by.
+    pub intensity: Float,
+}
+
+impl<S: Skybox> Scaled<S> {
+    pub fn new(skybox: S, intensity: Float) -> Self {
+        Self { skybox, intensity }
+    }
+}
+
+impl<S: Skybox> Skybox for Scaled<S> {
+    fn ray_color(&self, ray: &Ray) -> Color {
+        self.skybox.ray_color(ray)
+    }
+
+    fn ray_color_rough(&self, ray: &Ray, roughness: Float) -> Color {
+        self.skybox.ray_color_rough(ray, roughness)
+    }
+
+    fn intensity(&self) -> Float {
+        self.skybox.intensity() * self.intensity
+    }
+
+    fn texture_bytes(&self) -> usize {
+        self.skybox.texture_bytes()
+    }
+
+    fn clone_box(&self) -> Box<dyn Skybox> {
+        Box::new(Scaled::new(self.skybox.clone_box(), self.intensity))
+    }
+
+    fn fingerprint(&self, hasher: &mut Fingerprint) {
+        hasher.write_debug(&self.intensity);
+        self.skybox.fingerprint(hasher);
+    }
+
+    #[cfg(feature = "serde")]
+    fn tagged(&self) -> TaggedSkybox<'_> {
+        TaggedSkybox::Scaled {
+            skybox: &self.skybox,
+            intensity: self.intensity,
+        }
+    }
+}
+
+/// A skybox rotated by a rotation matrix, for example to spin a cubemap around to frame the
+/// background without editing its image.
+#[derive(Debug, Clone)]
+pub struct Rotated<S: Skybox> {
+    /// The skybox being rotated.
+    pub skybox: S,
+
+    rotation: Matrix,
+    inverse: Matrix,
+}
+
+impl<S: Skybox> Rotated<S> {
+    /// Instantiate a new `Rotated` from a skybox and a rotation matrix.
+    pub fn new(skybox: S, rotation: Matrix) -> Self {
+        Self {
+            skybox,
+            rotation,
+            inverse: rotation.inverse(),
+        }
+    }
+
+    /// Instantiate a new `Rotated` from a skybox spun around the up axis by `yaw` radians.
+    pub fn from_yaw(skybox: S, yaw: Float) -> Self {
+        Self::new(skybox, Matrix::from_euler_xyz(0., yaw, 0.))
+    }
+
+    /// Gets the rotation of the skybox.
+    pub fn rotation(&self) -> Matrix {
+        self.rotation
+    }
+
+    /// Replace the rotation of the skybox.
+    pub fn set_rotation(&mut self, rotation: Matrix) {
+        self.rotation = rotation;
+        self.inverse = rotation.inverse();
+    }
+
+    /// Rotate a ray to look up the direction the rotation moved onto it.
+    fn unrotate(&self, ray: &Ray) -> Ray {
+        Ray::new(ray.origin, self.inverse.transform_vector(ray.direction))
+    }
+}
+
+impl<S: Skybox> Skybox for Rotated<S> {
+    fn ray_color(&self, ray: &Ray) -> Color {
+        self.skybox.ray_color(&self.unrotate(ray))
+    }
+
+    fn ray_color_rough(&self, ray: &Ray, roughness: Float) -> Color {
+        self.skybox.ray_color_rough(&self.unrotate(ray), roughness)
+    }
+
+    fn intensity(&self) -> Float {
+        self.skybox.intensity()
+    }
+
+    fn texture_bytes(&self) -> usize {
+        self.skybox.texture_bytes()
+    }
+
+    fn clone_box(&self) -> Box<dyn Skybox> {
+        Box::new(Rotated {
+            skybox: self.skybox.clone_box(),
+            rotation: self.rotation,
+            inverse: self.inverse,
+        })
+    }
+
+    fn fingerprint(&self, hasher: &mut Fingerprint) {
+        hasher.write_debug(&self.rotation);
+        self.skybox.fingerprint(hasher);
+    }
+
+    #[cfg(feature = "serde")]
+    fn tagged(&self) -> TaggedSkybox<'_> {
+        TaggedSkybox::Rotated {
+            skybox: &self.skybox,
+            rotation: self.rotation,
+        }
+    }
 }
 
 /// A solid color skybox.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Solid(pub Color);
 
 impl Skybox for Solid {
     fn ray_color(&self, _: &Ray) -> Color {
         self.0
     }
+
+    fn clone_box(&self) -> Box<dyn Skybox> {
+        Box::new(self.clone())
+    }
+
+    fn fingerprint(&self, hasher: &mut Fingerprint) {
+        hasher.write_debug(self);
+    }
+
+    #[cfg(feature = "serde")]
+    fn tagged(&self) -> TaggedSkybox<'_> {
+        TaggedSkybox::Solid(self)
+    }
 }
 
 /// A skybox that creates a color from the ray's direction as if it were a normal.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Normal;
 
 impl Skybox for Normal {
     fn ray_color(&self, ray: &Ray) -> Color {
         Color::from_normal(ray.direction)
     }
+
+    fn clone_box(&self) -> Box<dyn Skybox> {
+        Box::new(self.clone())
+    }
+
+    fn fingerprint(&self, hasher: &mut Fingerprint) {
+        hasher.write_debug(self);
+    }
+
+    #[cfg(feature = "serde")]
+    fn tagged(&self) -> TaggedSkybox<'_> {
+        TaggedSkybox::Normal
+    }
+}
+
+/// A procedural sky that blends from the horizon color up to the zenith color above, and down
+/// to the ground color below, for quick scenes without any image assets.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Gradient {
+    /// The color straight up.
+    pub zenith: Color,
+
+    /// The color at the horizon.
+    pub horizon: Color,
+
+    /// The color straight down.
+    pub ground: Color,
+}
+
+impl Default for Gradient {
+    fn default() -> Self {
+        Self {
+            zenith: Color::new(60, 120, 220),
+            horizon: Color::new(200, 220, 240),
+            ground: Color::new(90, 80, 70),
+        }
+    }
+}
+
+impl Skybox for Gradient {
+    fn ray_color(&self, ray: &Ray) -> Color {
+        // blend by the sine of the elevation, eased so the colors meet smoothly
+        let y = ray.direction.normalize().y.clamp(-1., 1.);
+        let t = y.abs() * y.abs() * (3. - 2. * y.abs());
+        let end = if y >= 0. { self.zenith } else { self.ground };
+
+        Vector3::from(self.horizon).lerp(end.into(), t).into()
+    }
+
+    fn clone_box(&self) -> Box<dyn Skybox> {
+        Box::new(self.clone())
+    }
+
+    fn fingerprint(&self, hasher: &mut Fingerprint) {
+        hasher.write_debug(self);
+    }
+
+    #[cfg(feature = "serde")]
+    fn tagged(&self) -> TaggedSkybox<'_> {
+        TaggedSkybox::Gradient(self)
+    }
+}
+
+/// A physically based daytime sky, using the Preetham model of how sunlight scatters through a
+/// clear atmosphere, with a visible sun disc.
+///
+/// Build it with [`Atmosphere::from_sun`] to keep it consistent with the scene's sun light.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Atmosphere {
+    /// The vector direction sunlight travels in, like [`Sun::vector`].
+    pub vector: Vector3,
+
+    /// How hazy the air is, from about 2 (a very clear day) to 10 (a hazy one).
+    pub turbidity: Float,
+
+    /// The brightness of the sky. The zenith has a luminance of 1 before this is applied.
+    pub exposure: Float,
+
+    /// The angular radius (in radians) of the visible sun disc.
+    pub sun_radius: Float,
+
+    /// The color below the horizon.
+    pub ground: Color,
+}
+
+impl Default for Atmosphere {
+    fn default() -> Self {
+        Self {
+            vector: Vector3::new(0., -1., 0.),
+            turbidity: 3.,
+            exposure: 1.,
+            sun_radius: 0.01,
+            ground: Color::new(90, 80, 70),
+        }
+    }
+}
+
+/// The coefficients of the Perez sky luminance distribution for a turbidity.
+type Perez = [Float; 5];
+
+impl Atmosphere {
+    /// Create a sky lit by a sun light, with its sun disc the size of the light's (if it has one).
+    pub fn from_sun(sun: &Sun) -> Self {
+        let default = Self::default();
+        Self {
+            vector: sun.vector.normalize(),
+            sun_radius: if sun.angular_radius > 0. {
+                sun.angular_radius
+            } else {
+                default.sun_radius
+            },
+            ..default
+        }
+    }
+
+    /// The Perez distribution, relative to the zenith, for a view `theta` from the zenith
+    /// and `gamma` from the sun.
+    fn perez(c: &Perez, theta: Float, gamma: Float) -> Float {
+        (1. + c[0] * (c[1] / theta.cos()).exp())
+            * (1. + c[2] * (c[3] * gamma).exp() + c[4] * gamma.cos().powi(2))
+    }
+
+    /// The luminance (Y) and chromaticity (x, y) of the sky, relative to the luminance of the
+    /// zenith, for a view `theta` from the zenith and `gamma` from the sun, when the sun is
+    /// `theta_s` from the zenith.
+    fn sky_xyy(&self, theta: Float, gamma: Float, theta_s: Float) -> Vector3 {
+        let t = self.turbidity;
+        let coefficients: [Perez; 3] = [
+            [
+                0.1787 * t - 1.4630,
+                -0.3554 * t + 0.4275,
+                -0.0227 * t + 5.3251,
+                0.1206 * t - 2.5771,
+                -0.0670 * t + 0.3703,
+            ],
+            [
+                -0.0193 * t - 0.2592,
+                -0.0665 * t + 0.0008,
+                -0.0004 * t + 0.2125,
+                -0.0641 * t - 0.8989,
+                -0.0033 * t + 0.0452,
+            ],
+            [
+                -0.0167 * t - 0.2608,
+                -0.0950 * t + 0.0092,
+                -0.0079 * t + 0.2102,
+                -0.0441 * t - 1.6537,
+                -0.0109 * t + 0.0529,
+            ],
+        ];
+
+        // the chromaticity of the zenith
+        let (t2, s, s2, s3) = (t * t, theta_s, theta_s * theta_s, theta_s.powi(3));
+        let xz = t2 * (0.00166 * s3 - 0.00375 * s2 + 0.00209 * s)
+            + t * (-0.02903 * s3 + 0.06377 * s2 - 0.03202 * s + 0.00394)
+            + (0.11693 * s3 - 0.21196 * s2 + 0.06052 * s + 0.25886);
+        let yz = t2 * (0.00275 * s3 - 0.00610 * s2 + 0.00317 * s)
+            + t * (-0.04214 * s3 + 0.08970 * s2 - 0.04153 * s + 0.00516)
+            + (0.15346 * s3 - 0.26756 * s2 + 0.06670 * s + 0.26688);
+
+        let [cy, cx, cyy] = &coefficients;
+        let relative = |c: &Perez| Self::perez(c, theta, gamma) / Self::perez(c, 0., theta_s);
+        Vector3::new(relative(cy), xz * relative(cx), yz * relative(cyy))
+    }
 }
 
+impl Skybox for Atmosphere {
+    fn ray_color(&self, ray: &Ray) -> Color {
+        let direction = ray.direction.normalize();
+        if direction.y < 0. {
+            return self.ground;
+        }
+
+        let to_sun = -self.vector.normalize();
+        let theta = direction.y.clamp(0.001, 1.).acos();
+        let theta_s = to_sun.y.clamp(0., 1.).acos();
+        let gamma = direction.dot(to_sun).clamp(-1., 1.).acos();
+
+        let xyy = self.sky_xyy(theta, gamma, theta_s);
+        let (luminance, x, y) = (xyy.x * self.exposure, xyy.y, xyy.z);
+
+        // tone map the luminance, then convert from CIE XYZ to linear sRGB
+        let luminance = 1. - (-luminance).exp();
+        let (cx, cz) = (x / y * luminance, (1. - x - y) / y * luminance);
+        let mut color = Vector3::new(
+            3.2406 * cx - 1.5372 * luminance - 0.4986 * cz,
+            -0.9689 * cx + 1.8758 * luminance + 0.0415 * cz,
+            0.0557 * cx - 0.2040 * luminance + 1.0570 * cz,
+        );
+
+        if gamma < self.sun_radius {
+            color = Vector3::new(1., 1., 1.);
+        }
+
+        Color::from_linear(color)
+    }
+
+    fn clone_box(&self) -> Box<dyn Skybox> {
+        Box::new(self.clone())
+    }
+
+    fn fingerprint(&self, hasher: &mut Fingerprint) {
+        hasher.write_debug(self);
+    }
+
+    #[cfg(feature = "serde")]
+    fn tagged(&self) -> TaggedSkybox<'_> {
+        TaggedSkybox::Atmosphere(self)
+    }
+}
+
+/// The cells of the faces of a cubemap in its cross, in the order x-positive, x-negative,
+/// y-positive, y-negative, z-positive, z-negative.
+const CUBEMAP_CELLS: [(u32, u32); 6] = [(2, 1), (0, 1), (1, 0), (1, 2), (1, 1), (3, 1)];
+
+/// The most blurred levels a cubemap keeps for rough reflections.
+const CUBEMAP_BLUR_LEVELS: usize = 6;
+
 /// A skybox derived from a cubemap image, shaped as a cross angled 90 degrees CCW.
 #[derive(Debug, Clone)]
 pub struct Cubemap {
@@ -41,6 +485,10 @@ pub struct Cubemap {
 
     /// The sidelength of one cubemap side.
     cell_size: u32,
+
+    /// Successively blurrier copies of the texture, each half the size of the last, with the
+    /// sidelength of one of their sides. Rough surfaces reflect these.
+    blurred: Vec<(image::RgbImage, u32)>,
 }
 
 impl Cubemap {
@@ -50,6 +498,16 @@ impl Cubemap {
         let csh = tex.height() / 3;
         assert!(csw == csh);
 
+        let mut blurred: Vec<(image::RgbImage, u32)> = Vec::new();
+        while blurred.len() < CUBEMAP_BLUR_LEVELS {
+            let (prev, prev_size) = blurred.last().map_or((&tex, csw), |(t, s)| (t, *s));
+            if prev_size < 2 {
+                break;
+            }
+
+            blurred.push((Self::halve(prev, prev_size), prev_size / 2));
+        }
+
         Cubemap {
             aabb: AabbIntersector {
                 pos: Vector3::default(),
@@ -57,25 +515,96 @@ impl Cubemap {
             },
             tex,
             cell_size: csw,
+            blurred,
+        }
+    }
+
+    /// Create a new cubemap from the images of its six faces, as they would be laid out in the
+    /// cross. Every face must be square, and the same size.
+    pub fn from_faces(
+        xpos: image::RgbImage,
+        xneg: image::RgbImage,
+        ypos: image::RgbImage,
+        yneg: image::RgbImage,
+        zpos: image::RgbImage,
+        zneg: image::RgbImage,
+    ) -> Self {
+        let size = xpos.width();
+        let faces = [xpos, xneg, ypos, yneg, zpos, zneg];
+        assert!(faces
+            .iter()
+            .all(|face| face.width() == size && face.height() == size));
+
+        Self::new(Self::stitch(&faces, size))
+    }
+
+    /// The cross-shaped texture of this cubemap.
+    pub fn image(&self) -> &image::RgbImage {
+        &self.tex
+    }
+
+    /// Shrink a cubemap texture to half its size, averaging each 2x2 block of pixels. Each face is
+    /// shrunk separately, so they don't bleed into the empty parts of the cross.
+    fn halve(tex: &image::RgbImage, cell_size: u32) -> image::RgbImage {
+        let size = cell_size / 2;
+        let mut out = image::RgbImage::new(size * 4, size * 3);
+        for (cx, cy) in CUBEMAP_CELLS {
+            for y in 0..size {
+                for x in 0..size {
+                    let (sx, sy) = (cx * cell_size + x * 2, cy * cell_size + y * 2);
+                    let mut sum = [0u32; 3];
+                    for (dx, dy) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                        let pixel = tex.get_pixel(sx + dx, sy + dy).0;
+                        for (sum, channel) in sum.iter_mut().zip(pixel) {
+                            *sum += channel as u32;
+                        }
+                    }
+                    out.put_pixel(
+                        cx * size + x,
+                        cy * size + y,
+                        image::Rgb(sum.map(|c| (c / 4) as u8)),
+                    );
+                }
+            }
         }
+
+        out
+    }
+
+    /// Stitch the six faces of a cubemap into its cross, where each face's cell is.
+    fn stitch(faces: &[image::RgbImage; 6], size: u32) -> image::RgbImage {
+        let mut tex = image::RgbImage::new(size * 4, size * 3);
+        for (face, (cx, cy)) in faces.iter().zip(CUBEMAP_CELLS) {
+            image::imageops::replace(&mut tex, face, cx * size, cy * size);
+        }
+
+        tex
     }
 
     /// Grab a pixel from the cubemap at a certain cell.
     pub fn poll_tex(&self, cx: u32, cy: u32, x: f32, y: f32) -> Color {
-        let x = (cx * self.cell_size) as f64 + x as f64 * (self.cell_size - 1) as f64;
-        let y = (cy * self.cell_size) as f64 + y as f64 * (self.cell_size - 1) as f64;
+        Self::poll(&self.tex, self.cell_size, cx, cy, x, y)
+    }
+
+    /// Grab a pixel from a cubemap texture with cells of `cell_size` at a certain cell.
+    fn poll(tex: &image::RgbImage, cell_size: u32, cx: u32, cy: u32, x: f32, y: f32) -> Color {
+        // rays along the edges of a face can land a rounding error outside of it
+        let (x, y) = (x.clamp(0., 1.), y.clamp(0., 1.));
+        let x = (cx * cell_size) as Float + x as Float * (cell_size - 1) as Float;
+        let y = (cy * cell_size) as Float + y as Float * (cell_size - 1) as Float;
         if x % 1. == 0. && y % 1. == 0. {
-            self.tex.get_pixel(x as u32, y as u32).0.into()
+            tex.get_pixel(x as u32, y as u32).0.into()
         } else {
             let (xmin, xmax) = (x.floor(), x.ceil());
             let (ymin, ymax) = (y.floor(), y.ceil());
-            let c00: Color = self.tex.get_pixel(xmin as u32, ymin as u32).0.into();
-            let c10: Color = self.tex.get_pixel(xmax as u32, ymin as u32).0.into();
-            let c01: Color = self.tex.get_pixel(xmin as u32, ymax as u32).0.into();
-            let c11: Color = self.tex.get_pixel(xmax as u32, ymax as u32).0.into();
+            let c00: Color = tex.get_pixel(xmin as u32, ymin as u32).0.into();
+            let c10: Color = tex.get_pixel(xmax as u32, ymin as u32).0.into();
+            let c01: Color = tex.get_pixel(xmin as u32, ymax as u32).0.into();
+            let c11: Color = tex.get_pixel(xmax as u32, ymax as u32).0.into();
+            // the pixels are a whole step apart, unless a coordinate is whole and they're the same
             blerp(
-                (x - xmin) / (xmax - xmin),
-                (y - ymin) / (ymax - ymin),
+                x - xmin,
+                y - ymin,
                 c00.into(),
                 c10.into(),
                 c01.into(),
@@ -84,10 +613,9 @@ impl Cubemap {
             .into()
         }
     }
-}
 
-impl Skybox for Cubemap {
-    fn ray_color(&self, ray: &Ray) -> Color {
+    /// Find the cell and UV coordinates a ray strikes the cubemap at.
+    fn cell_uv(&self, ray: &Ray) -> (u32, u32, Vector2) {
         let ray = Ray::new(ray.direction * 2., -ray.direction);
         let Hit { normal, uv, .. } = self.aabb.intersect(&ray).unwrap();
 
@@ -108,13 +636,66 @@ impl Skybox for Cubemap {
         };
 
         let uv = if normal.y == 1. {
-            (1. - uv.0, 1. - uv.1)
+            Vector2::new(1. - uv.x, 1. - uv.y)
         } else if normal.y == -1. {
-            (uv.0, 1. - uv.1)
+            Vector2::new(uv.x, 1. - uv.y)
         } else {
             uv
         };
 
-        self.poll_tex(cx, cy, uv.0, uv.1)
+        (cx, cy, uv)
+    }
+
+    /// Grab a pixel from the texture, or one of its blurred copies, at a blur `level` (0 being
+    /// the texture itself).
+    fn poll_level(&self, level: usize, cx: u32, cy: u32, uv: Vector2) -> Color {
+        match level.checked_sub(1).and_then(|i| self.blurred.get(i)) {
+            Some((tex, cell_size)) => Self::poll(tex, *cell_size, cx, cy, uv.x, uv.y),
+            None => self.poll_tex(cx, cy, uv.x, uv.y),
+        }
+    }
+}
+
+impl Skybox for Cubemap {
+    fn ray_color(&self, ray: &Ray) -> Color {
+        let (cx, cy, uv) = self.cell_uv(ray);
+        self.poll_tex(cx, cy, uv.x, uv.y)
+    }
+
+    fn ray_color_rough(&self, ray: &Ray, roughness: Float) -> Color {
+        let (cx, cy, uv) = self.cell_uv(ray);
+
+        // blend between the two blurred levels nearest to the roughness, squared like the
+        // width of the highlights of the BRDF
+        let level = roughness.clamp(0., 1.).powi(2) * self.blurred.len() as Float;
+        let (lower, t) = (level.floor() as usize, level.fract());
+        let color: Vector3 = self.poll_level(lower, cx, cy, uv).into();
+        if t <= 0. {
+            return color.into();
+        }
+
+        color
+            .lerp(self.poll_level(lower + 1, cx, cy, uv).into(), t)
+            .into()
+    }
+
+    fn texture_bytes(&self) -> usize {
+        let blurred = self.blurred.iter().map(|(tex, _)| tex.as_raw().len());
+        self.tex.as_raw().len() + blurred.sum::<usize>()
+    }
+
+    fn clone_box(&self) -> Box<dyn Skybox> {
+        Box::new(self.clone())
+    }
+
+    fn fingerprint(&self, hasher: &mut Fingerprint) {
+        // the blurred faces are generated from the image, so only it needs hashing
+        hasher.write_debug(&(self.tex.dimensions(), self.cell_size));
+        hasher.write(self.tex.as_raw());
+    }
+
+    #[cfg(feature = "serde")]
+    fn tagged(&self) -> TaggedSkybox<'_> {
+        TaggedSkybox::Cubemap(self)
     }
 }
@@ -0,0 +1,123 @@
+//! Checks for mistakes in a scene that would make it render wrong, or panic partway through a
+//! render, so they can be reported up front (see [`Scene::validate`](crate::scene::Scene::validate)).
+
+use std::fmt;
+
+use slotmap::Key;
+
+use crate::{
+    math::{Float, Vector3},
+    scene::ObjectHandle,
+};
+
+/// How far from 1 the length of a vector that should be normalized can be.
+const UNIT_TOLERANCE: Float = 1e-3;
+
+/// Something wrong with an object or light of a scene.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Problem {
+    /// The triangle at this index has no area, so it has no normal and can never be hit.
+    DegenerateTriangle(usize),
+
+    /// The vertex at this index has a NaN or infinite coordinate.
+    NonFiniteVertex(usize),
+
+    /// The normal at this index isn't unit length.
+    UnnormalizedNormal(usize),
+
+    /// A normal or direction vector, named here, isn't unit length.
+    UnnormalizedVector(&'static str),
+
+    /// An axis-aligned box with no volume.
+    ZeroSizeAabb,
+
+    /// A mesh with neither an SBVH nor a kd-tree, which panics when it is traced.
+    MissingSbvh,
+
+    /// A light is linked to an object, named or described here, that isn't in the scene,
+    /// for example because it was removed.
+    MissingLinkedObject(String),
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::DegenerateTriangle(i) => write!(f, "triangle {} is degenerate", i),
+            Self::NonFiniteVertex(i) => write!(f, "vertex {} is not finite", i),
+            Self::UnnormalizedNormal(i) => write!(f, "normal {} is not normalized", i),
+            Self::UnnormalizedVector(name) => write!(f, "{} is not normalized", name),
+            Self::ZeroSizeAabb => write!(f, "box has no volume"),
+            Self::MissingSbvh => write!(f, "mesh has no SBVH, see `Mesh::generate_sbvh`"),
+            Self::MissingLinkedObject(object) => {
+                write!(f, "linked object {} is not in the scene", object)
+            }
+        }
+    }
+}
+
+/// The part of a scene a problem was found in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Location {
+    Object(ObjectHandle),
+
+    /// The light at this index of the scene's lights.
+    Light(usize),
+}
+
+/// A problem found in a scene, and where.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Issue {
+    pub location: Location,
+    pub problem: Problem,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.location {
+            Location::Object(handle) => write!(f, "object {:?}: {}", handle.data(), self.problem),
+            Location::Light(index) => write!(f, "light {}: {}", index, self.problem),
+        }
+    }
+}
+
+/// Whether every coordinate of a vector is a finite number.
+pub fn is_finite(v: Vector3) -> bool {
+    v.x.is_finite() && v.y.is_finite() && v.z.is_finite()
+}
+
+/// Whether a vector is unit length, give or take rounding.
+pub fn is_unit(v: Vector3) -> bool {
+    (v.magnitude() - 1.).abs() <= UNIT_TOLERANCE
+}
+
+/// Whether a triangle has no area.
+pub fn is_degenerate(verts: [Vector3; 3]) -> bool {
+    (verts[1] - verts[0]).cross(verts[2] - verts[0]).magnitude() <= 0.
+}
+
+/// Check a list of triangles for degenerate triangles and non-finite vertices, and a list of
+/// normals for unnormalized ones.
+pub(crate) fn check_triangles<'a>(
+    verts: &[Vector3],
+    tris: impl Iterator<Item = [usize; 3]>,
+    normals: impl Iterator<Item = &'a Vector3>,
+) -> Vec<Problem> {
+    let mut problems: Vec<Problem> = verts
+        .iter()
+        .enumerate()
+        .filter(|(_, v)| !is_finite(**v))
+        .map(|(i, _)| Problem::NonFiniteVertex(i))
+        .collect();
+    problems.extend(
+        tris.enumerate()
+            .filter(|(_, t)| is_degenerate([verts[t[0]], verts[t[1]], verts[t[2]]]))
+            .map(|(i, _)| Problem::DegenerateTriangle(i)),
+    );
+    problems.extend(
+        normals
+            .enumerate()
+            .filter(|(_, n)| !is_unit(**n))
+            .map(|(i, _)| Problem::UnnormalizedNormal(i)),
+    );
+    problems
+}
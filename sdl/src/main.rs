@@ -1,26 +1,59 @@
 use std::{
-    fs::File,
-    path::PathBuf,
+    path::{Path, PathBuf},
     sync::mpsc,
     time::{Duration, Instant},
 };
 
-use clap::{App, Arg};
+use clap::{App, AppSettings, Arg, SubCommand};
 use notify::Watcher;
+use raytracer::{
+    exr,
+    math::Float,
+    scene::Scene,
+    sdl::{InterpretError, Interpreter, Value},
+};
+
+use crate::report::RenderStats;
+
+mod report;
 
-use crate::interpret::{InterpretError, Interpreter, Value};
+/// How often a render saves its progress to its checkpoint file, see `--checkpoint`.
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(30);
 
-mod ast;
-mod function;
-mod interpret;
-mod reference;
-mod tokenize;
+/// The file format a render is written out as, picked by the output's extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    Png,
+    /// OpenEXR, which keeps the full range of the render's colors along with its depth and
+    /// normals.
+    Exr,
+    /// Plain PPM, which can be diffed as text.
+    Ppm,
+    /// PFM, which keeps the full range of the render's colors.
+    Pfm,
+}
+
+impl OutputFormat {
+    fn of(out: &str) -> Self {
+        let ext = Path::new(out)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| ext.to_ascii_lowercase());
+        match ext.as_deref() {
+            Some("exr") => Self::Exr,
+            Some("ppm") => Self::Ppm,
+            Some("pfm") => Self::Pfm,
+            _ => Self::Png,
+        }
+    }
+}
 
 fn main() {
     let matches = App::new("Raytracer SDL Interpreter")
         .version("1.0")
         .author("Zander F. <zander@zanderf.net>")
         .about("A SDL runtime that uses a proprietary SDL language to describe a scene to the raytracer")
+        .setting(AppSettings::SubcommandsNegateReqs)
         .arg(
             Arg::with_name("SOURCE")
                 .help("The source file")
@@ -31,7 +64,7 @@ fn main() {
             Arg::with_name("output")
                 .long("output")
                 .short("o")
-                .help("The output file")
+                .help("The output file, written as an OpenEXR file with depth and normals if it ends in .exr, a plain PPM if it ends in .ppm, a float PFM if it ends in .pfm, or a PNG otherwise")
                 .default_value("render.png")
                 .required(false),
         )
@@ -42,6 +75,32 @@ fn main() {
                 .help("Whether or not to watch the file and rerender on save")
                 .required(false),
         )
+        .arg(
+            Arg::with_name("light_groups")
+                .long("light-groups")
+                .help("Whether or not to also render the lighting of each light group to its own file, named after the output and the group")
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("stream")
+                .long("stream")
+                .help("Render and write PNG and EXR output a block of rows at a time, so very large renders don't need the whole frame in memory")
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("checkpoint")
+                .long("checkpoint")
+                .help("A file to save the progress of a PNG render to every 30 seconds. If it already holds part of the same render, the render resumes from it. It is removed once the render is saved.")
+                .required(false)
+                .takes_value(true)
+                .conflicts_with_all(&["stream", "bvh_heatmap", "sequence"])
+        )
+        .arg(
+            Arg::with_name("bvh_heatmap")
+                .long("bvh-heatmap")
+                .help("Render a heat map of how many BVH nodes each pixel's camera ray visits instead of the scene, to find meshes that are slow to trace")
+                .required(false),
+        )
         .arg(
             Arg::with_name("sequence")
                 .long("sequence")
@@ -49,16 +108,93 @@ fn main() {
                 .required(false)
                 .takes_value(true)
         )
+        .arg(
+            Arg::with_name("sbvh_cache")
+                .long("sbvh-cache")
+                .help("A directory to cache the SBVHs of meshes in, so they are loaded rather than rebuilt on later runs. Watch mode caches them in a temporary directory by default.")
+                .required(false)
+                .takes_value(true)
+        )
+        .subcommand(
+            SubCommand::with_name("report")
+                .about("Renders a scene and writes an HTML report of it, including the render, camera, objects, lights, materials, assets and render statistics")
+                .arg(
+                    Arg::with_name("SOURCE")
+                        .help("The source file")
+                        .required(true)
+                        .index(1),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .long("output")
+                        .short("o")
+                        .help("The output HTML file")
+                        .default_value("report.html")
+                        .required(false),
+                ),
+        )
         .get_matches();
 
-    fn render(matches: &clap::ArgMatches) -> Result<(), InterpretError> {
+    fn render<F>(
+        construct: F,
+        out: &str,
+        light_groups: bool,
+        bvh_heatmap: bool,
+        stream: bool,
+        checkpoint: Option<&Path>,
+    ) -> Result<(), InterpretError>
+    where
+        F: FnOnce() -> Result<Scene, InterpretError>,
+    {
         let now = Instant::now();
-        let scene =
-            Interpreter::new(File::open(matches.value_of("SOURCE").unwrap()).unwrap())?.run()?;
+        let scene = construct()?;
 
         println!("Scene constructed in {}s", now.elapsed().as_secs_f32());
+        println!("{}", scene.stats());
+        for issue in scene.validate() {
+            println!("Warning: {}", issue);
+        }
 
-        scene.render_to(matches.value_of("output").unwrap(), image::ImageFormat::Png);
+        if bvh_heatmap {
+            let (frame, max) = scene.render_bvh_heatmap();
+            println!("At most {} BVH nodes visited by a pixel", max);
+            scene.save_frame(&frame, out, image::ImageFormat::Png);
+        } else {
+            let channels = exr::Channels {
+                depth: true,
+                normal: true,
+            };
+            let written = match OutputFormat::of(out) {
+                OutputFormat::Png if stream => scene.render_to_png_streaming(out),
+                OutputFormat::Png if checkpoint.is_some() => scene.render_to_resumable(
+                    out,
+                    image::ImageFormat::Png,
+                    checkpoint.unwrap(),
+                    CHECKPOINT_INTERVAL,
+                ),
+                OutputFormat::Png => {
+                    scene.render_to(out, image::ImageFormat::Png);
+                    Ok(())
+                }
+                OutputFormat::Exr if stream => scene.render_to_exr_streaming(out, channels),
+                OutputFormat::Exr => scene.render_to_exr(out, channels),
+                OutputFormat::Ppm => scene.render_to_ppm(out),
+                OutputFormat::Pfm => scene.render_to_pfm(out),
+            };
+            if let Err(e) = written {
+                println!("Failed to write {}: {}", out, e);
+            }
+        }
+        if light_groups {
+            let groups = scene.light_groups();
+            for group in groups.into_iter().map(Some).chain([None]) {
+                let path = group_path(out, group.unwrap_or("ungrouped"));
+                println!("Rendering light group to {}", path);
+                if let Err(e) = scene.render_light_group_to_exr(group, &path) {
+                    println!("Failed to write {}: {}", path, e);
+                }
+            }
+        }
         println!(
             "Operation complete in in {}s\n",
             now.elapsed().as_secs_f32()
@@ -67,6 +203,116 @@ fn main() {
         Ok(())
     }
 
+    /// Construct the scene from `interpreter`, printing its warnings, and add the metadata of
+    /// the source it was read from.
+    fn construct(interpreter: &mut Interpreter, source: &str) -> Result<Scene, InterpretError> {
+        let mut scene = interpreter.run_cloned()?;
+        print_warnings(interpreter);
+        scene
+            .options
+            .metadata
+            .extend(source_metadata(source, interpreter.includes()));
+        Ok(scene)
+    }
+
+    /// Metadata naming the source file a render was built from, and a hash of its contents and
+    /// those of every file it includes (FNV-1a, like mesh geometry hashes) to tell apart renders
+    /// of different versions of them.
+    fn source_metadata(source: &str, includes: &[String]) -> Vec<(String, String)> {
+        let mut entries = vec![(String::from("Source"), source.to_owned())];
+        let files = std::iter::once(source)
+            .chain(includes.iter().map(String::as_str))
+            .map(std::fs::read)
+            .collect::<Result<Vec<_>, _>>();
+        if let Ok(files) = files {
+            // each file's length goes first, so bytes can't move between files unnoticed
+            let bytes = files.iter().flat_map(|file| {
+                (file.len() as u64)
+                    .to_le_bytes()
+                    .into_iter()
+                    .chain(file.iter().copied())
+            });
+            let hash = bytes.fold(0xcbf29ce484222325u64, |hash, byte| {
+                (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+            });
+            entries.push((String::from("Source hash"), format!("{:016x}", hash)));
+        }
+        entries
+    }
+
+    /// The path to render a light group to, next to the output, e.g. `render_key.exr`. Light
+    /// groups are always EXRs, so that they aren't clamped and add up to the full render.
+    fn group_path(out: &str, group: &str) -> String {
+        let out = Path::new(out);
+        let stem = out.file_stem().and_then(|s| s.to_str()).unwrap_or("render");
+        let name = format!("{}_{}.exr", stem, group);
+        out.with_file_name(name).to_string_lossy().into_owned()
+    }
+
+    /// Print the problems that didn't stop the interpreter from constructing the scene.
+    fn print_warnings(interpreter: &Interpreter) {
+        for warning in interpreter.warnings() {
+            println!("Warning: {}", warning);
+        }
+    }
+
+    /// Canonicalize a path so that watcher events can be compared against it.
+    fn canonical<P: AsRef<Path>>(path: P) -> PathBuf {
+        let path = path.as_ref();
+        path.canonicalize().unwrap_or_else(|_| path.to_owned())
+    }
+
+    fn report(matches: &clap::ArgMatches) -> Result<(), InterpretError> {
+        let source = matches.value_of("SOURCE").unwrap();
+
+        let now = Instant::now();
+        let mut interpreter = Interpreter::from_file(source)?;
+        let scene = interpreter.run_cloned()?;
+        print_warnings(&interpreter);
+        let construct_time = now.elapsed();
+        println!("Scene constructed in {}s", construct_time.as_secs_f32());
+
+        let now = Instant::now();
+        let rendered = scene.render();
+        let render_time = now.elapsed();
+        println!("Scene rendered in {}s", render_time.as_secs_f32());
+
+        let png = report::encode_png(&scene, &rendered)?;
+        let html = report::html_report(
+            source,
+            &scene,
+            interpreter.assets(),
+            &png,
+            &RenderStats {
+                construct_time,
+                render_time,
+            },
+        );
+
+        let out = matches.value_of("output").unwrap();
+        std::fs::write(out, html).expect("Failed to write report");
+        println!("Report written to {}", out);
+
+        Ok(())
+    }
+
+    if let Some(matches) = matches.subcommand_matches("report") {
+        if let Err(e) = report(matches) {
+            println!("Failed to render: {}", e);
+        }
+
+        return;
+    }
+
+    let sbvh_cache = matches
+        .value_of("sbvh_cache")
+        .map(PathBuf::from)
+        .or_else(|| {
+            matches
+                .is_present("watch")
+                .then(|| std::env::temp_dir().join("raytracer-sbvh"))
+        });
+
     if matches.is_present("sequence") {
         let source = matches.value_of("SOURCE").unwrap();
         let out = matches.value_of("output").unwrap();
@@ -77,16 +323,21 @@ fn main() {
             .parse()
             .expect("Failed to parse sequence frame count");
 
-        let mut interpreter = Interpreter::new(File::open(source).unwrap()).unwrap();
+        let mut interpreter = Interpreter::from_file(source).expect("Failed to read scene");
+        interpreter.set_sbvh_cache(sbvh_cache);
         let _ = std::fs::remove_dir_all(out);
         let _ = std::fs::create_dir_all(out);
 
         for i in 0..frames {
             let mut path = PathBuf::from(out);
             path.push(format!("frame_{}.png", i));
-            interpreter.set_global(String::from("t"), Value::Number(i as f64));
+            interpreter.set_global(String::from("t"), Value::Number(i as Float));
 
-            let scene = interpreter.run_cloned().expect("Failed to construct scene");
+            let mut scene = construct(&mut interpreter, source).expect("Failed to construct scene");
+            scene
+                .options
+                .metadata
+                .push((String::from("Frame"), i.to_string()));
             println!("Rendering to {}", path.as_os_str().to_str().unwrap());
             scene.render_to(path.as_os_str().to_str().unwrap(), image::ImageFormat::Png);
         }
@@ -94,8 +345,22 @@ fn main() {
         return;
     }
 
+    let source = matches.value_of("SOURCE").unwrap();
+    let out = matches.value_of("output").unwrap();
+    let light_groups = matches.is_present("light_groups");
+    let bvh_heatmap = matches.is_present("bvh_heatmap");
+    let stream = matches.is_present("stream");
+    let checkpoint = matches.value_of("checkpoint").map(Path::new);
+    if checkpoint.is_some() && OutputFormat::of(out) != OutputFormat::Png {
+        clap::Error::with_description(
+            "--checkpoint can only be used with PNG output",
+            clap::ErrorKind::ArgumentConflict,
+        )
+        .exit();
+    }
+
     if matches.is_present("watch") {
-        let source = matches.value_of("SOURCE").unwrap();
+        let source_path = canonical(source);
 
         let (tx, rx) = mpsc::channel();
         let mut watcher = notify::watcher(tx, Duration::from_millis(500)).unwrap();
@@ -103,20 +368,93 @@ fn main() {
             .watch(source, notify::RecursiveMode::NonRecursive)
             .unwrap();
 
-        println!("Now listening for file changes at {}", source);
+        let mut interpreter: Option<Interpreter> = None;
+        let mut watched: Vec<PathBuf> = Vec::new();
+        let mut includes: Vec<PathBuf> = Vec::new();
+
+        // `None` means the source itself changed and must be re-parsed
+        let mut changed: Option<PathBuf> = None;
         loop {
-            match rx.recv() {
-                Ok(event) if matches!(event, notify::DebouncedEvent::Write(_)) => {
-                    if let Err(e) = render(&matches) {
+            match changed.take() {
+                Some(asset) => {
+                    if let Some(interpreter) = interpreter.as_mut() {
+                        interpreter.invalidate_asset(&asset);
+                    }
+                }
+                None => match Interpreter::from_file(source) {
+                    Ok(mut i) => {
+                        i.set_sbvh_cache(sbvh_cache.clone());
+                        includes = i.includes().iter().map(canonical).collect();
+                        interpreter = Some(i);
+                    }
+                    Err(e) => {
+                        interpreter = None;
                         println!("Failed to render: {}", e);
                     }
+                },
+            }
+
+            // render, then watch every asset the scene referenced
+            let mut assets = Vec::new();
+            if let Some(interpreter) = interpreter.as_mut() {
+                if let Err(e) = render(
+                    || construct(interpreter, source),
+                    out,
+                    light_groups,
+                    bvh_heatmap,
+                    stream,
+                    checkpoint,
+                ) {
+                    println!("Failed to render: {}", e);
+                }
+
+                assets = interpreter.assets().iter().map(canonical).collect();
+                for asset in assets.iter().chain(includes.iter()) {
+                    if !watched.contains(asset)
+                        && watcher
+                            .watch(asset, notify::RecursiveMode::NonRecursive)
+                            .is_ok()
+                    {
+                        watched.push(asset.clone());
+                    }
                 }
-                Err(_) => panic!("failed to watch file!"),
-                _ => (),
             }
+
+            println!(
+                "Now listening for file changes at {} and {} asset(s)",
+                source,
+                assets.len()
+            );
+            changed = loop {
+                match rx.recv() {
+                    Ok(notify::DebouncedEvent::Write(path))
+                    | Ok(notify::DebouncedEvent::Create(path)) => {
+                        let path = canonical(path);
+                        if path == source_path || includes.contains(&path) {
+                            break None;
+                        } else if assets.contains(&path) {
+                            println!("Asset {} changed", path.display());
+                            break Some(path);
+                        }
+                    }
+                    Err(_) => panic!("failed to watch file!"),
+                    _ => (),
+                }
+            };
         }
     } else {
-        if let Err(e) = render(&matches) {
+        if let Err(e) = render(
+            || {
+                let mut interpreter = Interpreter::from_file(source)?;
+                interpreter.set_sbvh_cache(sbvh_cache);
+                construct(&mut interpreter, source)
+            },
+            out,
+            light_groups,
+            bvh_heatmap,
+            stream,
+            checkpoint,
+        ) {
             println!("Failed to render: {}", e);
         }
     }
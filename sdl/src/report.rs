@@ -0,0 +1,345 @@
+use std::{
+    fmt::{self, Write},
+    time::Duration,
+};
+
+use image::{codecs::png::PngEncoder, ColorType};
+use raytracer::{
+    material::{Color, Material, Texture, UvTransform},
+    scene::Scene,
+};
+
+/// Statistics gathered while constructing and rendering a scene, shown in a report.
+pub struct RenderStats {
+    /// How long the interpreter took to construct the scene.
+    pub construct_time: Duration,
+
+    /// How long the raytracer took to render the scene.
+    pub render_time: Duration,
+}
+
+/// Encode a rendered frame (as returned by `Scene::render`) as a PNG.
+pub fn encode_png(scene: &Scene, rendered: &[Color]) -> Result<Vec<u8>, image::ImageError> {
+    let (fw, fh) = scene.camera.frame_size();
+    let data = rendered
+        .iter()
+        .flat_map(|c| [c.r, c.g, c.b])
+        .collect::<Vec<_>>();
+
+    let mut png = Vec::new();
+    PngEncoder::new(&mut png).encode(&data, fw as u32, fh as u32, ColorType::Rgb8)?;
+    Ok(png)
+}
+
+/// Build a self-contained HTML page describing a rendered scene: the render itself, the camera,
+/// every object, light and material, the assets that were loaded, and render statistics.
+pub fn html_report(
+    title: &str,
+    scene: &Scene,
+    assets: &[String],
+    png: &[u8],
+    stats: &RenderStats,
+) -> String {
+    let mut out = String::new();
+    write_report(&mut out, title, scene, assets, png, stats).unwrap();
+    out
+}
+
+fn write_report(
+    out: &mut String,
+    title: &str,
+    scene: &Scene,
+    assets: &[String],
+    png: &[u8],
+    stats: &RenderStats,
+) -> fmt::Result {
+    let camera = &scene.camera;
+    let (fw, fh) = camera.frame_size();
+
+    writeln!(out, "<!DOCTYPE html>")?;
+    writeln!(out, "<html>\n<head>\n<meta charset=\"utf-8\">")?;
+    writeln!(out, "<title>Render report: {}</title>", escape(title))?;
+    writeln!(
+        out,
+        "<style>body {{ font-family: sans-serif; margin: 2em; }} \
+         table {{ border-collapse: collapse; margin-bottom: 2em; }} \
+         th, td {{ border: 1px solid #ccc; padding: 4px 8px; text-align: left; }} \
+         .swatch {{ display: inline-block; width: 1em; height: 1em; border: 1px solid #000; \
+         vertical-align: middle; margin-right: 4px; }} img {{ max-width: 100%; }}</style>"
+    )?;
+    writeln!(out, "</head>\n<body>")?;
+    writeln!(out, "<h1>{}</h1>", escape(title))?;
+    writeln!(
+        out,
+        "<img alt=\"render\" src=\"data:image/png;base64,{}\">",
+        base64(png)
+    )?;
+
+    // camera and scene options
+    writeln!(out, "<h2>Camera</h2>\n<table>")?;
+    row(out, "Viewport", &format!("{} x {}", camera.vw, camera.vh))?;
+    row(
+        out,
+        "Origin",
+        &format!(
+            "&lt;{}, {}, {}&gt;",
+            camera.origin.x, camera.origin.y, camera.origin.z
+        ),
+    )?;
+    row(out, "Yaw", &format!("{} rad", camera.yaw))?;
+    row(out, "Pitch", &format!("{} rad", camera.pitch))?;
+    if let Some(q) = camera.orientation {
+        row(
+            out,
+            "Orientation",
+            &format!("quaternion ({}, {}, {}, {})", q.w, q.x, q.y, q.z),
+        )?;
+    }
+    row(out, "FOV", &format!("{}&deg;", camera.fov))?;
+    if let Some(stereo) = camera.stereo {
+        row(
+            out,
+            "Stereo",
+            &format!("{:?}, IPD {}", stereo.layout, stereo.ipd),
+        )?;
+    }
+    if let Some(distortion) = camera.distortion {
+        row(
+            out,
+            "Distortion",
+            &format!("k1 {}, k2 {}", distortion.k1, distortion.k2),
+        )?;
+    }
+    row(
+        out,
+        "Max ray depth",
+        &scene.options.max_ray_depth.to_string(),
+    )?;
+    row(out, "Ambient", &swatch(scene.options.ambient))?;
+    writeln!(out, "</table>")?;
+
+    // objects and their materials
+    writeln!(out, "<h2>Objects ({})</h2>\n<table>", scene.objects.len())?;
+    writeln!(
+        out,
+        "<tr><th>#</th><th>Kind</th><th>Texture</th><th>Reflectiveness</th>\
+         <th>Transparency</th><th>IOR</th><th>Emissivity</th><th>Roughness</th><th>Metallic</th><th>Opacity</th><th>Normal map</th></tr>"
+    )?;
+    for (i, object) in scene.objects.values().enumerate() {
+        let Material {
+            texture,
+            uv_transform,
+            triplanar,
+            reflectiveness,
+            transparency,
+            ior,
+            emissivity,
+            emission,
+            roughness,
+            metallic,
+            clearcoat,
+            clearcoat_roughness,
+            thin_film_thickness,
+            thin_film_ior,
+            opacity,
+            alpha_cutoff,
+            normal_map,
+        } = object.material();
+
+        writeln!(
+            out,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td>\
+             <td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            i,
+            object.kind(),
+            match triplanar {
+                Some(triplanar) => format!(
+                    "{} (triplanar, scale {}, sharpness {})",
+                    describe_texture(texture),
+                    triplanar.scale,
+                    triplanar.sharpness
+                ),
+                None => describe_uv_texture(texture, uv_transform),
+            },
+            if *thin_film_thickness > 0. {
+                format!(
+                    "{} (thin film {} nm, IOR {})",
+                    reflectiveness, thin_film_thickness, thin_film_ior
+                )
+            } else {
+                reflectiveness.to_string()
+            },
+            transparency,
+            ior,
+            match emission {
+                Some(emission) => format!("{} {}", emissivity, describe_texture(emission)),
+                None => emissivity.to_string(),
+            },
+            if *clearcoat > 0. {
+                format!(
+                    "{} (clearcoat {}, roughness {})",
+                    roughness, clearcoat, clearcoat_roughness
+                )
+            } else {
+                roughness.to_string()
+            },
+            metallic,
+            match opacity {
+                Some(opacity) => format!("{} (cutoff {})", describe_texture(opacity), alpha_cutoff),
+                None => String::from("opaque"),
+            },
+            normal_map
+                .as_ref()
+                .map(describe_texture)
+                .unwrap_or_else(|| String::from("none")),
+        )?;
+    }
+    writeln!(out, "</table>")?;
+
+    // lights
+    writeln!(out, "<h2>Lights ({})</h2>\n<table>", scene.lights.len())?;
+    writeln!(
+        out,
+        "<tr><th>#</th><th>Kind</th><th>Color</th><th>Intensity</th>\
+         <th>Specular strength</th></tr>"
+    )?;
+    for (i, light) in scene.lights.iter().enumerate() {
+        writeln!(
+            out,
+            "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            i,
+            light.kind(),
+            swatch(*light.color()),
+            light.intensity(),
+            light.specular_strength(),
+        )?;
+    }
+    writeln!(out, "</table>")?;
+
+    // assets
+    writeln!(out, "<h2>Assets ({})</h2>", assets.len())?;
+    if assets.is_empty() {
+        writeln!(out, "<p>No assets were loaded.</p>")?;
+    } else {
+        writeln!(out, "<ul>")?;
+        for asset in assets {
+            writeln!(out, "<li><code>{}</code></li>", escape(asset))?;
+        }
+        writeln!(out, "</ul>")?;
+    }
+
+    // statistics
+    let render_secs = stats.render_time.as_secs_f64();
+    writeln!(out, "<h2>Render statistics</h2>\n<table>")?;
+    row(out, "Frame size", &format!("{} x {}", fw, fh))?;
+    row(
+        out,
+        "Scene construction",
+        &format!("{:.3}s", stats.construct_time.as_secs_f64()),
+    )?;
+    row(out, "Render", &format!("{:.3}s", render_secs))?;
+    row(
+        out,
+        "Throughput",
+        &format!("{:.0} pixels/s", (fw * fh) as f64 / render_secs),
+    )?;
+    writeln!(out, "</table>")?;
+
+    writeln!(out, "</body>\n</html>")
+}
+
+/// Write a two-column table row.
+fn row(out: &mut String, key: &str, value: &str) -> fmt::Result {
+    writeln!(out, "<tr><th>{}</th><td>{}</td></tr>", key, value)
+}
+
+/// Describe a texture as HTML.
+fn describe_texture(texture: &Texture) -> String {
+    match texture {
+        Texture::Solid(color) => format!("solid {}", swatch(*color)),
+        Texture::Image(texture) => format!(
+            "image ({} x {}, {:?}, {:?}{}{}{})",
+            texture.dimensions().0,
+            texture.dimensions().1,
+            texture.filter,
+            texture.wrap,
+            if texture.is_decoded() {
+                ", decoded to linear"
+            } else {
+                ""
+            },
+            if texture.has_mipmaps() {
+                ", mipmapped"
+            } else {
+                ""
+            },
+            if texture.has_alpha() {
+                ", with alpha"
+            } else {
+                ""
+            }
+        ),
+        Texture::Checkerboard(a, b) => format!("checkerboard {}{}", swatch(*a), swatch(*b)),
+        Texture::Function(_) => String::from("procedural"),
+        Texture::VertexColor => String::from("vertex colors"),
+    }
+}
+
+/// Describe a texture as HTML, along with its UV transform if it has one.
+fn describe_uv_texture(texture: &Texture, uv_transform: &UvTransform) -> String {
+    if *uv_transform == UvTransform::default() {
+        return describe_texture(texture);
+    }
+
+    format!(
+        "{} (UV offset {:?}, scale {:?}, rotation {} rad)",
+        describe_texture(texture),
+        uv_transform.offset,
+        uv_transform.scale,
+        uv_transform.rotation
+    )
+}
+
+/// A small colored square followed by the color's RGB values.
+fn swatch(color: Color) -> String {
+    format!(
+        "<span class=\"swatch\" style=\"background: rgb({r}, {g}, {b})\"></span>\
+         rgb({r}, {g}, {b})",
+        r = color.r,
+        g = color.g,
+        b = color.b,
+    )
+}
+
+/// Escape text to be placed in HTML.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Encode bytes as standard, padded base64.
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - i * 6)) as usize & 63] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+
+    out
+}